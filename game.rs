@@ -1,27 +1,95 @@
 use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
 use anchor_lang::solana_program::{
     hash::hashv,
+    instruction::{AccountMeta, Instruction},
     sysvar::clock::Clock,
-    program::invoke_signed,
+    program::{invoke_signed, set_return_data},
     system_instruction,
     pubkey::Pubkey,
 };
-use anchor_spl::token::{self, Token, TokenAccount, Transfer, Mint};
+use anchor_spl::token::{self, Token, TokenAccount, Transfer, Mint, Approve};
 use anchor_spl::associated_token::{self, AssociatedToken};
 
 declare_id!("4hmtAprg26SJgUKURwVMscyMv9mTtHnbvxaAXy6VJrr8");
 
+pub const TOKEN_METADATA_PROGRAM_ID: Pubkey = pubkey!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+
 //
 // BattleChain — Anchor program (v2)
 // Implements:
 //  - Config PDA (trait_authority, SPL whitelist, fee_bps, inactivity timeout default)
 //  - EntropyPool: VRF seed batches, monotonic global_next_index
 //  - NFT-backed Character PDA + Progression (xp/level/mmr)
-//  - Offer / Request with SOL or SPL staking (PDA-managed escrow ATAs)
+//  - Character profile: bounded name / emoji / banner set at creation or via set_character_profile
+//  - Level-gated stances: Berserker / Counter unlock at progression levels set in Config
+//  - Level-gap handicap (Config): the higher-level attacker's damage is cut per level of gap past a threshold, capped
+//  - Character elements (fire / water / earth / air) with a matchup multiplier table in the damage pipeline
+//  - Level-ups grant stat points; the owner spends them into HP / damage / crit / dodge / defense / armor pen / crit resist (per-level caps)
+//  - Armor penetration (ignores a share of flat defense) and crit resist (lowers the attacker's crit chance)
+//  - Offer / Request with SOL or SPL staking (SPL: one program-owned escrow ATA per mint, per-offer/request/battle ledger amounts)
+//  - SOL stakes held on zero-data escrow PDAs, never on the Offer / Request / Battle data accounts
 //  - Approve -> create Battle (moves stakes) and picks first mover from entropy
+//  - Auto-approve offers (creator's character fixed at creation): join_battle_offer creates the Battle itself
+//  - Matchmaking queue: MatchQueue entry per player (stake + character + MMR band), permissionless match_players pairs two compatible entries into a Battle
+//  - One-click rematch: after settlement either player proposes the same stakes / currency, the opponent's accept spawns the Battle directly
+//  - Character battle lock (active_battle): set at approve, released at settlement; one battle per character at a time
+//  - Arena modifier rolled from entropy at approve (low gravity, bloodbath, frenzy, fortress) and applied every turn
+//  - Ready check: both players confirm before the battle goes Active; no-show refund + penalty otherwise
+//  - Character NFTs delegated to and frozen by the battle PDA (Metaplex) from ready-up until the battle is over
 //  - Execute turn consuming entropy (per-battle monotonic check), last_action_ts updates
+//  - DoT ticks on the acting player at the start of each turn (can end the battle before the attack)
+//  - Turn actions: attack, defend (rolled guard vs the next hit), heal (cooldown + per-battle charges), item (per-battle charges)
+//  - Best-of-N rounds: HP and effects reset between rounds, battle finishes once a player has enough round wins
+//  - Turn cap (Config.max_turns): decided on round wins, then remaining HP share, else a draw
+//  - Stamina per player: abilities and Berserker stance spend it, turns without either regenerate it
+//  - Status effects per player (stun, poison, shield, buff, debuff) with turn counters, applied by abilities / items
+//  - Ability loadouts: a Loadout PDA per character picks up to 4 catalogue abilities, frozen onto the Battle with per-ability cooldowns
+//  - Battle HP seeded from each Character's max_hp; remaining HP written back to current_hp when the battle ends
+//  - close_offer / close_request: spent offers and requests closed for rent, leftover SOL escrow lamports swept back
+//  - OfferRegistry pages: offers listed (optionally) at creation, unlisted on cancel, prune crank drops consumed / closed offers
+//  - update_offer: creator edits levels / classes / start / stake (escrow topped up or refunded) while no request is pending
+//  - MMR-range offers (Offer.min_mmr / max_mmr) enforced at join alongside the level range
+//  - Private offers (Offer.target_opponent): only the named wallet may join
+//  - Offer.requests lists pending requests (bounded); approve_request_at picks one by index and rejects + refunds the rest
+//  - Reject challenger: the creator declines a pending request, its stake is refunded to the challenger
 //  - Withdraw request, cancel offer, forfeit_by_timeout, finalize battle with payouts & XP
+//  - Keeper reward (Config.forfeit_keeper_bps): forfeit_by_timeout pays its caller a cut of the idle player's stake
+//  - settle_forfeit: permissionless payout of a forfeited battle (no player signatures needed)
+//  - Elo MMR: Progression.mmr moves by Config.elo_k_factor * (score - expected) when a battle ends by play or forfeit
+//  - Reconnect grace (Offer.reconnect_grace_secs): each player's first timeout gets extra time before forfeit_by_timeout applies
+//  - Optional per-turn deadline (Config): skip_idle_turn passes an idle player's turn with an HP / stamina penalty, forfeit after N consecutive skips
+//  - Optional chess-clock time banks per player (Config.time_bank_secs); an empty bank forfeits on time
+//  - Optional commit-reveal stances (Offer.commit_reveal): both players commit hash(stance, salt), then reveal, before the turn resolves
+//  - 2v2 team battles: four seats, turns rotate across both teams' living combatants, SOL pot split among the winning team
+//  - Boss raids (PvE): admin-defined Boss with an entropy-driven move script, any character can attack it, SOL reward pool shared by damage dealt
+//  - Leaderboard PDA: top LEADERBOARD_SIZE characters by MMR, refreshed whenever a rating moves
+//  - Achievements: per-character PDAs (first win, 10-win streak, flawless victory, veteran) claimed against Progression counters
+//  - Ranked seasons: Season PDAs with start/end, per-character SeasonResult snapshot + MMR soft reset at rollover, SOL rewards by MMR tier
+//    (MMR is frozen between seasons; rewards are pro-rated when the pool can't cover every tier)
+//  - Async battles (Offer.async_mode): both players commit then reveal a full strategy, resolve_async_battle simulates every turn in one call
+//  - Crowd-funded bounties: any wallet adds SOL / whitelisted SPL to a battle's prize before it finishes (protocol fee applies, unlike the sponsor bonus)
+//  - Spectator reactions: bounded emote codes on active battles, optional SOL tip into the winner's bonus pot
+//  - Mutual-consent pause/resume (inactivity timer frozen, capped per battle)
+//  - Double-AFK cancel: both stakes refunded minus a crank fee
+//  - Mutual draw offer/accept: battle ends without a winner, finalize returns both stakes fee-free
+//  - TurnLog PDA per battle (opt-in via init_turn_log): ring buffer of per-turn action, rolls, damage and HP for replays / disputes
+//  - close_battle: settled Battle closed after a delay (prediction markets settle first), rent to the winner or creator
+//  - Read-only views (battle summary, combat stats) returned via sol_set_return_data
+//  - Tutorial: scripted AI opponent with seeded rolls, TutorialProgress PDA gates a one-time XP reward
+//  - Practice battles: zero-stake duel against a program AI mirroring the character, entropy rolls + simple stance policy, reduced XP
+//  - Winrate stats PDAs per class / stance, updated at finalize
+//  - Seasonal class auto-balance from winrate stats (bounded damage adjustments)
+//  - ClassRegistry PDAs: admin-set base stats and signature ability per class, used over the built-in defaults
+//  - Player vaults: deposit once, fund offers/joins from the vault, SOL winnings settle back into it
+//  - Recurring offers relisted by a crank from the creator's vault
+//  - Per-wallet hourly rate limit on offers / joins / approvals
+//  - Guilds: members pledge a share of battle XP as guild points; tiers unlock fee discounts
 //  - Trait bundle writer by trait_authority (writes compact modifiers into Character PDA)
+//  - Equipment: item NFTs registered by trait_authority, equipped into fixed Character slots (attack / defense / crit / dodge)
+//  - Character retirement: closes Character/Progression PDAs, converts level/rarity into crafting materials
+//  - Character merge: same-class source character's XP partly carried onto a target, source burned
+//  - Character fusion: two parents (locked by a cooldown) produce a new character whose stats are rolled between theirs
 //  - Safe fixed-point arithmetic and clamping
 //
 // Notes:
@@ -31,7 +99,7 @@ declare_id!("4hmtAprg26SJgUKURwVMscyMv9mTtHnbvxaAXy6VJrr8");
 //  - SPL whitelist: configurable in Config PDA (restrict accepted SPL mints).
 //  - fee_bps = 200 (2%)
 //  - default_inactivity_timeout = 300s (5 minutes)
-//  - PDA-managed escrow ATAs created by program via CPI with payer provided by tx signer
+//  - Per-mint escrow ATA created once by init_escrow_vault via CPI with payer provided by tx signer
 //
 
 // Fixed-point & limits
@@ -41,6 +109,121 @@ pub const MAX_COMBO_STACK: u8 = 5;
 pub const SEED_LEN: usize = 32;
 pub const MAX_BATCHES: usize = 8;
 pub const MIN_ENTROPY_PER_TURN: u64 = 4; // require this many available entries
+pub const MAX_TOTAL_PAUSE_SECS: i64 = 900; // 15 minutes of mutual pause per battle
+pub const READY_WINDOW_SECS: i64 = 300; // both players must ready up within this long after start_ts
+pub const NO_SHOW_PENALTY_BPS: u64 = 1000; // 10% of the absent player's stake goes to the player who showed up
+pub const LATE_SHOW_GRACE_SECS: i64 = 120; // first mover must play within this long after start_ts
+pub const ABANDON_CRANK_BPS: u64 = 50; // 0.5% of each refunded stake to whoever cancels a double-AFK battle
+pub const MAX_FORFEIT_KEEPER_BPS: u16 = 500; // forfeit_by_timeout caller reward is capped at 5% of the idle player's stake
+pub const BATTLE_CLOSE_DELAY_SECS: i64 = 604_800; // settled battles stay readable a week (keepers, markets, rematches) before close_battle
+pub const WIN_XP: u64 = 100;
+pub const DRAW_XP: u64 = 25;
+pub const DEFAULT_ELO_K_FACTOR: u16 = 32;
+pub const MAX_ELO_K_FACTOR: u16 = 128;
+pub const LEADERBOARD_SIZE: usize = 25; // characters ranked on the on-chain Leaderboard
+pub const ACHIEVEMENT_WIN_STREAK: u16 = 10;
+pub const ACHIEVEMENT_VETERAN_BATTLES: u32 = 100;
+// Elo expected score (bps) for a rating lead of 0, 50, ..., 800 points; interpolated in between, capped at 800
+pub const ELO_EXPECTED_BPS: [u64; 17] = [5000, 5715, 6401, 7034, 7597, 8083, 8490, 8823, 9091, 9302, 9468, 9595, 9693, 9768, 9825, 9868, 9901];
+pub const MAX_GUILD_CONTRIBUTION_BPS: u16 = 5000; // at most half of a member's battle XP flows to the guild
+// guild points needed per perk tier, and the fee discount (bps off fee_bps) each tier unlocks
+pub const GUILD_PERK_THRESHOLDS: [u64; 3] = [1_000, 10_000, 100_000];
+pub const GUILD_FEE_DISCOUNT_BPS: [u16; 3] = [10, 25, 50];
+// crafting materials granted when a character is retired
+pub const MATERIALS_PER_LEVEL: u64 = 10;
+pub const MATERIALS_PER_RARITY: u64 = 25;
+// tutorial: scripted AI opponent, lessons are stance -> special -> combo, then finish the AI off
+pub const TUTORIAL_AI_HP: u64 = 60;
+pub const TUTORIAL_AI_DAMAGE: u64 = 5;
+pub const TUTORIAL_LESSONS: u8 = 3;
+pub const TUTORIAL_REWARD_XP: u64 = 150;
+pub const PRACTICE_XP_BPS: u64 = 2000; // practice wins earn 20% of WIN_XP
+pub const PRACTICE_REWARD_COOLDOWN_SECS: i64 = 3600; // at most one rewarded practice win per hour per character
+pub const MERGE_XP_SHARE_BPS: u64 = 5000; // half of the source character's lifetime XP carries over on merge
+pub const FUSION_COOLDOWN_SECS: i64 = 604_800; // parents (and the child) can't fuse again for a week
+// WinrateStats.kind
+pub const STATS_KIND_CLASS: u8 = 0;
+pub const STATS_KIND_STANCE: u8 = 1;
+// seasonal auto-balance: classes whose winrate leaves the band get a bounded damage nudge
+pub const BALANCE_WINRATE_LOW_BPS: u64 = 4500;
+pub const BALANCE_WINRATE_HIGH_BPS: u64 = 5500;
+pub const BALANCE_STEP_BPS: u16 = 300; // ±3% damage per season
+pub const BALANCE_MIN_DAMAGE_BPS: u16 = 7000;
+pub const BALANCE_MAX_DAMAGE_BPS: u16 = 13000;
+pub const BALANCE_MIN_SAMPLE: u64 = 50; // decisive battles needed before a class is adjusted
+pub const RATE_WINDOW_SECS: i64 = 3600;
+// turn actions: rolled ranges are bps of the actor's max_hp (heal / item) or of the next incoming hit (defend).
+// Cooldowns count the actor's own turns and tick down at the start of each of them.
+pub const DEFEND_MIN_BPS: u64 = 3000;
+pub const DEFEND_MAX_BPS: u64 = 6000;
+pub const DEFEND_COOLDOWN_TURNS: u8 = 2; // no defending two turns in a row
+pub const HEAL_MIN_BPS: u64 = 1000;
+pub const HEAL_MAX_BPS: u64 = 2000;
+pub const HEAL_COOLDOWN_TURNS: u8 = 3;
+pub const HEALS_PER_BATTLE: u8 = 3;
+pub const ITEM_MIN_BPS: u64 = 1500;
+pub const ITEM_MAX_BPS: u64 = 2500;
+pub const ITEMS_PER_BATTLE: u8 = 1; // potion: heals and cures DoT / poison
+// status effects: turns count the owner's turn starts; magnitudes are bps (buff/debuff), HP per tick (poison), absorb HP (shield)
+pub const MAX_STATUS_EFFECTS: usize = 4;
+pub const MAX_ROUNDS: u8 = 5; // best of 1 / 3 / 5
+pub const MAX_ALLOWED_CLASSES: usize = 5; // Offer.allowed_classes (one per CharacterClass)
+pub const MAX_PENDING_REQUESTS: usize = 8; // Offer.requests: pending requests an offer can hold at once
+pub const OFFER_REGISTRY_PAGE_SIZE: usize = 32; // listings per OfferRegistry page
+pub const WARRIOR_BUFF_BPS: u16 = 2000;
+pub const ASSASSIN_POISON_PER_TURN: u16 = 4;
+pub const TANK_SHIELD_HP: u16 = 20;
+pub const TRICKSTER_DEBUFF_BPS: u16 = 2000;
+pub const TRICKSTER_STUN_CHANCE_BPS: u64 = 3000; // rolled on the reserved "wild" entropy
+pub const MAX_LOADOUT_ABILITIES: usize = 4;
+pub const EMOTE_COUNT: u8 = 16; // spectate_react emote codes 0..EMOTE_COUNT
+pub const TURN_LOG_CAPACITY: usize = 32; // TurnLog ring buffer entries; older turns are overwritten
+pub const TURN_LOG_STUNNED: u8 = 4; // TurnLogEntry.action for a turn lost to a stun (0-3 are TurnAction)
+pub const MAX_ASYNC_STRATEGY: usize = 10; // moves per async strategy, cycled until the battle ends
+pub const ASYNC_MAX_TURNS: u16 = 60; // async simulation cap, then the turn-cap rule decides
+pub const EQUIPMENT_SLOTS: usize = 3;
+pub const TEAM_SIZE: usize = 2; // team battles are 2v2
+pub const TEAM_SEATS: usize = TEAM_SIZE * 2; // seats 0-1 team A, 2-3 team B
+pub const TEAM_TURN_ORDER: [usize; TEAM_SEATS] = [0, 2, 1, 3]; // alternates A, B, A, B
+pub const MAX_BOSS_SCRIPT: usize = 8; // boss moves, each a damage multiplier in bps
+pub const RAID_ATTACK_COOLDOWN_SECS: i64 = 30; // per character per boss
+pub const SEASON_REWARD_TIERS: usize = 3;
+pub const SEASON_MMR_BASE: u64 = 100; // starting MMR; rollover pulls a rating halfway back towards it
+pub const SEASON_RECORD_WINDOW_SECS: i64 = 7 * 24 * 60 * 60; // results are recorded this long after end_ts (or until the next season starts); rewards pay out after
+// character profile limits (bytes)
+pub const MAX_NAME_LEN: usize = 32;
+pub const MAX_EMOJI_LEN: usize = 8;
+pub const MAX_BANNER_LEN: usize = 64;
+// stamina: refilled at approve and between rounds
+pub const MAX_STAMINA: u16 = 100;
+pub const ABILITY_STAMINA_COST: u16 = 30;
+pub const BERSERKER_STAMINA_COST: u16 = 20;
+pub const STAMINA_REGEN_PER_TURN: u16 = 15; // turns that spend nothing
+pub const SKIP_STAMINA_PENALTY: u16 = 30; // drained from a player whose turn is skipped
+// stat points: granted per level-up, each stat takes at most STAT_CAP_PER_LEVEL points per character level
+pub const STAT_POINTS_PER_LEVEL: u16 = 3;
+pub const STAT_CAP_PER_LEVEL: u16 = 2;
+pub const STAT_HP_PER_POINT: u32 = 5;
+pub const STAT_DAMAGE_PER_POINT: u16 = 1; // added to both base_damage_min and base_damage_max
+pub const STAT_CRIT_BPS_PER_POINT: u16 = 50;
+pub const STAT_DODGE_BPS_PER_POINT: u16 = 50;
+pub const STAT_DEFENSE_PER_POINT: u16 = 1;
+pub const STAT_ARMOR_PEN_BPS_PER_POINT: u16 = 100;
+pub const STAT_CRIT_RESIST_BPS_PER_POINT: u16 = 100;
+// arena modifiers rolled at approve
+pub const ARENA_MODIFIER_COUNT: u64 = 5; // including Neutral
+pub const ARENA_LOW_GRAVITY_DODGE_BPS: u64 = 1500; // added to the defender's dodge
+pub const ARENA_BLOODBATH_DAMAGE_BPS: u128 = 12500; // +25% damage, heals and potions disabled
+pub const ARENA_FRENZY_CRIT_BPS: u64 = 1000; // added to the attacker's crit chance
+pub const ARENA_FORTRESS_DEFENSE_MULT: u64 = 2; // flat defense counts double
+// elemental matchups, damage bps indexed [attacker][defender] in Element order (fire, water, earth, air):
+// water > fire > air > earth > water; everything else is neutral
+pub const ELEMENT_MATCHUP_BPS: [[u16; 4]; 4] = [
+    [10000, 8000, 10000, 12500],
+    [12500, 10000, 8000, 10000],
+    [10000, 12500, 10000, 8000],
+    [8000, 10000, 12500, 10000],
+];
 
 #[program]
 pub mod battlechain_v2 {
@@ -62,11 +245,182 @@ pub mod battlechain_v2 {
         cfg.inactivity_timeout = inactivity_timeout;
         cfg.spl_whitelist = spl_whitelist;
         cfg.trait_authority = trait_authority;
+        cfg.max_actions_per_hour = 0;
+        cfg.max_turns = 0;
+        cfg.time_bank_secs = 0;
+        cfg.handicap_level_gap = 0;
+        cfg.handicap_bps_per_level = 0;
+        cfg.handicap_max_bps = 0;
+        cfg.berserker_unlock_level = 0;
+        cfg.counter_unlock_level = 0;
+        cfg.turn_deadline_secs = 0;
+        cfg.skip_penalty_bps = 0;
+        cfg.max_skips = 0;
+        cfg.forfeit_keeper_bps = 0;
+        cfg.elo_k_factor = DEFAULT_ELO_K_FACTOR;
+        cfg.current_season = 0;
+        cfg.season_start_ts = 0;
+        cfg.season_end_ts = 0;
         cfg.bump = *ctx.bumps.get("config").unwrap_or(&0);
         emit!(ConfigCreated { config: ctx.accounts.config.key(), admin: cfg.admin });
         Ok(())
     }
 
+    pub fn set_rate_limit(ctx: Context<UpdateConfig>, max_actions_per_hour: u16) -> Result<()> {
+        ctx.accounts.config.max_actions_per_hour = max_actions_per_hour;
+        emit!(RateLimitUpdated { config: ctx.accounts.config.key(), max_actions_per_hour });
+        Ok(())
+    }
+
+    // per-player chess clock for new battles; 0 = off (single inactivity_timeout only)
+    pub fn set_time_bank(ctx: Context<UpdateConfig>, time_bank_secs: i64) -> Result<()> {
+        require!(time_bank_secs >= 0, GameError::InvalidRange);
+        ctx.accounts.config.time_bank_secs = time_bank_secs;
+        emit!(TimeBankUpdated { config: ctx.accounts.config.key(), time_bank_secs });
+        Ok(())
+    }
+
+    // 0 = no cap; applies to battles approved after the change
+    pub fn set_max_turns(ctx: Context<UpdateConfig>, max_turns: u16) -> Result<()> {
+        ctx.accounts.config.max_turns = max_turns;
+        emit!(MaxTurnsUpdated { config: ctx.accounts.config.key(), max_turns });
+        Ok(())
+    }
+
+    // level_gap = 0 turns the handicap off; otherwise each level of gap from level_gap on costs bps_per_level, up to max_bps
+    pub fn set_level_handicap(ctx: Context<UpdateConfig>, level_gap: u16, bps_per_level: u16, max_bps: u16) -> Result<()> {
+        require!(max_bps <= 10_000, GameError::InvalidRange);
+        let cfg = &mut ctx.accounts.config;
+        cfg.handicap_level_gap = level_gap;
+        cfg.handicap_bps_per_level = bps_per_level;
+        cfg.handicap_max_bps = max_bps;
+        emit!(LevelHandicapUpdated { config: cfg.key(), level_gap, bps_per_level, max_bps });
+        Ok(())
+    }
+
+    // progression level needed to play Berserker / Counter; 0 or 1 = available from the start
+    pub fn set_stance_unlocks(ctx: Context<UpdateConfig>, berserker_level: u16, counter_level: u16) -> Result<()> {
+        let cfg = &mut ctx.accounts.config;
+        cfg.berserker_unlock_level = berserker_level;
+        cfg.counter_unlock_level = counter_level;
+        emit!(StanceUnlocksUpdated { config: cfg.key(), berserker_level, counter_level });
+        Ok(())
+    }
+
+    // per-turn deadline for new battles (0 = off, inactivity_timeout forfeits as before); each skip costs
+    // penalty_bps of the idle player's remaining HP, max_skips consecutive skips forfeit the battle
+    pub fn set_skip_turn(ctx: Context<UpdateConfig>, turn_deadline_secs: i64, penalty_bps: u16, max_skips: u8) -> Result<()> {
+        require!(turn_deadline_secs >= 0 && penalty_bps <= 10_000, GameError::InvalidRange);
+        require!(turn_deadline_secs == 0 || max_skips > 0, GameError::InvalidRange);
+        let cfg = &mut ctx.accounts.config;
+        cfg.turn_deadline_secs = turn_deadline_secs;
+        cfg.skip_penalty_bps = penalty_bps;
+        cfg.max_skips = max_skips;
+        emit!(SkipTurnUpdated { config: cfg.key(), turn_deadline_secs, penalty_bps, max_skips });
+        Ok(())
+    }
+
+    // share of the idle player's stake paid to whoever cranks forfeit_by_timeout (0 = no reward)
+    pub fn set_forfeit_keeper_bps(ctx: Context<UpdateConfig>, keeper_bps: u16) -> Result<()> {
+        require!(keeper_bps <= MAX_FORFEIT_KEEPER_BPS, GameError::InvalidRange);
+        let cfg = &mut ctx.accounts.config;
+        cfg.forfeit_keeper_bps = keeper_bps;
+        emit!(ForfeitKeeperUpdated { config: cfg.key(), keeper_bps });
+        Ok(())
+    }
+
+    // K-factor for MMR updates, snapshotted onto each battle at creation (0 = ratings frozen)
+    pub fn set_elo_k_factor(ctx: Context<UpdateConfig>, k_factor: u16) -> Result<()> {
+        require!(k_factor <= MAX_ELO_K_FACTOR, GameError::InvalidRange);
+        let cfg = &mut ctx.accounts.config;
+        cfg.elo_k_factor = k_factor;
+        emit!(EloKFactorUpdated { config: cfg.key(), k_factor });
+        Ok(())
+    }
+
+    // ------------------------
+    // Player vault: deposit once, fund offers/joins from it, collect SOL winnings into it.
+    // SOL sits on the vault PDA (tracked in sol_balance); SPL sits in ATAs owned by the vault PDA.
+    // ------------------------
+    pub fn deposit_to_vault(ctx: Context<DepositToVault>, amount: u64) -> Result<()> {
+        require!(amount > 0, GameError::InvalidRange);
+        let vault = &mut ctx.accounts.vault;
+        if vault.owner == Pubkey::default() {
+            vault.owner = ctx.accounts.owner.key();
+            vault.bump = *ctx.bumps.get("vault").unwrap_or(&0);
+        }
+        match &ctx.accounts.vault_ata {
+            None => {
+                invoke_signed(
+                    &system_instruction::transfer(&ctx.accounts.owner.key(), &vault.key(), amount),
+                    &[ctx.accounts.owner.to_account_info(), vault.to_account_info()],
+                    &[],
+                )?;
+                vault.sol_balance = vault.sol_balance.saturating_add(amount);
+            }
+            Some(vault_ata) => {
+                require!(vault_ata.owner == vault.key(), GameError::InvalidNftAta);
+                let cpi_accounts = token::Transfer {
+                    from: ctx.accounts.owner_ata.to_account_info(),
+                    to: vault_ata.to_account_info(),
+                    authority: ctx.accounts.owner.to_account_info(),
+                };
+                token::transfer(CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts), amount)?;
+            }
+        }
+        emit!(VaultDeposited { vault: vault.key(), owner: vault.owner, amount, spl: ctx.accounts.vault_ata.is_some() });
+        Ok(())
+    }
+
+    pub fn withdraw_from_vault(ctx: Context<WithdrawFromVault>, amount: u64) -> Result<()> {
+        require!(amount > 0, GameError::InvalidRange);
+        let vault = &mut ctx.accounts.vault;
+        match &ctx.accounts.vault_ata {
+            None => {
+                vault.sol_balance = vault.sol_balance.checked_sub(amount).ok_or(GameError::InsufficientVaultBalance)?;
+                pay_from_pda(&vault.to_account_info(), &ctx.accounts.owner.to_account_info(), amount)?;
+            }
+            Some(vault_ata) => {
+                let cpi_accounts = token::Transfer {
+                    from: vault_ata.to_account_info(),
+                    to: ctx.accounts.owner_ata.to_account_info(),
+                    authority: vault.to_account_info(),
+                };
+                let bump = [vault.bump];
+                let signer_seeds = &[&[b"vault", vault.owner.as_ref(), &bump][..]];
+                token::transfer(CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds), amount)?;
+            }
+        }
+        emit!(VaultWithdrawn { vault: vault.key(), owner: vault.owner, amount, spl: ctx.accounts.vault_ata.is_some() });
+        Ok(())
+    }
+
+    // ------------------------
+    // SPL escrow: one EscrowVault PDA per whitelisted mint owns a single ATA holding every offer/request/battle stake.
+    // Who owns what is tracked on the Offer / Request / Battle amounts; total_escrowed is the vault-wide sum.
+    // ------------------------
+    pub fn init_escrow_vault(ctx: Context<InitEscrowVault>) -> Result<()> {
+        let mint = ctx.accounts.mint.key();
+        require!(ctx.accounts.config.spl_whitelist.contains(&mint), GameError::SPLNotWhitelisted);
+        let escrow = &mut ctx.accounts.escrow_vault;
+        escrow.mint = mint;
+        escrow.total_escrowed = 0;
+        escrow.bump = *ctx.bumps.get("escrow_vault").unwrap_or(&0);
+        let cpi_accounts = associated_token::Create {
+            payer: ctx.accounts.payer.to_account_info(),
+            associated_token: ctx.accounts.escrow_ata.to_account_info(),
+            authority: escrow.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+            token_program: ctx.accounts.token_program.to_account_info(),
+            rent: ctx.accounts.rent.to_account_info(),
+            associated_token_program: ctx.accounts.associated_token_program.to_account_info(),
+        };
+        associated_token::create(CpiContext::new(ctx.accounts.associated_token_program.to_account_info(), cpi_accounts))?;
+        emit!(EscrowVaultCreated { escrow: escrow.key(), mint, escrow_ata: ctx.accounts.escrow_ata.key() });
+        Ok(())
+    }
+
     // ------------------------
     // Entropy pool: seed batches
     // ------------------------
@@ -113,8 +467,11 @@ pub mod battlechain_v2 {
     // ------------------------
     pub fn create_character_from_nft(
         ctx: Context<CreateCharacterFromNft>,
+        name: String,
         base_class: CharacterClass,
+        element: Element,
     ) -> Result<()> {
+        require!(name.len() <= MAX_NAME_LEN, GameError::ProfileTooLong);
         // NFT ATA checks (client must include nft_ata)
         require!(ctx.accounts.nft_ata.mint == ctx.accounts.nft_mint.key(), GameError::InvalidNftAta);
         require!(ctx.accounts.nft_ata.amount == 1, GameError::NotNftOwner);
@@ -124,15 +481,29 @@ pub mod battlechain_v2 {
         let character = &mut ctx.accounts.character;
         character.nft_mint = ctx.accounts.nft_mint.key();
         character.base_class = base_class;
-        // base stats (tuneable)
-        match base_class {
-            CharacterClass::Warrior => { character.max_hp = 120; character.current_hp = 120; character.base_damage_min = 8; character.base_damage_max = 15; character.crit_bps = 1500; },
-            CharacterClass::Assassin => { character.max_hp = 90; character.current_hp = 90; character.base_damage_min = 12; character.base_damage_max = 20; character.crit_bps = 3500; },
-            CharacterClass::Mage => { character.max_hp = 80; character.current_hp = 80; character.base_damage_min = 10; character.base_damage_max = 18; character.crit_bps = 2000; },
-            CharacterClass::Tank => { character.max_hp = 150; character.current_hp = 150; character.base_damage_min = 6; character.base_damage_max = 12; character.crit_bps = 1000; },
-            CharacterClass::Trickster => { character.max_hp = 100; character.current_hp = 100; character.base_damage_min = 8; character.base_damage_max = 16; character.crit_bps = 2500; },
-        }
-        character.defense = 0;
+        character.element = element;
+        character.name = name;
+        // base stats: the class registry when the admin has set one, built-in defaults otherwise
+        if let Some(registry) = &ctx.accounts.class_registry {
+            require!(registry.class == base_class, GameError::CharacterConstraint);
+            let st = registry.stats;
+            character.max_hp = st.max_hp;
+            character.current_hp = st.max_hp;
+            character.base_damage_min = st.base_damage_min;
+            character.base_damage_max = st.base_damage_max;
+            character.crit_bps = st.crit_bps;
+            character.dodge_bps = st.dodge_bps;
+            character.defense = st.defense;
+        } else {
+            match base_class {
+                CharacterClass::Warrior => { character.max_hp = 120; character.current_hp = 120; character.base_damage_min = 8; character.base_damage_max = 15; character.crit_bps = 1500; },
+                CharacterClass::Assassin => { character.max_hp = 90; character.current_hp = 90; character.base_damage_min = 12; character.base_damage_max = 20; character.crit_bps = 3500; },
+                CharacterClass::Mage => { character.max_hp = 80; character.current_hp = 80; character.base_damage_min = 10; character.base_damage_max = 18; character.crit_bps = 2000; },
+                CharacterClass::Tank => { character.max_hp = 150; character.current_hp = 150; character.base_damage_min = 6; character.base_damage_max = 12; character.crit_bps = 1000; },
+                CharacterClass::Trickster => { character.max_hp = 100; character.current_hp = 100; character.base_damage_min = 8; character.base_damage_max = 16; character.crit_bps = 2500; },
+            }
+            character.defense = 0;
+        }
         character.special_cooldown = 0;
         character.last_damage = 0;
         character.combo_count = 0;
@@ -148,6 +519,7 @@ pub mod battlechain_v2 {
             prog.level = 1;
             prog.mmr = 100;
             prog.last_played = 0;
+            prog.season = 0;
             prog.bump = *ctx.bumps.get("progression").unwrap_or(&0);
             emit!(ProgressionCreated { nft_mint: prog.nft_mint });
         }
@@ -158,6 +530,176 @@ pub mod battlechain_v2 {
     }
 
     // Apply a trait bundle signed by trait_authority in Config PDA. This writes compact modifiers to Character PDA.
+    // Retire: burns the Character + Progression PDAs (rent back to the owner) and converts level/rarity
+    // into crafting materials. The NFT itself stays with the owner and can be re-registered from scratch.
+    pub fn retire_character(ctx: Context<RetireCharacter>) -> Result<()> {
+        require!(ctx.accounts.nft_ata.mint == ctx.accounts.nft_mint.key(), GameError::InvalidNftAta);
+        require!(ctx.accounts.nft_ata.amount == 1, GameError::NotNftOwner);
+        require!(ctx.accounts.nft_ata.owner == ctx.accounts.owner.key(), GameError::NotNftOwner);
+        require!(ctx.accounts.character.active_battle == Pubkey::default(), GameError::CharacterInBattle);
+
+        let level = ctx.accounts.progression.level as u64;
+        let rarity = ctx.accounts.character.rarity as u64;
+        let granted = level.saturating_mul(MATERIALS_PER_LEVEL).saturating_add(rarity.saturating_mul(MATERIALS_PER_RARITY));
+
+        let materials = &mut ctx.accounts.materials;
+        if materials.owner == Pubkey::default() {
+            materials.owner = ctx.accounts.owner.key();
+            materials.bump = *ctx.bumps.get("materials").unwrap_or(&0);
+        }
+        materials.amount = materials.amount.saturating_add(granted);
+        materials.characters_retired = materials.characters_retired.saturating_add(1);
+
+        emit!(CharacterRetired { nft_mint: ctx.accounts.nft_mint.key(), owner: materials.owner, level: level as u16, rarity: rarity as u8, materials: granted });
+        Ok(())
+    }
+
+    // Holder of the character NFT sets how UIs display it
+    pub fn set_character_profile(ctx: Context<SetCharacterProfile>, name: String, emoji: String, banner: String) -> Result<()> {
+        require!(name.len() <= MAX_NAME_LEN && emoji.len() <= MAX_EMOJI_LEN && banner.len() <= MAX_BANNER_LEN, GameError::ProfileTooLong);
+        let ch = &mut ctx.accounts.character;
+        ch.name = name;
+        ch.emoji = emoji;
+        ch.banner = banner;
+        emit!(CharacterProfileSet { nft_mint: ch.nft_mint, name: ch.name.clone() });
+        Ok(())
+    }
+
+    // Fusion: the holder of both parent NFTs binds a third NFT to a new character. Class comes from one parent,
+    // element from the other, each stat is rolled between the parents' values; trait modifiers are averaged.
+    // Parents are kept but locked out of fusing again for FUSION_COOLDOWN_SECS.
+    pub fn fuse_characters(ctx: Context<FuseCharacters>) -> Result<()> {
+        let owner = ctx.accounts.owner.key();
+        let (a, b) = (&ctx.accounts.parent_a, &ctx.accounts.parent_b);
+        let child_mint = ctx.accounts.child_mint.key();
+        require!(a.nft_mint != b.nft_mint && child_mint != a.nft_mint && child_mint != b.nft_mint, GameError::CharacterConstraint);
+        let now = Clock::get()?.unix_timestamp;
+        require!(now >= a.fusion_ready_at && now >= b.fusion_ready_at, GameError::FusionOnCooldown);
+
+        // one entropy entry, split per stat by hashing with the stat's tag
+        require!(ctx.accounts.pool.total_available >= 1, GameError::NoEntropyAvailable);
+        let (seed, _) = ctx.accounts.pool.consume_mixed_u64_return_index(&owner, b"fusion", 0, 0, u32::MAX as u64)?;
+        let roll = |tag: u8, x: u64, y: u64| -> u64 {
+            let (lo, hi) = (x.min(y), x.max(y));
+            let h = hashv(&[&seed.to_le_bytes(), &child_mint.to_bytes(), &[tag]]).0;
+            let mut arr = [0u8; 8];
+            arr.copy_from_slice(&h[0..8]);
+            lo + u64::from_le_bytes(arr) % (hi - lo + 1)
+        };
+        let (class_parent, element_parent) = if roll(0, 0, 1) == 0 { (a, b) } else { (b, a) };
+
+        let child = &mut ctx.accounts.child_character;
+        child.nft_mint = child_mint;
+        child.base_class = class_parent.base_class;
+        child.element = element_parent.element;
+        child.max_hp = roll(1, a.max_hp as u64, b.max_hp as u64) as u32;
+        child.current_hp = child.max_hp;
+        let (d1, d2) = (roll(2, a.base_damage_min as u64, b.base_damage_min as u64) as u16, roll(3, a.base_damage_max as u64, b.base_damage_max as u64) as u16);
+        child.base_damage_min = d1.min(d2);
+        child.base_damage_max = d1.max(d2);
+        child.crit_bps = roll(4, a.crit_bps as u64, b.crit_bps as u64) as u16;
+        child.crit_multiplier_fp = a.crit_multiplier_fp.max(b.crit_multiplier_fp);
+        child.dodge_bps = roll(5, a.dodge_bps as u64, b.dodge_bps as u64) as u16;
+        child.defense = roll(6, a.defense as u64, b.defense as u64) as u16;
+        child.armor_pen_bps = roll(7, a.armor_pen_bps as u64, b.armor_pen_bps as u64) as u16;
+        child.crit_resist_bps = roll(8, a.crit_resist_bps as u64, b.crit_resist_bps as u64) as u16;
+        child.mod_attack_bps = ((a.mod_attack_bps as i32 + b.mod_attack_bps as i32) / 2) as i16;
+        child.mod_defense_bps = ((a.mod_defense_bps as i32 + b.mod_defense_bps as i32) / 2) as i16;
+        child.mod_crit_bps = ((a.mod_crit_bps as i32 + b.mod_crit_bps as i32) / 2) as i16;
+        child.rarity = a.rarity.max(b.rarity);
+        child.created_at = now;
+        child.fusion_ready_at = now.saturating_add(FUSION_COOLDOWN_SECS);
+        child.bump = *ctx.bumps.get("child_character").unwrap_or(&0);
+
+        let prog = &mut ctx.accounts.child_progression;
+        prog.nft_mint = child_mint;
+        prog.level = 1;
+        prog.mmr = 100;
+        prog.bump = *ctx.bumps.get("child_progression").unwrap_or(&0);
+
+        ctx.accounts.parent_a.fusion_ready_at = now.saturating_add(FUSION_COOLDOWN_SECS);
+        ctx.accounts.parent_b.fusion_ready_at = now.saturating_add(FUSION_COOLDOWN_SECS);
+        emit!(CharactersFused { parent_a: ctx.accounts.parent_a.nft_mint, parent_b: ctx.accounts.parent_b.nft_mint, child_mint, owner, base_class: ctx.accounts.child_character.base_class });
+        Ok(())
+    }
+
+    // Permissionless: grow a Progression created before `season` and the achievement counters were added to the
+    // current layout. The new fields start at zero, the bump moves to the end, and the payer covers the extra rent.
+    pub fn migrate_progression(ctx: Context<MigrateProgression>) -> Result<()> {
+        let info = ctx.accounts.progression.to_account_info();
+        let old_len = info.data_len();
+        let new_len = 8 + Progression::INIT_SPACE;
+        require!(old_len == 8 + PROGRESSION_V1_SPACE || old_len == 8 + PROGRESSION_V2_SPACE, GameError::ProgressionAlreadyMigrated);
+        require!(info.try_borrow_data()?[..8] == Progression::DISCRIMINATOR, GameError::ProgressionAlreadyMigrated);
+
+        let top_up = Rent::get()?.minimum_balance(new_len).saturating_sub(info.lamports());
+        if top_up > 0 {
+            invoke_signed(
+                &system_instruction::transfer(ctx.accounts.payer.key, info.key, top_up),
+                &[ctx.accounts.payer.to_account_info(), info.clone(), ctx.accounts.system_program.to_account_info()],
+                &[],
+            )?;
+        }
+        info.realloc(new_len, false)?;
+        let mut data = info.try_borrow_mut_data()?;
+        // the old layout ended in the bump; everything from there up to the new bump slot is a new (zeroed) field
+        let bump = data[old_len - 1];
+        data[old_len - 1..new_len - 1].fill(0);
+        data[new_len - 1] = bump;
+        emit!(ProgressionMigrated { nft_mint: ctx.accounts.character.nft_mint, old_space: old_len as u32 });
+        Ok(())
+    }
+
+    // Owner allocates unspent stat points (earned on level-up) into one stat
+    pub fn spend_stat_points(ctx: Context<SpendStatPoints>, stat: StatKind, points: u16) -> Result<()> {
+        let prog = &mut ctx.accounts.progression;
+        let ch = &mut ctx.accounts.character;
+        require!(points > 0 && points <= prog.stat_points, GameError::NotEnoughStatPoints);
+        let spent = prog.points_spent[stat as usize].saturating_add(points);
+        require!(spent <= prog.level.saturating_mul(STAT_CAP_PER_LEVEL), GameError::StatCapReached);
+        match stat {
+            StatKind::Hp => {
+                let gain = STAT_HP_PER_POINT.saturating_mul(points as u32);
+                ch.max_hp = ch.max_hp.saturating_add(gain);
+                ch.current_hp = ch.current_hp.saturating_add(gain);
+            },
+            StatKind::Damage => {
+                ch.base_damage_min = ch.base_damage_min.saturating_add(STAT_DAMAGE_PER_POINT.saturating_mul(points));
+                ch.base_damage_max = ch.base_damage_max.saturating_add(STAT_DAMAGE_PER_POINT.saturating_mul(points));
+            },
+            StatKind::Crit => ch.crit_bps = ch.crit_bps.saturating_add(STAT_CRIT_BPS_PER_POINT.saturating_mul(points)).min(10_000),
+            StatKind::Dodge => ch.dodge_bps = ch.dodge_bps.saturating_add(STAT_DODGE_BPS_PER_POINT.saturating_mul(points)).min(10_000),
+            StatKind::Defense => ch.defense = ch.defense.saturating_add(STAT_DEFENSE_PER_POINT.saturating_mul(points)),
+            StatKind::ArmorPen => ch.armor_pen_bps = ch.armor_pen_bps.saturating_add(STAT_ARMOR_PEN_BPS_PER_POINT.saturating_mul(points)).min(10_000),
+            StatKind::CritResist => ch.crit_resist_bps = ch.crit_resist_bps.saturating_add(STAT_CRIT_RESIST_BPS_PER_POINT.saturating_mul(points)).min(10_000),
+        }
+        prog.stat_points -= points;
+        prog.points_spent[stat as usize] = spent;
+        emit!(StatPointsSpent { nft_mint: ch.nft_mint, stat: stat as u8, points, remaining: prog.stat_points });
+        Ok(())
+    }
+
+    // Merge: same owner, same class. Moves a share of the source's lifetime XP onto the target
+    // (levelling it up as needed) and burns the source Character + Progression PDAs.
+    pub fn merge_characters(ctx: Context<MergeCharacters>) -> Result<()> {
+        let owner = ctx.accounts.owner.key();
+        require!(ctx.accounts.source_mint.key() != ctx.accounts.target_mint.key(), GameError::CharacterConstraint);
+        for (ata, mint) in [(&ctx.accounts.source_nft_ata, &ctx.accounts.source_mint), (&ctx.accounts.target_nft_ata, &ctx.accounts.target_mint)] {
+            require!(ata.mint == mint.key(), GameError::InvalidNftAta);
+            require!(ata.amount == 1 && ata.owner == owner, GameError::NotNftOwner);
+        }
+        require!(ctx.accounts.source_character.base_class == ctx.accounts.target_character.base_class, GameError::CharacterConstraint);
+        require!(ctx.accounts.source_character.active_battle == Pubkey::default(), GameError::CharacterInBattle);
+
+        let carried = lifetime_xp(&ctx.accounts.source_progression).saturating_mul(MERGE_XP_SHARE_BPS) / 10_000;
+        let target_prog = &mut ctx.accounts.target_progression;
+        target_prog.xp = target_prog.xp.saturating_add(carried);
+        level_up_if_needed(target_prog, &mut ctx.accounts.target_character)?;
+
+        emit!(CharactersMerged { source_mint: ctx.accounts.source_mint.key(), target_mint: ctx.accounts.target_mint.key(), owner, xp_carried: carried, target_level: ctx.accounts.target_progression.level });
+        Ok(())
+    }
+
     pub fn apply_trait_bundle(ctx: Context<ApplyTraitBundle>, bundle: TraitBundle) -> Result<()> {
         // Only Config.trait_authority may sign this instruction
         let cfg = &ctx.accounts.config;
@@ -168,11 +710,70 @@ pub mod battlechain_v2 {
         ch.mod_attack_bps = ch.mod_attack_bps.saturating_add(bundle.attack_bps as i16);
         ch.mod_defense_bps = ch.mod_defense_bps.saturating_add(bundle.defense_bps as i16);
         ch.mod_crit_bps = ch.mod_crit_bps.saturating_add(bundle.crit_bps as i16);
+        ch.armor_pen_bps = (ch.armor_pen_bps as i32 + bundle.armor_pen_bps as i32).clamp(0, 10_000) as u16;
+        ch.crit_resist_bps = (ch.crit_resist_bps as i32 + bundle.crit_resist_bps as i32).clamp(0, 10_000) as u16;
         ch.rarity = bundle.rarity;
         emit!(TraitApplied { nft_mint: ch.nft_mint, by: ctx.accounts.trait_authority.key() });
         Ok(())
     }
 
+    // ------------------------
+    // Equipment (item NFTs)
+    // ------------------------
+    // trait_authority binds modifiers to an item NFT mint
+    pub fn register_equipment(ctx: Context<RegisterEquipment>, mods: ItemModifiers) -> Result<()> {
+        require!(ctx.accounts.trait_authority.key() == ctx.accounts.config.trait_authority, GameError::Unauthorized);
+        let equipment = &mut ctx.accounts.equipment;
+        equipment.item_mint = ctx.accounts.item_mint.key();
+        equipment.mods = mods;
+        equipment.equipped_on = Pubkey::default();
+        equipment.bump = *ctx.bumps.get("equipment").unwrap_or(&0);
+        emit!(EquipmentRegistered { item_mint: equipment.item_mint, mods });
+        Ok(())
+    }
+
+    // Holder of both NFTs puts the item into an empty slot; the modifiers are copied onto the Character
+    pub fn equip_item(ctx: Context<EquipItem>, slot: u8) -> Result<()> {
+        let slot = slot as usize;
+        require!(slot < EQUIPMENT_SLOTS, GameError::InvalidIndex);
+        let equipment = &mut ctx.accounts.equipment;
+        let ch = &mut ctx.accounts.character;
+        require!(equipment.equipped_on == Pubkey::default(), GameError::ItemAlreadyEquipped);
+        require!(ch.equipment[slot].item_mint == Pubkey::default(), GameError::SlotOccupied);
+        ch.equipment[slot] = EquipSlot { item_mint: equipment.item_mint, mods: equipment.mods };
+        equipment.equipped_on = ch.key();
+        emit!(ItemEquipped { nft_mint: ch.nft_mint, item_mint: equipment.item_mint, slot: slot as u8 });
+        Ok(())
+    }
+
+    // Either the character's holder or the item's holder (e.g. after the item NFT was sold) can take it off
+    pub fn unequip_item(ctx: Context<UnequipItem>) -> Result<()> {
+        let equipment = &mut ctx.accounts.equipment;
+        let ch = &mut ctx.accounts.character;
+        let proof = &ctx.accounts.holder_ata;
+        require!(proof.mint == ch.nft_mint || proof.mint == equipment.item_mint, GameError::InvalidNftAta);
+        require!(equipment.equipped_on == ch.key(), GameError::ItemNotEquipped);
+        let slot = ch.equipment.iter().position(|s| s.item_mint == equipment.item_mint).ok_or(GameError::ItemNotEquipped)?;
+        ch.equipment[slot] = EquipSlot::default();
+        equipment.equipped_on = Pubkey::default();
+        emit!(ItemUnequipped { nft_mint: ch.nft_mint, item_mint: equipment.item_mint, slot: slot as u8 });
+        Ok(())
+    }
+
+    // Pick the abilities a character brings into battles (snapshotted at join / approve)
+    pub fn set_loadout(ctx: Context<SetLoadout>, abilities: Vec<Ability>) -> Result<()> {
+        require!(!abilities.is_empty() && abilities.len() <= MAX_LOADOUT_ABILITIES, GameError::InvalidLoadout);
+        for (i, ability) in abilities.iter().enumerate() {
+            require!(!abilities[..i].contains(ability), GameError::InvalidLoadout);
+        }
+        let loadout = &mut ctx.accounts.loadout;
+        loadout.nft_mint = ctx.accounts.character.nft_mint;
+        loadout.abilities = abilities;
+        loadout.bump = *ctx.bumps.get("loadout").unwrap_or(&0);
+        emit!(LoadoutSet { nft_mint: loadout.nft_mint, abilities: loadout.abilities.iter().map(|a| *a as u8).collect() });
+        Ok(())
+    }
+
     // ------------------------
     // Offers / Requests (SOL or SPL)
     // ------------------------
@@ -186,14 +787,40 @@ pub mod battlechain_v2 {
         allowed_classes: Vec<CharacterClass>,
         auto_approve: bool,
         start_ts: i64,
+        recurring: bool,
+        rounds: u8,
+        commit_reveal: bool,
+        async_mode: bool,
+        reconnect_grace_secs: i64,
+        target_opponent: Option<Pubkey>,
+        min_mmr: u64,
+        max_mmr: u64,
     ) -> Result<()> {
         let cfg = &ctx.accounts.config;
+        require!(rounds % 2 == 1 && rounds <= MAX_ROUNDS, GameError::InvalidRange);
+        // async battles are a single simulated round with their own commit-reveal of the whole strategy
+        require!(!async_mode || (rounds == 1 && !commit_reveal), GameError::InvalidRange);
+        require!(reconnect_grace_secs >= 0, GameError::InvalidRange);
+        require!(allowed_classes.len() <= MAX_ALLOWED_CLASSES, GameError::InvalidRange);
+        require!(min_mmr <= max_mmr, GameError::InvalidRange);
         // If SPL, enforce whitelist
         if let Currency::SPL(mint) = currency {
             require!(cfg.spl_whitelist.contains(&mint), GameError::SPLNotWhitelisted);
         }
+        // auto-approved joins start the battle without the creator, so their character is fixed up front
+        let creator_character = if auto_approve {
+            let (ch, ata) = match (&ctx.accounts.creator_character, &ctx.accounts.creator_nft_ata) {
+                (Some(ch), Some(ata)) => (ch, ata),
+                _ => return err!(GameError::AutoApproveAccountsMissing),
+            };
+            require!(ata.mint == ch.nft_mint && ata.owner == ctx.accounts.creator.key() && ata.amount == 1, GameError::NotNftOwner);
+            ch.key()
+        } else {
+            Pubkey::default()
+        };
         let clock = Clock::get()?;
         require!(start_ts >= clock.unix_timestamp, GameError::InvalidTimestamp);
+        consume_rate_limit(&mut ctx.accounts.rate_limit, ctx.accounts.creator.key(), cfg.max_actions_per_hour, clock.unix_timestamp, *ctx.bumps.get("rate_limit").unwrap_or(&0))?;
 
         let offer = &mut ctx.accounts.offer;
         offer.creator = ctx.accounts.creator.key();
@@ -207,49 +834,58 @@ pub mod battlechain_v2 {
         offer.start_ts = start_ts;
         offer.created_at = clock.unix_timestamp;
         offer.is_active = true;
+        offer.recurring = recurring;
+        offer.last_battle = Pubkey::default();
+        offer.relist_count = 0;
+        offer.rounds = rounds;
+        offer.commit_reveal = commit_reveal;
+        offer.async_mode = async_mode;
+        offer.reconnect_grace_secs = reconnect_grace_secs;
+        offer.creator_character = creator_character;
+        offer.target_opponent = target_opponent;
+        offer.min_mmr = min_mmr;
+        offer.max_mmr = max_mmr;
+        offer.requests = Vec::new();
+        offer.request_count = 0;
+        offer.registry_page = None;
         offer.bump = *ctx.bumps.get("offer").unwrap_or(&0);
 
-        // For SOL: require creator funds the offer PDA (creator pays txn; program will transfer lamports to offer PDA via CPI)
-        // For SPL: create an escrow ATA for Offer PDA and transfer tokens from creator's ATA to it
+        // optional lobby listing
+        if let Some(registry) = ctx.accounts.registry.as_mut() {
+            require!(registry.listings.len() < OFFER_REGISTRY_PAGE_SIZE, GameError::RegistryPageFull);
+            registry.listings.push(OfferListing::of(offer));
+            offer.registry_page = Some(registry.page);
+            emit!(OfferListed { offer: offer.key(), page: registry.page });
+        }
+
+        // For SOL: lamports go to the offer's zero-data SOL escrow PDA (from the creator's wallet or vault)
+        // For SPL: transfer tokens from creator's ATA (or vault ATA) into the mint's global escrow ATA
         match currency {
             Currency::SOL => {
                 if stake_amount > 0 {
-                    // transfer lamports from creator to offer PDA (creator pays)
-                    invoke_signed(
-                        &system_instruction::transfer(&ctx.accounts.creator.key(), &ctx.accounts.offer.key(), stake_amount),
-                        &[ctx.accounts.creator.to_account_info(), ctx.accounts.offer.to_account_info()],
-                        &[],
-                    )?;
+                    let (escrow, _) = sol_escrow(&ctx.accounts.offer_sol_escrow, &offer.key())?;
+                    if let Some(vault) = ctx.accounts.creator_vault.as_mut() {
+                        // draw from the creator's vault instead of the wallet
+                        require!(vault.owner == ctx.accounts.creator.key(), GameError::Unauthorized);
+                        vault.sol_balance = vault.sol_balance.checked_sub(stake_amount).ok_or(GameError::InsufficientVaultBalance)?;
+                        sol_escrow_deposit(&vault.to_account_info(), &escrow, stake_amount)?;
+                    } else {
+                        sol_escrow_deposit(&ctx.accounts.creator.to_account_info(), &escrow, stake_amount)?;
+                    }
                 }
             },
             Currency::SPL(mint) => {
-                // create associated token account for offer PDA and transfer tokens
-                // Client must pass creator_token_ata and offer_escrow_ata (or program creates ATA paid by creator)
-                // Use CPI to create associated token account for offer PDA if needed
-                if stake_amount > 0 {
-                    // create offer escrow ATA if not already
-                    if ctx.accounts.offer_escrow.to_account_info().data_is_empty() {
-                        let cpi_accounts = associated_token::Create {
-                            payer: ctx.accounts.creator.to_account_info(),
-                            associated_token: ctx.accounts.offer_escrow.to_account_info(),
-                            authority: ctx.accounts.offer.to_account_info(),
-                            mint: ctx.accounts.currency_mint.as_ref().unwrap().to_account_info(),
-                            system_program: ctx.accounts.system_program.to_account_info(),
-                            token_program: ctx.accounts.token_program.to_account_info(),
-                            rent: ctx.accounts.rent.to_account_info(),
-                            associated_token_program: ctx.accounts.associated_token_program.to_account_info(),
-                        };
-                        let cpi_ctx = CpiContext::new(ctx.accounts.associated_token_program.to_account_info(), cpi_accounts);
-                        associated_token::create(cpi_ctx)?;
-                    }
-                    // transfer tokens from creator_ata -> offer_escrow
-                    let cpi_accounts = token::Transfer {
-                        from: ctx.accounts.creator_ata.to_account_info(),
-                        to: ctx.accounts.offer_escrow.to_account_info(),
-                        authority: ctx.accounts.creator.to_account_info(),
-                    };
-                    let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
-                    token::transfer(cpi_ctx, stake_amount)?;
+                // stake joins the shared per-mint escrow; offer.stake_amount is the offer's ledger entry
+                let (escrow, escrow_ata) = spl_escrow(&mut ctx.accounts.escrow_vault, &ctx.accounts.escrow_ata, mint)?;
+                let token_program = ctx.accounts.token_program.to_account_info();
+                if let Some(vault) = &ctx.accounts.creator_vault {
+                    // transfer tokens from the creator's vault ATA (vault PDA signs)
+                    require!(vault.owner == ctx.accounts.creator.key(), GameError::Unauthorized);
+                    let bump = [vault.bump];
+                    let signer_seeds = &[&[b"vault", vault.owner.as_ref(), &bump][..]];
+                    escrow_deposit(escrow, escrow_ata, ctx.accounts.creator_vault_ata.to_account_info(), vault.to_account_info(), signer_seeds, token_program, stake_amount)?;
+                } else {
+                    escrow_deposit(escrow, escrow_ata, ctx.accounts.creator_ata.to_account_info(), ctx.accounts.creator.to_account_info(), &[], token_program, stake_amount)?;
                 }
             }
         }
@@ -258,20 +894,25 @@ pub mod battlechain_v2 {
         Ok(())
     }
 
-    // Challenger joins offer; for SPL creates request_escrow ATA and transfers tokens
+    // Challenger joins offer; for SPL transfers tokens into the mint's global escrow ATA
     pub fn join_battle_offer(ctx: Context<JoinBattleOffer>, offered_stake: u64) -> Result<()> {
         let offer = &mut ctx.accounts.offer;
         require!(offer.is_active, GameError::OfferNotActive);
+        // private offer: only the targeted wallet can take it
+        require!(offer.target_opponent.map_or(true, |t| t == ctx.accounts.challenger.key()), GameError::NotTargetOpponent);
 
         // validate progression & character
         let prog = &ctx.accounts.progression;
         require!(prog.level >= offer.min_level && prog.level <= offer.max_level, GameError::CharacterConstraint);
+        require!(prog.mmr >= offer.min_mmr && prog.mmr <= offer.max_mmr, GameError::MmrOutOfRange);
         if !offer.allowed_classes.is_empty() {
             let ch = &ctx.accounts.character;
             require!(offer.allowed_classes.contains(&ch.base_class), GameError::CharacterConstraint);
         }
+        require!(ctx.accounts.character.active_battle == Pubkey::default(), GameError::CharacterInBattle);
 
         let clock = Clock::get()?;
+        consume_rate_limit(&mut ctx.accounts.rate_limit, ctx.accounts.challenger.key(), ctx.accounts.config.max_actions_per_hour, clock.unix_timestamp, *ctx.bumps.get("rate_limit").unwrap_or(&0))?;
         let request = &mut ctx.accounts.request;
         request.offer = offer.key();
         request.challenger = ctx.accounts.challenger.key();
@@ -279,46 +920,90 @@ pub mod battlechain_v2 {
         request.offered_stake = offered_stake;
         request.created_at = clock.unix_timestamp;
         request.status = JoinStatus::Pending;
+        request.abilities = ctx.accounts.loadout.as_ref().map(|l| l.abilities.clone()).unwrap_or_default();
         request.bump = *ctx.bumps.get("request").unwrap_or(&0);
 
         match offer.currency {
             Currency::SOL => {
                 if offered_stake > 0 {
-                    invoke_signed(
-                        &system_instruction::transfer(&ctx.accounts.challenger.key(), &ctx.accounts.request.key(), offered_stake),
-                        &[ctx.accounts.challenger.to_account_info(), ctx.accounts.request.to_account_info()],
-                        &[],
-                    )?;
+                    let (escrow, _) = sol_escrow(&ctx.accounts.request_sol_escrow, &request.key())?;
+                    if let Some(vault) = ctx.accounts.challenger_vault.as_mut() {
+                        require!(vault.owner == ctx.accounts.challenger.key(), GameError::Unauthorized);
+                        vault.sol_balance = vault.sol_balance.checked_sub(offered_stake).ok_or(GameError::InsufficientVaultBalance)?;
+                        sol_escrow_deposit(&vault.to_account_info(), &escrow, offered_stake)?;
+                    } else {
+                        sol_escrow_deposit(&ctx.accounts.challenger.to_account_info(), &escrow, offered_stake)?;
+                    }
                 }
             },
             Currency::SPL(mint) => {
-                // create request_escrow ATA for request PDA and transfer tokens
-                if offered_stake > 0 {
-                    if ctx.accounts.request_escrow.to_account_info().data_is_empty() {
-                        let cpi_accounts = associated_token::Create {
-                            payer: ctx.accounts.challenger.to_account_info(),
-                            associated_token: ctx.accounts.request_escrow.to_account_info(),
-                            authority: ctx.accounts.request.to_account_info(),
-                            mint: ctx.accounts.currency_mint.as_ref().unwrap().to_account_info(),
-                            system_program: ctx.accounts.system_program.to_account_info(),
-                            token_program: ctx.accounts.token_program.to_account_info(),
-                            rent: ctx.accounts.rent.to_account_info(),
-                            associated_token_program: ctx.accounts.associated_token_program.to_account_info(),
-                        };
-                        let cpi_ctx = CpiContext::new(ctx.accounts.associated_token_program.to_account_info(), cpi_accounts);
-                        associated_token::create(cpi_ctx)?;
-                    }
-                    let cpi_accounts = token::Transfer {
-                        from: ctx.accounts.challenger_ata.to_account_info(),
-                        to: ctx.accounts.request_escrow.to_account_info(),
-                        authority: ctx.accounts.challenger.to_account_info(),
-                    };
-                    token::transfer(CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts), offered_stake)?;
+                // stake joins the shared per-mint escrow; request.offered_stake is the request's ledger entry
+                let (escrow, escrow_ata) = spl_escrow(&mut ctx.accounts.escrow_vault, &ctx.accounts.escrow_ata, mint)?;
+                let token_program = ctx.accounts.token_program.to_account_info();
+                if let Some(vault) = &ctx.accounts.challenger_vault {
+                    require!(vault.owner == ctx.accounts.challenger.key(), GameError::Unauthorized);
+                    let bump = [vault.bump];
+                    let signer_seeds = &[&[b"vault", vault.owner.as_ref(), &bump][..]];
+                    escrow_deposit(escrow, escrow_ata, ctx.accounts.challenger_vault_ata.to_account_info(), vault.to_account_info(), signer_seeds, token_program, offered_stake)?;
+                } else {
+                    escrow_deposit(escrow, escrow_ata, ctx.accounts.challenger_ata.to_account_info(), ctx.accounts.challenger.to_account_info(), &[], token_program, offered_stake)?;
                 }
             }
         }
 
+        offer.request_count = offer.request_count.saturating_add(1);
         emit!(JoinRequested { offer: offer.key(), request: ctx.accounts.request.key(), challenger: request.challenger, stake: offered_stake });
+        if !offer.auto_approve {
+            require!(offer.requests.len() < MAX_PENDING_REQUESTS, GameError::OfferRequestsFull);
+            offer.requests.push(request.key());
+            return Ok(());
+        }
+
+        // auto_approve: approve the request on the spot (same battle setup and stake moves as approve_challenger)
+        let (battle, creator_character, pool) = match (ctx.accounts.battle.as_mut(), ctx.accounts.creator_character.as_mut(), ctx.accounts.pool.as_mut()) {
+            (Some(b), Some(c), Some(p)) => (b, c, p),
+            _ => return err!(GameError::AutoApproveAccountsMissing),
+        };
+        let creator_nft_ata = ctx.accounts.creator_nft_ata.as_ref().ok_or(GameError::AutoApproveAccountsMissing)?;
+        require!(creator_nft_ata.mint == creator_character.nft_mint && creator_nft_ata.owner == offer.creator && creator_nft_ata.amount == 1, GameError::NotNftOwner);
+        if let Some(loadout) = ctx.accounts.creator_loadout.as_ref() {
+            let expected = Pubkey::create_program_address(&[b"loadout", creator_character.nft_mint.as_ref(), &[loadout.bump]], &crate::ID).map_err(|_| GameError::InvalidLoadout)?;
+            require_keys_eq!(loadout.key(), expected, GameError::InvalidLoadout);
+        }
+        if let Some(registry) = ctx.accounts.creator_class_registry.as_ref() {
+            let expected = Pubkey::create_program_address(&[b"class_registry", &[creator_character.base_class as u8], &[registry.bump]], &crate::ID).map_err(|_| GameError::CharacterConstraint)?;
+            require_keys_eq!(registry.key(), expected, GameError::CharacterConstraint);
+        }
+        require!(creator_character.active_battle == Pubkey::default(), GameError::CharacterInBattle);
+        let battle_key = battle.key();
+        creator_character.active_battle = battle_key;
+        ctx.accounts.character.active_battle = battle_key;
+
+        let creator_signature = ctx.accounts.creator_class_registry.as_ref().map(|r| r.stats.signature).unwrap_or(Ability::signature(creator_character.base_class));
+        let challenger_signature = ctx.accounts.challenger_class_registry.as_ref().map(|r| r.stats.signature).unwrap_or(Ability::signature(ctx.accounts.character.base_class));
+        let creator_abilities = ctx.accounts.creator_loadout.as_ref().map(|l| l.abilities.clone()).unwrap_or(vec![creator_signature]);
+        let challenger_abilities = if request.abilities.is_empty() { vec![challenger_signature] } else { request.abilities.clone() };
+        init_battle(battle, offer, &ctx.accounts.config, request.challenger, [&*creator_character, &ctx.accounts.character], [creator_abilities, challenger_abilities], offered_stake, offer.start_ts, clock.unix_timestamp, *ctx.bumps.get("battle").unwrap_or(&0));
+
+        let total_stake = offer.stake_amount.saturating_add(offered_stake);
+        // SOL: offer/request escrow PDAs -> battle escrow PDA; SPL: ledger only
+        if offer.currency == Currency::SOL && total_stake > 0 {
+            let (battle_escrow, _) = sol_escrow(&ctx.accounts.battle_sol_escrow, &battle_key)?;
+            if offer.stake_amount > 0 {
+                let (offer_escrow, bump) = sol_escrow(&ctx.accounts.offer_sol_escrow, &offer.key())?;
+                sol_escrow_release(&offer_escrow, &offer.key(), bump, &battle_escrow, offer.stake_amount)?;
+            }
+            if offered_stake > 0 {
+                let (request_escrow, bump) = sol_escrow(&ctx.accounts.request_sol_escrow, &request.key())?;
+                sol_escrow_release(&request_escrow, &request.key(), bump, &battle_escrow, offered_stake)?;
+            }
+        }
+
+        request.status = JoinStatus::Approved;
+        offer.is_active = false;
+        offer.last_battle = battle_key;
+        roll_battle_opening(pool, &request.challenger, battle)?;
+        emit!(BattleCreated { battle: battle_key, player1: battle.player1, player2: battle.player2, first_turn: battle.current_turn, stake_total: total_stake });
         Ok(())
     }
 
@@ -331,35 +1016,47 @@ pub mod battlechain_v2 {
         // refund based on currency
         match offer.currency {
             Currency::SOL => {
-                let bal = ctx.accounts.request.to_account_info().lamports();
-                if bal > 0 {
-                    invoke_signed(
-                        &system_instruction::transfer(&ctx.accounts.request.key(), &ctx.accounts.challenger.key(), bal),
-                        &[ctx.accounts.request.to_account_info(), ctx.accounts.challenger.to_account_info()],
-                        &[],
-                    )?;
+                if request.offered_stake > 0 {
+                    let (escrow, bump) = sol_escrow(&ctx.accounts.request_sol_escrow, &request.key())?;
+                    sol_escrow_release(&escrow, &request.key(), bump, &ctx.accounts.challenger.to_account_info(), request.offered_stake)?;
                 }
             },
-            Currency::SPL(_) => {
-                // transfer tokens back from request_escrow -> challenger_ata and close escrow
-                let amount = ctx.accounts.request_escrow.amount;
-                if amount > 0 {
-                    let cpi_accounts = token::Transfer {
-                        from: ctx.accounts.request_escrow.to_account_info(),
-                        to: ctx.accounts.challenger_ata.to_account_info(),
-                        authority: ctx.accounts.request.to_account_info(),
-                    };
-                    let signer_seeds = &[b"request", offer.key().as_ref(), ctx.accounts.challenger.key.as_ref(), &[request.bump]];
-                    token::transfer(CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, &[signer_seeds]), amount)?;
-                }
-                // close request_escrow (optional)
+            Currency::SPL(mint) => {
+                // release the request's ledger share from the per-mint escrow back to challenger_ata
+                let (escrow, escrow_ata) = spl_escrow(&mut ctx.accounts.escrow_vault, &ctx.accounts.escrow_ata, mint)?;
+                escrow_release(escrow, escrow_ata, ctx.accounts.challenger_ata.to_account_info(), ctx.accounts.token_program.to_account_info(), request.offered_stake)?;
             }
         }
         request.status = JoinStatus::Withdrawn;
+        let request_key = request.key();
+        ctx.accounts.offer.requests.retain(|r| *r != request_key);
         emit!(RequestWithdrawn { request: request.key(), by: ctx.accounts.challenger.key() });
         Ok(())
     }
 
+    // Creator declines a pending request; the challenger's stake goes back to their wallet (SPL: their ATA)
+    pub fn reject_challenger(ctx: Context<RejectChallenger>) -> Result<()> {
+        let request = &mut ctx.accounts.request;
+        require!(request.status == JoinStatus::Pending, GameError::InvalidRequestState);
+        match ctx.accounts.offer.currency {
+            Currency::SOL => {
+                if request.offered_stake > 0 {
+                    let (escrow, bump) = sol_escrow(&ctx.accounts.request_sol_escrow, &request.key())?;
+                    sol_escrow_release(&escrow, &request.key(), bump, &ctx.accounts.challenger.to_account_info(), request.offered_stake)?;
+                }
+            },
+            Currency::SPL(mint) => {
+                let (escrow, escrow_ata) = spl_escrow(&mut ctx.accounts.escrow_vault, &ctx.accounts.escrow_ata, mint)?;
+                escrow_release(escrow, escrow_ata, ctx.accounts.challenger_ata.to_account_info(), ctx.accounts.token_program.to_account_info(), request.offered_stake)?;
+            }
+        }
+        request.status = JoinStatus::Rejected;
+        let request_key = request.key();
+        ctx.accounts.offer.requests.retain(|r| *r != request_key);
+        emit!(ChallengerRejected { offer: ctx.accounts.offer.key(), request: request.key(), challenger: request.challenger, refunded: request.offered_stake });
+        Ok(())
+    }
+
     // Cancel offer (creator), refunds if no approved request
     pub fn cancel_offer(ctx: Context<CancelOffer>) -> Result<()> {
         let offer = &mut ctx.accounts.offer;
@@ -369,35 +1066,157 @@ pub mod battlechain_v2 {
         // refund stake to creator (SOL or SPL)
         match offer.currency {
             Currency::SOL => {
-                let bal = ctx.accounts.offer.to_account_info().lamports();
-                if bal > 0 {
-                    invoke_signed(
-                        &system_instruction::transfer(&ctx.accounts.offer.key(), &ctx.accounts.creator.key(), bal),
-                        &[ctx.accounts.offer.to_account_info(), ctx.accounts.creator.to_account_info()],
-                        &[],
-                    )?;
+                if offer.stake_amount > 0 {
+                    let (escrow, bump) = sol_escrow(&ctx.accounts.offer_sol_escrow, &offer.key())?;
+                    sol_escrow_release(&escrow, &offer.key(), bump, &ctx.accounts.creator.to_account_info(), offer.stake_amount)?;
                 }
             },
-            Currency::SPL(_) => {
-                // transfer from offer_escrow -> creator_ata with PDA signer
-                let amount = ctx.accounts.offer_escrow.amount;
-                if amount > 0 {
-                    let cpi_accounts = token::Transfer {
-                        from: ctx.accounts.offer_escrow.to_account_info(),
-                        to: ctx.accounts.creator_ata.to_account_info(),
-                        authority: ctx.accounts.offer.to_account_info(),
-                    };
-                    let signer_seeds = &[b"offer", ctx.accounts.creator.key.as_ref(), &offer.offer_nonce.to_le_bytes(), &[offer.bump]];
-                    token::transfer(CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, &[signer_seeds]), amount)?;
-                }
+            Currency::SPL(mint) => {
+                // release the offer's ledger share from the per-mint escrow back to creator_ata
+                let (escrow, escrow_ata) = spl_escrow(&mut ctx.accounts.escrow_vault, &ctx.accounts.escrow_ata, mint)?;
+                escrow_release(escrow, escrow_ata, ctx.accounts.creator_ata.to_account_info(), ctx.accounts.token_program.to_account_info(), offer.stake_amount)?;
             }
         }
         offer.is_active = false;
+        offer.stake_amount = 0;
+        if let Some(registry) = ctx.accounts.registry.as_mut() {
+            unlist_offer(registry, offer.key());
+        }
         emit!(OfferCancelled { offer: ctx.accounts.offer.key(), by: ctx.accounts.creator.key() });
         Ok(())
     }
 
-    // Approve challenger -> create battle, move stakes (SOL or SPL) into battle escrow, pick first mover (monotonic entropy)
+    // Creator edits an open offer before anyone is waiting on it; a stake change is topped up from / refunded to the
+    // creator's wallet (SPL: creator_ata)
+    pub fn update_offer(ctx: Context<UpdateOffer>, min_level: u16, max_level: u16, allowed_classes: Vec<CharacterClass>, start_ts: i64, stake_amount: u64) -> Result<()> {
+        let offer = &mut ctx.accounts.offer;
+        require!(offer.is_active, GameError::OfferNotActive);
+        require!(offer.requests.is_empty(), GameError::OfferHasPendingRequests);
+        require!(min_level <= max_level && allowed_classes.len() <= MAX_ALLOWED_CLASSES, GameError::InvalidRange);
+        require!(start_ts >= Clock::get()?.unix_timestamp, GameError::InvalidTimestamp);
+        let old_stake = offer.stake_amount;
+        match offer.currency {
+            Currency::SOL => {
+                if stake_amount != old_stake {
+                    let (escrow, bump) = sol_escrow(&ctx.accounts.offer_sol_escrow, &offer.key())?;
+                    if stake_amount > old_stake {
+                        sol_escrow_deposit(&ctx.accounts.creator.to_account_info(), &escrow, stake_amount - old_stake)?;
+                    } else {
+                        sol_escrow_release(&escrow, &offer.key(), bump, &ctx.accounts.creator.to_account_info(), old_stake - stake_amount)?;
+                    }
+                }
+            },
+            Currency::SPL(mint) => {
+                if stake_amount != old_stake {
+                    let (escrow, escrow_ata) = spl_escrow(&mut ctx.accounts.escrow_vault, &ctx.accounts.escrow_ata, mint)?;
+                    let token_program = ctx.accounts.token_program.to_account_info();
+                    if stake_amount > old_stake {
+                        escrow_deposit(escrow, escrow_ata, ctx.accounts.creator_ata.to_account_info(), ctx.accounts.creator.to_account_info(), &[], token_program, stake_amount - old_stake)?;
+                    } else {
+                        escrow_release(escrow, escrow_ata, ctx.accounts.creator_ata.to_account_info(), token_program, old_stake - stake_amount)?;
+                    }
+                }
+            }
+        }
+        offer.min_level = min_level;
+        offer.max_level = max_level;
+        offer.allowed_classes = allowed_classes;
+        offer.start_ts = start_ts;
+        offer.stake_amount = stake_amount;
+        if let Some(registry) = ctx.accounts.registry.as_mut() {
+            let listing = OfferListing::of(offer);
+            if let Some(l) = registry.listings.iter_mut().find(|l| l.offer == listing.offer) {
+                *l = listing;
+            }
+        }
+        emit!(OfferUpdated { offer: offer.key(), min_level, max_level, start_ts, old_stake, stake: stake_amount });
+        Ok(())
+    }
+
+    // Anyone opens the next OfferRegistry page (pages are independent; clients read them in order)
+    pub fn init_offer_registry(ctx: Context<InitOfferRegistry>, page: u32) -> Result<()> {
+        let registry = &mut ctx.accounts.registry;
+        registry.page = page;
+        registry.listings = Vec::new();
+        registry.bump = *ctx.bumps.get("registry").unwrap_or(&0);
+        Ok(())
+    }
+
+    // Crank: drops listings whose offer (passed in remaining_accounts) is no longer an active Offer (approved, closed, ...)
+    pub fn prune_offer_registry<'info>(ctx: Context<'_, '_, '_, 'info, PruneOfferRegistry<'info>>) -> Result<()> {
+        let registry = &mut ctx.accounts.registry;
+        for info in ctx.remaining_accounts.iter() {
+            let live = info.owner == &crate::ID && Account::<Offer>::try_from(info).map(|o| o.is_active).unwrap_or(false);
+            if !live {
+                unlist_offer(registry, info.key());
+            }
+        }
+        Ok(())
+    }
+
+    // Closes a spent offer (cancelled / refunded, or its battle created) and returns the rent to the creator.
+    // SPL stakes live in the shared per-mint escrow ATA, which stays open; a SOL escrow PDA is swept to the creator.
+    pub fn close_offer(ctx: Context<CloseOffer>) -> Result<()> {
+        let offer = &ctx.accounts.offer;
+        require!(!offer.is_active && !offer.recurring && offer.requests.is_empty(), GameError::OfferInUse);
+        // an inactive offer still holds its stake until a battle takes it (rematch / queue) or it is refunded
+        require!(offer.last_battle != Pubkey::default() || offer.stake_amount == 0, GameError::OfferInUse);
+        if let Some(registry) = ctx.accounts.registry.as_mut() {
+            unlist_offer(registry, offer.key());
+        }
+        sweep_sol_escrow(&ctx.accounts.offer_sol_escrow, &offer.key(), &ctx.accounts.creator.to_account_info())?;
+        emit!(OfferClosed { offer: offer.key(), creator: offer.creator });
+        Ok(())
+    }
+
+    // Closes a request that is no longer pending (approved, rejected or withdrawn); rent back to the challenger
+    pub fn close_request(ctx: Context<CloseRequest>) -> Result<()> {
+        let request = &ctx.accounts.request;
+        require!(request.status != JoinStatus::Pending, GameError::InvalidRequestState);
+        sweep_sol_escrow(&ctx.accounts.request_sol_escrow, &request.key(), &ctx.accounts.challenger.to_account_info())?;
+        emit!(RequestClosed { request: request.key(), challenger: request.challenger });
+        Ok(())
+    }
+
+    pub fn set_offer_recurring(ctx: Context<SetOfferRecurring>, recurring: bool) -> Result<()> {
+        ctx.accounts.offer.recurring = recurring;
+        Ok(())
+    }
+
+    // Crank: once the battle spawned by a recurring offer is settled, reopen the offer with the same
+    // parameters, funding the stake from the creator's vault. Each battle can relist the offer once.
+    pub fn relist_offer(ctx: Context<RelistOffer>) -> Result<()> {
+        let offer = &mut ctx.accounts.offer;
+        require!(offer.recurring, GameError::OfferNotRecurring);
+        require!(!offer.is_active, GameError::InvalidRequestState);
+        require!(offer.last_battle == ctx.accounts.battle.key(), GameError::InvalidBattleState);
+        require!(ctx.accounts.battle.settled, GameError::BattleNotFinished);
+        let vault = &mut ctx.accounts.creator_vault;
+        let stake = offer.stake_amount;
+        match offer.currency {
+            Currency::SOL => {
+                vault.sol_balance = vault.sol_balance.checked_sub(stake).ok_or(GameError::InsufficientVaultBalance)?;
+                let (escrow, _) = sol_escrow(&ctx.accounts.offer_sol_escrow, &offer.key())?;
+                sol_escrow_deposit(&vault.to_account_info(), &escrow, stake)?;
+            },
+            Currency::SPL(mint) => {
+                let (escrow, escrow_ata) = spl_escrow(&mut ctx.accounts.escrow_vault, &ctx.accounts.escrow_ata, mint)?;
+                let bump = [vault.bump];
+                let signer_seeds = &[&[b"vault", vault.owner.as_ref(), &bump][..]];
+                escrow_deposit(escrow, escrow_ata, ctx.accounts.creator_vault_ata.to_account_info(), vault.to_account_info(), signer_seeds, ctx.accounts.token_program.to_account_info(), stake)?;
+            }
+        }
+        let now = Clock::get()?.unix_timestamp;
+        offer.is_active = true;
+        offer.start_ts = now;
+        offer.created_at = now;
+        offer.last_battle = Pubkey::default();
+        offer.relist_count = offer.relist_count.saturating_add(1);
+        emit!(OfferRelisted { offer: offer.key(), creator: offer.creator, stake, relist_count: offer.relist_count });
+        Ok(())
+    }
+
+    // Approve challenger -> create battle, move stakes (SOL lamports / SPL ledger) into the battle, pick first mover (monotonic entropy)
     pub fn approve_challenger(ctx: Context<ApproveChallenger>) -> Result<()> {
         // Validate offer/request pair
         let offer = &mut ctx.accounts.offer;
@@ -405,135 +1224,605 @@ pub mod battlechain_v2 {
         require!(offer.is_active, GameError::OfferNotActive);
         require!(request.status == JoinStatus::Pending, GameError::InvalidRequestState);
         require!(ctx.accounts.creator.key() == offer.creator, GameError::Unauthorized);
+        // neither character may be fighting elsewhere; both are locked to this battle until it settles
+        require!(ctx.accounts.creator_character.active_battle == Pubkey::default() && ctx.accounts.challenger_character.active_battle == Pubkey::default(), GameError::CharacterInBattle);
+        let battle_key = ctx.accounts.battle.key();
+        ctx.accounts.creator_character.active_battle = battle_key;
+        ctx.accounts.challenger_character.active_battle = battle_key;
 
         let clock = Clock::get()?;
+        consume_rate_limit(&mut ctx.accounts.rate_limit, ctx.accounts.creator.key(), ctx.accounts.config.max_actions_per_hour, clock.unix_timestamp, *ctx.bumps.get("rate_limit").unwrap_or(&0))?;
         let battle = &mut ctx.accounts.battle;
-        // init battle
-        battle.battle_id = offer.offer_nonce.wrapping_add(clock.unix_timestamp as u64);
-        battle.player1 = offer.creator;
-        battle.player2 = request.challenger;
-        battle.start_ts = offer.start_ts;
-        battle.current_turn = 0;
-        battle.turn_number = 0;
-        battle.player1_health = 100;
-        battle.player2_health = 100;
-        battle.state = BattleState::Active;
-        battle.player1_stance = StanceType::Balanced;
-        battle.player2_stance = StanceType::Balanced;
-        battle.created_at = clock.unix_timestamp;
-        // set inactivity timeout from offer or config
-        battle.inactivity_timeout = if offer.inactivity_timeout > 0 { offer.inactivity_timeout } else { ctx.accounts.config.inactivity_timeout };
-        battle.last_action_ts = clock.unix_timestamp;
-        battle.bump = *ctx.bumps.get("battle").unwrap_or(&0);
-        battle.last_entropy_index = 0;
+        // loadouts are frozen onto the battle; no loadout = the class's signature ability
+        let creator_signature = ctx.accounts.creator_class_registry.as_ref().map(|r| r.stats.signature).unwrap_or(Ability::signature(ctx.accounts.creator_character.base_class));
+        let challenger_signature = ctx.accounts.challenger_class_registry.as_ref().map(|r| r.stats.signature).unwrap_or(Ability::signature(ctx.accounts.challenger_character.base_class));
+        let creator_abilities = match &ctx.accounts.creator_loadout {
+            Some(loadout) => loadout.abilities.clone(),
+            None => vec![creator_signature],
+        };
+        let challenger_abilities = if request.abilities.is_empty() { vec![challenger_signature] } else { request.abilities.clone() };
+        init_battle(battle, offer, &ctx.accounts.config, request.challenger, [&ctx.accounts.creator_character, &ctx.accounts.challenger_character], [creator_abilities, challenger_abilities], request.offered_stake, offer.start_ts, clock.unix_timestamp, *ctx.bumps.get("battle").unwrap_or(&0));
 
         let total_stake = offer.stake_amount.saturating_add(request.offered_stake);
 
-        // move stakes into battle escrow (SOL: transfer lamports; SPL: transfer escrow ATAs into battle_escrow ATA)
+        // move stakes into battle escrow (SOL: offer/request escrow PDAs -> battle escrow PDA; SPL: ledger only, tokens stay in the mint's escrow ATA)
         match offer.currency {
             Currency::SOL => {
-                // transfer lamports from offer PDA to battle PDA and from request PDA to battle PDA
-                let offer_bal = ctx.accounts.offer.to_account_info().lamports();
-                if offer_bal > 0 {
-                    invoke_signed(
-                        &system_instruction::transfer(&ctx.accounts.offer.key(), &ctx.accounts.battle.key(), offer.stake_amount),
-                        &[ctx.accounts.offer.to_account_info(), ctx.accounts.battle.to_account_info()],
-                        &[],
-                    )?;
-                }
-                let req_bal = ctx.accounts.request.to_account_info().lamports();
-                if req_bal > 0 {
-                    invoke_signed(
-                        &system_instruction::transfer(&ctx.accounts.request.key(), &ctx.accounts.battle.key(), request.offered_stake),
-                        &[ctx.accounts.request.to_account_info(), ctx.accounts.battle.to_account_info()],
-                        &[],
-                    )?;
+                if total_stake > 0 {
+                    let (battle_escrow, _) = sol_escrow(&ctx.accounts.battle_sol_escrow, &battle.key())?;
+                    if offer.stake_amount > 0 {
+                        let (offer_escrow, bump) = sol_escrow(&ctx.accounts.offer_sol_escrow, &offer.key())?;
+                        sol_escrow_release(&offer_escrow, &offer.key(), bump, &battle_escrow, offer.stake_amount)?;
+                    }
+                    if request.offered_stake > 0 {
+                        let (request_escrow, bump) = sol_escrow(&ctx.accounts.request_sol_escrow, &request.key())?;
+                        sol_escrow_release(&request_escrow, &request.key(), bump, &battle_escrow, request.offered_stake)?;
+                    }
                 }
             },
-            Currency::SPL(mint) => {
-                // create battle escrow ATA for battle PDA and transfer tokens from offer_escrow & request_escrow
-                if ctx.accounts.battle_escrow.to_account_info().data_is_empty() {
-                    let cpi_accounts = associated_token::Create {
-                        payer: ctx.accounts.creator.to_account_info(),
-                        associated_token: ctx.accounts.battle_escrow.to_account_info(),
-                        authority: ctx.accounts.battle.to_account_info(),
-                        mint: ctx.accounts.currency_mint.as_ref().unwrap().to_account_info(),
-                        system_program: ctx.accounts.system_program.to_account_info(),
-                        token_program: ctx.accounts.token_program.to_account_info(),
-                        rent: ctx.accounts.rent.to_account_info(),
-                        associated_token_program: ctx.accounts.associated_token_program.to_account_info(),
-                    };
-                    associated_token::create(CpiContext::new(ctx.accounts.associated_token_program.to_account_info(), cpi_accounts))?;
-                }
-                // transfer from offer_escrow -> battle_escrow
-                let offer_amount = ctx.accounts.offer_escrow.amount;
-                if offer_amount > 0 {
-                    let cpi_accounts = token::Transfer {
-                        from: ctx.accounts.offer_escrow.to_account_info(),
-                        to: ctx.accounts.battle_escrow.to_account_info(),
-                        authority: ctx.accounts.offer.to_account_info(),
-                    };
-                    let signer_seeds = &[&[b"offer", offer.creator.as_ref(), &offer.offer_nonce.to_le_bytes(), &[offer.bump]][..]];
-                    token::transfer(CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds), offer_amount)?;
-                }
-                // transfer from request_escrow -> battle_escrow
-                let req_amount = ctx.accounts.request_escrow.amount;
-                if req_amount > 0 {
-                    let cpi_accounts = token::Transfer {
-                        from: ctx.accounts.request_escrow.to_account_info(),
-                        to: ctx.accounts.battle_escrow.to_account_info(),
-                        authority: ctx.accounts.request.to_account_info(),
-                    };
-                    let signer_seeds = &[&[b"request", offer.key().as_ref(), request.challenger.as_ref(), &[request.bump]][..]];
-                    token::transfer(CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds), req_amount)?;
-                }
+            Currency::SPL(_) => {
+                // offer.stake_amount / request.offered_stake now count as battle.player1_stake / player2_stake;
+                // no token transfer or per-battle ATA needed
             }
         }
 
         // finalize states
         request.status = JoinStatus::Approved;
+        let request_key = request.key();
+        offer.requests.retain(|r| *r != request_key);
         offer.is_active = false;
+        offer.last_battle = battle_key;
 
-        // pick first mover consuming 1 entropy entry; ensure pool has enough and enforce per-battle monotonicity
-        require!(ctx.accounts.pool.total_available >= 1, GameError::NoEntropyAvailable);
-        let (choice, used_index) = ctx.accounts.pool.consume_mixed_u64_return_index(&ctx.accounts.creator.key(), b"first_mover", battle.turn_number as u32, 0, 1)?;
-        // ensure used_index > battle.last_entropy_index
-        require!(used_index > battle.last_entropy_index, GameError::SeedReplay);
-        battle.last_entropy_index = used_index;
-        battle.current_turn = if choice == 0 { 1 } else { 2 };
+        roll_battle_opening(&mut ctx.accounts.pool, &ctx.accounts.creator.key(), battle)?;
 
-        emit!(BattleCreated { battle: ctx.accounts.battle.key(), player1: battle.player1, player2: battle.player2, first_turn: battle.current_turn, stake_total: total_stake });
+        emit!(BattleCreated { battle: battle_key, player1: battle.player1, player2: battle.player2, first_turn: battle.current_turn, stake_total: total_stake });
         Ok(())
     }
 
+    // Approve the index-th entry of Offer.requests; every other pending request is rejected and refunded first.
+    // remaining_accounts, per other pending request: SOL [request, its sol_escrow PDA, challenger wallet]; SPL [request, challenger ATA]
+    pub fn approve_request_at<'info>(ctx: Context<'_, '_, '_, 'info, ApproveChallenger<'info>>, index: u8) -> Result<()> {
+        let offer_key = ctx.accounts.offer.key();
+        let chosen = ctx.accounts.request.key();
+        require!(ctx.accounts.offer.requests.get(index as usize) == Some(&chosen), GameError::InvalidIndex);
+        let others = ctx.accounts.offer.requests.len() - 1;
+        let per_request = if ctx.accounts.offer.currency == Currency::SOL { 3 } else { 2 };
+        require!(ctx.remaining_accounts.len() == others * per_request, GameError::InvalidIndex);
+        for accs in ctx.remaining_accounts.chunks(per_request) {
+            let mut other: Account<Request> = Account::try_from(&accs[0])?;
+            let other_key = other.key();
+            require!(other_key != chosen && other.offer == offer_key && other.status == JoinStatus::Pending && ctx.accounts.offer.requests.contains(&other_key), GameError::InvalidRequestState);
+            match ctx.accounts.offer.currency {
+                Currency::SOL => {
+                    require_keys_eq!(accs[2].key(), other.challenger, GameError::Unauthorized);
+                    let (escrow, bump) = Pubkey::find_program_address(&[b"sol_escrow", other_key.as_ref()], &crate::ID);
+                    require_keys_eq!(accs[1].key(), escrow, GameError::MissingEscrowAccount);
+                    sol_escrow_release(&accs[1], &other_key, bump, &accs[2], other.offered_stake)?;
+                },
+                Currency::SPL(mint) => {
+                    let ata: Account<TokenAccount> = Account::try_from(&accs[1])?;
+                    require!(ata.owner == other.challenger, GameError::Unauthorized);
+                    let (escrow, escrow_ata) = spl_escrow(&mut ctx.accounts.escrow_vault, &ctx.accounts.escrow_ata, mint)?;
+                    escrow_release(escrow, escrow_ata, accs[1].clone(), ctx.accounts.token_program.to_account_info(), other.offered_stake)?;
+                }
+            }
+            other.status = JoinStatus::Rejected;
+            other.exit(&crate::ID)?;
+            ctx.accounts.offer.requests.retain(|r| *r != other_key);
+            emit!(ChallengerRejected { offer: offer_key, request: other_key, challenger: other.challenger, refunded: other.offered_stake });
+        }
+        approve_challenger(ctx)
+    }
+
     // ------------------------
-    // Execute turn
+    // Matchmaking queue
     // ------------------------
-    // This function consumes entropy and updates battle.last_action_ts and last_entropy_index
-    pub fn execute_turn(ctx: Context<ExecuteTurn>, chosen_stance: StanceType, use_special: bool) -> Result<()> {
-        let cfg = &ctx.accounts.config;
-        let pool = &mut ctx.accounts.pool;
-        let battle = &mut ctx.accounts.battle;
-        let attacker_char = &mut ctx.accounts.attacker_character;
-        let defender_char = &mut ctx.accounts.defender_character;
-        let attacker_prog = &mut ctx.accounts.attacker_prog;
-
-        // ownership checks on NFT ATAs — enforced by account constraints in context (client must pass)
-        // Basic turn checks
-        require!(battle.state == BattleState::Active, GameError::InvalidBattleState);
-        let signer = ctx.accounts.signer.key();
-        let is_player1 = if signer == battle.player1 { true } else if signer == battle.player2 { false } else { return Err(error!(GameError::Unauthorized).into()); };
-        if is_player1 { require!(battle.current_turn == 1, GameError::NotYourTurn); } else { require!(battle.current_turn == 2, GameError::NotYourTurn); }
-
-        // require pool has sufficient entropy
-        require!(pool.total_available >= MIN_ENTROPY_PER_TURN, GameError::NoEntropyAvailable);
-
-        // record last_action_ts
+    // Quick play: the player's stake goes into a private Offer (never active) and a MatchQueue entry advertises
+    // character, MMR and the widest MMR gap they accept. match_players turns two compatible entries into a Battle.
+    pub fn enter_queue(ctx: Context<EnterQueue>, offer_nonce: u64, currency: Currency, stake_amount: u64, mmr_band: u64) -> Result<()> {
+        if let Currency::SPL(mint) = currency {
+            require!(ctx.accounts.config.spl_whitelist.contains(&mint), GameError::SPLNotWhitelisted);
+        }
+        require!(ctx.accounts.character.active_battle == Pubkey::default(), GameError::CharacterInBattle);
         let now = Clock::get()?.unix_timestamp;
-        battle.last_action_ts = now;
+        let player = ctx.accounts.player.key();
+        let offer = &mut ctx.accounts.offer;
+        init_private_offer(offer, player, offer_nonce, currency.clone(), stake_amount, ctx.accounts.character.key(), None, now, *ctx.bumps.get("offer").unwrap_or(&0));
+        match currency {
+            Currency::SOL => {
+                if stake_amount > 0 {
+                    let (escrow, _) = sol_escrow(&ctx.accounts.offer_sol_escrow, &offer.key())?;
+                    sol_escrow_deposit(&ctx.accounts.player.to_account_info(), &escrow, stake_amount)?;
+                }
+            },
+            Currency::SPL(mint) => {
+                let (escrow, escrow_ata) = spl_escrow(&mut ctx.accounts.escrow_vault, &ctx.accounts.escrow_ata, mint)?;
+                escrow_deposit(escrow, escrow_ata, ctx.accounts.player_ata.to_account_info(), ctx.accounts.player.to_account_info(), &[], ctx.accounts.token_program.to_account_info(), stake_amount)?;
+            }
+        }
 
-        // set attacker stance immediately
-        if is_player1 { battle.player1_stance = chosen_stance; } else { battle.player2_stance = chosen_stance; }
+        let entry = &mut ctx.accounts.entry;
+        entry.player = player;
+        entry.character = ctx.accounts.character.key();
+        entry.offer = offer.key();
+        entry.currency = currency;
+        entry.stake = stake_amount;
+        entry.mmr = ctx.accounts.progression.mmr;
+        entry.mmr_band = mmr_band;
+        entry.created_at = now;
+        entry.bump = *ctx.bumps.get("entry").unwrap_or(&0);
+        emit!(QueueEntered { entry: entry.key(), player, character: entry.character, stake: stake_amount, mmr: entry.mmr, mmr_band });
+        Ok(())
+    }
+
+    // Player leaves the queue before being matched: stake refunded, entry closed
+    pub fn leave_queue(ctx: Context<LeaveQueue>) -> Result<()> {
+        let offer = &mut ctx.accounts.offer;
+        match offer.currency {
+            Currency::SOL => {
+                if offer.stake_amount > 0 {
+                    let (escrow, bump) = sol_escrow(&ctx.accounts.offer_sol_escrow, &offer.key())?;
+                    sol_escrow_release(&escrow, &offer.key(), bump, &ctx.accounts.player.to_account_info(), offer.stake_amount)?;
+                }
+            },
+            Currency::SPL(mint) => {
+                let (escrow, escrow_ata) = spl_escrow(&mut ctx.accounts.escrow_vault, &ctx.accounts.escrow_ata, mint)?;
+                escrow_release(escrow, escrow_ata, ctx.accounts.player_ata.to_account_info(), ctx.accounts.token_program.to_account_info(), offer.stake_amount)?;
+            }
+        }
+        offer.stake_amount = 0;
+        emit!(QueueLeft { entry: ctx.accounts.entry.key(), player: ctx.accounts.player.key() });
+        Ok(())
+    }
+
+    // Permissionless crank: pairs two entries with the same currency and stake whose MMR gap fits both bands.
+    // entry_a's offer becomes the battle's offer (entry_a is player1); both entries close to the cranker.
+    pub fn match_players(ctx: Context<MatchPlayers>) -> Result<()> {
+        let (a, b) = (&ctx.accounts.entry_a, &ctx.accounts.entry_b);
+        require!(a.player != b.player, GameError::QueueMismatch);
+        require!(a.currency == b.currency && a.stake == b.stake, GameError::QueueMismatch);
+        let mmr_gap = a.mmr.abs_diff(b.mmr);
+        require!(mmr_gap <= a.mmr_band.min(b.mmr_band), GameError::QueueMismatch);
+        let (player2, stake) = (b.player, b.stake);
+        require!(ctx.accounts.character_a.active_battle == Pubkey::default() && ctx.accounts.character_b.active_battle == Pubkey::default(), GameError::CharacterInBattle);
+        let battle_key = ctx.accounts.battle.key();
+        ctx.accounts.character_a.active_battle = battle_key;
+        ctx.accounts.character_b.active_battle = battle_key;
+
+        let now = Clock::get()?.unix_timestamp;
+        let offer = &mut ctx.accounts.offer_a;
+        let battle = &mut ctx.accounts.battle;
+        let signature_a = ctx.accounts.class_registry_a.as_ref().map(|r| r.stats.signature).unwrap_or(Ability::signature(ctx.accounts.character_a.base_class));
+        let signature_b = ctx.accounts.class_registry_b.as_ref().map(|r| r.stats.signature).unwrap_or(Ability::signature(ctx.accounts.character_b.base_class));
+        let abilities_a = ctx.accounts.loadout_a.as_ref().map(|l| l.abilities.clone()).unwrap_or(vec![signature_a]);
+        let abilities_b = ctx.accounts.loadout_b.as_ref().map(|l| l.abilities.clone()).unwrap_or(vec![signature_b]);
+        init_battle(battle, offer, &ctx.accounts.config, player2, [&ctx.accounts.character_a, &ctx.accounts.character_b], [abilities_a, abilities_b], stake, now, now, *ctx.bumps.get("battle").unwrap_or(&0));
+
+        // SOL: both offer escrows -> battle escrow; SPL: the offers' ledger entries become the battle's stakes
+        let total_stake = offer.stake_amount.saturating_add(stake);
+        if offer.currency == Currency::SOL && total_stake > 0 {
+            let (battle_escrow, _) = sol_escrow(&ctx.accounts.battle_sol_escrow, &battle_key)?;
+            for (escrow, holder) in [(&ctx.accounts.offer_a_sol_escrow, offer.key()), (&ctx.accounts.offer_b_sol_escrow, ctx.accounts.offer_b.key())] {
+                let (escrow, bump) = sol_escrow(escrow, &holder)?;
+                sol_escrow_release(&escrow, &holder, bump, &battle_escrow, stake)?;
+            }
+        }
+        offer.last_battle = battle_key;
+        ctx.accounts.offer_b.last_battle = battle_key;
+
+        roll_battle_opening(&mut ctx.accounts.pool, &ctx.accounts.cranker.key(), battle)?;
+        emit!(QueueMatched { battle: battle_key, player1: battle.player1, player2, mmr_gap });
+        emit!(BattleCreated { battle: battle_key, player1: battle.player1, player2, first_turn: battle.current_turn, stake_total: total_stake });
+        Ok(())
+    }
+
+    // ------------------------
+    // Rematch
+    // ------------------------
+    // Either player of a settled battle offers a rematch with the same stakes and currency. The proposer's stake goes
+    // into a private Offer (never active, so nobody else can join it); the opponent's accept creates the Battle.
+    pub fn propose_rematch(ctx: Context<ProposeRematch>, offer_nonce: u64) -> Result<()> {
+        let old = &ctx.accounts.old_battle;
+        require!(old.settled, GameError::BattleNotFinished);
+        let proposer = ctx.accounts.proposer.key();
+        let (character, opponent, opponent_character, stake, opponent_stake) = if proposer == old.player1 {
+            (old.player1_character, old.player2, old.player2_character, old.player1_stake, old.player2_stake)
+        } else if proposer == old.player2 {
+            (old.player2_character, old.player1, old.player1_character, old.player2_stake, old.player1_stake)
+        } else {
+            return err!(GameError::Unauthorized);
+        };
+        require!(ctx.accounts.proposer_character.key() == character, GameError::CharacterConstraint);
+        require!(ctx.accounts.proposer_nft_ata.mint == ctx.accounts.proposer_character.nft_mint, GameError::NotNftOwner);
+        require!(ctx.accounts.proposer_character.active_battle == Pubkey::default(), GameError::CharacterInBattle);
+        let old_offer = &ctx.accounts.old_offer;
+        if let Currency::SPL(mint) = old_offer.currency {
+            require!(ctx.accounts.config.spl_whitelist.contains(&mint), GameError::SPLNotWhitelisted);
+        }
+        let now = Clock::get()?.unix_timestamp;
+
+        let offer = &mut ctx.accounts.offer;
+        init_private_offer(offer, proposer, offer_nonce, old_offer.currency.clone(), stake, character, Some(opponent), now, *ctx.bumps.get("offer").unwrap_or(&0));
+        offer.inactivity_timeout = old_offer.inactivity_timeout;
+        offer.rounds = old_offer.rounds;
+        offer.commit_reveal = old_offer.commit_reveal;
+        offer.async_mode = old_offer.async_mode;
+        offer.reconnect_grace_secs = old_offer.reconnect_grace_secs;
+
+        match offer.currency {
+            Currency::SOL => {
+                if stake > 0 {
+                    let (escrow, _) = sol_escrow(&ctx.accounts.offer_sol_escrow, &offer.key())?;
+                    sol_escrow_deposit(&ctx.accounts.proposer.to_account_info(), &escrow, stake)?;
+                }
+            },
+            Currency::SPL(mint) => {
+                let (escrow, escrow_ata) = spl_escrow(&mut ctx.accounts.escrow_vault, &ctx.accounts.escrow_ata, mint)?;
+                escrow_deposit(escrow, escrow_ata, ctx.accounts.proposer_ata.to_account_info(), ctx.accounts.proposer.to_account_info(), &[], ctx.accounts.token_program.to_account_info(), stake)?;
+            }
+        }
+
+        let rematch = &mut ctx.accounts.rematch;
+        rematch.old_battle = ctx.accounts.old_battle.key();
+        rematch.offer = offer.key();
+        rematch.proposer = proposer;
+        rematch.opponent = opponent;
+        rematch.proposer_character = character;
+        rematch.opponent_character = opponent_character;
+        rematch.opponent_stake = opponent_stake;
+        rematch.created_at = now;
+        rematch.accepted = false;
+        rematch.bump = *ctx.bumps.get("rematch").unwrap_or(&0);
+        emit!(RematchProposed { rematch: rematch.key(), old_battle: rematch.old_battle, offer: rematch.offer, proposer, opponent, stake, opponent_stake });
+        Ok(())
+    }
+
+    // Opponent accepts: stakes their side, and the Battle is created as approve_challenger would (proposer is player1)
+    pub fn accept_rematch(ctx: Context<AcceptRematch>) -> Result<()> {
+        let rematch = &mut ctx.accounts.rematch;
+        let offer = &mut ctx.accounts.offer;
+        require!(!rematch.accepted, GameError::RematchAlreadyAccepted);
+        require!(ctx.accounts.proposer_character.active_battle == Pubkey::default() && ctx.accounts.opponent_character.active_battle == Pubkey::default(), GameError::CharacterInBattle);
+        let battle_key = ctx.accounts.battle.key();
+        ctx.accounts.proposer_character.active_battle = battle_key;
+        ctx.accounts.opponent_character.active_battle = battle_key;
+
+        let clock = Clock::get()?;
+        let battle = &mut ctx.accounts.battle;
+        // both sides bring their current loadout; no loadout = the class's signature ability
+        let proposer_signature = ctx.accounts.proposer_class_registry.as_ref().map(|r| r.stats.signature).unwrap_or(Ability::signature(ctx.accounts.proposer_character.base_class));
+        let opponent_signature = ctx.accounts.opponent_class_registry.as_ref().map(|r| r.stats.signature).unwrap_or(Ability::signature(ctx.accounts.opponent_character.base_class));
+        let proposer_abilities = ctx.accounts.proposer_loadout.as_ref().map(|l| l.abilities.clone()).unwrap_or(vec![proposer_signature]);
+        let opponent_abilities = ctx.accounts.opponent_loadout.as_ref().map(|l| l.abilities.clone()).unwrap_or(vec![opponent_signature]);
+        init_battle(battle, offer, &ctx.accounts.config, rematch.opponent, [&ctx.accounts.proposer_character, &ctx.accounts.opponent_character], [proposer_abilities, opponent_abilities], rematch.opponent_stake, clock.unix_timestamp, clock.unix_timestamp, *ctx.bumps.get("battle").unwrap_or(&0));
+
+        let total_stake = offer.stake_amount.saturating_add(rematch.opponent_stake);
+
+        // proposer's stake moves from the offer escrow, the opponent's comes straight from their wallet
+        match offer.currency {
+            Currency::SOL => {
+                if total_stake > 0 {
+                    let (battle_escrow, _) = sol_escrow(&ctx.accounts.battle_sol_escrow, &battle.key())?;
+                    if offer.stake_amount > 0 {
+                        let (offer_escrow, bump) = sol_escrow(&ctx.accounts.offer_sol_escrow, &offer.key())?;
+                        sol_escrow_release(&offer_escrow, &offer.key(), bump, &battle_escrow, offer.stake_amount)?;
+                    }
+                    if rematch.opponent_stake > 0 {
+                        sol_escrow_deposit(&ctx.accounts.opponent.to_account_info(), &battle_escrow, rematch.opponent_stake)?;
+                    }
+                }
+            },
+            Currency::SPL(mint) => {
+                // the offer's ledger entry becomes player1_stake; the opponent's tokens join the mint's escrow ATA
+                let (escrow, escrow_ata) = spl_escrow(&mut ctx.accounts.escrow_vault, &ctx.accounts.escrow_ata, mint)?;
+                escrow_deposit(escrow, escrow_ata, ctx.accounts.opponent_ata.to_account_info(), ctx.accounts.opponent.to_account_info(), &[], ctx.accounts.token_program.to_account_info(), rematch.opponent_stake)?;
+            }
+        }
+
+        rematch.accepted = true;
+        offer.last_battle = battle.key();
+
+        roll_battle_opening(&mut ctx.accounts.pool, &ctx.accounts.opponent.key(), battle)?;
+
+        emit!(RematchAccepted { rematch: rematch.key(), battle: battle.key(), old_battle: rematch.old_battle });
+        emit!(BattleCreated { battle: battle.key(), player1: battle.player1, player2: battle.player2, first_turn: battle.current_turn, stake_total: total_stake });
+        Ok(())
+    }
+
+    // Proposer withdraws an unanswered rematch: stake refunded, Rematch PDA closed so the pair can propose again
+    pub fn cancel_rematch(ctx: Context<CancelRematch>) -> Result<()> {
+        require!(!ctx.accounts.rematch.accepted, GameError::RematchAlreadyAccepted);
+        let offer = &mut ctx.accounts.offer;
+        match offer.currency {
+            Currency::SOL => {
+                if offer.stake_amount > 0 {
+                    let (escrow, bump) = sol_escrow(&ctx.accounts.offer_sol_escrow, &offer.key())?;
+                    sol_escrow_release(&escrow, &offer.key(), bump, &ctx.accounts.proposer.to_account_info(), offer.stake_amount)?;
+                }
+            },
+            Currency::SPL(mint) => {
+                let (escrow, escrow_ata) = spl_escrow(&mut ctx.accounts.escrow_vault, &ctx.accounts.escrow_ata, mint)?;
+                escrow_release(escrow, escrow_ata, ctx.accounts.proposer_ata.to_account_info(), ctx.accounts.token_program.to_account_info(), offer.stake_amount)?;
+            }
+        }
+        offer.stake_amount = 0;
+        emit!(RematchCancelled { rematch: ctx.accounts.rematch.key(), old_battle: ctx.accounts.rematch.old_battle });
+        Ok(())
+    }
+
+    // ------------------------
+    // Ready check
+    // ------------------------
+    pub fn ready_up(ctx: Context<ReadyUp>) -> Result<()> {
+        let battle = &mut ctx.accounts.battle;
+        let player = ctx.accounts.player.key();
+        require!(battle.state == BattleState::Waiting, GameError::InvalidBattleState);
+        let now = Clock::get()?.unix_timestamp;
+        require!(now <= battle.ready_deadline, GameError::ReadyWindowClosed);
+        let is_player1 = if player == battle.player1 { true } else if player == battle.player2 { false } else { return Err(error!(GameError::Unauthorized).into()); };
+        require!(!(if is_player1 { battle.player1_ready } else { battle.player2_ready }), GameError::InvalidBattleState);
+        require!(ctx.accounts.character.key() == if is_player1 { battle.player1_character } else { battle.player2_character }, GameError::CharacterConstraint);
+        // the character NFT can't be sold mid-battle: battle PDA becomes its delegate and freezes the token account
+        token::approve(CpiContext::new(ctx.accounts.token_program.to_account_info(), Approve {
+            to: ctx.accounts.nft_ata.to_account_info(),
+            delegate: battle.to_account_info(),
+            authority: ctx.accounts.player.to_account_info(),
+        }), 1)?;
+        set_nft_frozen(battle, ctx.accounts.nft_ata.to_account_info(), ctx.accounts.nft_edition.to_account_info(), ctx.accounts.nft_mint.to_account_info(), ctx.accounts.token_program.to_account_info(), ctx.accounts.token_metadata_program.to_account_info(), true)?;
+        if is_player1 { battle.player1_ready = true; battle.player1_nft_frozen = true; } else { battle.player2_ready = true; battle.player2_nft_frozen = true; }
+        emit!(PlayerReady { battle: battle.key(), player });
+        if battle.player1_ready && battle.player2_ready {
+            battle.state = BattleState::Active;
+            // inactivity clock starts once the battle is live (never before start_ts)
+            battle.last_action_ts = now.max(battle.start_ts);
+            emit!(BattleActivated { battle: battle.key(), first_turn: battle.current_turn });
+        }
+        Ok(())
+    }
+
+    // Once the battle is over (any ending), anyone can thaw a character NFT frozen at ready-up
+    pub fn release_character_nft(ctx: Context<ReleaseCharacterNft>) -> Result<()> {
+        let battle = &mut ctx.accounts.battle;
+        require!(battle.state == BattleState::Finished, GameError::BattleNotFinished);
+        let character = ctx.accounts.character.key();
+        let is_player1 = if character == battle.player1_character { true } else if character == battle.player2_character { false } else { return Err(error!(GameError::CharacterConstraint).into()); };
+        require!(if is_player1 { battle.player1_nft_frozen } else { battle.player2_nft_frozen }, GameError::InvalidBattleState);
+        set_nft_frozen(battle, ctx.accounts.nft_ata.to_account_info(), ctx.accounts.nft_edition.to_account_info(), ctx.accounts.nft_mint.to_account_info(), ctx.accounts.token_program.to_account_info(), ctx.accounts.token_metadata_program.to_account_info(), false)?;
+        if is_player1 { battle.player1_nft_frozen = false; } else { battle.player2_nft_frozen = false; }
+        emit!(CharacterNftReleased { battle: battle.key(), nft_mint: ctx.accounts.character.nft_mint });
+        Ok(())
+    }
+
+    // After the ready window: the player who readied gets their stake back plus a penalty from the absent
+    // player's stake; the absent player gets the rest. If nobody readied, both stakes are refunded in full.
+    pub fn claim_no_show(ctx: Context<ClaimNoShow>) -> Result<()> {
+        let battle = &mut ctx.accounts.battle;
+        require!(battle.state == BattleState::Waiting, GameError::InvalidBattleState);
+        require!(!battle.settled, GameError::AlreadySettled);
+        let now = Clock::get()?.unix_timestamp;
+        require!(now > battle.ready_deadline, GameError::ReadyWindowOpen);
+
+        let (mut pay1, mut pay2) = (battle.player1_stake, battle.player2_stake);
+        let mut penalty = 0u64;
+        if battle.player1_ready && !battle.player2_ready {
+            penalty = battle.player2_stake.saturating_mul(NO_SHOW_PENALTY_BPS) / 10_000;
+            pay1 = pay1.saturating_add(penalty);
+            pay2 = pay2.saturating_sub(penalty);
+        } else if battle.player2_ready && !battle.player1_ready {
+            penalty = battle.player1_stake.saturating_mul(NO_SHOW_PENALTY_BPS) / 10_000;
+            pay2 = pay2.saturating_add(penalty);
+            pay1 = pay1.saturating_sub(penalty);
+        }
+        // sponsor bonus and bounties go to whoever showed up, split when neither or both did
+        let bonus = battle.bonus_pot.saturating_add(battle.bounty_pot);
+        match (battle.player1_ready, battle.player2_ready) {
+            (true, false) => pay1 = pay1.saturating_add(bonus),
+            (false, true) => pay2 = pay2.saturating_add(bonus),
+            _ => { pay1 = pay1.saturating_add(bonus / 2); pay2 = pay2.saturating_add(bonus - bonus / 2); }
+        }
+        battle.bonus_pot = 0;
+        battle.bounty_pot = 0;
+
+        match ctx.accounts.offer.currency {
+            Currency::SOL => {
+                let (escrow, bump) = sol_escrow(&ctx.accounts.battle_sol_escrow, &battle.key())?;
+                sol_escrow_release(&escrow, &battle.key(), bump, &ctx.accounts.player1.to_account_info(), pay1)?;
+                sol_escrow_release(&escrow, &battle.key(), bump, &ctx.accounts.player2.to_account_info(), pay2)?;
+            },
+            Currency::SPL(mint) => {
+                let (escrow, escrow_ata) = spl_escrow(&mut ctx.accounts.escrow_vault, &ctx.accounts.escrow_ata, mint)?;
+                for (to, amount) in [(&ctx.accounts.player1_ata, pay1), (&ctx.accounts.player2_ata, pay2)] {
+                    escrow_release(escrow, escrow_ata, to.to_account_info(), ctx.accounts.token_program.to_account_info(), amount)?;
+                }
+            }
+        }
+
+        battle.state = BattleState::Finished;
+        battle.winner = None;
+        battle.settled = true;
+        battle.settled_at = now;
+        battle.settlement = Settlement { bonus_paid: bonus, refund_player1: pay1, refund_player2: pay2, ..Default::default() };
+        release_character(&mut ctx.accounts.player1_character, battle.player1_character, battle.key());
+        release_character(&mut ctx.accounts.player2_character, battle.player2_character, battle.key());
+        emit!(BattleSettled { battle: battle.key(), winner: None, settlement: battle.settlement });
+        emit!(NoShowSettled { battle: battle.key(), player1_ready: battle.player1_ready, player2_ready: battle.player2_ready, paid_player1: pay1, paid_player2: pay2, penalty });
+        Ok(())
+    }
+
+    // ------------------------
+    // Execute turn
+    // ------------------------
+    // This function consumes entropy and updates battle.last_action_ts and last_entropy_index
+    pub fn execute_turn(ctx: Context<ExecuteTurn>, chosen_stance: StanceType, ability: Option<u8>, action: TurnAction) -> Result<()> {
+        let cfg = &ctx.accounts.config;
+        let pool = &mut ctx.accounts.pool;
+        let battle = &mut ctx.accounts.battle;
+        let attacker_char = &mut ctx.accounts.attacker_character;
+        let defender_char = &mut ctx.accounts.defender_character;
+        let attacker_prog = &mut ctx.accounts.attacker_prog;
+
+        // Basic turn checks
+        require!(battle.state == BattleState::Active, GameError::InvalidBattleState);
+        require!(!battle.async_mode, GameError::InvalidBattleState);
+        require!(Clock::get()?.unix_timestamp >= battle.start_ts, GameError::BattleNotStarted);
+        let signer = ctx.accounts.signer.key();
+        let is_player1 = if signer == battle.player1 { true } else if signer == battle.player2 { false } else { return Err(error!(GameError::Unauthorized).into()); };
+        if is_player1 { require!(battle.current_turn == 1, GameError::NotYourTurn); } else { require!(battle.current_turn == 2, GameError::NotYourTurn); }
+        // characters must be the ones that entered the battle (their HP is written back at the end)
+        let (own_char, opp_char) = if is_player1 { (battle.player1_character, battle.player2_character) } else { (battle.player2_character, battle.player1_character) };
+        require!(attacker_char.key() == own_char && defender_char.key() == opp_char, GameError::CharacterConstraint);
+        let opponent_wallet = if is_player1 { battle.player2 } else { battle.player1 };
+        require!(ctx.accounts.defender_nft_ata.owner == opponent_wallet, GameError::NotNftOwner);
+        require!(!battle.has_turn_log || ctx.accounts.turn_log.is_some(), GameError::TurnLogRequired);
+
+        // turn cap reached: this call settles the battle instead of playing a turn
+        if battle.max_turns > 0 && battle.turn_number >= battle.max_turns as u64 {
+            let (p1_max, p2_max) = if is_player1 { (attacker_char.max_hp, defender_char.max_hp) } else { (defender_char.max_hp, attacker_char.max_hp) };
+            let winner = turn_limit_winner(battle, p1_max, p2_max);
+            emit!(TurnLimitReached { battle: battle.key(), turn_number: battle.turn_number, winner });
+            finish_battle(battle, winner, is_player1, attacker_char, defender_char, attacker_prog, &mut ctx.accounts.defender_prog, &mut ctx.accounts.leaderboard)?;
+            return Ok(());
+        }
+
+        // commit-reveal: both stances for this turn must be revealed; the actor plays the stance they revealed
+        if battle.commit_reveal {
+            require!(battle.commit_turn == battle.turn_number && battle.player1_revealed && battle.player2_revealed, GameError::MovesNotRevealed);
+            let revealed = if is_player1 { battle.player1_stance } else { battle.player2_stance };
+            require!(chosen_stance == revealed, GameError::CommitmentMismatch);
+        }
+
+        // require pool has sufficient entropy
+        require!(pool.total_available >= MIN_ENTROPY_PER_TURN, GameError::NoEntropyAvailable);
+
+        // record last_action_ts
+        let now = Clock::get()?.unix_timestamp;
+        // chess clock: the actor's bank pays for the time since the previous action (pauses already excluded)
+        if battle.time_control {
+            let used = now.saturating_sub(battle.last_action_ts).max(0);
+            let bank = if is_player1 { battle.player1_time_bank } else { battle.player2_time_bank };
+            require!(used < bank, GameError::TimeBankExpired);
+            if is_player1 { battle.player1_time_bank = bank - used; } else { battle.player2_time_bank = bank - used; }
+        }
+        // acting after the plain timeout means the player just used their reconnect grace
+        if battle.reconnect_grace > 0 && now.saturating_sub(battle.last_action_ts) > battle.inactivity_timeout {
+            if is_player1 { battle.player1_grace_used = true; } else { battle.player2_grace_used = true; }
+        }
+        battle.last_action_ts = now;
+        if is_player1 { battle.player1_skips = 0; } else { battle.player2_skips = 0; }
+        // playing on drops any unconfirmed pause request / draw offer
+        battle.pause_requested_by = None;
+        battle.pending_draw = None;
+
+        require!(stance_unlocked(cfg, chosen_stance, attacker_prog.level), GameError::StanceLocked);
+        // set attacker stance immediately
+        if is_player1 { battle.player1_stance = chosen_stance; } else { battle.player2_stance = chosen_stance; }
+        // remember the class each side fought with (winrate stats at finalize)
+        if is_player1 { battle.player1_class = Some(attacker_char.base_class); } else { battle.player2_class = Some(attacker_char.base_class); }
+
+        // DoT ticks on the acting player at the start of their turn; dying to it ends the battle without an attack
+        let (dot_damage, dot_turns) = if is_player1 { (battle.player1_dot_damage, battle.player1_dot_turns) } else { (battle.player2_dot_damage, battle.player2_dot_turns) };
+        if dot_turns > 0 {
+            let turns_left = dot_turns - 1;
+            let health = if is_player1 {
+                battle.player1_health = battle.player1_health.saturating_sub(dot_damage);
+                battle.player1_dot_turns = turns_left;
+                if turns_left == 0 { battle.player1_dot_damage = 0; }
+                battle.player1_health
+            } else {
+                battle.player2_health = battle.player2_health.saturating_sub(dot_damage);
+                battle.player2_dot_turns = turns_left;
+                if turns_left == 0 { battle.player2_dot_damage = 0; }
+                battle.player2_health
+            };
+            emit!(DotTicked { battle: battle.key(), player: signer, damage: dot_damage, turns_left, health });
+            if end_battle_if_dead(battle, is_player1, attacker_char, defender_char, attacker_prog, &mut ctx.accounts.defender_prog, &mut ctx.accounts.leaderboard)? {
+                return Ok(());
+            }
+        }
+
+        // status effects on the actor: poison ticks, every effect loses a turn, a stun skips the turn
+        let opponent = if is_player1 { battle.player2 } else { battle.player1 };
+        let (poison, stunned) = tick_status_effects(if is_player1 { &mut battle.player1_effects } else { &mut battle.player2_effects });
+        if poison > 0 {
+            if is_player1 { battle.player1_health = battle.player1_health.saturating_sub(poison); } else { battle.player2_health = battle.player2_health.saturating_sub(poison); }
+        }
+        if poison > 0 || stunned {
+            emit!(StatusTicked { battle: battle.key(), player: signer, poison_damage: poison, stunned });
+        }
+        if end_battle_if_dead(battle, is_player1, attacker_char, defender_char, attacker_prog, &mut ctx.accounts.defender_prog, &mut ctx.accounts.leaderboard)? {
+            return Ok(());
+        }
+        if stunned {
+            for cd in (if is_player1 { &mut battle.player1_ability_cooldowns } else { &mut battle.player2_ability_cooldowns }).iter_mut() { *cd = cd.saturating_sub(1); }
+            record_turn(&mut ctx.accounts.turn_log, TurnLogEntry { turn_number: battle.turn_number, actor: battle.current_turn, action: TURN_LOG_STUNNED, stance: chosen_stance as u8, ability: None, rolls: [0; 3], damage: 0, is_crit: false, dodged: false, player1_health: battle.player1_health, player2_health: battle.player2_health });
+            battle.current_turn = if battle.current_turn == 1 { 2 } else { 1 };
+            battle.turn_number = battle.turn_number.saturating_add(1);
+            return Ok(());
+        }
+
+        // own action cooldowns tick and last turn's guard drops at the start of the actor's turn
+        if is_player1 {
+            battle.player1_guard_bps = 0;
+            battle.player1_defend_cooldown = battle.player1_defend_cooldown.saturating_sub(1);
+            battle.player1_heal_cooldown = battle.player1_heal_cooldown.saturating_sub(1);
+            for cd in battle.player1_ability_cooldowns.iter_mut() { *cd = cd.saturating_sub(1); }
+        } else {
+            battle.player2_guard_bps = 0;
+            battle.player2_defend_cooldown = battle.player2_defend_cooldown.saturating_sub(1);
+            battle.player2_heal_cooldown = battle.player2_heal_cooldown.saturating_sub(1);
+            for cd in battle.player2_ability_cooldowns.iter_mut() { *cd = cd.saturating_sub(1); }
+        }
+
+        // stamina: strong choices spend it, a light turn regenerates
+        let stamina_cost = if ability.is_some() { ABILITY_STAMINA_COST } else { 0 } + if chosen_stance == StanceType::Berserker { BERSERKER_STAMINA_COST } else { 0 };
+        let stamina = if is_player1 { battle.player1_stamina } else { battle.player2_stamina };
+        require!(stamina >= stamina_cost, GameError::InsufficientStamina);
+        let stamina = if stamina_cost == 0 { stamina.saturating_add(STAMINA_REGEN_PER_TURN).min(MAX_STAMINA) } else { stamina - stamina_cost };
+        if is_player1 { battle.player1_stamina = stamina; } else { battle.player2_stamina = stamina; }
+
+        // defend / heal / item resolve here and end the turn without an attack
+        if action != TurnAction::Attack {
+            require!(ability.is_none(), GameError::SpecialRequiresAttack);
+            require!(action == TurnAction::Defend || battle.arena != ArenaModifier::Bloodbath, GameError::ArenaForbidsAction);
+            let tag: &[u8] = match action { TurnAction::Defend => b"defend", TurnAction::Heal => b"heal", _ => b"item" };
+            let (roll, idx_action) = pool.consume_mixed_u64_return_index(&signer, tag, battle.turn_number as u32, 0, 9999)?;
+            require!(idx_action > battle.last_entropy_index, GameError::SeedReplay);
+            battle.last_entropy_index = idx_action;
+            let max_hp = attacker_char.max_hp as u64;
+            let amount = match action {
+                TurnAction::Defend => {
+                    let cooldown = if is_player1 { battle.player1_defend_cooldown } else { battle.player2_defend_cooldown };
+                    require!(cooldown == 0, GameError::ActionOnCooldown);
+                    let guard = DEFEND_MIN_BPS + roll % (DEFEND_MAX_BPS - DEFEND_MIN_BPS + 1);
+                    if is_player1 { battle.player1_guard_bps = guard as u16; battle.player1_defend_cooldown = DEFEND_COOLDOWN_TURNS; } else { battle.player2_guard_bps = guard as u16; battle.player2_defend_cooldown = DEFEND_COOLDOWN_TURNS; }
+                    guard
+                }
+                TurnAction::Heal => {
+                    let cooldown = if is_player1 { battle.player1_heal_cooldown } else { battle.player2_heal_cooldown };
+                    require!(cooldown == 0, GameError::ActionOnCooldown);
+                    let heals = if is_player1 { battle.player1_heals } else { battle.player2_heals };
+                    require!(heals > 0, GameError::NoHealsLeft);
+                    if is_player1 { battle.player1_heal_cooldown = HEAL_COOLDOWN_TURNS; battle.player1_heals = heals - 1; } else { battle.player2_heal_cooldown = HEAL_COOLDOWN_TURNS; battle.player2_heals = heals - 1; }
+                    max_hp.saturating_mul(HEAL_MIN_BPS + roll % (HEAL_MAX_BPS - HEAL_MIN_BPS + 1)) / 10_000
+                }
+                _ => {
+                    let items = if is_player1 { battle.player1_items } else { battle.player2_items };
+                    require!(items > 0, GameError::NoItemsLeft);
+                    // potion also cures any DoT / poison still running on the actor
+                    if is_player1 { battle.player1_items = items - 1; battle.player1_dot_turns = 0; battle.player1_dot_damage = 0; } else { battle.player2_items = items - 1; battle.player2_dot_turns = 0; battle.player2_dot_damage = 0; }
+                    (if is_player1 { &mut battle.player1_effects } else { &mut battle.player2_effects }).retain(|fx| fx.kind != StatusKind::Poison);
+                    max_hp.saturating_mul(ITEM_MIN_BPS + roll % (ITEM_MAX_BPS - ITEM_MIN_BPS + 1)) / 10_000
+                }
+            };
+            if action != TurnAction::Defend {
+                if is_player1 { battle.player1_health = battle.player1_health.saturating_add(amount).min(max_hp); } else { battle.player2_health = battle.player2_health.saturating_add(amount).min(max_hp); }
+            }
+            record_turn(&mut ctx.accounts.turn_log, TurnLogEntry { turn_number: battle.turn_number, actor: battle.current_turn, action: action as u8, stance: chosen_stance as u8, ability: None, rolls: [roll as u16, 0, 0], damage: amount, is_crit: false, dodged: false, player1_health: battle.player1_health, player2_health: battle.player2_health });
+            battle.current_turn = if battle.current_turn == 1 { 2 } else { 1 };
+            battle.turn_number = battle.turn_number.saturating_add(1);
+            emit!(ActionResolved { battle: battle.key(), player: signer, action: action as u8, amount });
+            return Ok(());
+        }
 
         // consume base damage
         let min_d = attacker_char.base_damage_min as u64;
@@ -543,12 +1832,16 @@ pub mod battlechain_v2 {
         battle.last_entropy_index = idx_base;
 
         let base_u128 = (base as u128).checked_add((attacker_prog.level as u64).saturating_sub(1) as u128 * 2u128).ok_or(GameError::MathOverflow)?;
+        // equipped items of both sides
+        let (attacker_gear, defender_gear) = (equipment_totals(attacker_char), equipment_totals(defender_char));
+        let base_u128 = (base_u128 as i128 + attacker_gear.attack as i128).max(0) as u128;
 
         // crit roll
         let (crit_roll, idx_crit) = pool.consume_mixed_u64_return_index(&signer, b"crit", battle.turn_number as u32, 0, 9999)?;
         require!(idx_crit > battle.last_entropy_index, GameError::SeedReplay);
         battle.last_entropy_index = idx_crit;
-        let is_crit = (crit_roll as u64) < attacker_char.crit_bps as u64;
+        let crit_chance = (attacker_char.crit_bps as i64 + attacker_gear.crit_bps as i64).max(0) as u64 + if battle.arena == ArenaModifier::Frenzy { ARENA_FRENZY_CRIT_BPS } else { 0 };
+        let is_crit = (crit_roll as u64) < crit_chance.saturating_sub(defender_char.crit_resist_bps as u64);
 
         // dodge roll
         let (dodge_roll, idx_dodge) = pool.consume_mixed_u64_return_index(&signer, b"dodge", battle.turn_number as u32, 0, 9999)?;
@@ -581,17 +1874,62 @@ pub mod battlechain_v2 {
         }
         attacker_char.last_damage = base.min(u64::from(u16::MAX)) as u16;
 
-        // special handling
-        if use_special {
-            require!(attacker_char.special_cooldown == 0, GameError::SpecialOnCooldown);
-            match attacker_char.base_class {
-                CharacterClass::Warrior => { damage_fp = mul_fp_checked(damage_fp, FP_SCALE * 3)?; attacker_char.special_cooldown = 3; },
-                CharacterClass::Assassin => { damage_fp = mul_fp_checked(damage_fp, FP_SCALE * 3)?; attacker_char.special_cooldown = 4; },
-                CharacterClass::Mage => { if is_player1 { battle.player2_dot_damage = battle.player2_dot_damage.saturating_add(5); battle.player2_dot_turns = battle.player2_dot_turns.saturating_add(3) } else { battle.player1_dot_damage = battle.player1_dot_damage.saturating_add(5); battle.player1_dot_turns = battle.player1_dot_turns.saturating_add(3) } attacker_char.special_cooldown = 3; },
-                CharacterClass::Tank => { if is_player1 { battle.player1_reflection = battle.player1_reflection.saturating_add(50) } else { battle.player2_reflection = battle.player2_reflection.saturating_add(50) } attacker_char.special_cooldown = 4; },
-                CharacterClass::Trickster => { damage_fp = mul_fp_checked(damage_fp, FP_SCALE * 2)?; attacker_char.special_cooldown = 2; },
+        // ability from the actor's loadout slot
+        if let Some(slot) = ability {
+            let slot = slot as usize;
+            let chosen = *(if is_player1 { &battle.player1_abilities } else { &battle.player2_abilities }).get(slot).ok_or(GameError::InvalidLoadout)?;
+            let cooldown = if is_player1 { battle.player1_ability_cooldowns[slot] } else { battle.player2_ability_cooldowns[slot] };
+            require!(cooldown == 0, GameError::SpecialOnCooldown);
+            match chosen {
+                Ability::Cleave => {
+                    damage_fp = mul_fp_checked(damage_fp, FP_SCALE * 3)?;
+                    apply_status(battle.key(), signer, if is_player1 { &mut battle.player1_effects } else { &mut battle.player2_effects }, StatusKind::Buff, WARRIOR_BUFF_BPS, 2);
+                },
+                Ability::Backstab => {
+                    damage_fp = mul_fp_checked(damage_fp, FP_SCALE * 3)?;
+                    apply_status(battle.key(), opponent, if is_player1 { &mut battle.player2_effects } else { &mut battle.player1_effects }, StatusKind::Poison, ASSASSIN_POISON_PER_TURN, 3);
+                },
+                Ability::Ignite => { if is_player1 { battle.player2_dot_damage = battle.player2_dot_damage.saturating_add(5); battle.player2_dot_turns = battle.player2_dot_turns.saturating_add(3) } else { battle.player1_dot_damage = battle.player1_dot_damage.saturating_add(5); battle.player1_dot_turns = battle.player1_dot_turns.saturating_add(3) } },
+                Ability::Bulwark => {
+                    if is_player1 { battle.player1_reflection = battle.player1_reflection.saturating_add(50) } else { battle.player2_reflection = battle.player2_reflection.saturating_add(50) }
+                    apply_status(battle.key(), signer, if is_player1 { &mut battle.player1_effects } else { &mut battle.player2_effects }, StatusKind::Shield, TANK_SHIELD_HP, 2);
+                },
+                Ability::Hex => {
+                    damage_fp = mul_fp_checked(damage_fp, FP_SCALE * 2)?;
+                    apply_status(battle.key(), opponent, if is_player1 { &mut battle.player2_effects } else { &mut battle.player1_effects }, StatusKind::Debuff, TRICKSTER_DEBUFF_BPS, 2);
+                    if (wild as u64) < TRICKSTER_STUN_CHANCE_BPS {
+                        apply_status(battle.key(), opponent, if is_player1 { &mut battle.player2_effects } else { &mut battle.player1_effects }, StatusKind::Stun, 0, 1);
+                    }
+                },
+                Ability::QuickStrike => { damage_fp = mul_fp_checked(damage_fp, FP_SCALE * 3 / 2)?; },
             }
-            emit!(SpecialUsed { battle: battle.key(), attacker: attacker_char.nft_mint, special: attacker_char.base_class as u8 });
+            if is_player1 { battle.player1_ability_cooldowns[slot] = chosen.cooldown(); } else { battle.player2_ability_cooldowns[slot] = chosen.cooldown(); }
+            emit!(SpecialUsed { battle: battle.key(), attacker: attacker_char.nft_mint, special: chosen as u8 });
+        }
+
+        // seasonal class balance (neutral until the admin has created the class's ClassBalance)
+        let balance_info = ctx.accounts.attacker_class_balance.to_account_info();
+        if !balance_info.data_is_empty() {
+            let balance: Account<ClassBalance> = Account::try_from(&balance_info)?;
+            damage_fp = mul_fp_checked(damage_fp, FP_SCALE * balance.damage_bps as u128 / 10_000)?;
+        }
+
+        // arena damage bonus
+        if battle.arena == ArenaModifier::Bloodbath {
+            damage_fp = mul_fp_checked(damage_fp, FP_SCALE * ARENA_BLOODBATH_DAMAGE_BPS / 10_000)?;
+        }
+
+        // elemental matchup
+        let element_bps = ELEMENT_MATCHUP_BPS[attacker_char.element as usize][defender_char.element as usize];
+        if element_bps != 10_000 {
+            damage_fp = mul_fp_checked(damage_fp, FP_SCALE * element_bps as u128 / 10_000)?;
+        }
+
+        // level-gap handicap on the higher-level attacker
+        let handicap_bps = level_handicap_bps(cfg, attacker_prog.level, ctx.accounts.defender_prog.level);
+        if handicap_bps > 0 {
+            damage_fp = mul_fp_checked(damage_fp, FP_SCALE * (10_000 - handicap_bps) as u128 / 10_000)?;
+            emit!(HandicapApplied { battle: battle.key(), attacker: attacker_char.nft_mint, level_gap: attacker_prog.level - ctx.accounts.defender_prog.level, reduction_bps: handicap_bps });
         }
 
         // stance multipliers (simple function)
@@ -600,6 +1938,12 @@ pub mod battlechain_v2 {
         damage_fp = mul_fp_checked(damage_fp, att_fp)?;
         damage_fp = mul_fp_checked(damage_fp, def_fp)?;
 
+        // attacker's buffs / debuffs
+        let status_bps = status_damage_bps(if is_player1 { &battle.player1_effects } else { &battle.player2_effects });
+        if status_bps != 10_000 {
+            damage_fp = mul_fp_checked(damage_fp, FP_SCALE * status_bps as u128 / 10_000)?;
+        }
+
         // clamp
         if damage_fp > MAX_TOTAL_MULTIPLIER_FP.checked_mul(FP_SCALE).unwrap_or(damage_fp) {
             damage_fp = MAX_TOTAL_MULTIPLIER_FP.checked_mul(FP_SCALE).unwrap_or(damage_fp);
@@ -607,15 +1951,35 @@ pub mod battlechain_v2 {
         }
 
         let mut final_damage = fp_to_u64_clamped(damage_fp, GameError::MathOverflow)?;
-        final_damage = final_damage.saturating_sub(defender_char.defense as u64);
+        let defense = (defender_char.defense as i64 + defender_gear.defense as i64).max(0) as u64 * if battle.arena == ArenaModifier::Fortress { ARENA_FORTRESS_DEFENSE_MULT } else { 1 };
+        let defense = defense.saturating_mul(10_000u64.saturating_sub(attacker_char.armor_pen_bps as u64)) / 10_000;
+        final_damage = final_damage.saturating_sub(defense);
 
         // dodge
-        if (dodge_roll as u64) < defender_char.dodge_bps as u64 {
+        let dodge_chance = (defender_char.dodge_bps as i64 + defender_gear.dodge_bps as i64).max(0) as u64 + if battle.arena == ArenaModifier::LowGravity { ARENA_LOW_GRAVITY_DODGE_BPS } else { 0 };
+        let dodged = (dodge_roll as u64) < dodge_chance;
+        if dodged {
             final_damage = 0;
             if is_player1 { battle.player1_miss_count = battle.player1_miss_count.saturating_add(1) } else { battle.player2_miss_count = battle.player2_miss_count.saturating_add(1) }
             emit!(AttackMissed { battle: battle.key(), attacker: attacker_char.nft_mint, defender: defender_char.nft_mint });
         }
 
+        // defender's guard (if they defended last turn) soaks part of the hit
+        let guard_bps = if is_player1 { battle.player2_guard_bps } else { battle.player1_guard_bps } as u64;
+        if guard_bps > 0 && final_damage > 0 {
+            let blocked = final_damage.saturating_mul(guard_bps) / 10_000;
+            final_damage = final_damage.saturating_sub(blocked);
+            emit!(GuardApplied { battle: battle.key(), defender: defender_char.nft_mint, blocked });
+        }
+        // defender's shields absorb what's left before it lands
+        if final_damage > 0 {
+            let absorbed = absorb_with_shield(if is_player1 { &mut battle.player2_effects } else { &mut battle.player1_effects }, final_damage);
+            if absorbed > 0 {
+                final_damage = final_damage.saturating_sub(absorbed);
+                emit!(ShieldAbsorbed { battle: battle.key(), defender: defender_char.nft_mint, absorbed });
+            }
+        }
+
         // apply damage and reflection/counter/self
         if is_player1 {
             battle.player2_health = battle.player2_health.saturating_sub(final_damage);
@@ -653,37 +2017,10 @@ pub mod battlechain_v2 {
             }
         }
 
-        // cooldown tick
-        if attacker_char.special_cooldown > 0 { attacker_char.special_cooldown = attacker_char.special_cooldown.saturating_sub(1); }
+        record_turn(&mut ctx.accounts.turn_log, TurnLogEntry { turn_number: battle.turn_number, actor: battle.current_turn, action: TurnAction::Attack as u8, stance: chosen_stance as u8, ability, rolls: [base as u16, crit_roll as u16, dodge_roll as u16], damage: final_damage, is_crit, dodged, player1_health: battle.player1_health, player2_health: battle.player2_health });
 
         // check death, lifes, finalize if needed (simplified: award XP and finalize)
-        if battle.player1_health == 0 || battle.player2_health == 0 {
-            battle.state = BattleState::Finished;
-            let winner_opt = if battle.player1_health > battle.player2_health { Some(battle.player1) } else if battle.player2_health > battle.player1_health { Some(battle.player2) } else { None };
-            battle.winner = winner_opt;
-            // award xp
-            let (winner_pk, loser_pk) = match winner_opt {
-                Some(pk) => (Some(pk), if pk == battle.player1 { Some(battle.player2) } else { Some(battle.player1) }),
-                None => (None, None),
-            };
-            // update progression: simple defaults
-            if let Some(wpk) = winner_pk {
-                if wpk == battle.player1 {
-                    // player1 winner
-                    ctx.accounts.attacker_prog.xp = ctx.accounts.attacker_prog.xp.saturating_add(100);
-                    // maybe level up
-                    level_up_if_needed(&mut ctx.accounts.attacker_prog, &mut ctx.accounts.attacker_character)?;
-                } else {
-                    ctx.accounts.defender_prog.xp = ctx.accounts.defender_prog.xp.saturating_add(100);
-                    level_up_if_needed(&mut ctx.accounts.defender_prog, &mut ctx.accounts.defender_character)?;
-                }
-            } else {
-                // draw
-                ctx.accounts.attacker_prog.xp = ctx.accounts.attacker_prog.xp.saturating_add(25);
-                ctx.accounts.defender_prog.xp = ctx.accounts.defender_prog.xp.saturating_add(25);
-            }
-            emit!(BattleEnded { battle: battle.key(), winner: battle.winner });
-        } else {
+        if !end_battle_if_dead(battle, is_player1, attacker_char, defender_char, attacker_prog, &mut ctx.accounts.defender_prog, &mut ctx.accounts.leaderboard)? {
             // advance turn
             battle.current_turn = if battle.current_turn == 1 { 2 } else { 1 };
             battle.turn_number = battle.turn_number.saturating_add(1);
@@ -693,298 +2030,2959 @@ pub mod battlechain_v2 {
         Ok(())
     }
 
-    // Forfeit by timeout — any caller can call after inactivity_timeout since last_action_ts
+    // Forfeit by timeout — any caller can call after inactivity_timeout (plus the idle player's unused reconnect grace) since last_action_ts
     pub fn forfeit_by_timeout(ctx: Context<ForfeitByTimeout>) -> Result<()> {
         let battle = &mut ctx.accounts.battle;
         let now = Clock::get()?.unix_timestamp;
         require!(battle.state == BattleState::Active, GameError::InvalidBattleState);
-        require!(now.saturating_sub(battle.last_action_ts) > battle.inactivity_timeout, GameError::TimeoutNotReached);
-        // determine idle player: whoever was expected to act (current_turn)
-        let winner = if battle.current_turn == 1 { battle.player2 } else { battle.player1 };
+        // determine idle player: whoever was expected to act (current_turn, or the side holding up commit-reveal)
+        let idle_p1 = idle_is_player1(battle);
+        let grace_used = if idle_p1 { battle.player1_grace_used } else { battle.player2_grace_used };
+        let grace = if grace_used { 0 } else { battle.reconnect_grace };
+        require!(now.saturating_sub(battle.last_action_ts) > battle.inactivity_timeout.saturating_add(grace), GameError::TimeoutNotReached);
+        // with a per-turn deadline an idle turn is skipped (skip_idle_turn) rather than forfeited outright
+        require!(battle.turn_deadline == 0 || battle.commit_reveal || battle.async_mode, GameError::SkipTurnRequired);
+        // a fully revealed async battle waits on resolve_async_battle, not on either player
+        require!(!battle.async_mode || battle.player1_strategy.is_empty() || battle.player2_strategy.is_empty(), GameError::InvalidBattleState);
+        let winner = if idle_p1 { battle.player2 } else { battle.player1 };
         battle.state = BattleState::Finished;
         battle.winner = Some(winner);
-        // payout stakes to winner — Simplified: caller must pass battle escrow & winner account
-        // actual transfer logic handled in finalize_battle to reuse code
+        write_back_hp(&mut ctx.accounts.player1_character, battle.player1_health);
+        write_back_hp(&mut ctx.accounts.player2_character, battle.player2_health);
+        forfeit_elo(battle, &mut ctx.accounts.player1_prog, &mut ctx.accounts.player2_prog, &mut ctx.accounts.leaderboard)?;
+
+        // keeper reward comes out of the idle player's stake ledger, so settlement pays the winner what's left
+        let idle_stake = if idle_p1 { &mut battle.player1_stake } else { &mut battle.player2_stake };
+        let keeper_fee = idle_stake.saturating_mul(ctx.accounts.config.forfeit_keeper_bps as u64) / 10_000;
+        *idle_stake -= keeper_fee;
+        if keeper_fee > 0 {
+            match ctx.accounts.offer.currency {
+                Currency::SOL => {
+                    let (escrow, bump) = sol_escrow(&ctx.accounts.battle_sol_escrow, &battle.key())?;
+                    sol_escrow_release(&escrow, &battle.key(), bump, &ctx.accounts.caller.to_account_info(), keeper_fee)?;
+                },
+                Currency::SPL(mint) => {
+                    let (escrow, escrow_ata) = spl_escrow(&mut ctx.accounts.escrow_vault, &ctx.accounts.escrow_ata, mint)?;
+                    escrow_release(escrow, escrow_ata, ctx.accounts.caller_ata.to_account_info(), ctx.accounts.token_program.to_account_info(), keeper_fee)?;
+                }
+            }
+            emit!(ForfeitKeeperPaid { battle: battle.key(), keeper: ctx.accounts.caller.key(), amount: keeper_fee });
+        }
+        battle.forfeited = true;
+        // remaining stakes are paid out by settle_forfeit
         emit!(BattleForfeited { battle: battle.key(), winner });
         Ok(())
     }
 
-    // finalize_battle: distribute stakes and fees (SOL & SPL support)
-    pub fn finalize_battle(ctx: Context<FinalizeBattle>) -> Result<()> {
-        let cfg = &ctx.accounts.config;
+    // Per-turn deadline passed: anyone can pass the idle player's turn to the opponent. The idle player loses
+    // skip_penalty_bps of their remaining HP (never the last point) and some stamina; max_skips in a row forfeits.
+    pub fn skip_idle_turn(ctx: Context<ForfeitByTimeout>) -> Result<()> {
         let battle = &mut ctx.accounts.battle;
-        require!(battle.state == BattleState::Finished, GameError::BattleNotFinished);
+        let now = Clock::get()?.unix_timestamp;
+        require!(battle.state == BattleState::Active, GameError::InvalidBattleState);
+        require!(battle.turn_deadline > 0 && !battle.commit_reveal && !battle.async_mode, GameError::InvalidBattleState);
+        require!(now.saturating_sub(battle.last_action_ts) > battle.turn_deadline, GameError::TimeoutNotReached);
+        let idle_is_p1 = battle.current_turn == 1;
+        let (idle, other) = if idle_is_p1 { (battle.player1, battle.player2) } else { (battle.player2, battle.player1) };
+        let skips = if idle_is_p1 { &mut battle.player1_skips } else { &mut battle.player2_skips };
+        *skips = skips.saturating_add(1);
+        let skips = *skips;
 
-        // compute total lamports or token amount in battle escrow (for SOL: lamports; for SPL: battle_escrow.amount)
-        // For SOL: the battle PDA holds lamports from previous transfers; for SPL we use battle_escrow ATA
-        match ctx.accounts.offer.currency {
-            Currency::SOL => {
-                let total = ctx.accounts.battle.to_account_info().lamports();
-                let fee = ((total as u128) * (cfg.fee_bps as u128) / 10_000u128) as u64;
-                let payout = total.saturating_sub(fee);
-                // transfer fee to treasury
-                if fee > 0 {
-                    invoke_signed(&system_instruction::transfer(&ctx.accounts.battle.key(), &ctx.accounts.treasury.key(), fee), &[ctx.accounts.battle.to_account_info(), ctx.accounts.treasury.to_account_info()], &[&[b"battle", &battle.battle_id.to_le_bytes(), &[battle.bump]]])?;
-                }
-                if let Some(winner_pk) = battle.winner {
-                    let dest = if winner_pk == battle.player1 { &ctx.accounts.player1_owner } else { &ctx.accounts.player2_owner };
-                    invoke_signed(&system_instruction::transfer(&ctx.accounts.battle.key(), &dest.key(), payout), &[ctx.accounts.battle.to_account_info(), dest.to_account_info()], &[&[b"battle", &battle.battle_id.to_le_bytes(), &[battle.bump]]])?;
-                } else {
-                    // draw -> treasury
-                    invoke_signed(&system_instruction::transfer(&ctx.accounts.battle.key(), &ctx.accounts.treasury.key(), payout), &[ctx.accounts.battle.to_account_info(), ctx.accounts.treasury.to_account_info()], &[&[b"battle", &battle.battle_id.to_le_bytes(), &[battle.bump]]])?;
-                }
-            },
-            Currency::SPL(_) => {
-                // token transfers using CPI from battle_escrow to winner ATA / treasury
-                let total_tokens = ctx.accounts.battle_escrow.amount;
-                let fee_amt = ((total_tokens as u128) * (cfg.fee_bps as u128) / 10_000u128) as u64;
-                let payout_amt = total_tokens.saturating_sub(fee_amt);
-                // transfer fee to treasury_ata
-                if fee_amt > 0 {
-                    let cpi_accounts = token::Transfer {
-                        from: ctx.accounts.battle_escrow.to_account_info(),
-                        to: ctx.accounts.treasury_ata.to_account_info(),
-                        authority: ctx.accounts.battle.to_account_info(),
-                    };
-                    let signer_seeds = &[&[b"battle", &battle.battle_id.to_le_bytes(), &[battle.bump]][..]];
-                    token::transfer(CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds), fee_amt)?;
-                }
-                if let Some(winner_pk) = battle.winner {
-                    let dest_ata = if winner_pk == battle.player1 { &ctx.accounts.player1_ata } else { &ctx.accounts.player2_ata };
-                    let cpi_accounts = token::Transfer {
-                        from: ctx.accounts.battle_escrow.to_account_info(),
-                        to: dest_ata.to_account_info(),
-                        authority: ctx.accounts.battle.to_account_info(),
-                    };
-                    let signer_seeds = &[&[b"battle", &battle.battle_id.to_le_bytes(), &[battle.bump]][..]];
-                    token::transfer(CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds), payout_amt)?;
-                } else {
-                    // draw -> treasury_ata
-                    let cpi_accounts = token::Transfer {
-                        from: ctx.accounts.battle_escrow.to_account_info(),
-                        to: ctx.accounts.treasury_ata.to_account_info(),
-                        authority: ctx.accounts.battle.to_account_info(),
-                    };
-                    let signer_seeds = &[&[b"battle", &battle.battle_id.to_le_bytes(), &[battle.bump]][..]];
-                    token::transfer(CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds), payout_amt)?;
-                }
-            }
+        if skips >= battle.max_skips {
+            battle.state = BattleState::Finished;
+            battle.winner = Some(other);
+            battle.forfeited = true;
+            write_back_hp(&mut ctx.accounts.player1_character, battle.player1_health);
+            write_back_hp(&mut ctx.accounts.player2_character, battle.player2_health);
+            forfeit_elo(battle, &mut ctx.accounts.player1_prog, &mut ctx.accounts.player2_prog, &mut ctx.accounts.leaderboard)?;
+            emit!(TurnSkipped { battle: battle.key(), player: idle, skips, hp_penalty: 0 });
+            emit!(BattleForfeited { battle: battle.key(), winner: other });
+            return Ok(());
         }
 
-        emit!(BattleSettled { battle: battle.key(), total_paid: 0 }); // could report actual payouts
+        let penalty_bps = battle.skip_penalty_bps as u64;
+        let health = if idle_is_p1 { &mut battle.player1_health } else { &mut battle.player2_health };
+        let hp_penalty = (health.saturating_mul(penalty_bps) / 10_000).min(health.saturating_sub(1));
+        *health -= hp_penalty;
+        if idle_is_p1 { battle.player1_stamina = battle.player1_stamina.saturating_sub(SKIP_STAMINA_PENALTY); } else { battle.player2_stamina = battle.player2_stamina.saturating_sub(SKIP_STAMINA_PENALTY); }
+        battle.current_turn = if idle_is_p1 { 2 } else { 1 };
+        battle.turn_number = battle.turn_number.saturating_add(1);
+        battle.last_action_ts = now;
+        emit!(TurnSkipped { battle: battle.key(), player: idle, skips, hp_penalty });
         Ok(())
     }
-}
-
-// ------------------------
-// CONTEXTS & ACCOUNTS
-// ------------------------
 
-#[derive(Accounts)]
-pub struct CreateConfig<'info> {
-    #[account(init, payer = admin, space = 8 + Config::INIT_SPACE, seeds = [b"config"], bump)]
-    pub config: Account<'info, Config>,
-    #[account(mut)]
-    pub admin: Signer<'info>,
-    pub system_program: Program<'info, System>,
-}
+    // Chess clock ran out for the player to move: anyone can settle it as a loss on time
+    pub fn forfeit_on_time(ctx: Context<ForfeitByTimeout>) -> Result<()> {
+        let battle = &mut ctx.accounts.battle;
+        let now = Clock::get()?.unix_timestamp;
+        require!(battle.state == BattleState::Active, GameError::InvalidBattleState);
+        require!(battle.time_control, GameError::InvalidBattleState);
+        let bank = if battle.current_turn == 1 { battle.player1_time_bank } else { battle.player2_time_bank };
+        require!(now.saturating_sub(battle.last_action_ts) >= bank, GameError::TimeoutNotReached);
+        let (loser, winner) = if battle.current_turn == 1 { (battle.player1, battle.player2) } else { (battle.player2, battle.player1) };
+        if battle.current_turn == 1 { battle.player1_time_bank = 0; } else { battle.player2_time_bank = 0; }
+        battle.state = BattleState::Finished;
+        battle.winner = Some(winner);
+        battle.forfeited = true;
+        write_back_hp(&mut ctx.accounts.player1_character, battle.player1_health);
+        write_back_hp(&mut ctx.accounts.player2_character, battle.player2_health);
+        forfeit_elo(battle, &mut ctx.accounts.player1_prog, &mut ctx.accounts.player2_prog, &mut ctx.accounts.leaderboard)?;
+        emit!(TimeForfeited { battle: battle.key(), loser, winner });
+        Ok(())
+    }
 
-#[derive(Accounts)]
-pub struct CreateEntropyPool<'info> {
-    #[account(init, payer = payer, space = 8 + EntropyPool::INIT_SPACE, seeds = [b"entropy_pool"], bump)]
-    pub pool: Account<'info, EntropyPool>,
-    #[account(mut)]
-    pub payer: Signer<'info>,
-    /// CHECK: authority (admin)
-    pub authority: Signer<'info>,
-    pub system_program: Program<'info, System>,
-}
+    // Late show — first mover hasn't played within LATE_SHOW_GRACE_SECS of start_ts; any caller can award the win to the other player
+    pub fn forfeit_late_show(ctx: Context<ForfeitByTimeout>) -> Result<()> {
+        let battle = &mut ctx.accounts.battle;
+        let now = Clock::get()?.unix_timestamp;
+        require!(battle.state == BattleState::Active, GameError::InvalidBattleState);
+        require!(battle.turn_number == 0, GameError::InvalidBattleState);
+        require!(now > battle.start_ts.saturating_add(LATE_SHOW_GRACE_SECS), GameError::TimeoutNotReached);
+        let no_show = if battle.current_turn == 1 { battle.player1 } else { battle.player2 };
+        let winner = if battle.current_turn == 1 { battle.player2 } else { battle.player1 };
+        battle.state = BattleState::Finished;
+        battle.winner = Some(winner);
+        battle.forfeited = true;
+        write_back_hp(&mut ctx.accounts.player1_character, battle.player1_health);
+        write_back_hp(&mut ctx.accounts.player2_character, battle.player2_health);
+        forfeit_elo(battle, &mut ctx.accounts.player1_prog, &mut ctx.accounts.player2_prog, &mut ctx.accounts.leaderboard)?;
+        emit!(LateShowForfeited { battle: battle.key(), no_show, winner });
+        Ok(())
+    }
 
-#[derive(Accounts)]
-pub struct RefillSeedBatch<'info> {
-    #[account(mut, has_one = authority)]
-    pub pool: Account<'info, EntropyPool>,
-    /// CHECK: refiller (oracle)
-    pub refiller: Signer<'info>,
+    // ------------------------
+    // Pause / resume (two-step: one player requests, the other confirms)
+    // ------------------------
+    pub fn pause_battle(ctx: Context<PauseBattle>) -> Result<()> {
+        let battle = &mut ctx.accounts.battle;
+        let player = ctx.accounts.player.key();
+        require!(player == battle.player1 || player == battle.player2, GameError::Unauthorized);
+        require!(battle.state == BattleState::Active, GameError::InvalidBattleState);
+        require!(battle.total_paused_secs < MAX_TOTAL_PAUSE_SECS, GameError::PauseBudgetExhausted);
+        match battle.pause_requested_by {
+            Some(requester) if requester != player => {
+                // opponent confirms -> freeze
+                battle.state = BattleState::Paused;
+                battle.paused_at = Clock::get()?.unix_timestamp;
+                battle.pause_requested_by = None;
+                emit!(BattlePaused { battle: battle.key(), paused_at: battle.paused_at });
+            }
+            Some(_) => return Err(error!(GameError::ConsentPending).into()),
+            None => {
+                battle.pause_requested_by = Some(player);
+                emit!(PauseRequested { battle: battle.key(), by: player });
+            }
+        }
+        Ok(())
+    }
+
+    // Resume needs the same two-step consent, unless the pause budget is used up (then anyone can resume)
+    pub fn resume_battle(ctx: Context<ResumeBattle>) -> Result<()> {
+        let battle = &mut ctx.accounts.battle;
+        let caller = ctx.accounts.caller.key();
+        require!(battle.state == BattleState::Paused, GameError::InvalidBattleState);
+        let now = Clock::get()?.unix_timestamp;
+        let elapsed = now.saturating_sub(battle.paused_at);
+        let remaining = MAX_TOTAL_PAUSE_SECS.saturating_sub(battle.total_paused_secs);
+        let budget_spent = elapsed >= remaining;
+        if !budget_spent {
+            require!(caller == battle.player1 || caller == battle.player2, GameError::Unauthorized);
+            match battle.pause_requested_by {
+                Some(requester) if requester != caller => {}
+                Some(_) => return Err(error!(GameError::ConsentPending).into()),
+                None => {
+                    battle.pause_requested_by = Some(caller);
+                    emit!(ResumeRequested { battle: battle.key(), by: caller });
+                    return Ok(());
+                }
+            }
+        }
+        // only the in-budget part of the pause is frozen; time past the cap counts towards inactivity
+        let credited = elapsed.min(remaining);
+        battle.total_paused_secs = battle.total_paused_secs.saturating_add(credited);
+        battle.last_action_ts = battle.last_action_ts.saturating_add(credited);
+        battle.pause_requested_by = None;
+        battle.paused_at = 0;
+        battle.state = BattleState::Active;
+        emit!(BattleResumed { battle: battle.key(), paused_secs: credited, total_paused_secs: battle.total_paused_secs });
+        Ok(())
+    }
+
+    // ------------------------
+    // Commit-reveal stances: both players commit for the current turn, reveals open only once both are locked
+    // ------------------------
+    pub fn commit_move(ctx: Context<MoveCommit>, commitment: [u8; 32]) -> Result<()> {
+        let battle = &mut ctx.accounts.battle;
+        let player = ctx.accounts.player.key();
+        require!(battle.state == BattleState::Active, GameError::InvalidBattleState);
+        require!(battle.commit_reveal, GameError::InvalidBattleState);
+        let is_player1 = if player == battle.player1 { true } else if player == battle.player2 { false } else { return Err(error!(GameError::Unauthorized).into()); };
+        // first commit of a new turn clears the previous turn's commitments
+        if battle.commit_turn != battle.turn_number {
+            battle.commit_turn = battle.turn_number;
+            battle.player1_commitment = None;
+            battle.player2_commitment = None;
+            battle.player1_revealed = false;
+            battle.player2_revealed = false;
+        }
+        if is_player1 {
+            require!(battle.player1_commitment.is_none(), GameError::AlreadyCommitted);
+            battle.player1_commitment = Some(commitment);
+        } else {
+            require!(battle.player2_commitment.is_none(), GameError::AlreadyCommitted);
+            battle.player2_commitment = Some(commitment);
+        }
+        emit!(MoveCommitted { battle: battle.key(), player, turn_number: battle.turn_number });
+        Ok(())
+    }
+
+    pub fn reveal_move(ctx: Context<MoveCommit>, stance: StanceType, salt: [u8; 32]) -> Result<()> {
+        let battle = &mut ctx.accounts.battle;
+        let player = ctx.accounts.player.key();
+        require!(battle.state == BattleState::Active, GameError::InvalidBattleState);
+        require!(battle.commit_reveal && battle.commit_turn == battle.turn_number, GameError::InvalidBattleState);
+        require!(battle.player1_commitment.is_some() && battle.player2_commitment.is_some(), GameError::MovesNotCommitted);
+        let is_player1 = if player == battle.player1 { true } else if player == battle.player2 { false } else { return Err(error!(GameError::Unauthorized).into()); };
+        let (commitment, revealed) = if is_player1 { (battle.player1_commitment, battle.player1_revealed) } else { (battle.player2_commitment, battle.player2_revealed) };
+        require!(!revealed, GameError::AlreadyRevealed);
+        require!(commitment == Some(hashv(&[&[stance as u8], &salt]).0), GameError::CommitmentMismatch);
+        if is_player1 { battle.player1_stance = stance; battle.player1_revealed = true; } else { battle.player2_stance = stance; battle.player2_revealed = true; }
+        emit!(MoveRevealed { battle: battle.key(), player, turn_number: battle.turn_number, stance: stance as u8 });
+        Ok(())
+    }
+
+    // ------------------------
+    // Async battles: commit hash(strategy, salt) any time before the battle ends, reveal once both are committed,
+    // then anyone can resolve. Strategies can't be changed after the reveal.
+    // ------------------------
+    pub fn commit_async_strategy(ctx: Context<MoveCommit>, commitment: [u8; 32]) -> Result<()> {
+        let battle = &mut ctx.accounts.battle;
+        let player = ctx.accounts.player.key();
+        require!(battle.async_mode && battle.state != BattleState::Finished, GameError::InvalidBattleState);
+        let is_player1 = if player == battle.player1 { true } else if player == battle.player2 { false } else { return Err(error!(GameError::Unauthorized).into()); };
+        let slot = if is_player1 { &mut battle.player1_commitment } else { &mut battle.player2_commitment };
+        require!(slot.is_none(), GameError::AlreadyCommitted);
+        *slot = Some(commitment);
+        emit!(AsyncStrategyCommitted { battle: battle.key(), player });
+        Ok(())
+    }
+
+    pub fn reveal_async_strategy(ctx: Context<MoveCommit>, strategy: Vec<AsyncMove>, salt: [u8; 32]) -> Result<()> {
+        let battle = &mut ctx.accounts.battle;
+        let player = ctx.accounts.player.key();
+        require!(battle.async_mode && battle.state != BattleState::Finished, GameError::InvalidBattleState);
+        require!(battle.player1_commitment.is_some() && battle.player2_commitment.is_some(), GameError::MovesNotCommitted);
+        require!(!strategy.is_empty() && strategy.len() <= MAX_ASYNC_STRATEGY, GameError::InvalidRange);
+        let is_player1 = if player == battle.player1 { true } else if player == battle.player2 { false } else { return Err(error!(GameError::Unauthorized).into()); };
+        let abilities = if is_player1 { battle.player1_abilities.len() } else { battle.player2_abilities.len() };
+        require!(strategy.iter().all(|m| m.ability.map_or(true, |slot| (slot as usize) < abilities)), GameError::InvalidLoadout);
+        let (commitment, revealed) = if is_player1 { (battle.player1_commitment, !battle.player1_strategy.is_empty()) } else { (battle.player2_commitment, !battle.player2_strategy.is_empty()) };
+        require!(!revealed, GameError::AlreadyRevealed);
+        require!(commitment == Some(hashv(&[&strategy.try_to_vec()?, &salt]).0), GameError::CommitmentMismatch);
+        let moves = strategy.len() as u8;
+        if is_player1 { battle.player1_strategy = strategy; } else { battle.player2_strategy = strategy; }
+        emit!(AsyncStrategyRevealed { battle: battle.key(), player, moves });
+        Ok(())
+    }
+
+    // Plays the battle out from both strategies: each side cycles through its own list (stance + optional loadout
+    // ability, skipped while on cooldown). Condensed pipeline: base / crit / dodge rolls, gear, element, stances,
+    // ability burst, armor-pen-reduced defense; stance self-damage and counters hit the attacker.
+    pub fn resolve_async_battle(ctx: Context<ResolveAsyncBattle>) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        let battle = &mut ctx.accounts.battle;
+        require!(battle.state == BattleState::Active && battle.async_mode, GameError::InvalidBattleState);
+        require!(Clock::get()?.unix_timestamp >= battle.start_ts, GameError::BattleNotStarted);
+        require!(!battle.player1_strategy.is_empty() && !battle.player2_strategy.is_empty(), GameError::MovesNotRevealed);
+        let battle_key = battle.key();
+        let (p1_char, p2_char) = (&mut ctx.accounts.player1_character, &mut ctx.accounts.player2_character);
+        let (p1_gear, p2_gear) = (equipment_totals(p1_char), equipment_totals(p2_char));
+        let levels = [ctx.accounts.player1_prog.level, ctx.accounts.player2_prog.level];
+        let mut own_turns = [0usize; 2];
+        let mut turns = 0u16;
+
+        while battle.player1_health > 0 && battle.player2_health > 0 && turns < ASYNC_MAX_TURNS {
+            let is_player1 = battle.current_turn == 1;
+            let side = if is_player1 { 0 } else { 1 };
+            let (attacker, defender, att_gear, def_gear) = if is_player1 { (&**p1_char, &**p2_char, p1_gear, p2_gear) } else { (&**p2_char, &**p1_char, p2_gear, p1_gear) };
+            let strategy = if is_player1 { &battle.player1_strategy } else { &battle.player2_strategy };
+            let mv = strategy[own_turns[side] % strategy.len()];
+            own_turns[side] += 1;
+            // a stance the character hasn't unlocked is played as Balanced
+            let stance = if stance_unlocked(&ctx.accounts.config, mv.stance, levels[side]) { mv.stance } else { StanceType::Balanced };
+            let cooldowns = if is_player1 { &mut battle.player1_ability_cooldowns } else { &mut battle.player2_ability_cooldowns };
+            for cd in cooldowns.iter_mut() { *cd = cd.saturating_sub(1); }
+            if is_player1 { battle.player1_stance = stance; } else { battle.player2_stance = stance; }
+            let turn = battle.turn_number as u32;
+
+            let (base, idx_base) = pool.consume_mixed_u64_return_index(&battle_key, b"async_base", turn, attacker.base_damage_min as u64, attacker.base_damage_max as u64)?;
+            let (crit_roll, idx_crit) = pool.consume_mixed_u64_return_index(&battle_key, b"async_crit", turn, 0, 9999)?;
+            let (dodge_roll, idx_dodge) = pool.consume_mixed_u64_return_index(&battle_key, b"async_dodge", turn, 0, 9999)?;
+            require!(idx_base > battle.last_entropy_index && idx_crit > idx_base && idx_dodge > idx_crit, GameError::SeedReplay);
+            battle.last_entropy_index = idx_dodge;
+
+            let mut damage_fp = ((base as i128 + att_gear.attack as i128).max(0) as u128).checked_mul(FP_SCALE).ok_or(GameError::MathOverflow)?;
+            let crit_chance = (attacker.crit_bps as i64 + att_gear.crit_bps as i64).max(0) as u64;
+            let is_crit = crit_roll < crit_chance.saturating_sub(defender.crit_resist_bps as u64);
+            if is_crit {
+                damage_fp = mul_fp_checked(damage_fp, (2000000u128).min(attacker.crit_multiplier_fp as u128))?;
+            }
+            if let Some(slot) = mv.ability.map(|s| s as usize) {
+                let ability = if is_player1 { battle.player1_abilities[slot] } else { battle.player2_abilities[slot] };
+                let cooldowns = if is_player1 { &mut battle.player1_ability_cooldowns } else { &mut battle.player2_ability_cooldowns };
+                if cooldowns[slot] == 0 {
+                    cooldowns[slot] = ability.cooldown();
+                    damage_fp = mul_fp_checked(damage_fp, ability.burst_fp())?;
+                    emit!(SpecialUsed { battle: battle_key, attacker: attacker.nft_mint, special: ability as u8 });
+                }
+            }
+            let element_bps = ELEMENT_MATCHUP_BPS[attacker.element as usize][defender.element as usize];
+            damage_fp = mul_fp_checked(damage_fp, FP_SCALE * element_bps as u128 / 10_000)?;
+            let defender_stance = if is_player1 { battle.player2_stance } else { battle.player1_stance };
+            let (att_fp, def_fp, self_bps, counter_bps) = stance_multipliers(stance, defender_stance);
+            damage_fp = mul_fp_checked(mul_fp_checked(damage_fp, att_fp)?, def_fp)?;
+            damage_fp = damage_fp.min(MAX_TOTAL_MULTIPLIER_FP.checked_mul(FP_SCALE).unwrap_or(damage_fp));
+
+            let mut damage = fp_to_u64_clamped(damage_fp, GameError::MathOverflow)?;
+            let defense = (defender.defense as i64 + def_gear.defense as i64).max(0) as u64;
+            damage = damage.saturating_sub(defense.saturating_mul(10_000u64.saturating_sub(attacker.armor_pen_bps as u64)) / 10_000);
+            if dodge_roll < (defender.dodge_bps as i64 + def_gear.dodge_bps as i64).max(0) as u64 { damage = 0; }
+            let recoil = damage.saturating_mul(self_bps as u64 + counter_bps as u64) / 10_000;
+            if is_player1 {
+                battle.player2_health = battle.player2_health.saturating_sub(damage);
+                battle.player1_health = battle.player1_health.saturating_sub(recoil);
+            } else {
+                battle.player1_health = battle.player1_health.saturating_sub(damage);
+                battle.player2_health = battle.player2_health.saturating_sub(recoil);
+            }
+            emit!(TurnResolved { battle: battle_key, turn_number: battle.turn_number, attacker: attacker.nft_mint, defender: defender.nft_mint, damage_dealt: damage, is_crit });
+            battle.current_turn = if is_player1 { 2 } else { 1 };
+            battle.turn_number = battle.turn_number.saturating_add(1);
+            turns += 1;
+        }
+
+        let winner = match (battle.player1_health, battle.player2_health) {
+            (0, h) if h > 0 => Some(battle.player2),
+            (h, 0) if h > 0 => Some(battle.player1),
+            _ => turn_limit_winner(battle, p1_char.max_hp, p2_char.max_hp),
+        };
+        battle.last_action_ts = Clock::get()?.unix_timestamp;
+        finish_battle(battle, winner, true, p1_char, p2_char, &mut ctx.accounts.player1_prog, &mut ctx.accounts.player2_prog, &mut ctx.accounts.leaderboard)?;
+        emit!(AsyncBattleResolved { battle: battle_key, turns, winner });
+        Ok(())
+    }
+
+    // ------------------------
+    // Mutual draw: one player offers, the opponent accepts; playing a turn drops a pending offer
+    // ------------------------
+    pub fn offer_draw(ctx: Context<DrawOffer>) -> Result<()> {
+        let battle = &mut ctx.accounts.battle;
+        let player = ctx.accounts.player.key();
+        require!(player == battle.player1 || player == battle.player2, GameError::Unauthorized);
+        require!(battle.state == BattleState::Active || battle.state == BattleState::Paused, GameError::InvalidBattleState);
+        require!(battle.pending_draw.is_none(), GameError::ConsentPending);
+        battle.pending_draw = Some(player);
+        emit!(DrawOffered { battle: battle.key(), by: player });
+        Ok(())
+    }
+
+    pub fn accept_draw(ctx: Context<DrawOffer>) -> Result<()> {
+        let battle = &mut ctx.accounts.battle;
+        let player = ctx.accounts.player.key();
+        require!(player == battle.player1 || player == battle.player2, GameError::Unauthorized);
+        require!(battle.state == BattleState::Active || battle.state == BattleState::Paused, GameError::InvalidBattleState);
+        require!(matches!(battle.pending_draw, Some(by) if by != player), GameError::NoDrawOffered);
+        battle.pending_draw = None;
+        battle.mutual_draw = true;
+        battle.state = BattleState::Finished;
+        battle.winner = None;
+        emit!(DrawAgreed { battle: battle.key() });
+        Ok(())
+    }
+
+    // Double-AFK: neither player acted for 2x the timeout -> anyone cancels, both stakes refunded minus crank fee.
+    // Battle ends as Finished with no winner (prediction markets settle it as no-winner) and is marked settled.
+    pub fn cancel_abandoned_battle(ctx: Context<CancelAbandonedBattle>) -> Result<()> {
+        let battle = &mut ctx.accounts.battle;
+        let now = Clock::get()?.unix_timestamp;
+        require!(battle.state == BattleState::Active, GameError::InvalidBattleState);
+        require!(!battle.settled, GameError::AlreadySettled);
+        require!(now.saturating_sub(battle.last_action_ts) > battle.inactivity_timeout.saturating_mul(2), GameError::TimeoutNotReached);
+
+        let fee1 = battle.player1_stake.saturating_mul(ABANDON_CRANK_BPS) / 10_000;
+        let fee2 = battle.player2_stake.saturating_mul(ABANDON_CRANK_BPS) / 10_000;
+        // sponsor bonus and bounties are split evenly between both players on cancellation
+        let bonus = battle.bonus_pot.saturating_add(battle.bounty_pot);
+        let refund1 = battle.player1_stake.saturating_sub(fee1).saturating_add(bonus / 2);
+        let refund2 = battle.player2_stake.saturating_sub(fee2).saturating_add(bonus - bonus / 2);
+        battle.bonus_pot = 0;
+        battle.bounty_pot = 0;
+        let crank_fee = fee1.saturating_add(fee2);
+
+        match ctx.accounts.offer.currency {
+            Currency::SOL => {
+                let (escrow, bump) = sol_escrow(&ctx.accounts.battle_sol_escrow, &battle.key())?;
+                sol_escrow_release(&escrow, &battle.key(), bump, &ctx.accounts.player1.to_account_info(), refund1)?;
+                sol_escrow_release(&escrow, &battle.key(), bump, &ctx.accounts.player2.to_account_info(), refund2)?;
+                sol_escrow_release(&escrow, &battle.key(), bump, &ctx.accounts.caller.to_account_info(), crank_fee)?;
+            },
+            Currency::SPL(mint) => {
+                let (escrow, escrow_ata) = spl_escrow(&mut ctx.accounts.escrow_vault, &ctx.accounts.escrow_ata, mint)?;
+                for (to, amount) in [(&ctx.accounts.player1_ata, refund1), (&ctx.accounts.player2_ata, refund2), (&ctx.accounts.caller_ata, crank_fee)] {
+                    escrow_release(escrow, escrow_ata, to.to_account_info(), ctx.accounts.token_program.to_account_info(), amount)?;
+                }
+            }
+        }
+
+        battle.state = BattleState::Finished;
+        battle.winner = None;
+        battle.settled = true;
+        battle.settled_at = now;
+        battle.settlement = Settlement { fee: crank_fee, bonus_paid: bonus, refund_player1: refund1, refund_player2: refund2, ..Default::default() };
+        release_character(&mut ctx.accounts.player1_character, battle.player1_character, battle.key());
+        release_character(&mut ctx.accounts.player2_character, battle.player2_character, battle.key());
+        emit!(BattleSettled { battle: battle.key(), winner: None, settlement: battle.settlement });
+        emit!(BattleAbandoned { battle: battle.key(), cranker: ctx.accounts.caller.key(), refund_player1: refund1, refund_player2: refund2, crank_fee });
+        Ok(())
+    }
+
+    // Sponsor/streamer prize boost for a specific match; paid to the winner on top of the stakes, fee-free
+    pub fn fund_bonus(ctx: Context<FundBonus>, amount: u64) -> Result<()> {
+        require!(amount > 0, GameError::InvalidRange);
+        let battle = &mut ctx.accounts.battle;
+        require!(battle.state != BattleState::Finished, GameError::BattleAlreadyFinished);
+        require!(!battle.settled, GameError::AlreadySettled);
+        match ctx.accounts.offer.currency {
+            Currency::SOL => {
+                let (escrow, _) = sol_escrow(&ctx.accounts.battle_sol_escrow, &battle.key())?;
+                sol_escrow_deposit(&ctx.accounts.sponsor.to_account_info(), &escrow, amount)?;
+            },
+            Currency::SPL(mint) => {
+                let (escrow, escrow_ata) = spl_escrow(&mut ctx.accounts.escrow_vault, &ctx.accounts.escrow_ata, mint)?;
+                escrow_deposit(escrow, escrow_ata, ctx.accounts.sponsor_ata.to_account_info(), ctx.accounts.sponsor.to_account_info(), &[], ctx.accounts.token_program.to_account_info(), amount)?;
+            }
+        }
+        battle.bonus_pot = battle.bonus_pot.checked_add(amount).ok_or(GameError::MathOverflow)?;
+        emit!(BonusFunded { battle: battle.key(), sponsor: ctx.accounts.sponsor.key(), amount, bonus_pot: battle.bonus_pot });
+        Ok(())
+    }
+
+    // Crowd-funded bounty: anyone tops up the prize before the battle finishes. Unlike fund_bonus, bounties are
+    // part of the fee base at finalize; refunds on no-show / abandon / agreed draw split them like the bonus.
+    pub fn add_bounty(ctx: Context<AddBounty>, amount: u64) -> Result<()> {
+        require!(amount > 0, GameError::InvalidRange);
+        let battle = &mut ctx.accounts.battle;
+        require!(battle.state != BattleState::Finished, GameError::BattleAlreadyFinished);
+        require!(!battle.settled, GameError::AlreadySettled);
+        match ctx.accounts.offer.currency {
+            Currency::SOL => {
+                let (escrow, _) = sol_escrow(&ctx.accounts.battle_sol_escrow, &battle.key())?;
+                sol_escrow_deposit(&ctx.accounts.contributor.to_account_info(), &escrow, amount)?;
+            },
+            Currency::SPL(mint) => {
+                require!(ctx.accounts.config.spl_whitelist.contains(&mint), GameError::SPLNotWhitelisted);
+                let (escrow, escrow_ata) = spl_escrow(&mut ctx.accounts.escrow_vault, &ctx.accounts.escrow_ata, mint)?;
+                escrow_deposit(escrow, escrow_ata, ctx.accounts.contributor_ata.to_account_info(), ctx.accounts.contributor.to_account_info(), &[], ctx.accounts.token_program.to_account_info(), amount)?;
+            }
+        }
+        battle.bounty_pot = battle.bounty_pot.checked_add(amount).ok_or(GameError::MathOverflow)?;
+        emit!(BountyAdded { battle: battle.key(), contributor: ctx.accounts.contributor.key(), amount, bounty_pot: battle.bounty_pot });
+        Ok(())
+    }
+
+    // Spectator emote on an active battle; a tip (SOL battles only) joins the bonus pot paid out with the winner
+    pub fn spectate_react(ctx: Context<SpectateReact>, emote: u8, tip: u64) -> Result<()> {
+        require!(emote < EMOTE_COUNT, GameError::InvalidRange);
+        let battle = &mut ctx.accounts.battle;
+        require!(battle.state == BattleState::Active, GameError::InvalidBattleState);
+        if tip > 0 {
+            require!(ctx.accounts.offer.currency == Currency::SOL, GameError::InvalidRange);
+            let (escrow, _) = sol_escrow(&ctx.accounts.battle_sol_escrow, &battle.key())?;
+            sol_escrow_deposit(&ctx.accounts.spectator.to_account_info(), &escrow, tip)?;
+            battle.bonus_pot = battle.bonus_pot.checked_add(tip).ok_or(GameError::MathOverflow)?;
+        }
+        emit!(SpectatorReacted { battle: battle.key(), spectator: ctx.accounts.spectator.key(), emote, tip, bonus_pot: battle.bonus_pot });
+        Ok(())
+    }
+
+    // finalize_battle: distribute stakes and fees (SOL & SPL support)
+    // remaining_accounts (optional, writable): WinrateStats PDAs to update for the players' classes / final stances
+    pub fn finalize_battle<'info>(ctx: Context<'_, '_, '_, 'info, FinalizeBattle<'info>>) -> Result<()> {
+        record_winrates(&ctx.accounts.battle, ctx.remaining_accounts)?;
+        let cfg = &ctx.accounts.config;
+        let battle = &mut ctx.accounts.battle;
+        require!(battle.state == BattleState::Finished, GameError::BattleNotFinished);
+        require!(!battle.settled, GameError::AlreadySettled);
+        // the winner must still hold their character NFT to collect
+        if let Some(winner_pk) = battle.winner {
+            let (ch, ata) = if winner_pk == battle.player1 { (&ctx.accounts.player1_character, &ctx.accounts.player1_nft_ata) } else { (&ctx.accounts.player2_character, &ctx.accounts.player2_nft_ata) };
+            require!(ata.mint == ch.nft_mint && ata.owner == winner_pk && ata.amount == 1, GameError::NotNftOwner);
+        }
+        battle.settled = true;
+        battle.settled_at = Clock::get()?.unix_timestamp;
+        let battle_key = battle.key();
+        for ch in [&mut ctx.accounts.player1_character, &mut ctx.accounts.player2_character] {
+            if ch.active_battle == battle_key { ch.active_battle = Pubkey::default(); }
+        }
+        // sponsor bonus and bounties ride along with the winner payout (draws send them to treasury with the stakes)
+        let bonus = battle.bonus_pot;
+        let bounty = battle.bounty_pot;
+        // winner's guild perk tier discounts the protocol fee
+        let fee_bps = cfg.fee_bps.saturating_sub(winner_guild_discount_bps(battle.winner, &ctx.accounts.winner_guild_member, &ctx.accounts.winner_guild));
+
+        // agreed draw: both stakes back in full, sponsor bonus and bounties split evenly, no protocol fee
+        if battle.mutual_draw {
+            let extra = bonus.saturating_add(bounty);
+            let refund1 = battle.player1_stake.saturating_add(extra / 2);
+            let refund2 = battle.player2_stake.saturating_add(extra - extra / 2);
+            match ctx.accounts.offer.currency {
+                Currency::SOL => {
+                    let (escrow, bump) = sol_escrow(&ctx.accounts.battle_sol_escrow, &battle.key())?;
+                    sol_escrow_release(&escrow, &battle.key(), bump, &ctx.accounts.player1_owner.to_account_info(), refund1)?;
+                    sol_escrow_release(&escrow, &battle.key(), bump, &ctx.accounts.player2_owner.to_account_info(), refund2)?;
+                },
+                Currency::SPL(mint) => {
+                    let (escrow, escrow_ata) = spl_escrow(&mut ctx.accounts.escrow_vault, &ctx.accounts.escrow_ata, mint)?;
+                    escrow_release(escrow, escrow_ata, ctx.accounts.player1_ata.to_account_info(), ctx.accounts.token_program.to_account_info(), refund1)?;
+                    escrow_release(escrow, escrow_ata, ctx.accounts.player2_ata.to_account_info(), ctx.accounts.token_program.to_account_info(), refund2)?;
+                }
+            }
+            battle.settlement = Settlement { bonus_paid: bonus, refund_player1: refund1, refund_player2: refund2, ..Default::default() };
+            emit!(BattleSettled { battle: battle.key(), winner: None, settlement: battle.settlement });
+            return Ok(());
+        }
+
+        // payouts are sized from the battle's ledger (stakes + bonus); the funds themselves sit in the battle's
+        // SOL escrow PDA or, for SPL, in the mint's shared escrow ATA
+        let total = battle.player1_stake.saturating_add(battle.player2_stake).saturating_add(bounty).saturating_add(bonus);
+        // protocol fee is taken on stakes and bounties; the sponsor bonus goes through untouched
+        let fee = ((total.saturating_sub(bonus) as u128) * (fee_bps as u128) / 10_000u128) as u64;
+        let payout = total.saturating_sub(fee);
+        match ctx.accounts.offer.currency {
+            Currency::SOL => {
+                let (escrow, bump) = sol_escrow(&ctx.accounts.battle_sol_escrow, &battle.key())?;
+                // transfer fee to treasury
+                sol_escrow_release(&escrow, &battle.key(), bump, &ctx.accounts.treasury.to_account_info(), fee)?;
+                if let Some(vault) = ctx.accounts.winner_vault.as_mut().filter(|v| Some(v.owner) == battle.winner) {
+                    // winnings settle straight into the winner's vault
+                    sol_escrow_release(&escrow, &battle.key(), bump, &vault.to_account_info(), payout)?;
+                    vault.sol_balance = vault.sol_balance.saturating_add(payout);
+                } else if let Some(winner_pk) = battle.winner {
+                    let dest = if winner_pk == battle.player1 { &ctx.accounts.player1_owner } else { &ctx.accounts.player2_owner };
+                    sol_escrow_release(&escrow, &battle.key(), bump, &dest.to_account_info(), payout)?;
+                } else {
+                    // draw -> treasury
+                    sol_escrow_release(&escrow, &battle.key(), bump, &ctx.accounts.treasury.to_account_info(), payout)?;
+                }
+            },
+            Currency::SPL(mint) => {
+                // token transfers from the mint's escrow ATA to winner ATA / treasury
+                let (escrow, escrow_ata) = spl_escrow(&mut ctx.accounts.escrow_vault, &ctx.accounts.escrow_ata, mint)?;
+                // transfer fee to treasury_ata
+                escrow_release(escrow, escrow_ata, ctx.accounts.treasury_ata.to_account_info(), ctx.accounts.token_program.to_account_info(), fee)?;
+                let dest_ata = match battle.winner {
+                    Some(winner_pk) if winner_pk == battle.player1 => &ctx.accounts.player1_ata,
+                    Some(_) => &ctx.accounts.player2_ata,
+                    // draw -> treasury_ata
+                    None => &ctx.accounts.treasury_ata,
+                };
+                escrow_release(escrow, escrow_ata, dest_ata.to_account_info(), ctx.accounts.token_program.to_account_info(), payout)?;
+            }
+        }
+
+        battle.settlement = if battle.winner.is_some() {
+            Settlement { fee, winner_payout: payout, bonus_paid: bonus, ..Default::default() }
+        } else {
+            Settlement { fee, draw_to_treasury: payout, ..Default::default() }
+        };
+        emit!(BattleSettled { battle: battle.key(), winner: battle.winner, settlement: battle.settlement });
+        Ok(())
+    }
+
+    // Permissionless payout of a forfeited battle: the idle player can't be made to sign finalize_battle, so anyone
+    // can pay the winner (stakes + bounties - protocol fee, plus the sponsor bonus) and release both characters.
+    pub fn settle_forfeit(ctx: Context<SettleForfeit>) -> Result<()> {
+        let cfg = &ctx.accounts.config;
+        let battle = &mut ctx.accounts.battle;
+        require!(battle.state == BattleState::Finished && battle.forfeited, GameError::BattleNotFinished);
+        require!(!battle.settled, GameError::AlreadySettled);
+        let winner_pk = battle.winner.ok_or(GameError::InvalidBattleState)?;
+        // same rule as finalize_battle: the winner must still hold their character NFT to collect
+        let ch = if winner_pk == battle.player1 { &ctx.accounts.player1_character } else { &ctx.accounts.player2_character };
+        let ata = &ctx.accounts.winner_nft_ata;
+        require!(ata.mint == ch.nft_mint && ata.owner == winner_pk && ata.amount == 1, GameError::NotNftOwner);
+        battle.settled = true;
+        battle.settled_at = Clock::get()?.unix_timestamp;
+        let battle_key = battle.key();
+        for ch in [&mut ctx.accounts.player1_character, &mut ctx.accounts.player2_character] {
+            if ch.active_battle == battle_key { ch.active_battle = Pubkey::default(); }
+        }
+
+        let bonus = battle.bonus_pot;
+        let fee_bps = cfg.fee_bps.saturating_sub(winner_guild_discount_bps(battle.winner, &ctx.accounts.winner_guild_member, &ctx.accounts.winner_guild));
+        let total = battle.player1_stake.saturating_add(battle.player2_stake).saturating_add(battle.bounty_pot).saturating_add(bonus);
+        let fee = ((total.saturating_sub(bonus) as u128) * (fee_bps as u128) / 10_000u128) as u64;
+        let payout = total.saturating_sub(fee);
+        match ctx.accounts.offer.currency {
+            Currency::SOL => {
+                let (escrow, bump) = sol_escrow(&ctx.accounts.battle_sol_escrow, &battle_key)?;
+                sol_escrow_release(&escrow, &battle_key, bump, &ctx.accounts.treasury.to_account_info(), fee)?;
+                sol_escrow_release(&escrow, &battle_key, bump, &ctx.accounts.winner.to_account_info(), payout)?;
+            },
+            Currency::SPL(mint) => {
+                let (escrow, escrow_ata) = spl_escrow(&mut ctx.accounts.escrow_vault, &ctx.accounts.escrow_ata, mint)?;
+                escrow_release(escrow, escrow_ata, ctx.accounts.treasury_ata.to_account_info(), ctx.accounts.token_program.to_account_info(), fee)?;
+                escrow_release(escrow, escrow_ata, ctx.accounts.winner_ata.to_account_info(), ctx.accounts.token_program.to_account_info(), payout)?;
+            }
+        }
+        battle.settlement = Settlement { fee, winner_payout: payout, bonus_paid: bonus, ..Default::default() };
+        emit!(BattleSettled { battle: battle_key, winner: battle.winner, settlement: battle.settlement });
+        Ok(())
+    }
+
+    // Releases a character still locked to a battle that has been settled (its accounts weren't passed at settlement)
+    pub fn unlock_character(ctx: Context<UnlockCharacter>) -> Result<()> {
+        let battle = &ctx.accounts.battle;
+        require!(battle.settled, GameError::BattleNotFinished);
+        let ch = &mut ctx.accounts.character;
+        require!(ch.active_battle == battle.key(), GameError::CharacterConstraint);
+        ch.active_battle = Pubkey::default();
+        emit!(CharacterUnlocked { nft_mint: ch.nft_mint, battle: battle.key() });
+        Ok(())
+    }
+
+    // Permissionless: once a battle has been settled for BATTLE_CLOSE_DELAY_SECS and both NFTs are thawed, close it.
+    // Rent goes to the winner (player1, the offer creator, on draws); SPL funds sit in the shared per-mint escrow ATA,
+    // a SOL escrow PDA is swept along with the rent. A prediction market still open on the battle is voided
+    // prediction-side (void_game_pool) and its bettors reclaim their stakes.
+    pub fn close_battle(ctx: Context<CloseBattle>) -> Result<()> {
+        let battle = &ctx.accounts.battle;
+        require!(battle.settled, GameError::BattleNotFinished);
+        require!(Clock::get()?.unix_timestamp >= battle.settled_at.saturating_add(BATTLE_CLOSE_DELAY_SECS), GameError::TimeoutNotReached);
+        require!(!battle.player1_nft_frozen && !battle.player2_nft_frozen, GameError::InvalidBattleState);
+        let battle_key = battle.key();
+        for ch in [&mut ctx.accounts.player1_character, &mut ctx.accounts.player2_character] {
+            if ch.active_battle == battle_key { ch.active_battle = Pubkey::default(); }
+        }
+        sweep_sol_escrow(&ctx.accounts.battle_sol_escrow, &battle_key, &ctx.accounts.rent_receiver.to_account_info())?;
+        emit!(BattleClosed { battle: battle_key, rent_receiver: ctx.accounts.rent_receiver.key() });
+        Ok(())
+    }
+
+    // Anyone can open a battle's TurnLog (usually right after approve); from then on every turn must write to it
+    pub fn init_turn_log(ctx: Context<InitTurnLog>) -> Result<()> {
+        let battle = &mut ctx.accounts.battle;
+        require!(battle.state != BattleState::Finished, GameError::BattleAlreadyFinished);
+        let log = &mut ctx.accounts.turn_log;
+        log.battle = battle.key();
+        log.total_turns = 0;
+        log.entries = Vec::new();
+        log.bump = *ctx.bumps.get("turn_log").unwrap_or(&0);
+        battle.has_turn_log = true;
+        emit!(TurnLogOpened { battle: battle.key(), turn_log: log.key(), from_turn: battle.turn_number });
+        Ok(())
+    }
+
+    // ------------------------
+    // Views: read-only, Borsh-encoded result goes out via sol_set_return_data (for CPI callers / simulateTransaction)
+    // ------------------------
+    pub fn get_battle_summary(ctx: Context<GetBattleSummary>) -> Result<()> {
+        let battle = &ctx.accounts.battle;
+        let now = Clock::get()?.unix_timestamp;
+        let summary = BattleSummary {
+            battle_id: battle.battle_id,
+            state: battle.state,
+            current_turn: battle.current_turn,
+            turn_number: battle.turn_number,
+            player1_health: battle.player1_health,
+            player2_health: battle.player2_health,
+            winner: battle.winner,
+            // frozen while paused, 0 once expired or not Active
+            secs_until_timeout: if battle.state == BattleState::Active { battle.last_action_ts.saturating_add(battle.inactivity_timeout).saturating_sub(now).max(0) } else { 0 },
+            stake_total: battle.player1_stake.saturating_add(battle.player2_stake),
+            bonus_pot: battle.bonus_pot,
+            bounty_pot: battle.bounty_pot,
+            settled: battle.settled,
+        };
+        set_return_data(&summary.try_to_vec()?);
+        Ok(())
+    }
+
+    // effective numbers as execute_turn would use them (level bonus included, before stance/crit/combo rolls)
+    pub fn get_character_combat_stats(ctx: Context<GetCharacterCombatStats>) -> Result<()> {
+        let ch = &ctx.accounts.character;
+        let level_bonus = (ctx.accounts.progression.level as u64).saturating_sub(1).saturating_mul(2);
+        let stats = CharacterCombatStats {
+            nft_mint: ch.nft_mint,
+            base_class: ch.base_class,
+            level: ctx.accounts.progression.level,
+            max_hp: ch.max_hp,
+            damage_min: (ch.base_damage_min as u64).saturating_add(level_bonus),
+            damage_max: (ch.base_damage_max as u64).saturating_add(level_bonus),
+            crit_bps: ch.crit_bps,
+            crit_multiplier_fp: (2000000u32).min(ch.crit_multiplier_fp),
+            dodge_bps: ch.dodge_bps,
+            defense: ch.defense,
+            special_cooldown: ch.special_cooldown,
+            combo_count: ch.combo_count,
+        };
+        set_return_data(&stats.try_to_vec()?);
+        Ok(())
+    }
+
+    // ------------------------
+    // Tutorial (no stakes, no entropy pool: rolls are seeded from the player key + turn so runs are reproducible)
+    // ------------------------
+    pub fn start_tutorial(ctx: Context<StartTutorial>) -> Result<()> {
+        let t = &mut ctx.accounts.tutorial;
+        require!(!t.completed, GameError::TutorialAlreadyCompleted);
+        t.player = ctx.accounts.player.key();
+        t.step = 0;
+        t.turn = 0;
+        t.player_health = 100;
+        t.ai_health = TUTORIAL_AI_HP;
+        t.last_base = 0;
+        t.combo_count = 0;
+        t.bump = *ctx.bumps.get("tutorial").unwrap_or(&0);
+        emit!(TutorialStarted { player: t.player });
+        Ok(())
+    }
+
+    // Lesson 0: pick a non-Balanced stance. Lesson 1: use the special. Lesson 2: land a (scripted) combo.
+    // After the lessons the player just has to bring the AI to 0.
+    pub fn tutorial_turn(ctx: Context<TutorialTurn>, chosen_stance: StanceType, use_special: bool) -> Result<()> {
+        let t = &mut ctx.accounts.tutorial;
+        require!(!t.completed, GameError::TutorialAlreadyCompleted);
+        match t.step {
+            0 => require!(chosen_stance != StanceType::Balanced, GameError::TutorialStepNotMet),
+            1 => require!(use_special, GameError::TutorialStepNotMet),
+            _ => {}
+        }
+
+        // deterministic base roll 8..=15; the combo lesson repeats the previous roll so the combo triggers
+        let h = hashv(&[b"tutorial", &t.player.to_bytes(), &t.turn.to_le_bytes()]).0;
+        let mut base = 8 + (h[0] as u64 % 8);
+        if t.step == 2 && t.last_base > 0 { base = t.last_base; }
+        let mut damage_fp = (base as u128).checked_mul(FP_SCALE).ok_or(GameError::MathOverflow)?;
+        if base == t.last_base {
+            t.combo_count = t.combo_count.saturating_add(1).min(MAX_COMBO_STACK);
+            damage_fp = mul_fp_checked(damage_fp, FP_SCALE + 150_000u128 * (t.combo_count as u128))?;
+        } else {
+            t.combo_count = 0;
+        }
+        t.last_base = base;
+        if use_special { damage_fp = mul_fp_checked(damage_fp, FP_SCALE * 2)?; }
+        let (att_fp, def_fp, _, _) = stance_multipliers(chosen_stance, StanceType::Balanced);
+        damage_fp = mul_fp_checked(mul_fp_checked(damage_fp, att_fp)?, def_fp)?;
+        let damage = fp_to_u64_clamped(damage_fp, GameError::MathOverflow)?;
+
+        t.ai_health = t.ai_health.saturating_sub(damage);
+        // scripted AI hits back but never knocks the player out
+        t.player_health = t.player_health.saturating_sub(TUTORIAL_AI_DAMAGE).max(1);
+        let lesson_done = match t.step { 0 => true, 1 => use_special, 2 => t.combo_count > 0, _ => false };
+        if lesson_done && t.step < TUTORIAL_LESSONS { t.step += 1; }
+        t.turn = t.turn.saturating_add(1);
+        if t.step >= TUTORIAL_LESSONS && t.ai_health == 0 {
+            t.completed = true;
+            emit!(TutorialCompleted { player: t.player, turns: t.turn });
+        }
+        emit!(TutorialTurnResolved { player: t.player, step: t.step, damage, ai_health: t.ai_health, combo: t.combo_count });
+        Ok(())
+    }
+
+    // One-time reward: XP onto one of the player's characters
+    pub fn claim_tutorial_reward(ctx: Context<ClaimTutorialReward>) -> Result<()> {
+        require!(ctx.accounts.nft_ata.mint == ctx.accounts.character.nft_mint, GameError::InvalidNftAta);
+        require!(ctx.accounts.nft_ata.amount == 1 && ctx.accounts.nft_ata.owner == ctx.accounts.player.key(), GameError::NotNftOwner);
+        let t = &mut ctx.accounts.tutorial;
+        require!(t.completed, GameError::TutorialNotCompleted);
+        require!(!t.reward_claimed, GameError::TutorialRewardClaimed);
+        t.reward_claimed = true;
+        ctx.accounts.progression.xp = ctx.accounts.progression.xp.saturating_add(TUTORIAL_REWARD_XP);
+        level_up_if_needed(&mut ctx.accounts.progression, &mut ctx.accounts.character)?;
+        emit!(TutorialRewardClaimed { player: t.player, nft_mint: ctx.accounts.character.nft_mint, xp: TUTORIAL_REWARD_XP });
+        Ok(())
+    }
+
+    // ------------------------
+    // Practice: no stakes, the AI mirrors the player's character; restarting abandons the running practice
+    // ------------------------
+    pub fn start_practice(ctx: Context<StartPractice>) -> Result<()> {
+        let p = &mut ctx.accounts.practice;
+        let ch = &ctx.accounts.character;
+        p.player = ctx.accounts.player.key();
+        p.character = ch.key();
+        p.player_health = ch.max_hp as u64;
+        p.ai_health = ch.max_hp as u64;
+        p.ai_stance = StanceType::Balanced;
+        p.turn = 0;
+        p.active = true;
+        p.bump = *ctx.bumps.get("practice").unwrap_or(&0);
+        emit!(PracticeStarted { player: p.player, nft_mint: ch.nft_mint });
+        Ok(())
+    }
+
+    // Player attacks, then the AI picks a stance (practice_ai_stance) and hits back with the same stat line
+    pub fn practice_turn(ctx: Context<PracticeTurn>, chosen_stance: StanceType) -> Result<()> {
+        let p = &mut ctx.accounts.practice;
+        let pool = &mut ctx.accounts.pool;
+        let ch = &ctx.accounts.character;
+        let signer = ctx.accounts.player.key();
+        require!(p.active, GameError::InvalidBattleState);
+        require!(stance_unlocked(&ctx.accounts.config, chosen_stance, ctx.accounts.progression.level), GameError::StanceLocked);
+        require!(pool.total_available >= 4, GameError::NoEntropyAvailable);
+        let max_hp = ch.max_hp as u64;
+        let gear = equipment_totals(ch);
+        let defense = (ch.defense as i64 + gear.defense as i64).max(0) as u64;
+
+        let (base, idx_base) = pool.consume_mixed_u64_return_index(&signer, b"practice_base", p.turn, ch.base_damage_min as u64, ch.base_damage_max as u64)?;
+        let (crit_roll, idx_crit) = pool.consume_mixed_u64_return_index(&signer, b"practice_crit", p.turn, 0, 9999)?;
+        let (policy_roll, idx_policy) = pool.consume_mixed_u64_return_index(&signer, b"practice_ai", p.turn, 0, 3)?;
+        let (ai_base, idx_ai) = pool.consume_mixed_u64_return_index(&signer, b"practice_ai_base", p.turn, ch.base_damage_min as u64, ch.base_damage_max as u64)?;
+        require!(idx_base > p.last_entropy_index && idx_crit > idx_base && idx_policy > idx_crit && idx_ai > idx_policy, GameError::SeedReplay);
+        p.last_entropy_index = idx_ai;
+
+        // player's hit, against the stance the AI showed last turn
+        let mut damage_fp = ((base as i128 + gear.attack as i128).max(0) as u128).checked_mul(FP_SCALE).ok_or(GameError::MathOverflow)?;
+        let is_crit = crit_roll < (ch.crit_bps as i64 + gear.crit_bps as i64).max(0) as u64;
+        if is_crit {
+            damage_fp = mul_fp_checked(damage_fp, (2000000u128).min(ch.crit_multiplier_fp as u128))?;
+        }
+        let (att_fp, def_fp, _, _) = stance_multipliers(chosen_stance, p.ai_stance);
+        damage_fp = mul_fp_checked(mul_fp_checked(damage_fp, att_fp)?, def_fp)?;
+        let damage = fp_to_u64_clamped(damage_fp, GameError::MathOverflow)?.saturating_sub(defense);
+        p.ai_health = p.ai_health.saturating_sub(damage);
+
+        // AI answers unless it just went down
+        let mut ai_damage = 0;
+        if p.ai_health > 0 {
+            p.ai_stance = practice_ai_stance(p.ai_health, p.player_health, max_hp, policy_roll);
+            let (att_fp, def_fp, _, _) = stance_multipliers(p.ai_stance, chosen_stance);
+            let ai_fp = mul_fp_checked(mul_fp_checked((ai_base as u128).checked_mul(FP_SCALE).ok_or(GameError::MathOverflow)?, att_fp)?, def_fp)?;
+            ai_damage = fp_to_u64_clamped(ai_fp, GameError::MathOverflow)?.saturating_sub(defense);
+            p.player_health = p.player_health.saturating_sub(ai_damage);
+        }
+        p.turn = p.turn.saturating_add(1);
+        emit!(PracticeTurnResolved { player: signer, turn: p.turn, damage, is_crit, ai_stance: p.ai_stance as u8, ai_damage, player_health: p.player_health, ai_health: p.ai_health });
+
+        if p.ai_health == 0 || p.player_health == 0 {
+            p.active = false;
+            let won = p.ai_health == 0;
+            let now = Clock::get()?.unix_timestamp;
+            let mut xp = 0;
+            if won && now >= p.last_reward_at.saturating_add(PRACTICE_REWARD_COOLDOWN_SECS) {
+                xp = WIN_XP.saturating_mul(PRACTICE_XP_BPS) / 10_000;
+                p.last_reward_at = now;
+                ctx.accounts.progression.xp = ctx.accounts.progression.xp.saturating_add(xp);
+                level_up_if_needed(&mut ctx.accounts.progression, &mut ctx.accounts.character)?;
+            }
+            emit!(PracticeFinished { player: signer, nft_mint: ctx.accounts.character.nft_mint, won, turns: p.turn, xp });
+        }
+        Ok(())
+    }
+
+    // ------------------------
+    // Winrate stats (one PDA per class / per stance, created by the admin, updated by finalize_battle)
+    // ------------------------
+    pub fn init_winrate_stats(ctx: Context<InitWinrateStats>, kind: u8, key: u8) -> Result<()> {
+        match kind {
+            STATS_KIND_CLASS => require!(key <= CharacterClass::Trickster as u8, GameError::InvalidRange),
+            STATS_KIND_STANCE => require!(key <= StanceType::Counter as u8, GameError::InvalidRange),
+            _ => return Err(error!(GameError::InvalidRange).into()),
+        }
+        let stats = &mut ctx.accounts.stats;
+        stats.kind = kind;
+        stats.key = key;
+        stats.battles = 0;
+        stats.wins = 0;
+        stats.losses = 0;
+        stats.draws = 0;
+        stats.bump = *ctx.bumps.get("stats").unwrap_or(&0);
+        Ok(())
+    }
+
+    // Create or patch a class's base stats / signature ability (new characters and battles pick it up, no redeploy)
+    pub fn set_class_stats(ctx: Context<SetClassStats>, class: CharacterClass, stats: ClassStats) -> Result<()> {
+        require!(stats.max_hp > 0 && stats.base_damage_min <= stats.base_damage_max, GameError::InvalidRange);
+        require!(stats.crit_bps <= 10_000 && stats.dodge_bps <= 10_000, GameError::InvalidRange);
+        let registry = &mut ctx.accounts.class_registry;
+        registry.class = class;
+        registry.stats = stats;
+        registry.bump = *ctx.bumps.get("class_registry").unwrap_or(&0);
+        emit!(ClassStatsSet { class, stats });
+        Ok(())
+    }
+
+    pub fn init_class_balance(ctx: Context<InitClassBalance>, class: CharacterClass) -> Result<()> {
+        let balance = &mut ctx.accounts.class_balance;
+        balance.class = class;
+        balance.damage_bps = 10_000;
+        balance.season = 0;
+        balance.bump = *ctx.bumps.get("class_balance").unwrap_or(&0);
+        Ok(())
+    }
+
+    // Season rollover (admin): remaining_accounts are [WinrateStats(class), ClassBalance] pairs. A class outside
+    // the winrate band with enough decisive battles gets BALANCE_STEP_BPS of damage moved toward the band,
+    // clamped to [BALANCE_MIN_DAMAGE_BPS, BALANCE_MAX_DAMAGE_BPS]; its stats are then reset for the new season.
+    pub fn rebalance_classes<'info>(ctx: Context<'_, '_, '_, 'info, RebalanceClasses<'info>>, season: u32) -> Result<()> {
+        require!(ctx.remaining_accounts.len() % 2 == 0, GameError::InvalidIndex);
+        for pair in ctx.remaining_accounts.chunks(2) {
+            let mut stats: Account<WinrateStats> = Account::try_from(&pair[0])?;
+            let mut balance: Account<ClassBalance> = Account::try_from(&pair[1])?;
+            require!(stats.kind == STATS_KIND_CLASS && stats.key == balance.class as u8, GameError::InvalidIndex);
+            require!(season > balance.season, GameError::InvalidRange);
+
+            let decisive = stats.wins.saturating_add(stats.losses);
+            let winrate_bps = if decisive == 0 { 5000 } else { stats.wins.saturating_mul(10_000) / decisive };
+            let old_damage_bps = balance.damage_bps;
+            if decisive >= BALANCE_MIN_SAMPLE {
+                if winrate_bps > BALANCE_WINRATE_HIGH_BPS {
+                    balance.damage_bps = balance.damage_bps.saturating_sub(BALANCE_STEP_BPS).max(BALANCE_MIN_DAMAGE_BPS);
+                } else if winrate_bps < BALANCE_WINRATE_LOW_BPS {
+                    balance.damage_bps = balance.damage_bps.saturating_add(BALANCE_STEP_BPS).min(BALANCE_MAX_DAMAGE_BPS);
+                }
+            }
+            balance.season = season;
+            emit!(ClassBalanceAdjusted { class: balance.class, season, battles: stats.battles, winrate_bps, old_damage_bps, new_damage_bps: balance.damage_bps });
+
+            stats.battles = 0;
+            stats.wins = 0;
+            stats.losses = 0;
+            stats.draws = 0;
+            stats.exit(&crate::ID)?;
+            balance.exit(&crate::ID)?;
+        }
+        Ok(())
+    }
+
+    // ------------------------
+    // Guilds: members pledge a share of battle XP as guild points (their own XP is untouched)
+    // ------------------------
+    pub fn create_guild(ctx: Context<CreateGuild>, guild_id: u64, contribution_bps: u16) -> Result<()> {
+        require!(contribution_bps <= MAX_GUILD_CONTRIBUTION_BPS, GameError::InvalidRange);
+        let guild = &mut ctx.accounts.guild;
+        guild.authority = ctx.accounts.authority.key();
+        guild.guild_id = guild_id;
+        guild.contribution_bps = contribution_bps;
+        guild.points = 0;
+        guild.member_count = 0;
+        guild.perk_tier = 0;
+        guild.bump = *ctx.bumps.get("guild").unwrap_or(&0);
+        emit!(GuildCreated { guild: guild.key(), authority: guild.authority, contribution_bps });
+        Ok(())
+    }
+
+    pub fn set_guild_contribution(ctx: Context<SetGuildContribution>, contribution_bps: u16) -> Result<()> {
+        require!(contribution_bps <= MAX_GUILD_CONTRIBUTION_BPS, GameError::InvalidRange);
+        ctx.accounts.guild.contribution_bps = contribution_bps;
+        Ok(())
+    }
+
+    pub fn join_guild(ctx: Context<JoinGuild>) -> Result<()> {
+        let member = &mut ctx.accounts.member;
+        member.player = ctx.accounts.player.key();
+        member.guild = ctx.accounts.guild.key();
+        member.points_contributed = 0;
+        member.joined_at = Clock::get()?.unix_timestamp;
+        member.bump = *ctx.bumps.get("member").unwrap_or(&0);
+        let guild = &mut ctx.accounts.guild;
+        guild.member_count = guild.member_count.saturating_add(1);
+        emit!(GuildJoined { guild: guild.key(), player: member.player });
+        Ok(())
+    }
+
+    // closes the membership; points already contributed stay with the guild
+    pub fn leave_guild(ctx: Context<LeaveGuild>) -> Result<()> {
+        let guild = &mut ctx.accounts.guild;
+        guild.member_count = guild.member_count.saturating_sub(1);
+        emit!(GuildLeft { guild: guild.key(), player: ctx.accounts.player.key() });
+        Ok(())
+    }
+
+    // Once per finished battle per member: credits contribution_bps of the XP that battle earned them
+    pub fn contribute_battle_xp(ctx: Context<ContributeBattleXp>) -> Result<()> {
+        let battle = &ctx.accounts.battle;
+        let player = ctx.accounts.player.key();
+        require!(battle.state == BattleState::Finished, GameError::BattleNotFinished);
+        require!(player == battle.player1 || player == battle.player2, GameError::Unauthorized);
+        let earned = match battle.winner {
+            Some(w) if w == player => WIN_XP,
+            Some(_) => 0,
+            None => DRAW_XP,
+        };
+        let guild = &mut ctx.accounts.guild;
+        let points = earned.saturating_mul(guild.contribution_bps as u64) / 10_000;
+        guild.points = guild.points.saturating_add(points);
+        let tier = GUILD_PERK_THRESHOLDS.iter().filter(|t| guild.points >= **t).count() as u8;
+        if tier > guild.perk_tier {
+            guild.perk_tier = tier;
+            emit!(GuildPerkUnlocked { guild: guild.key(), tier, fee_discount_bps: GUILD_FEE_DISCOUNT_BPS[tier as usize - 1] });
+        }
+        let member = &mut ctx.accounts.member;
+        member.points_contributed = member.points_contributed.saturating_add(points);
+
+        let receipt = &mut ctx.accounts.receipt;
+        receipt.battle = battle.key();
+        receipt.player = player;
+        receipt.points = points;
+        receipt.bump = *ctx.bumps.get("receipt").unwrap_or(&0);
+        emit!(GuildPointsContributed { guild: guild.key(), player, battle: battle.key(), points, guild_points: guild.points });
+        Ok(())
+    }
+
+    // ------------------------
+    // 2v2 team battles: SOL stake per seat, the battle starts once all four seats are filled
+    // ------------------------
+    pub fn create_team_battle(ctx: Context<CreateTeamBattle>, nonce: u64, stake_per_player: u64) -> Result<()> {
+        let tb = &mut ctx.accounts.team_battle;
+        let player = ctx.accounts.player.key();
+        tb.creator = player;
+        tb.nonce = nonce;
+        tb.stake_per_player = stake_per_player;
+        tb.state = BattleState::Waiting;
+        tb.inactivity_timeout = ctx.accounts.config.inactivity_timeout;
+        tb.created_at = Clock::get()?.unix_timestamp;
+        tb.bump = *ctx.bumps.get("team_battle").unwrap_or(&0);
+        seat_team_member(tb, 0, player, &mut ctx.accounts.character)?;
+        let (escrow, _) = sol_escrow(&ctx.accounts.team_sol_escrow, &tb.key())?;
+        sol_escrow_deposit(&ctx.accounts.player.to_account_info(), &escrow, stake_per_player)?;
+        emit!(TeamBattleCreated { team_battle: tb.key(), creator: player, stake_per_player });
+        emit!(TeamMemberJoined { team_battle: tb.key(), player, team: 0, seat: 0 });
+        Ok(())
+    }
+
+    // Takes the first free seat of `team` (0 = A, 1 = B)
+    pub fn join_team_battle(ctx: Context<JoinTeamBattle>, team: u8) -> Result<()> {
+        require!((team as usize) < 2, GameError::InvalidRange);
+        let tb = &mut ctx.accounts.team_battle;
+        require!(tb.state == BattleState::Waiting, GameError::InvalidBattleState);
+        let first = team as usize * TEAM_SIZE;
+        let seat = (first..first + TEAM_SIZE).find(|s| tb.members[*s].player == Pubkey::default()).ok_or(GameError::TeamFull)?;
+        let player = ctx.accounts.player.key();
+        seat_team_member(tb, seat, player, &mut ctx.accounts.character)?;
+        let (escrow, _) = sol_escrow(&ctx.accounts.team_sol_escrow, &tb.key())?;
+        sol_escrow_deposit(&ctx.accounts.player.to_account_info(), &escrow, tb.stake_per_player)?;
+        emit!(TeamMemberJoined { team_battle: tb.key(), player, team, seat: seat as u8 });
+
+        // last seat filled: roll which team opens
+        if tb.members.iter().all(|m| m.player != Pubkey::default()) {
+            require!(ctx.accounts.pool.total_available >= 1, GameError::NoEntropyAvailable);
+            let (first_team, used_index) = ctx.accounts.pool.consume_mixed_u64_return_index(&player, b"team_first", 0, 0, 1)?;
+            tb.last_entropy_index = used_index;
+            tb.turn_cursor = first_team as u8; // TEAM_TURN_ORDER[0] is team A's, [1] team B's
+            tb.state = BattleState::Active;
+            tb.last_action_ts = Clock::get()?.unix_timestamp;
+            emit!(TeamBattleStarted { team_battle: tb.key(), first_team: first_team as u8 });
+        }
+        Ok(())
+    }
+
+    // Gives up a seat (and its stake) before the battle starts
+    pub fn leave_team_battle(ctx: Context<LeaveTeamBattle>) -> Result<()> {
+        let tb = &mut ctx.accounts.team_battle;
+        require!(tb.state == BattleState::Waiting, GameError::InvalidBattleState);
+        let player = ctx.accounts.player.key();
+        let seat = tb.members.iter().position(|m| m.player == player).ok_or(GameError::Unauthorized)?;
+        require_keys_eq!(ctx.accounts.character.key(), tb.members[seat].character, GameError::CharacterConstraint);
+        let tb_key = tb.key();
+        let (escrow, bump) = sol_escrow(&ctx.accounts.team_sol_escrow, &tb_key)?;
+        sol_escrow_release(&escrow, &tb_key, bump, &ctx.accounts.player.to_account_info(), tb.stake_per_player)?;
+        if ctx.accounts.character.active_battle == tb_key { ctx.accounts.character.active_battle = Pubkey::default(); }
+        tb.members[seat] = TeamMember { player: Pubkey::default(), character: Pubkey::default(), health: 0, stance: StanceType::Balanced };
+        emit!(TeamMemberLeft { team_battle: tb_key, player, seat: seat as u8 });
+        Ok(())
+    }
+
+    // The seat on turn attacks a living member of the other team. Condensed duel pipeline: base / crit / dodge rolls,
+    // gear, element and stance multipliers, armor-pen-reduced defense; stance self-damage and counters hit the actor.
+    pub fn execute_team_turn(ctx: Context<ExecuteTeamTurn>, target: u8, stance: StanceType) -> Result<()> {
+        let tb = &mut ctx.accounts.team_battle;
+        let pool = &mut ctx.accounts.pool;
+        let signer = ctx.accounts.signer.key();
+        require!(tb.state == BattleState::Active, GameError::InvalidBattleState);
+        let seat = TEAM_TURN_ORDER[tb.turn_cursor as usize];
+        require!(tb.members[seat].player == signer, GameError::NotYourTurn);
+        let target = target as usize;
+        require!(target < TEAM_SEATS && target / TEAM_SIZE != seat / TEAM_SIZE && tb.members[target].health > 0, GameError::InvalidTarget);
+        let (attacker, defender) = (&ctx.accounts.attacker_character, &ctx.accounts.defender_character);
+        require!(attacker.key() == tb.members[seat].character && defender.key() == tb.members[target].character, GameError::CharacterConstraint);
+        require!(pool.total_available >= 3, GameError::NoEntropyAvailable);
+        tb.members[seat].stance = stance;
+        let turn = tb.turn_number as u32;
+
+        let (base, idx_base) = pool.consume_mixed_u64_return_index(&signer, b"base", turn, attacker.base_damage_min as u64, attacker.base_damage_max as u64)?;
+        require!(idx_base > tb.last_entropy_index, GameError::SeedReplay);
+        let (crit_roll, idx_crit) = pool.consume_mixed_u64_return_index(&signer, b"crit", turn, 0, 9999)?;
+        require!(idx_crit > idx_base, GameError::SeedReplay);
+        let (dodge_roll, idx_dodge) = pool.consume_mixed_u64_return_index(&signer, b"dodge", turn, 0, 9999)?;
+        require!(idx_dodge > idx_crit, GameError::SeedReplay);
+        tb.last_entropy_index = idx_dodge;
+
+        let (attacker_gear, defender_gear) = (equipment_totals(attacker), equipment_totals(defender));
+        let mut damage_fp = ((base as i128 + attacker_gear.attack as i128).max(0) as u128).checked_mul(FP_SCALE).ok_or(GameError::MathOverflow)?;
+        let crit_chance = (attacker.crit_bps as i64 + attacker_gear.crit_bps as i64).max(0) as u64;
+        let is_crit = crit_roll < crit_chance.saturating_sub(defender.crit_resist_bps as u64);
+        if is_crit {
+            damage_fp = mul_fp_checked(damage_fp, (2000000u128).min(attacker.crit_multiplier_fp as u128))?;
+        }
+        let element_bps = ELEMENT_MATCHUP_BPS[attacker.element as usize][defender.element as usize];
+        damage_fp = mul_fp_checked(damage_fp, FP_SCALE * element_bps as u128 / 10_000)?;
+        let (att_fp, def_fp, self_bps, counter_bps) = stance_multipliers(stance, tb.members[target].stance);
+        damage_fp = mul_fp_checked(damage_fp, att_fp)?;
+        damage_fp = mul_fp_checked(damage_fp, def_fp)?;
+        damage_fp = damage_fp.min(MAX_TOTAL_MULTIPLIER_FP.checked_mul(FP_SCALE).unwrap_or(damage_fp));
+
+        let mut damage = fp_to_u64_clamped(damage_fp, GameError::MathOverflow)?;
+        let defense = (defender.defense as i64 + defender_gear.defense as i64).max(0) as u64;
+        damage = damage.saturating_sub(defense.saturating_mul(10_000u64.saturating_sub(attacker.armor_pen_bps as u64)) / 10_000);
+        let dodged = dodge_roll < (defender.dodge_bps as i64 + defender_gear.dodge_bps as i64).max(0) as u64;
+        if dodged { damage = 0; }
+
+        tb.members[target].health = tb.members[target].health.saturating_sub(damage);
+        let recoil = damage.saturating_mul(self_bps as u64 + counter_bps as u64) / 10_000;
+        tb.members[seat].health = tb.members[seat].health.saturating_sub(recoil);
+        tb.last_action_ts = Clock::get()?.unix_timestamp;
+        emit!(TeamTurnResolved { team_battle: tb.key(), turn_number: tb.turn_number, attacker_seat: seat as u8, target_seat: target as u8, damage, recoil, is_crit, dodged });
+        tb.turn_number = tb.turn_number.saturating_add(1);
+
+        // wiping the other team wins even if recoil also dropped the actor's side
+        let (own_team, other_team) = (seat / TEAM_SIZE, 1 - seat / TEAM_SIZE);
+        let winner_team = if tb.team_down(other_team) { Some(own_team) } else if tb.team_down(own_team) { Some(other_team) } else { None };
+        match winner_team {
+            Some(team) => {
+                tb.state = BattleState::Finished;
+                tb.winner_team = Some(team as u8);
+                emit!(TeamBattleFinished { team_battle: tb.key(), winner_team: team as u8, by_timeout: false });
+            },
+            None => tb.advance_turn(),
+        }
+        Ok(())
+    }
+
+    // The seat on turn idled past the inactivity timeout: their team loses
+    pub fn team_forfeit_by_timeout(ctx: Context<TeamForfeitByTimeout>) -> Result<()> {
+        let tb = &mut ctx.accounts.team_battle;
+        require!(tb.state == BattleState::Active, GameError::InvalidBattleState);
+        require!(Clock::get()?.unix_timestamp.saturating_sub(tb.last_action_ts) > tb.inactivity_timeout, GameError::TimeoutNotReached);
+        let winner_team = 1 - TEAM_TURN_ORDER[tb.turn_cursor as usize] / TEAM_SIZE;
+        tb.state = BattleState::Finished;
+        tb.winner_team = Some(winner_team as u8);
+        emit!(TeamBattleFinished { team_battle: tb.key(), winner_team: winner_team as u8, by_timeout: true });
+        Ok(())
+    }
+
+    // Protocol fee to treasury, the rest split equally among the winning team; releases all four character locks
+    pub fn finalize_team_battle(ctx: Context<FinalizeTeamBattle>) -> Result<()> {
+        let tb = &mut ctx.accounts.team_battle;
+        require!(tb.state == BattleState::Finished, GameError::BattleNotFinished);
+        require!(!tb.settled, GameError::AlreadySettled);
+        let winner_team = tb.winner_team.ok_or(GameError::BattleNotFinished)? as usize;
+        tb.settled = true;
+
+        let total = tb.stake_per_player.saturating_mul(TEAM_SEATS as u64);
+        let fee = ((total as u128) * (ctx.accounts.config.fee_bps as u128) / 10_000u128) as u64;
+        let payout_each = total.saturating_sub(fee) / TEAM_SIZE as u64;
+        let tb_key = tb.key();
+        let (escrow, bump) = sol_escrow(&ctx.accounts.team_sol_escrow, &tb_key)?;
+        // fee plus any rounding remainder
+        sol_escrow_release(&escrow, &tb_key, bump, &ctx.accounts.treasury.to_account_info(), total.saturating_sub(payout_each * TEAM_SIZE as u64))?;
+        for (i, winner) in [&ctx.accounts.winner_a, &ctx.accounts.winner_b].into_iter().enumerate() {
+            require_keys_eq!(winner.key(), tb.members[winner_team * TEAM_SIZE + i].player, GameError::Unauthorized);
+            sol_escrow_release(&escrow, &tb_key, bump, &winner.to_account_info(), payout_each)?;
+        }
+
+        let characters = [&mut ctx.accounts.seat0_character, &mut ctx.accounts.seat1_character, &mut ctx.accounts.seat2_character, &mut ctx.accounts.seat3_character];
+        for (ch, member) in characters.into_iter().zip(tb.members.iter()) {
+            if ch.active_battle == tb_key { ch.active_battle = Pubkey::default(); }
+            ch.current_hp = member.health.min(ch.max_hp as u64) as u32;
+        }
+        emit!(TeamBattleSettled { team_battle: tb_key, winner_team: winner_team as u8, fee, payout_each });
+        Ok(())
+    }
+
+    // ------------------------
+    // Boss raids: PvE boss shared by every character, reward pool split by damage dealt once it falls
+    // ------------------------
+    pub fn create_boss(ctx: Context<CreateBoss>, boss_id: u64, max_hp: u64, damage_min: u16, damage_max: u16, defense: u16, script: Vec<u16>, reward: u64) -> Result<()> {
+        require!(max_hp > 0 && damage_min <= damage_max, GameError::InvalidRange);
+        require!(!script.is_empty() && script.len() <= MAX_BOSS_SCRIPT, GameError::InvalidRange);
+        let boss = &mut ctx.accounts.boss;
+        boss.boss_id = boss_id;
+        boss.max_hp = max_hp;
+        boss.health = max_hp;
+        boss.damage_min = damage_min;
+        boss.damage_max = damage_max;
+        boss.defense = defense;
+        boss.script = script;
+        boss.reward_pool = reward;
+        boss.bump = *ctx.bumps.get("boss").unwrap_or(&0);
+        let (escrow, _) = sol_escrow(&ctx.accounts.boss_sol_escrow, &boss.key())?;
+        sol_escrow_deposit(&ctx.accounts.admin.to_account_info(), &escrow, reward)?;
+        emit!(BossCreated { boss: boss.key(), boss_id, max_hp, reward_pool: reward });
+        Ok(())
+    }
+
+    // Anyone can grow the reward pool while the boss is alive
+    pub fn fund_boss_reward(ctx: Context<FundBossReward>, amount: u64) -> Result<()> {
+        require!(amount > 0, GameError::InvalidRange);
+        let boss = &mut ctx.accounts.boss;
+        require!(boss.defeated_at == 0, GameError::BossDefeated);
+        let (escrow, _) = sol_escrow(&ctx.accounts.boss_sol_escrow, &boss.key())?;
+        sol_escrow_deposit(&ctx.accounts.funder.to_account_info(), &escrow, amount)?;
+        boss.reward_pool = boss.reward_pool.checked_add(amount).ok_or(GameError::MathOverflow)?;
+        emit!(BossRewardFunded { boss: boss.key(), funder: ctx.accounts.funder.key(), amount, reward_pool: boss.reward_pool });
+        Ok(())
+    }
+
+    // One attack per cooldown; the boss answers with a scripted move picked from entropy. Raid HP is tracked
+    // on the RaidEntry, so a character knocked out of the raid is unaffected elsewhere.
+    pub fn attack_boss(ctx: Context<AttackBoss>) -> Result<()> {
+        let boss = &mut ctx.accounts.boss;
+        let pool = &mut ctx.accounts.pool;
+        let ch = &ctx.accounts.character;
+        let entry = &mut ctx.accounts.raid_entry;
+        let signer = ctx.accounts.player.key();
+        let now = Clock::get()?.unix_timestamp;
+        require!(boss.defeated_at == 0, GameError::BossDefeated);
+        if entry.boss == Pubkey::default() {
+            entry.boss = boss.key();
+            entry.character = ch.key();
+            entry.health = ch.max_hp as u64;
+            entry.bump = *ctx.bumps.get("raid_entry").unwrap_or(&0);
+        }
+        require!(entry.health > 0, GameError::RaidCharacterDown);
+        require!(now >= entry.next_attack_at, GameError::ActionOnCooldown);
+        require!(pool.total_available >= 5, GameError::NoEntropyAvailable);
+        let turn = entry.attacks;
+        let gear = equipment_totals(ch);
+
+        let (base, idx_base) = pool.consume_mixed_u64_return_index(&signer, b"raid_base", turn, ch.base_damage_min as u64, ch.base_damage_max as u64)?;
+        require!(idx_base > boss.last_entropy_index, GameError::SeedReplay);
+        let (crit_roll, idx_crit) = pool.consume_mixed_u64_return_index(&signer, b"raid_crit", turn, 0, 9999)?;
+        require!(idx_crit > idx_base, GameError::SeedReplay);
+        boss.last_entropy_index = idx_crit;
+        let mut damage_fp = ((base as i128 + gear.attack as i128).max(0) as u128).checked_mul(FP_SCALE).ok_or(GameError::MathOverflow)?;
+        let is_crit = crit_roll < (ch.crit_bps as i64 + gear.crit_bps as i64).max(0) as u64;
+        if is_crit {
+            damage_fp = mul_fp_checked(damage_fp, (2000000u128).min(ch.crit_multiplier_fp as u128))?;
+        }
+        let damage = fp_to_u64_clamped(damage_fp, GameError::MathOverflow)?
+            .saturating_sub((boss.defense as u64).saturating_mul(10_000u64.saturating_sub(ch.armor_pen_bps as u64)) / 10_000);
+        // only damage that actually lands on the remaining HP counts towards the reward share
+        let dealt = damage.min(boss.health);
+        boss.health -= dealt;
+        entry.damage_dealt = entry.damage_dealt.saturating_add(dealt);
+        entry.attacks = entry.attacks.saturating_add(1);
+        entry.next_attack_at = now.saturating_add(RAID_ATTACK_COOLDOWN_SECS);
+        emit!(BossAttacked { boss: boss.key(), nft_mint: ch.nft_mint, damage: dealt, is_crit, boss_health: boss.health });
+        if boss.health == 0 {
+            boss.defeated_at = now;
+            emit!(BossDefeated { boss: boss.key(), finisher: ch.nft_mint, reward_pool: boss.reward_pool });
+            return Ok(());
+        }
+
+        // boss retaliates: move from its script, scaled damage roll, the character can still dodge
+        let (move_index, idx_move) = pool.consume_mixed_u64_return_index(&signer, b"boss_move", turn, 0, boss.script.len() as u64 - 1)?;
+        let (boss_base, idx_boss) = pool.consume_mixed_u64_return_index(&signer, b"boss_base", turn, boss.damage_min as u64, boss.damage_max as u64)?;
+        let (dodge_roll, idx_dodge) = pool.consume_mixed_u64_return_index(&signer, b"raid_dodge", turn, 0, 9999)?;
+        require!(idx_move > boss.last_entropy_index && idx_boss > idx_move && idx_dodge > idx_boss, GameError::SeedReplay);
+        boss.last_entropy_index = idx_dodge;
+        let mut hit = boss_base.saturating_mul(boss.script[move_index as usize] as u64) / 10_000;
+        hit = hit.saturating_sub((ch.defense as i64 + gear.defense as i64).max(0) as u64);
+        if dodge_roll < (ch.dodge_bps as i64 + gear.dodge_bps as i64).max(0) as u64 { hit = 0; }
+        entry.health = entry.health.saturating_sub(hit);
+        emit!(BossRetaliated { boss: boss.key(), nft_mint: ch.nft_mint, move_index: move_index as u8, damage: hit, health: entry.health });
+        Ok(())
+    }
+
+    // After the kill: reward_pool * damage_dealt / max_hp to the character's current holder
+    pub fn claim_raid_reward(ctx: Context<ClaimRaidReward>) -> Result<()> {
+        let boss = &ctx.accounts.boss;
+        let entry = &mut ctx.accounts.raid_entry;
+        require!(boss.defeated_at != 0, GameError::BossAlive);
+        require!(!entry.claimed, GameError::RaidRewardClaimed);
+        entry.claimed = true;
+        let share = ((boss.reward_pool as u128) * (entry.damage_dealt as u128) / (boss.max_hp as u128)) as u64;
+        let (escrow, bump) = sol_escrow(&ctx.accounts.boss_sol_escrow, &boss.key())?;
+        sol_escrow_release(&escrow, &boss.key(), bump, &ctx.accounts.player.to_account_info(), share)?;
+        emit!(RaidRewardClaimed { boss: boss.key(), nft_mint: ctx.accounts.character.nft_mint, player: ctx.accounts.player.key(), damage_dealt: entry.damage_dealt, reward: share });
+        Ok(())
+    }
+
+    // Admin, once: the global MMR leaderboard; every rated battle-end path and record_season_result keep it current
+    pub fn init_leaderboard(ctx: Context<InitLeaderboard>) -> Result<()> {
+        let board = &mut ctx.accounts.leaderboard;
+        board.entries = Vec::new();
+        board.bump = *ctx.bumps.get("leaderboard").unwrap_or(&0);
+        Ok(())
+    }
+
+    // ------------------------
+    // Achievements: one PDA per (character, kind); anyone can claim one the character's Progression already qualifies for
+    // ------------------------
+    pub fn claim_achievement(ctx: Context<ClaimAchievement>, kind: AchievementKind) -> Result<()> {
+        let prog = &ctx.accounts.progression;
+        let earned = match kind {
+            AchievementKind::FirstWin => prog.wins >= 1,
+            AchievementKind::WinStreak => prog.best_win_streak >= ACHIEVEMENT_WIN_STREAK,
+            AchievementKind::FlawlessVictory => prog.flawless_wins >= 1,
+            AchievementKind::Veteran => prog.battles >= ACHIEVEMENT_VETERAN_BATTLES,
+        };
+        require!(earned, GameError::AchievementNotEarned);
+        let achievement = &mut ctx.accounts.achievement;
+        achievement.nft_mint = prog.nft_mint;
+        achievement.kind = kind;
+        achievement.unlocked_at = Clock::get()?.unix_timestamp;
+        achievement.bump = *ctx.bumps.get("achievement").unwrap_or(&0);
+        emit!(AchievementUnlocked { nft_mint: prog.nft_mint, kind, unlocked_at: achievement.unlocked_at });
+        Ok(())
+    }
+
+    // ------------------------
+    // Ranked seasons: ids run 1, 2, ...; a season's SOL reward pool pays per MMR tier to characters with a SeasonResult
+    // ------------------------
+    pub fn start_season(ctx: Context<StartSeason>, season_id: u32, start_ts: i64, end_ts: i64, tier_mmr: [u64; SEASON_REWARD_TIERS], tier_rewards: [u64; SEASON_REWARD_TIERS], reward: u64) -> Result<()> {
+        let cfg = &mut ctx.accounts.config;
+        require!(season_id == cfg.current_season.saturating_add(1), GameError::InvalidRange);
+        require!(start_ts < end_ts, GameError::InvalidRange);
+        require!(tier_mmr.windows(2).all(|w| w[0] <= w[1]), GameError::InvalidRange);
+        // seasons never overlap: the next one starts once the current one has ended
+        if cfg.current_season > 0 {
+            let prev = ctx.accounts.previous_season.as_ref().ok_or(GameError::InvalidRange)?;
+            require!(prev.id == cfg.current_season && start_ts >= prev.end_ts, GameError::InvalidRange);
+        }
+        let season = &mut ctx.accounts.season;
+        season.id = season_id;
+        season.start_ts = start_ts;
+        season.end_ts = end_ts;
+        season.tier_mmr = tier_mmr;
+        season.tier_rewards = tier_rewards;
+        season.reward_pool = reward;
+        season.claimed = 0;
+        season.results = 0;
+        season.tier_counts = [0; SEASON_REWARD_TIERS];
+        season.bump = *ctx.bumps.get("season").unwrap_or(&0);
+        if reward > 0 {
+            let (escrow, _) = sol_escrow(&ctx.accounts.season_sol_escrow, &season.key())?;
+            sol_escrow_deposit(&ctx.accounts.admin.to_account_info(), &escrow, reward)?;
+        }
+        cfg.current_season = season_id;
+        cfg.season_start_ts = start_ts;
+        cfg.season_end_ts = end_ts;
+        emit!(SeasonStarted { season: season.key(), season_id, start_ts, end_ts, reward_pool: reward });
+        Ok(())
+    }
+
+    // Permissionless once the season has ended: snapshot the character's final MMR and tier into its SeasonResult,
+    // then soft-reset the rating halfway towards SEASON_MMR_BASE. Only characters with a rated battle in the season qualify.
+    // Ratings are frozen from end_ts, and recording closes when the next season starts or SEASON_RECORD_WINDOW_SECS pass.
+    pub fn record_season_result(ctx: Context<RecordSeasonResult>) -> Result<()> {
+        let season = &mut ctx.accounts.season;
+        let prog = &mut ctx.accounts.progression;
+        let now = Clock::get()?.unix_timestamp;
+        require!(now >= season.end_ts, GameError::SeasonNotEnded);
+        require!(season_recording_open(&ctx.accounts.config, season, now), GameError::SeasonRecordingClosed);
+        require!(season.id > prog.season, GameError::SeasonAlreadyRecorded);
+        require!(prog.last_played >= season.start_ts && prog.last_played < season.end_ts, GameError::SeasonNotPlayed);
+        let final_mmr = prog.mmr;
+        let tier = season.tier_mmr.iter().filter(|&&t| final_mmr >= t).count() as u8;
+        let result = &mut ctx.accounts.season_result;
+        result.season = season.id;
+        result.nft_mint = prog.nft_mint;
+        result.final_mmr = final_mmr;
+        result.tier = tier;
+        result.reward_claimed = false;
+        result.bump = *ctx.bumps.get("season_result").unwrap_or(&0);
+        prog.mmr = (final_mmr + SEASON_MMR_BASE) / 2;
+        prog.season = season.id;
+        season.results = season.results.saturating_add(1);
+        if tier > 0 {
+            season.tier_counts[tier as usize - 1] = season.tier_counts[tier as usize - 1].saturating_add(1);
+        }
+        leaderboard_record(&mut ctx.accounts.leaderboard, prog);
+        emit!(SeasonResultRecorded { season: season.key(), nft_mint: prog.nft_mint, final_mmr, tier, new_mmr: prog.mmr });
+        Ok(())
+    }
+
+    // Tier reward for a recorded SeasonResult, paid to whoever holds the character NFT now. Claims open once recording
+    // has closed; if the pool can't cover every recorded tier in full, each reward is cut pro-rata.
+    pub fn claim_season_reward(ctx: Context<ClaimSeasonReward>) -> Result<()> {
+        let season = &mut ctx.accounts.season;
+        let result = &mut ctx.accounts.season_result;
+        require!(!season_recording_open(&ctx.accounts.config, season, Clock::get()?.unix_timestamp), GameError::SeasonRecordingOpen);
+        require!(!result.reward_claimed, GameError::SeasonRewardClaimed);
+        require!(result.tier > 0, GameError::NoSeasonReward);
+        let reward = season_reward_for(season, result.tier);
+        require!(reward > 0, GameError::NoSeasonReward);
+        let claimed = season.claimed.checked_add(reward).ok_or(GameError::MathOverflow)?;
+        require!(claimed <= season.reward_pool, GameError::NoSeasonReward);
+        season.claimed = claimed;
+        result.reward_claimed = true;
+        let (escrow, bump) = sol_escrow(&ctx.accounts.season_sol_escrow, &season.key())?;
+        sol_escrow_release(&escrow, &season.key(), bump, &ctx.accounts.player.to_account_info(), reward)?;
+        emit!(SeasonRewardClaimed { season: season.key(), nft_mint: result.nft_mint, player: ctx.accounts.player.key(), tier: result.tier, reward });
+        Ok(())
+    }
+}
+
+// ------------------------
+// CONTEXTS & ACCOUNTS
+// ------------------------
+
+#[derive(Accounts)]
+pub struct CreateConfig<'info> {
+    #[account(init, payer = admin, space = 8 + Config::INIT_SPACE, seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(mut, has_one = admin)]
+    pub config: Account<'info, Config>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct DepositToVault<'info> {
+    #[account(init_if_needed, payer = owner, space = 8 + PlayerVault::INIT_SPACE, seeds = [b"vault", owner.key().as_ref()], bump)]
+    pub vault: Account<'info, PlayerVault>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    // SPL deposits: owner's ATA -> vault-owned ATA for the same mint
+    #[account(mut)]
+    pub owner_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub vault_ata: Option<Account<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawFromVault<'info> {
+    #[account(mut, has_one = owner, seeds = [b"vault", owner.key().as_ref()], bump = vault.bump)]
+    pub vault: Account<'info, PlayerVault>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(mut)]
+    pub owner_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub vault_ata: Option<Account<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct InitEscrowVault<'info> {
+    pub config: Account<'info, Config>,
+    #[account(init, payer = payer, space = 8 + EscrowVault::INIT_SPACE, seeds = [b"escrow", mint.key().as_ref()], bump)]
+    pub escrow_vault: Account<'info, EscrowVault>,
+    /// CHECK: ATA of escrow_vault for mint, created here via CPI
+    #[account(mut)]
+    pub escrow_ata: UncheckedAccount<'info>,
+    pub mint: Account<'info, Mint>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct CreateEntropyPool<'info> {
+    #[account(init, payer = payer, space = 8 + EntropyPool::INIT_SPACE, seeds = [b"entropy_pool"], bump)]
+    pub pool: Account<'info, EntropyPool>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: authority (admin)
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RefillSeedBatch<'info> {
+    #[account(mut, has_one = authority)]
+    pub pool: Account<'info, EntropyPool>,
+    /// CHECK: refiller (oracle)
+    pub refiller: Signer<'info>,
     /// CHECK: authority (for has_one)
     pub authority: Signer<'info>,
 }
 
 #[derive(Accounts)]
-#[instruction(name: String)]
-pub struct CreateCharacterFromNft<'info> {
-    #[account(init, payer = payer, space = 8 + Character::INIT_SPACE, seeds = [b"character", nft_mint.key().as_ref()], bump)]
-    pub character: Account<'info, Character>,
-    /// CHECK: nft mint
-    pub nft_mint: AccountInfo<'info>,
+#[instruction(name: String)]
+pub struct CreateCharacterFromNft<'info> {
+    #[account(init, payer = payer, space = 8 + Character::INIT_SPACE, seeds = [b"character", nft_mint.key().as_ref()], bump)]
+    pub character: Account<'info, Character>,
+    /// CHECK: nft mint
+    pub nft_mint: AccountInfo<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut)]
+    pub nft_ata: Account<'info, TokenAccount>,
+    #[account(init_if_needed, payer = payer, space = 8 + Progression::INIT_SPACE, seeds = [b"progress", nft_mint.key().as_ref()], bump)]
+    pub progression: Account<'info, Progression>,
+    // optional: admin-set stats for the chosen class
+    #[account(seeds = [b"class_registry", &[class_registry.class as u8]], bump = class_registry.bump)]
+    pub class_registry: Option<Account<'info, ClassRegistry>>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub rent: Sysvar<'info, Rent>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[derive(Accounts)]
+pub struct RetireCharacter<'info> {
+    #[account(mut, close = owner, seeds = [b"character", nft_mint.key().as_ref()], bump = character.bump)]
+    pub character: Account<'info, Character>,
+    #[account(mut, close = owner, seeds = [b"progress", nft_mint.key().as_ref()], bump = progression.bump)]
+    pub progression: Account<'info, Progression>,
+    /// CHECK: nft mint
+    pub nft_mint: AccountInfo<'info>,
+    pub nft_ata: Account<'info, TokenAccount>,
+    #[account(init_if_needed, payer = owner, space = 8 + PlayerMaterials::INIT_SPACE, seeds = [b"materials", owner.key().as_ref()], bump)]
+    pub materials: Account<'info, PlayerMaterials>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MergeCharacters<'info> {
+    #[account(mut, close = owner, seeds = [b"character", source_mint.key().as_ref()], bump = source_character.bump)]
+    pub source_character: Account<'info, Character>,
+    #[account(mut, close = owner, seeds = [b"progress", source_mint.key().as_ref()], bump = source_progression.bump)]
+    pub source_progression: Account<'info, Progression>,
+    /// CHECK: source nft mint
+    pub source_mint: AccountInfo<'info>,
+    pub source_nft_ata: Account<'info, TokenAccount>,
+    #[account(mut, seeds = [b"character", target_mint.key().as_ref()], bump = target_character.bump)]
+    pub target_character: Account<'info, Character>,
+    #[account(mut, seeds = [b"progress", target_mint.key().as_ref()], bump = target_progression.bump)]
+    pub target_progression: Account<'info, Progression>,
+    /// CHECK: target nft mint
+    pub target_mint: AccountInfo<'info>,
+    pub target_nft_ata: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetCharacterProfile<'info> {
+    #[account(mut, seeds = [b"character", character.nft_mint.as_ref()], bump = character.bump)]
+    pub character: Account<'info, Character>,
+    #[account(constraint = nft_ata.mint == character.nft_mint && nft_ata.owner == owner.key() && nft_ata.amount == 1 @ GameError::InvalidNftAta)]
+    pub nft_ata: Account<'info, TokenAccount>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct FuseCharacters<'info> {
+    #[account(mut, seeds = [b"character", parent_a.nft_mint.as_ref()], bump = parent_a.bump)]
+    pub parent_a: Account<'info, Character>,
+    #[account(constraint = parent_a_ata.mint == parent_a.nft_mint && parent_a_ata.owner == owner.key() && parent_a_ata.amount == 1 @ GameError::NotNftOwner)]
+    pub parent_a_ata: Account<'info, TokenAccount>,
+    #[account(mut, seeds = [b"character", parent_b.nft_mint.as_ref()], bump = parent_b.bump)]
+    pub parent_b: Account<'info, Character>,
+    #[account(constraint = parent_b_ata.mint == parent_b.nft_mint && parent_b_ata.owner == owner.key() && parent_b_ata.amount == 1 @ GameError::NotNftOwner)]
+    pub parent_b_ata: Account<'info, TokenAccount>,
+    #[account(init, payer = owner, space = 8 + Character::INIT_SPACE, seeds = [b"character", child_mint.key().as_ref()], bump)]
+    pub child_character: Account<'info, Character>,
+    #[account(init, payer = owner, space = 8 + Progression::INIT_SPACE, seeds = [b"progress", child_mint.key().as_ref()], bump)]
+    pub child_progression: Account<'info, Progression>,
+    /// CHECK: child nft mint
+    pub child_mint: AccountInfo<'info>,
+    #[account(constraint = child_nft_ata.mint == child_mint.key() && child_nft_ata.owner == owner.key() && child_nft_ata.amount == 1 @ GameError::NotNftOwner)]
+    pub child_nft_ata: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub pool: Account<'info, EntropyPool>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SpendStatPoints<'info> {
+    #[account(mut, seeds = [b"character", character.nft_mint.as_ref()], bump = character.bump)]
+    pub character: Account<'info, Character>,
+    #[account(mut, seeds = [b"progress", character.nft_mint.as_ref()], bump = progression.bump)]
+    pub progression: Account<'info, Progression>,
+    #[account(constraint = nft_ata.mint == character.nft_mint && nft_ata.owner == owner.key() && nft_ata.amount == 1 @ GameError::InvalidNftAta)]
+    pub nft_ata: Account<'info, TokenAccount>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterEquipment<'info> {
+    pub config: Account<'info, Config>,
+    #[account(init_if_needed, payer = trait_authority, space = 8 + Equipment::INIT_SPACE, seeds = [b"equipment", item_mint.key().as_ref()], bump)]
+    pub equipment: Account<'info, Equipment>,
+    pub item_mint: Account<'info, Mint>,
+    #[account(mut)]
+    pub trait_authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct EquipItem<'info> {
+    #[account(mut, seeds = [b"character", character.nft_mint.as_ref()], bump = character.bump)]
+    pub character: Account<'info, Character>,
+    #[account(constraint = nft_ata.mint == character.nft_mint && nft_ata.owner == owner.key() && nft_ata.amount == 1 @ GameError::InvalidNftAta)]
+    pub nft_ata: Account<'info, TokenAccount>,
+    #[account(mut, seeds = [b"equipment", equipment.item_mint.as_ref()], bump = equipment.bump)]
+    pub equipment: Account<'info, Equipment>,
+    #[account(constraint = item_ata.mint == equipment.item_mint && item_ata.owner == owner.key() && item_ata.amount == 1 @ GameError::InvalidNftAta)]
+    pub item_ata: Account<'info, TokenAccount>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UnequipItem<'info> {
+    #[account(mut, seeds = [b"character", character.nft_mint.as_ref()], bump = character.bump)]
+    pub character: Account<'info, Character>,
+    #[account(mut, seeds = [b"equipment", equipment.item_mint.as_ref()], bump = equipment.bump)]
+    pub equipment: Account<'info, Equipment>,
+    // the character NFT or the item NFT, held by the signer
+    #[account(constraint = holder_ata.owner == holder.key() && holder_ata.amount == 1 @ GameError::NotNftOwner)]
+    pub holder_ata: Account<'info, TokenAccount>,
+    pub holder: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetLoadout<'info> {
+    #[account(init_if_needed, payer = owner, space = 8 + Loadout::INIT_SPACE, seeds = [b"loadout", character.nft_mint.as_ref()], bump)]
+    pub loadout: Account<'info, Loadout>,
+    #[account(seeds = [b"character", character.nft_mint.as_ref()], bump = character.bump)]
+    pub character: Account<'info, Character>,
+    #[account(constraint = nft_ata.mint == character.nft_mint && nft_ata.owner == owner.key() && nft_ata.amount == 1 @ GameError::InvalidNftAta)]
+    pub nft_ata: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(offer_nonce: u64)]
+pub struct CreateBattleOffer<'info> {
+    #[account(init, payer = creator, space = 8 + Offer::INIT_SPACE, seeds = [b"offer", creator.key.as_ref(), &offer_nonce.to_le_bytes()], bump)]
+    pub offer: Account<'info, Offer>,
+    #[account(mut)]
+    pub creator: Signer<'info>,
+    #[account(mut)]
+    pub creator_ata: Option<Account<'info, TokenAccount>>, // if SPL
+    #[account(mut, seeds = [b"escrow", escrow_vault.mint.as_ref()], bump = escrow_vault.bump)]
+    pub escrow_vault: Option<Account<'info, EscrowVault>>,
+    #[account(mut)]
+    pub escrow_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub currency_mint: Option<Account<'info, Mint>>,
+    pub config: Account<'info, Config>,
+    #[account(init_if_needed, payer = creator, space = 8 + RateLimit::INIT_SPACE, seeds = [b"rate_limit", creator.key.as_ref()], bump)]
+    pub rate_limit: Account<'info, RateLimit>,
+    // optional: fund the stake from the creator's vault
+    #[account(mut, seeds = [b"vault", creator.key.as_ref()], bump = creator_vault.bump)]
+    pub creator_vault: Option<Account<'info, PlayerVault>>,
+    #[account(mut)]
+    pub creator_vault_ata: Option<Account<'info, TokenAccount>>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", offer], verified in sol_escrow
+    #[account(mut)]
+    pub offer_sol_escrow: Option<UncheckedAccount<'info>>,
+    // required for auto_approve: the character the creator fights with, and its NFT in the creator's wallet
+    pub creator_character: Option<Account<'info, Character>>,
+    pub creator_nft_ata: Option<Account<'info, TokenAccount>>,
+    // optional: lobby page to list the offer on
+    #[account(mut, seeds = [b"offer_registry", &registry.page.to_le_bytes()], bump = registry.bump)]
+    pub registry: Option<Account<'info, OfferRegistry>>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct JoinBattleOffer<'info> {
+    #[account(mut)]
+    pub offer: Account<'info, Offer>,
+    #[account(init, payer = challenger, space = 8 + Request::INIT_SPACE, seeds = [b"request", offer.key().as_ref(), challenger.key.as_ref()], bump)]
+    pub request: Account<'info, Request>,
+    #[account(mut)]
+    pub character: Account<'info, Character>,
+    #[account(mut, constraint = progression.nft_mint == character.nft_mint @ GameError::CharacterConstraint)]
+    pub progression: Account<'info, Progression>,
+    #[account(mut)]
+    pub challenger: Signer<'info>,
+    #[account(mut)]
+    pub challenger_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut, seeds = [b"escrow", escrow_vault.mint.as_ref()], bump = escrow_vault.bump)]
+    pub escrow_vault: Option<Account<'info, EscrowVault>>,
+    #[account(mut)]
+    pub escrow_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub currency_mint: Option<Account<'info, Mint>>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", request], verified in sol_escrow
+    #[account(mut)]
+    pub request_sol_escrow: Option<UncheckedAccount<'info>>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+    pub config: Account<'info, Config>,
+    #[account(init_if_needed, payer = challenger, space = 8 + RateLimit::INIT_SPACE, seeds = [b"rate_limit", challenger.key.as_ref()], bump)]
+    pub rate_limit: Account<'info, RateLimit>,
+    // optional: fund the stake from the challenger's vault
+    #[account(mut, seeds = [b"vault", challenger.key.as_ref()], bump = challenger_vault.bump)]
+    pub challenger_vault: Option<Account<'info, PlayerVault>>,
+    #[account(mut)]
+    pub challenger_vault_ata: Option<Account<'info, TokenAccount>>,
+    // optional: the character's ability loadout (class signature ability otherwise)
+    #[account(seeds = [b"loadout", character.nft_mint.as_ref()], bump = loadout.bump)]
+    pub loadout: Option<Account<'info, Loadout>>,
+    // auto_approve offers: the join creates the battle, so it needs what approve_challenger would
+    #[account(init, payer = challenger, space = 8 + Battle::INIT_SPACE, seeds = [b"battle", &offer.offer_nonce.to_le_bytes(), offer.creator.as_ref(), challenger.key.as_ref()], bump)]
+    pub battle: Option<Account<'info, Battle>>,
+    #[account(mut, constraint = creator_character.key() == offer.creator_character @ GameError::CharacterConstraint)]
+    pub creator_character: Option<Account<'info, Character>>,
+    // the creator_* accounts below depend on creator_character, which is optional: checked in join_battle_offer
+    pub creator_nft_ata: Option<Account<'info, TokenAccount>>,
+    pub creator_loadout: Option<Account<'info, Loadout>>,
+    pub creator_class_registry: Option<Account<'info, ClassRegistry>>,
+    #[account(seeds = [b"class_registry", &[character.base_class as u8]], bump = challenger_class_registry.bump)]
+    pub challenger_class_registry: Option<Account<'info, ClassRegistry>>,
+    #[account(mut)]
+    pub pool: Option<Account<'info, EntropyPool>>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", offer], verified in sol_escrow
+    #[account(mut)]
+    pub offer_sol_escrow: Option<UncheckedAccount<'info>>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", battle], verified in sol_escrow
+    #[account(mut)]
+    pub battle_sol_escrow: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawRequest<'info> {
+    #[account(mut, has_one = challenger, has_one = offer)]
+    pub request: Account<'info, Request>,
+    #[account(mut)]
+    pub challenger: Signer<'info>,
+    #[account(mut)]
+    pub offer: Account<'info, Offer>,
+    #[account(mut, seeds = [b"escrow", escrow_vault.mint.as_ref()], bump = escrow_vault.bump)]
+    pub escrow_vault: Option<Account<'info, EscrowVault>>,
+    #[account(mut)]
+    pub escrow_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub challenger_ata: Option<Account<'info, TokenAccount>>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", request], verified in sol_escrow
+    #[account(mut)]
+    pub request_sol_escrow: Option<UncheckedAccount<'info>>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct RejectChallenger<'info> {
+    #[account(mut, has_one = creator)]
+    pub offer: Account<'info, Offer>,
+    #[account(mut, has_one = offer, has_one = challenger)]
+    pub request: Account<'info, Request>,
+    pub creator: Signer<'info>,
+    /// CHECK: refund destination, must be the request's challenger (has_one above)
+    #[account(mut)]
+    pub challenger: UncheckedAccount<'info>,
+    #[account(mut, seeds = [b"escrow", escrow_vault.mint.as_ref()], bump = escrow_vault.bump)]
+    pub escrow_vault: Option<Account<'info, EscrowVault>>,
+    #[account(mut)]
+    pub escrow_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut, constraint = challenger_ata.owner == challenger.key() @ GameError::Unauthorized)]
+    pub challenger_ata: Option<Account<'info, TokenAccount>>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", request], verified in sol_escrow
+    #[account(mut)]
+    pub request_sol_escrow: Option<UncheckedAccount<'info>>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct CancelOffer<'info> {
+    #[account(mut, has_one = creator)]
+    pub offer: Account<'info, Offer>,
+    #[account(mut)]
+    pub creator: Signer<'info>,
+    // optional: the lobby page the offer is listed on
+    #[account(mut, seeds = [b"offer_registry", &registry.page.to_le_bytes()], bump = registry.bump, constraint = offer.registry_page == Some(registry.page) @ GameError::InvalidIndex)]
+    pub registry: Option<Account<'info, OfferRegistry>>,
+    #[account(mut, seeds = [b"escrow", escrow_vault.mint.as_ref()], bump = escrow_vault.bump)]
+    pub escrow_vault: Option<Account<'info, EscrowVault>>,
+    #[account(mut)]
+    pub escrow_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub creator_ata: Option<Account<'info, TokenAccount>>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", offer], verified in sol_escrow
+    #[account(mut)]
+    pub offer_sol_escrow: Option<UncheckedAccount<'info>>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(page: u32)]
+pub struct InitOfferRegistry<'info> {
+    #[account(init, payer = payer, space = 8 + OfferRegistry::INIT_SPACE, seeds = [b"offer_registry", &page.to_le_bytes()], bump)]
+    pub registry: Account<'info, OfferRegistry>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct PruneOfferRegistry<'info> {
+    #[account(mut, seeds = [b"offer_registry", &registry.page.to_le_bytes()], bump = registry.bump)]
+    pub registry: Account<'info, OfferRegistry>,
+    pub cranker: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateOffer<'info> {
+    #[account(mut, has_one = creator)]
+    pub offer: Account<'info, Offer>,
+    #[account(mut)]
+    pub creator: Signer<'info>,
+    // optional: the lobby page the offer is listed on (listing refreshed)
+    #[account(mut, seeds = [b"offer_registry", &registry.page.to_le_bytes()], bump = registry.bump, constraint = offer.registry_page == Some(registry.page) @ GameError::InvalidIndex)]
+    pub registry: Option<Account<'info, OfferRegistry>>,
+    // SPL relevant accounts
+    #[account(mut)]
+    pub creator_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut, seeds = [b"escrow", escrow_vault.mint.as_ref()], bump = escrow_vault.bump)]
+    pub escrow_vault: Option<Account<'info, EscrowVault>>,
+    #[account(mut)]
+    pub escrow_ata: Option<Account<'info, TokenAccount>>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", offer], verified in sol_escrow
+    #[account(mut)]
+    pub offer_sol_escrow: Option<UncheckedAccount<'info>>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseOffer<'info> {
+    #[account(mut, has_one = creator, close = creator)]
+    pub offer: Account<'info, Offer>,
+    #[account(mut)]
+    pub creator: Signer<'info>,
+    #[account(mut, seeds = [b"offer_registry", &registry.page.to_le_bytes()], bump = registry.bump)]
+    pub registry: Option<Account<'info, OfferRegistry>>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", offer], verified in sol_escrow
+    #[account(mut)]
+    pub offer_sol_escrow: Option<UncheckedAccount<'info>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseRequest<'info> {
+    #[account(mut, has_one = challenger, close = challenger)]
+    pub request: Account<'info, Request>,
+    #[account(mut)]
+    pub challenger: Signer<'info>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", request], verified in sol_escrow
+    #[account(mut)]
+    pub request_sol_escrow: Option<UncheckedAccount<'info>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetOfferRecurring<'info> {
+    #[account(mut, has_one = creator)]
+    pub offer: Account<'info, Offer>,
+    pub creator: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RelistOffer<'info> {
+    #[account(mut)]
+    pub offer: Account<'info, Offer>,
+    pub battle: Account<'info, Battle>,
+    #[account(mut, seeds = [b"vault", offer.creator.as_ref()], bump = creator_vault.bump)]
+    pub creator_vault: Account<'info, PlayerVault>,
+    // SPL relevant accounts
+    #[account(mut)]
+    pub creator_vault_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut, seeds = [b"escrow", escrow_vault.mint.as_ref()], bump = escrow_vault.bump)]
+    pub escrow_vault: Option<Account<'info, EscrowVault>>,
+    #[account(mut)]
+    pub escrow_ata: Option<Account<'info, TokenAccount>>,
+    pub cranker: Signer<'info>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", offer], verified in sol_escrow
+    #[account(mut)]
+    pub offer_sol_escrow: Option<UncheckedAccount<'info>>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveChallenger<'info> {
+    #[account(mut, has_one = creator)]
+    pub offer: Account<'info, Offer>,
+    #[account(mut, has_one = offer)]
+    pub request: Account<'info, Request>,
+    #[account(init, payer = creator, space = 8 + Battle::INIT_SPACE, seeds = [b"battle", &offer.offer_nonce.to_le_bytes(), offer.creator.as_ref(), request.challenger.as_ref()], bump)]
+    pub battle: Account<'info, Battle>,
+    #[account(mut)]
+    pub creator: Signer<'info>,
+    // fighting characters: battle HP starts from their max_hp
+    #[account(mut, constraint = creator_character.nft_mint == creator_nft_ata.mint @ GameError::InvalidNftAta)]
+    pub creator_character: Account<'info, Character>,
+    #[account(constraint = creator_nft_ata.owner == creator.key() && creator_nft_ata.amount == 1 @ GameError::InvalidNftAta)]
+    pub creator_nft_ata: Account<'info, TokenAccount>,
+    #[account(mut, constraint = challenger_character.key() == request.character @ GameError::CharacterConstraint)]
+    pub challenger_character: Account<'info, Character>,
+    #[account(seeds = [b"loadout", creator_character.nft_mint.as_ref()], bump = creator_loadout.bump)]
+    pub creator_loadout: Option<Account<'info, Loadout>>,
+    // optional: class registries, for the signature ability of a side without a loadout
+    #[account(seeds = [b"class_registry", &[creator_character.base_class as u8]], bump = creator_class_registry.bump)]
+    pub creator_class_registry: Option<Account<'info, ClassRegistry>>,
+    #[account(seeds = [b"class_registry", &[challenger_character.base_class as u8]], bump = challenger_class_registry.bump)]
+    pub challenger_class_registry: Option<Account<'info, ClassRegistry>>,
+    #[account(mut)]
+    pub pool: Account<'info, EntropyPool>,
+    #[account(mut)]
+    pub currency_mint: Option<Account<'info, Mint>>,
+    pub config: Account<'info, Config>,
+    #[account(init_if_needed, payer = creator, space = 8 + RateLimit::INIT_SPACE, seeds = [b"rate_limit", creator.key.as_ref()], bump)]
+    pub rate_limit: Account<'info, RateLimit>,
+    // SPL: only needed by approve_request_at to refund the requests it rejects
+    #[account(mut, seeds = [b"escrow", escrow_vault.mint.as_ref()], bump = escrow_vault.bump)]
+    pub escrow_vault: Option<Account<'info, EscrowVault>>,
+    #[account(mut)]
+    pub escrow_ata: Option<Account<'info, TokenAccount>>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", offer], verified in sol_escrow
+    #[account(mut)]
+    pub offer_sol_escrow: Option<UncheckedAccount<'info>>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", request], verified in sol_escrow
+    #[account(mut)]
+    pub request_sol_escrow: Option<UncheckedAccount<'info>>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", battle], verified in sol_escrow
+    #[account(mut)]
+    pub battle_sol_escrow: Option<UncheckedAccount<'info>>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+#[instruction(offer_nonce: u64)]
+pub struct EnterQueue<'info> {
+    #[account(init, payer = player, space = 8 + MatchQueue::INIT_SPACE, seeds = [b"match_queue", player.key.as_ref()], bump)]
+    pub entry: Account<'info, MatchQueue>,
+    #[account(init, payer = player, space = 8 + Offer::INIT_SPACE, seeds = [b"offer", player.key.as_ref(), &offer_nonce.to_le_bytes()], bump)]
+    pub offer: Account<'info, Offer>,
+    #[account(mut)]
+    pub player: Signer<'info>,
+    pub character: Account<'info, Character>,
+    #[account(constraint = progression.nft_mint == character.nft_mint @ GameError::CharacterConstraint)]
+    pub progression: Account<'info, Progression>,
+    #[account(constraint = nft_ata.mint == character.nft_mint && nft_ata.owner == player.key() && nft_ata.amount == 1 @ GameError::NotNftOwner)]
+    pub nft_ata: Account<'info, TokenAccount>,
+    pub config: Account<'info, Config>,
+    // SPL relevant accounts
+    #[account(mut)]
+    pub player_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut, seeds = [b"escrow", escrow_vault.mint.as_ref()], bump = escrow_vault.bump)]
+    pub escrow_vault: Option<Account<'info, EscrowVault>>,
+    #[account(mut)]
+    pub escrow_ata: Option<Account<'info, TokenAccount>>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", offer], verified in sol_escrow
+    #[account(mut)]
+    pub offer_sol_escrow: Option<UncheckedAccount<'info>>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct LeaveQueue<'info> {
+    #[account(mut, has_one = player, has_one = offer, close = player)]
+    pub entry: Account<'info, MatchQueue>,
+    #[account(mut)]
+    pub offer: Account<'info, Offer>,
+    #[account(mut)]
+    pub player: Signer<'info>,
+    #[account(mut)]
+    pub player_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut, seeds = [b"escrow", escrow_vault.mint.as_ref()], bump = escrow_vault.bump)]
+    pub escrow_vault: Option<Account<'info, EscrowVault>>,
+    #[account(mut)]
+    pub escrow_ata: Option<Account<'info, TokenAccount>>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", offer], verified in sol_escrow
+    #[account(mut)]
+    pub offer_sol_escrow: Option<UncheckedAccount<'info>>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct MatchPlayers<'info> {
+    #[account(mut, constraint = entry_a.offer == offer_a.key() @ GameError::QueueMismatch, close = cranker)]
+    pub entry_a: Account<'info, MatchQueue>,
+    #[account(mut, constraint = entry_b.offer == offer_b.key() @ GameError::QueueMismatch, close = cranker)]
+    pub entry_b: Account<'info, MatchQueue>,
+    #[account(mut)]
+    pub offer_a: Account<'info, Offer>,
+    #[account(mut)]
+    pub offer_b: Account<'info, Offer>,
+    #[account(init, payer = cranker, space = 8 + Battle::INIT_SPACE, seeds = [b"battle", &offer_a.offer_nonce.to_le_bytes(), offer_a.creator.as_ref(), entry_b.player.as_ref()], bump)]
+    pub battle: Account<'info, Battle>,
+    // both characters must still be held by their queued players
+    #[account(mut, constraint = character_a.key() == entry_a.character @ GameError::CharacterConstraint)]
+    pub character_a: Account<'info, Character>,
+    #[account(constraint = nft_ata_a.mint == character_a.nft_mint && nft_ata_a.owner == entry_a.player && nft_ata_a.amount == 1 @ GameError::NotNftOwner)]
+    pub nft_ata_a: Account<'info, TokenAccount>,
+    #[account(mut, constraint = character_b.key() == entry_b.character @ GameError::CharacterConstraint)]
+    pub character_b: Account<'info, Character>,
+    #[account(constraint = nft_ata_b.mint == character_b.nft_mint && nft_ata_b.owner == entry_b.player && nft_ata_b.amount == 1 @ GameError::NotNftOwner)]
+    pub nft_ata_b: Account<'info, TokenAccount>,
+    #[account(seeds = [b"loadout", character_a.nft_mint.as_ref()], bump = loadout_a.bump)]
+    pub loadout_a: Option<Account<'info, Loadout>>,
+    #[account(seeds = [b"loadout", character_b.nft_mint.as_ref()], bump = loadout_b.bump)]
+    pub loadout_b: Option<Account<'info, Loadout>>,
+    #[account(seeds = [b"class_registry", &[character_a.base_class as u8]], bump = class_registry_a.bump)]
+    pub class_registry_a: Option<Account<'info, ClassRegistry>>,
+    #[account(seeds = [b"class_registry", &[character_b.base_class as u8]], bump = class_registry_b.bump)]
+    pub class_registry_b: Option<Account<'info, ClassRegistry>>,
+    #[account(mut)]
+    pub pool: Account<'info, EntropyPool>,
+    pub config: Account<'info, Config>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", offer_a], verified in sol_escrow
+    #[account(mut)]
+    pub offer_a_sol_escrow: Option<UncheckedAccount<'info>>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", offer_b], verified in sol_escrow
+    #[account(mut)]
+    pub offer_b_sol_escrow: Option<UncheckedAccount<'info>>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", battle], verified in sol_escrow
+    #[account(mut)]
+    pub battle_sol_escrow: Option<UncheckedAccount<'info>>,
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(offer_nonce: u64)]
+pub struct ProposeRematch<'info> {
+    #[account(seeds = [b"battle", &old_offer.offer_nonce.to_le_bytes(), old_offer.creator.as_ref(), old_battle.player2.as_ref()], bump = old_battle.bump)]
+    pub old_battle: Account<'info, Battle>,
+    pub old_offer: Account<'info, Offer>,
+    #[account(init, payer = proposer, space = 8 + Offer::INIT_SPACE, seeds = [b"offer", proposer.key.as_ref(), &offer_nonce.to_le_bytes()], bump)]
+    pub offer: Account<'info, Offer>,
+    #[account(init, payer = proposer, space = 8 + Rematch::INIT_SPACE, seeds = [b"rematch", old_battle.key().as_ref()], bump)]
+    pub rematch: Account<'info, Rematch>,
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+    pub proposer_character: Account<'info, Character>,
+    #[account(constraint = proposer_nft_ata.owner == proposer.key() && proposer_nft_ata.amount == 1 @ GameError::NotNftOwner)]
+    pub proposer_nft_ata: Account<'info, TokenAccount>,
+    pub config: Account<'info, Config>,
+    // SPL relevant accounts
+    #[account(mut)]
+    pub proposer_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut, seeds = [b"escrow", escrow_vault.mint.as_ref()], bump = escrow_vault.bump)]
+    pub escrow_vault: Option<Account<'info, EscrowVault>>,
+    #[account(mut)]
+    pub escrow_ata: Option<Account<'info, TokenAccount>>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", offer], verified in sol_escrow
+    #[account(mut)]
+    pub offer_sol_escrow: Option<UncheckedAccount<'info>>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptRematch<'info> {
+    #[account(mut, has_one = offer, constraint = rematch.opponent == opponent.key() @ GameError::Unauthorized)]
+    pub rematch: Account<'info, Rematch>,
+    #[account(mut)]
+    pub offer: Account<'info, Offer>,
+    #[account(init, payer = opponent, space = 8 + Battle::INIT_SPACE, seeds = [b"battle", &offer.offer_nonce.to_le_bytes(), offer.creator.as_ref(), opponent.key().as_ref()], bump)]
+    pub battle: Account<'info, Battle>,
+    #[account(mut)]
+    pub opponent: Signer<'info>,
+    // both characters must still be held by their players
+    #[account(mut, constraint = proposer_character.key() == rematch.proposer_character @ GameError::CharacterConstraint)]
+    pub proposer_character: Account<'info, Character>,
+    #[account(constraint = proposer_nft_ata.mint == proposer_character.nft_mint && proposer_nft_ata.owner == rematch.proposer && proposer_nft_ata.amount == 1 @ GameError::NotNftOwner)]
+    pub proposer_nft_ata: Account<'info, TokenAccount>,
+    #[account(mut, constraint = opponent_character.key() == rematch.opponent_character @ GameError::CharacterConstraint)]
+    pub opponent_character: Account<'info, Character>,
+    #[account(constraint = opponent_nft_ata.mint == opponent_character.nft_mint && opponent_nft_ata.owner == opponent.key() && opponent_nft_ata.amount == 1 @ GameError::NotNftOwner)]
+    pub opponent_nft_ata: Account<'info, TokenAccount>,
+    #[account(seeds = [b"loadout", proposer_character.nft_mint.as_ref()], bump = proposer_loadout.bump)]
+    pub proposer_loadout: Option<Account<'info, Loadout>>,
+    #[account(seeds = [b"loadout", opponent_character.nft_mint.as_ref()], bump = opponent_loadout.bump)]
+    pub opponent_loadout: Option<Account<'info, Loadout>>,
+    #[account(seeds = [b"class_registry", &[proposer_character.base_class as u8]], bump = proposer_class_registry.bump)]
+    pub proposer_class_registry: Option<Account<'info, ClassRegistry>>,
+    #[account(seeds = [b"class_registry", &[opponent_character.base_class as u8]], bump = opponent_class_registry.bump)]
+    pub opponent_class_registry: Option<Account<'info, ClassRegistry>>,
+    #[account(mut)]
+    pub pool: Account<'info, EntropyPool>,
+    pub config: Account<'info, Config>,
+    // SPL relevant accounts
+    #[account(mut)]
+    pub opponent_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut, seeds = [b"escrow", escrow_vault.mint.as_ref()], bump = escrow_vault.bump)]
+    pub escrow_vault: Option<Account<'info, EscrowVault>>,
+    #[account(mut)]
+    pub escrow_ata: Option<Account<'info, TokenAccount>>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", offer], verified in sol_escrow
+    #[account(mut)]
+    pub offer_sol_escrow: Option<UncheckedAccount<'info>>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", battle], verified in sol_escrow
+    #[account(mut)]
+    pub battle_sol_escrow: Option<UncheckedAccount<'info>>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelRematch<'info> {
+    #[account(mut, has_one = offer, has_one = proposer, close = proposer)]
+    pub rematch: Account<'info, Rematch>,
+    #[account(mut)]
+    pub offer: Account<'info, Offer>,
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+    #[account(mut)]
+    pub proposer_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut, seeds = [b"escrow", escrow_vault.mint.as_ref()], bump = escrow_vault.bump)]
+    pub escrow_vault: Option<Account<'info, EscrowVault>>,
+    #[account(mut)]
+    pub escrow_ata: Option<Account<'info, TokenAccount>>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", offer], verified in sol_escrow
+    #[account(mut)]
+    pub offer_sol_escrow: Option<UncheckedAccount<'info>>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteTurn<'info> {
+    #[account(mut)]
+    pub pool: Account<'info, EntropyPool>,
+    #[account(mut)]
+    pub battle: Account<'info, Battle>,
+    // required once the battle has a TurnLog
+    #[account(mut, seeds = [b"turn_log", battle.key().as_ref()], bump = turn_log.bump)]
+    pub turn_log: Option<Account<'info, TurnLog>>,
+    #[account(mut)]
+    pub attacker_character: Account<'info, Character>,
+    /// CHECK: ClassBalance PDA of the attacker's class; always required so a nerf can't be skipped, may be uninitialized
+    #[account(seeds = [b"class_balance", &[attacker_character.base_class as u8]], bump)]
+    pub attacker_class_balance: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub defender_character: Account<'info, Character>,
+    #[account(mut, seeds = [b"progress", attacker_character.nft_mint.as_ref()], bump = attacker_prog.bump)]
+    pub attacker_prog: Account<'info, Progression>,
+    #[account(mut, seeds = [b"progress", defender_character.nft_mint.as_ref()], bump = defender_prog.bump)]
+    pub defender_prog: Account<'info, Progression>,
+    // both character NFTs must still be held: the attacker's by the signer, the defender's by the opponent (checked in the handler)
+    #[account(constraint = attacker_nft_ata.mint == attacker_character.nft_mint && attacker_nft_ata.owner == signer.key() && attacker_nft_ata.amount == 1 @ GameError::NotNftOwner)]
+    pub attacker_nft_ata: Account<'info, TokenAccount>,
+    #[account(constraint = defender_nft_ata.mint == defender_character.nft_mint && defender_nft_ata.amount == 1 @ GameError::NotNftOwner)]
+    pub defender_nft_ata: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub player1_character_opt: Option<Account<'info, Character>>,
+    #[account(mut)]
+    pub player2_character_opt: Option<Account<'info, Character>>,
+    // the MMR leaderboard, re-ranked with both players' new ratings
+    #[account(mut, seeds = [b"leaderboard"], bump = leaderboard.bump)]
+    pub leaderboard: Account<'info, Leaderboard>,
+    pub signer: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ForfeitByTimeout<'info> {
+    #[account(mut, seeds = [b"battle", &offer.offer_nonce.to_le_bytes(), offer.creator.as_ref(), battle.player2.as_ref()], bump = battle.bump)]
+    pub battle: Account<'info, Battle>,
+    pub offer: Account<'info, Offer>,
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub caller: Signer<'info>,
+    // the battle's characters (remaining HP persisted) and their progressions (Elo + battle stats)
+    #[account(mut, constraint = player1_character.key() == battle.player1_character @ GameError::CharacterConstraint)]
+    pub player1_character: Account<'info, Character>,
+    #[account(mut, constraint = player2_character.key() == battle.player2_character @ GameError::CharacterConstraint)]
+    pub player2_character: Account<'info, Character>,
+    #[account(mut, seeds = [b"progress", player1_character.nft_mint.as_ref()], bump = player1_prog.bump)]
+    pub player1_prog: Account<'info, Progression>,
+    #[account(mut, seeds = [b"progress", player2_character.nft_mint.as_ref()], bump = player2_prog.bump)]
+    pub player2_prog: Account<'info, Progression>,
+    // the MMR leaderboard, re-ranked with both players' new ratings
+    #[account(mut, seeds = [b"leaderboard"], bump = leaderboard.bump)]
+    pub leaderboard: Account<'info, Leaderboard>,
+    // keeper reward (forfeit_by_timeout with Config.forfeit_keeper_bps > 0): SOL from the battle escrow, SPL to caller_ata
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", battle], verified in sol_escrow
+    #[account(mut)]
+    pub battle_sol_escrow: Option<UncheckedAccount<'info>>,
+    #[account(mut, seeds = [b"escrow", escrow_vault.mint.as_ref()], bump = escrow_vault.bump)]
+    pub escrow_vault: Option<Account<'info, EscrowVault>>,
+    #[account(mut)]
+    pub escrow_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub caller_ata: Option<Account<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct PauseBattle<'info> {
+    #[account(mut)]
+    pub battle: Account<'info, Battle>,
+    pub player: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct MoveCommit<'info> {
+    #[account(mut)]
+    pub battle: Account<'info, Battle>,
+    pub player: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ResolveAsyncBattle<'info> {
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub pool: Account<'info, EntropyPool>,
+    #[account(mut)]
+    pub battle: Account<'info, Battle>,
+    #[account(mut, constraint = player1_character.key() == battle.player1_character @ GameError::CharacterConstraint)]
+    pub player1_character: Account<'info, Character>,
+    #[account(mut, constraint = player2_character.key() == battle.player2_character @ GameError::CharacterConstraint)]
+    pub player2_character: Account<'info, Character>,
+    #[account(mut, seeds = [b"progress", player1_character.nft_mint.as_ref()], bump = player1_prog.bump)]
+    pub player1_prog: Account<'info, Progression>,
+    #[account(mut, seeds = [b"progress", player2_character.nft_mint.as_ref()], bump = player2_prog.bump)]
+    pub player2_prog: Account<'info, Progression>,
+    // the MMR leaderboard, re-ranked with both players' new ratings
+    #[account(mut, seeds = [b"leaderboard"], bump = leaderboard.bump)]
+    pub leaderboard: Account<'info, Leaderboard>,
+    pub caller: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct DrawOffer<'info> {
+    #[account(mut)]
+    pub battle: Account<'info, Battle>,
+    pub player: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ResumeBattle<'info> {
+    #[account(mut)]
+    pub battle: Account<'info, Battle>,
+    pub caller: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ReadyUp<'info> {
+    #[account(mut)]
+    pub battle: Account<'info, Battle>,
+    pub player: Signer<'info>,
+    // the player's character NFT, delegated to and frozen by the battle PDA
+    pub character: Account<'info, Character>,
+    #[account(mut, constraint = nft_ata.mint == character.nft_mint && nft_ata.owner == player.key() && nft_ata.amount == 1 @ GameError::NotNftOwner)]
+    pub nft_ata: Account<'info, TokenAccount>,
+    /// CHECK: character NFT mint
+    #[account(constraint = nft_mint.key() == character.nft_mint @ GameError::InvalidNftAta)]
+    pub nft_mint: UncheckedAccount<'info>,
+    /// CHECK: Metaplex master edition of the NFT, validated by the metadata program
+    pub nft_edition: UncheckedAccount<'info>,
+    /// CHECK: Metaplex token metadata program
+    #[account(address = TOKEN_METADATA_PROGRAM_ID)]
+    pub token_metadata_program: UncheckedAccount<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ReleaseCharacterNft<'info> {
+    #[account(mut)]
+    pub battle: Account<'info, Battle>,
+    pub character: Account<'info, Character>,
+    #[account(mut, constraint = nft_ata.mint == character.nft_mint @ GameError::InvalidNftAta)]
+    pub nft_ata: Account<'info, TokenAccount>,
+    /// CHECK: character NFT mint
+    #[account(constraint = nft_mint.key() == character.nft_mint @ GameError::InvalidNftAta)]
+    pub nft_mint: UncheckedAccount<'info>,
+    /// CHECK: Metaplex master edition of the NFT, validated by the metadata program
+    pub nft_edition: UncheckedAccount<'info>,
+    /// CHECK: Metaplex token metadata program
+    #[account(address = TOKEN_METADATA_PROGRAM_ID)]
+    pub token_metadata_program: UncheckedAccount<'info>,
+    pub token_program: Program<'info, Token>,
+    pub caller: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimNoShow<'info> {
+    #[account(mut, seeds = [b"battle", &offer.offer_nonce.to_le_bytes(), offer.creator.as_ref(), battle.player2.as_ref()], bump = battle.bump)]
+    pub battle: Account<'info, Battle>,
+    pub offer: Account<'info, Offer>,
+    /// CHECK: payout destination, must be battle.player1
+    #[account(mut, constraint = player1.key() == battle.player1 @ GameError::Unauthorized)]
+    pub player1: UncheckedAccount<'info>,
+    /// CHECK: payout destination, must be battle.player2
+    #[account(mut, constraint = player2.key() == battle.player2 @ GameError::Unauthorized)]
+    pub player2: UncheckedAccount<'info>,
+    pub caller: Signer<'info>,
+    // SPL relevant accounts
+    #[account(mut, seeds = [b"escrow", escrow_vault.mint.as_ref()], bump = escrow_vault.bump)]
+    pub escrow_vault: Option<Account<'info, EscrowVault>>,
+    #[account(mut)]
+    pub escrow_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut, constraint = player1_ata.owner == battle.player1 && offer.currency == Currency::SPL(player1_ata.mint) @ GameError::TokenAccountMismatch)]
+    pub player1_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut, constraint = player2_ata.owner == battle.player2 && offer.currency == Currency::SPL(player2_ata.mint) @ GameError::TokenAccountMismatch)]
+    pub player2_ata: Option<Account<'info, TokenAccount>>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", battle], verified in sol_escrow
+    #[account(mut)]
+    pub battle_sol_escrow: Option<UncheckedAccount<'info>>,
+    // optional: the battle's characters, to release their battle lock
+    #[account(mut)]
+    pub player1_character: Option<Account<'info, Character>>,
+    #[account(mut)]
+    pub player2_character: Option<Account<'info, Character>>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct CancelAbandonedBattle<'info> {
+    #[account(mut, seeds = [b"battle", &offer.offer_nonce.to_le_bytes(), offer.creator.as_ref(), battle.player2.as_ref()], bump = battle.bump)]
+    pub battle: Account<'info, Battle>,
+    pub offer: Account<'info, Offer>,
+    /// CHECK: refund destination, must be battle.player1
+    #[account(mut, constraint = player1.key() == battle.player1 @ GameError::Unauthorized)]
+    pub player1: UncheckedAccount<'info>,
+    /// CHECK: refund destination, must be battle.player2
+    #[account(mut, constraint = player2.key() == battle.player2 @ GameError::Unauthorized)]
+    pub player2: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub caller: Signer<'info>,
+    // SPL relevant accounts
+    #[account(mut, seeds = [b"escrow", escrow_vault.mint.as_ref()], bump = escrow_vault.bump)]
+    pub escrow_vault: Option<Account<'info, EscrowVault>>,
+    #[account(mut)]
+    pub escrow_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut, constraint = player1_ata.owner == battle.player1 && offer.currency == Currency::SPL(player1_ata.mint) @ GameError::TokenAccountMismatch)]
+    pub player1_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut, constraint = player2_ata.owner == battle.player2 && offer.currency == Currency::SPL(player2_ata.mint) @ GameError::TokenAccountMismatch)]
+    pub player2_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut, constraint = offer.currency == Currency::SPL(caller_ata.mint) @ GameError::TokenAccountMismatch)]
+    pub caller_ata: Option<Account<'info, TokenAccount>>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", battle], verified in sol_escrow
+    #[account(mut)]
+    pub battle_sol_escrow: Option<UncheckedAccount<'info>>,
+    // optional: the battle's characters, to release their battle lock
+    #[account(mut)]
+    pub player1_character: Option<Account<'info, Character>>,
+    #[account(mut)]
+    pub player2_character: Option<Account<'info, Character>>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FundBonus<'info> {
+    #[account(mut, seeds = [b"battle", &offer.offer_nonce.to_le_bytes(), offer.creator.as_ref(), battle.player2.as_ref()], bump = battle.bump)]
+    pub battle: Account<'info, Battle>,
+    pub offer: Account<'info, Offer>,
     #[account(mut)]
-    pub payer: Signer<'info>,
+    pub sponsor: Signer<'info>,
+    // SPL relevant accounts
     #[account(mut)]
-    pub nft_ata: Account<'info, TokenAccount>,
-    #[account(init_if_needed, payer = payer, space = 8 + Progression::INIT_SPACE, seeds = [b"progress", nft_mint.key().as_ref()], bump)]
-    pub progression: Account<'info, Progression>,
-    pub system_program: Program<'info, System>,
+    pub sponsor_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut, seeds = [b"escrow", escrow_vault.mint.as_ref()], bump = escrow_vault.bump)]
+    pub escrow_vault: Option<Account<'info, EscrowVault>>,
+    #[account(mut)]
+    pub escrow_ata: Option<Account<'info, TokenAccount>>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", battle], verified in sol_escrow
+    #[account(mut)]
+    pub battle_sol_escrow: Option<UncheckedAccount<'info>>,
     pub token_program: Program<'info, Token>,
-    pub rent: Sysvar<'info, Rent>,
-    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-#[instruction(offer_nonce: u64)]
-pub struct CreateBattleOffer<'info> {
-    #[account(init, payer = creator, space = 8 + Offer::INIT_SPACE, seeds = [b"offer", creator.key.as_ref(), &offer_nonce.to_le_bytes()], bump)]
+pub struct AddBounty<'info> {
+    #[account(mut, seeds = [b"battle", &offer.offer_nonce.to_le_bytes(), offer.creator.as_ref(), battle.player2.as_ref()], bump = battle.bump)]
+    pub battle: Account<'info, Battle>,
     pub offer: Account<'info, Offer>,
+    pub config: Account<'info, Config>,
     #[account(mut)]
-    pub creator: Signer<'info>,
+    pub contributor: Signer<'info>,
+    // SPL relevant accounts
     #[account(mut)]
-    pub creator_ata: Option<Account<'info, TokenAccount>>, // if SPL
+    pub contributor_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut, seeds = [b"escrow", escrow_vault.mint.as_ref()], bump = escrow_vault.bump)]
+    pub escrow_vault: Option<Account<'info, EscrowVault>>,
     #[account(mut)]
-    pub offer_escrow: Option<Account<'info, TokenAccount>>, // to be created if SPL
+    pub escrow_ata: Option<Account<'info, TokenAccount>>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", battle], verified in sol_escrow
     #[account(mut)]
-    pub currency_mint: Option<Account<'info, Mint>>,
-    pub config: Account<'info, Config>,
+    pub battle_sol_escrow: Option<UncheckedAccount<'info>>,
     pub token_program: Program<'info, Token>,
-    pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
 }
 
 #[derive(Accounts)]
-pub struct JoinBattleOffer<'info> {
-    #[account(mut)]
+pub struct SpectateReact<'info> {
+    #[account(mut, seeds = [b"battle", &offer.offer_nonce.to_le_bytes(), offer.creator.as_ref(), battle.player2.as_ref()], bump = battle.bump)]
+    pub battle: Account<'info, Battle>,
     pub offer: Account<'info, Offer>,
-    #[account(init, payer = challenger, space = 8 + Request::INIT_SPACE, seeds = [b"request", offer.key().as_ref(), challenger.key.as_ref()], bump)]
-    pub request: Account<'info, Request>,
     #[account(mut)]
+    pub spectator: Signer<'info>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", battle], verified in sol_escrow (tips only)
+    #[account(mut)]
+    pub battle_sol_escrow: Option<UncheckedAccount<'info>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct GetBattleSummary<'info> {
+    pub battle: Account<'info, Battle>,
+}
+
+#[derive(Accounts)]
+pub struct GetCharacterCombatStats<'info> {
     pub character: Account<'info, Character>,
+    #[account(seeds = [b"progress", character.nft_mint.as_ref()], bump = progression.bump)]
+    pub progression: Account<'info, Progression>,
+}
+
+#[derive(Accounts)]
+pub struct StartTutorial<'info> {
+    // init_if_needed so an unfinished tutorial can be restarted
+    #[account(init_if_needed, payer = player, space = 8 + TutorialProgress::INIT_SPACE, seeds = [b"tutorial", player.key().as_ref()], bump)]
+    pub tutorial: Account<'info, TutorialProgress>,
+    #[account(mut)]
+    pub player: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct TutorialTurn<'info> {
+    #[account(mut, has_one = player, seeds = [b"tutorial", player.key().as_ref()], bump = tutorial.bump)]
+    pub tutorial: Account<'info, TutorialProgress>,
+    pub player: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimTutorialReward<'info> {
+    #[account(mut, has_one = player, seeds = [b"tutorial", player.key().as_ref()], bump = tutorial.bump)]
+    pub tutorial: Account<'info, TutorialProgress>,
     #[account(mut)]
+    pub character: Account<'info, Character>,
+    #[account(mut, seeds = [b"progress", character.nft_mint.as_ref()], bump = progression.bump)]
     pub progression: Account<'info, Progression>,
+    pub nft_ata: Account<'info, TokenAccount>,
+    pub player: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct StartPractice<'info> {
+    // init_if_needed so a practice can be restarted at any time
+    #[account(init_if_needed, payer = player, space = 8 + PracticeBattle::INIT_SPACE, seeds = [b"practice", character.key().as_ref()], bump)]
+    pub practice: Account<'info, PracticeBattle>,
+    #[account(seeds = [b"character", character.nft_mint.as_ref()], bump = character.bump)]
+    pub character: Account<'info, Character>,
+    #[account(constraint = nft_ata.mint == character.nft_mint && nft_ata.owner == player.key() && nft_ata.amount == 1 @ GameError::NotNftOwner)]
+    pub nft_ata: Account<'info, TokenAccount>,
     #[account(mut)]
-    pub challenger: Signer<'info>,
+    pub player: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct PracticeTurn<'info> {
+    pub config: Account<'info, Config>,
     #[account(mut)]
-    pub challenger_ata: Option<Account<'info, TokenAccount>>,
+    pub pool: Account<'info, EntropyPool>,
+    #[account(mut, has_one = player, has_one = character, seeds = [b"practice", character.key().as_ref()], bump = practice.bump)]
+    pub practice: Account<'info, PracticeBattle>,
     #[account(mut)]
-    pub request_escrow: Option<Account<'info, TokenAccount>>,
+    pub character: Account<'info, Character>,
+    #[account(mut, seeds = [b"progress", character.nft_mint.as_ref()], bump = progression.bump)]
+    pub progression: Account<'info, Progression>,
+    #[account(constraint = nft_ata.mint == character.nft_mint && nft_ata.owner == player.key() && nft_ata.amount == 1 @ GameError::NotNftOwner)]
+    pub nft_ata: Account<'info, TokenAccount>,
+    pub player: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(kind: u8, key: u8)]
+pub struct InitWinrateStats<'info> {
+    #[account(has_one = admin)]
+    pub config: Account<'info, Config>,
+    #[account(init, payer = admin, space = 8 + WinrateStats::INIT_SPACE, seeds = [b"winrate", &[kind], &[key]], bump)]
+    pub stats: Account<'info, WinrateStats>,
     #[account(mut)]
-    pub currency_mint: Option<Account<'info, Mint>>,
-    pub token_program: Program<'info, Token>,
-    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub admin: Signer<'info>,
     pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+#[instruction(class: CharacterClass)]
+pub struct SetClassStats<'info> {
+    #[account(has_one = admin)]
     pub config: Account<'info, Config>,
+    #[account(init_if_needed, payer = admin, space = 8 + ClassRegistry::INIT_SPACE, seeds = [b"class_registry", &[class as u8]], bump)]
+    pub class_registry: Account<'info, ClassRegistry>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct WithdrawRequest<'info> {
-    #[account(mut, has_one = challenger)]
-    pub request: Account<'info, Request>,
+#[instruction(class: CharacterClass)]
+pub struct InitClassBalance<'info> {
+    #[account(has_one = admin)]
+    pub config: Account<'info, Config>,
+    #[account(init, payer = admin, space = 8 + ClassBalance::INIT_SPACE, seeds = [b"class_balance", &[class as u8]], bump)]
+    pub class_balance: Account<'info, ClassBalance>,
     #[account(mut)]
-    pub challenger: Signer<'info>,
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RebalanceClasses<'info> {
+    #[account(has_one = admin)]
+    pub config: Account<'info, Config>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(guild_id: u64)]
+pub struct CreateGuild<'info> {
+    #[account(init, payer = authority, space = 8 + Guild::INIT_SPACE, seeds = [b"guild", authority.key().as_ref(), &guild_id.to_le_bytes()], bump)]
+    pub guild: Account<'info, Guild>,
     #[account(mut)]
-    pub offer: Account<'info, Offer>,
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetGuildContribution<'info> {
+    #[account(mut, has_one = authority)]
+    pub guild: Account<'info, Guild>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct JoinGuild<'info> {
     #[account(mut)]
-    pub request_escrow: Option<Account<'info, TokenAccount>>,
+    pub guild: Account<'info, Guild>,
+    // one guild per player
+    #[account(init, payer = player, space = 8 + GuildMember::INIT_SPACE, seeds = [b"guild_member", player.key().as_ref()], bump)]
+    pub member: Account<'info, GuildMember>,
     #[account(mut)]
-    pub challenger_ata: Option<Account<'info, TokenAccount>>,
-    pub token_program: Program<'info, Token>,
+    pub player: Signer<'info>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct CancelOffer<'info> {
-    #[account(mut, has_one = creator)]
+pub struct LeaveGuild<'info> {
+    #[account(mut)]
+    pub guild: Account<'info, Guild>,
+    #[account(mut, close = player, has_one = player, has_one = guild, seeds = [b"guild_member", player.key().as_ref()], bump = member.bump)]
+    pub member: Account<'info, GuildMember>,
+    #[account(mut)]
+    pub player: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ContributeBattleXp<'info> {
+    pub battle: Account<'info, Battle>,
+    #[account(mut)]
+    pub guild: Account<'info, Guild>,
+    #[account(mut, has_one = player, has_one = guild, seeds = [b"guild_member", player.key().as_ref()], bump = member.bump)]
+    pub member: Account<'info, GuildMember>,
+    // existence of the receipt blocks a second contribution for the same battle
+    #[account(init, payer = player, space = 8 + GuildContribution::INIT_SPACE, seeds = [b"guild_contrib", battle.key().as_ref(), player.key().as_ref()], bump)]
+    pub receipt: Account<'info, GuildContribution>,
+    #[account(mut)]
+    pub player: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FinalizeBattle<'info> {
+    #[account(mut)]
+    pub battle: Account<'info, Battle>,
+    #[account(mut)]
     pub offer: Account<'info, Offer>,
     #[account(mut)]
-    pub creator: Signer<'info>,
+    pub treasury: UncheckedAccount<'info>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", battle], verified in sol_escrow
     #[account(mut)]
-    pub offer_escrow: Option<Account<'info, TokenAccount>>,
+    pub battle_sol_escrow: Option<UncheckedAccount<'info>>,
+    // SPL relevant accounts
+    #[account(mut, seeds = [b"escrow", escrow_vault.mint.as_ref()], bump = escrow_vault.bump)]
+    pub escrow_vault: Option<Account<'info, EscrowVault>>,
     #[account(mut)]
-    pub creator_ata: Option<Account<'info, TokenAccount>>,
+    pub escrow_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub treasury_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub player1_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub player2_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub player1_owner: Signer<'info>,
+    #[account(mut)]
+    pub player2_owner: Signer<'info>,
+    pub config: Account<'info, Config>,
+    // optional: SOL winnings go to this vault when it belongs to the winner (SPL: pass the vault ATA as playerN_ata)
+    #[account(mut)]
+    pub winner_vault: Option<Account<'info, PlayerVault>>,
+    // optional: winner's guild membership for the perk fee discount
+    pub winner_guild_member: Option<Account<'info, GuildMember>>,
+    pub winner_guild: Option<Account<'info, Guild>>,
+    // the battle's characters (battle lock released) and their NFT ATAs (winner's ownership re-checked)
+    #[account(mut, constraint = player1_character.key() == battle.player1_character @ GameError::CharacterConstraint)]
+    pub player1_character: Account<'info, Character>,
+    #[account(mut, constraint = player2_character.key() == battle.player2_character @ GameError::CharacterConstraint)]
+    pub player2_character: Account<'info, Character>,
+    pub player1_nft_ata: Account<'info, TokenAccount>,
+    pub player2_nft_ata: Account<'info, TokenAccount>,
     pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct ApproveChallenger<'info> {
-    #[account(mut, has_one = creator)]
-    pub offer: Account<'info, Offer>,
-    #[account(mut, has_one = offer)]
-    pub request: Account<'info, Request>,
-    #[account(init, payer = creator, space = 8 + Battle::INIT_SPACE, seeds = [b"battle", &offer.offer_nonce.to_le_bytes(), offer.creator.as_ref(), request.challenger.as_ref()], bump)]
+pub struct SettleForfeit<'info> {
+    #[account(mut, seeds = [b"battle", &offer.offer_nonce.to_le_bytes(), offer.creator.as_ref(), battle.player2.as_ref()], bump = battle.bump)]
     pub battle: Account<'info, Battle>,
+    pub offer: Account<'info, Offer>,
+    pub config: Account<'info, Config>,
+    // nobody at the table signs here, so the fee can only go to the config admin
+    /// CHECK: protocol treasury (config admin), receives the fee
+    #[account(mut, constraint = treasury.key() == config.admin @ GameError::Unauthorized)]
+    pub treasury: UncheckedAccount<'info>,
+    /// CHECK: the forfeit winner, receives the SOL payout
+    #[account(mut, constraint = Some(winner.key()) == battle.winner @ GameError::Unauthorized)]
+    pub winner: UncheckedAccount<'info>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", battle], verified in sol_escrow
     #[account(mut)]
-    pub creator: Signer<'info>,
+    pub battle_sol_escrow: Option<UncheckedAccount<'info>>,
+    #[account(mut, seeds = [b"escrow", escrow_vault.mint.as_ref()], bump = escrow_vault.bump)]
+    pub escrow_vault: Option<Account<'info, EscrowVault>>,
     #[account(mut)]
-    pub pool: Account<'info, EntropyPool>,
-    // escrow accounts for SPL flows
+    pub escrow_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut, constraint = treasury_ata.owner == config.admin @ GameError::Unauthorized)]
+    pub treasury_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut, constraint = Some(winner_ata.owner) == battle.winner @ GameError::Unauthorized)]
+    pub winner_ata: Option<Account<'info, TokenAccount>>,
+    // optional: winner's guild membership for the perk fee discount
+    pub winner_guild_member: Option<Account<'info, GuildMember>>,
+    pub winner_guild: Option<Account<'info, Guild>>,
+    #[account(mut, constraint = player1_character.key() == battle.player1_character @ GameError::CharacterConstraint)]
+    pub player1_character: Account<'info, Character>,
+    #[account(mut, constraint = player2_character.key() == battle.player2_character @ GameError::CharacterConstraint)]
+    pub player2_character: Account<'info, Character>,
+    pub winner_nft_ata: Account<'info, TokenAccount>,
+    pub caller: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseBattle<'info> {
+    #[account(mut, close = rent_receiver)]
+    pub battle: Account<'info, Battle>,
+    /// CHECK: the battle's winner, or player1 when there is none
+    #[account(mut, constraint = rent_receiver.key() == battle.winner.unwrap_or(battle.player1) @ GameError::Unauthorized)]
+    pub rent_receiver: UncheckedAccount<'info>,
+    // characters still locked to the battle are released (no unlock_character once it's gone)
+    #[account(mut, constraint = player1_character.key() == battle.player1_character @ GameError::CharacterConstraint)]
+    pub player1_character: Account<'info, Character>,
+    #[account(mut, constraint = player2_character.key() == battle.player2_character @ GameError::CharacterConstraint)]
+    pub player2_character: Account<'info, Character>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", battle], verified in sol_escrow
     #[account(mut)]
-    pub offer_escrow: Option<Account<'info, TokenAccount>>,
+    pub battle_sol_escrow: Option<UncheckedAccount<'info>>,
+    pub caller: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitTurnLog<'info> {
     #[account(mut)]
-    pub request_escrow: Option<Account<'info, TokenAccount>>,
+    pub battle: Account<'info, Battle>,
+    #[account(init, payer = payer, space = 8 + TurnLog::INIT_SPACE, seeds = [b"turn_log", battle.key().as_ref()], bump)]
+    pub turn_log: Account<'info, TurnLog>,
     #[account(mut)]
-    pub battle_escrow: Option<Account<'info, TokenAccount>>,
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UnlockCharacter<'info> {
+    pub battle: Account<'info, Battle>,
+    #[account(mut, seeds = [b"character", character.nft_mint.as_ref()], bump = character.bump)]
+    pub character: Account<'info, Character>,
+}
+
+#[derive(Accounts)]
+#[instruction(nonce: u64)]
+pub struct CreateTeamBattle<'info> {
+    #[account(init, payer = player, space = 8 + TeamBattle::INIT_SPACE, seeds = [b"team_battle", player.key.as_ref(), &nonce.to_le_bytes()], bump)]
+    pub team_battle: Account<'info, TeamBattle>,
+    #[account(mut, seeds = [b"character", character.nft_mint.as_ref()], bump = character.bump)]
+    pub character: Account<'info, Character>,
+    #[account(constraint = nft_ata.mint == character.nft_mint && nft_ata.owner == player.key() && nft_ata.amount == 1 @ GameError::NotNftOwner)]
+    pub nft_ata: Account<'info, TokenAccount>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", team_battle], verified in sol_escrow
     #[account(mut)]
-    pub currency_mint: Option<Account<'info, Mint>>,
+    pub team_sol_escrow: Option<UncheckedAccount<'info>>,
     pub config: Account<'info, Config>,
-    pub token_program: Program<'info, Token>,
-    pub associated_token_program: Program<'info, AssociatedToken>,
+    #[account(mut)]
+    pub player: Signer<'info>,
     pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
 }
 
 #[derive(Accounts)]
-pub struct ExecuteTurn<'info> {
+pub struct JoinTeamBattle<'info> {
+    #[account(mut, seeds = [b"team_battle", team_battle.creator.as_ref(), &team_battle.nonce.to_le_bytes()], bump = team_battle.bump)]
+    pub team_battle: Account<'info, TeamBattle>,
     #[account(mut)]
     pub pool: Account<'info, EntropyPool>,
+    #[account(mut, seeds = [b"character", character.nft_mint.as_ref()], bump = character.bump)]
+    pub character: Account<'info, Character>,
+    #[account(constraint = nft_ata.mint == character.nft_mint && nft_ata.owner == player.key() && nft_ata.amount == 1 @ GameError::NotNftOwner)]
+    pub nft_ata: Account<'info, TokenAccount>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", team_battle], verified in sol_escrow
     #[account(mut)]
-    pub battle: Account<'info, Battle>,
+    pub team_sol_escrow: Option<UncheckedAccount<'info>>,
     #[account(mut)]
-    pub attacker_character: Account<'info, Character>,
+    pub player: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct LeaveTeamBattle<'info> {
+    #[account(mut, seeds = [b"team_battle", team_battle.creator.as_ref(), &team_battle.nonce.to_le_bytes()], bump = team_battle.bump)]
+    pub team_battle: Account<'info, TeamBattle>,
+    #[account(mut)]
+    pub character: Account<'info, Character>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", team_battle], verified in sol_escrow
+    #[account(mut)]
+    pub team_sol_escrow: Option<UncheckedAccount<'info>>,
+    #[account(mut)]
+    pub player: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteTeamTurn<'info> {
+    #[account(mut)]
+    pub pool: Account<'info, EntropyPool>,
     #[account(mut)]
+    pub team_battle: Account<'info, TeamBattle>,
+    pub attacker_character: Account<'info, Character>,
     pub defender_character: Account<'info, Character>,
+    // the actor must still hold their character NFT
+    #[account(constraint = attacker_nft_ata.mint == attacker_character.nft_mint && attacker_nft_ata.owner == signer.key() && attacker_nft_ata.amount == 1 @ GameError::NotNftOwner)]
+    pub attacker_nft_ata: Account<'info, TokenAccount>,
+    pub signer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct TeamForfeitByTimeout<'info> {
     #[account(mut)]
-    pub attacker_prog: Account<'info, Progression>,
+    pub team_battle: Account<'info, TeamBattle>,
+    pub caller: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct FinalizeTeamBattle<'info> {
     #[account(mut)]
-    pub defender_prog: Account<'info, Progression>,
+    pub team_battle: Account<'info, TeamBattle>,
+    pub config: Account<'info, Config>,
+    /// CHECK: protocol treasury, receives the fee
+    #[account(mut)]
+    pub treasury: UncheckedAccount<'info>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", team_battle], verified in sol_escrow
+    #[account(mut)]
+    pub team_sol_escrow: Option<UncheckedAccount<'info>>,
+    /// CHECK: first seat of the winning team, checked in the handler
+    #[account(mut)]
+    pub winner_a: UncheckedAccount<'info>,
+    /// CHECK: second seat of the winning team, checked in the handler
+    #[account(mut)]
+    pub winner_b: UncheckedAccount<'info>,
+    // all four seats' characters: battle lock released, remaining HP written back
+    #[account(mut, constraint = seat0_character.key() == team_battle.members[0].character @ GameError::CharacterConstraint)]
+    pub seat0_character: Account<'info, Character>,
+    #[account(mut, constraint = seat1_character.key() == team_battle.members[1].character @ GameError::CharacterConstraint)]
+    pub seat1_character: Account<'info, Character>,
+    #[account(mut, constraint = seat2_character.key() == team_battle.members[2].character @ GameError::CharacterConstraint)]
+    pub seat2_character: Account<'info, Character>,
+    #[account(mut, constraint = seat3_character.key() == team_battle.members[3].character @ GameError::CharacterConstraint)]
+    pub seat3_character: Account<'info, Character>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(boss_id: u64, max_hp: u64, damage_min: u16, damage_max: u16, defense: u16, script: Vec<u16>)]
+pub struct CreateBoss<'info> {
+    #[account(has_one = admin)]
+    pub config: Account<'info, Config>,
+    #[account(init, payer = admin, space = 8 + Boss::INIT_SPACE, seeds = [b"boss", &boss_id.to_le_bytes()], bump)]
+    pub boss: Account<'info, Boss>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", boss], verified in sol_escrow
+    #[account(mut)]
+    pub boss_sol_escrow: Option<UncheckedAccount<'info>>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FundBossReward<'info> {
+    #[account(mut, seeds = [b"boss", &boss.boss_id.to_le_bytes()], bump = boss.bump)]
+    pub boss: Account<'info, Boss>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", boss], verified in sol_escrow
+    #[account(mut)]
+    pub boss_sol_escrow: Option<UncheckedAccount<'info>>,
+    #[account(mut)]
+    pub funder: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AttackBoss<'info> {
     #[account(mut)]
-    pub attacker_nft_ata: Account<'info, TokenAccount>,
+    pub pool: Account<'info, EntropyPool>,
+    #[account(mut, seeds = [b"boss", &boss.boss_id.to_le_bytes()], bump = boss.bump)]
+    pub boss: Account<'info, Boss>,
+    #[account(seeds = [b"character", character.nft_mint.as_ref()], bump = character.bump)]
+    pub character: Account<'info, Character>,
+    #[account(init_if_needed, payer = player, space = 8 + RaidEntry::INIT_SPACE, seeds = [b"raid_entry", boss.key().as_ref(), character.key().as_ref()], bump)]
+    pub raid_entry: Account<'info, RaidEntry>,
+    #[account(constraint = nft_ata.mint == character.nft_mint && nft_ata.owner == player.key() && nft_ata.amount == 1 @ GameError::NotNftOwner)]
+    pub nft_ata: Account<'info, TokenAccount>,
     #[account(mut)]
-    pub defender_nft_ata: Account<'info, TokenAccount>,
+    pub player: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimRaidReward<'info> {
+    #[account(seeds = [b"boss", &boss.boss_id.to_le_bytes()], bump = boss.bump)]
+    pub boss: Account<'info, Boss>,
+    pub character: Account<'info, Character>,
+    #[account(mut, seeds = [b"raid_entry", boss.key().as_ref(), character.key().as_ref()], bump = raid_entry.bump)]
+    pub raid_entry: Account<'info, RaidEntry>,
+    // the reward goes to whoever holds the character NFT now
+    #[account(constraint = nft_ata.mint == character.nft_mint && nft_ata.owner == player.key() && nft_ata.amount == 1 @ GameError::NotNftOwner)]
+    pub nft_ata: Account<'info, TokenAccount>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", boss], verified in sol_escrow
     #[account(mut)]
-    pub player1_character_opt: Option<Account<'info, Character>>,
+    pub boss_sol_escrow: Option<UncheckedAccount<'info>>,
     #[account(mut)]
-    pub player2_character_opt: Option<Account<'info, Character>>,
-    pub signer: Signer<'info>,
-    pub token_program: Program<'info, Token>,
+    pub player: Signer<'info>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct ForfeitByTimeout<'info> {
+#[instruction(kind: AchievementKind)]
+pub struct ClaimAchievement<'info> {
+    pub character: Account<'info, Character>,
+    #[account(seeds = [b"progress", character.nft_mint.as_ref()], bump = progression.bump)]
+    pub progression: Account<'info, Progression>,
+    // existence of the PDA is the unlock; a second claim fails at init
+    #[account(init, payer = payer, space = 8 + Achievement::INIT_SPACE, seeds = [b"achievement", character.key().as_ref(), &[kind as u8]], bump)]
+    pub achievement: Account<'info, Achievement>,
     #[account(mut)]
-    pub battle: Account<'info, Battle>,
-    pub caller: Signer<'info>,
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct FinalizeBattle<'info> {
-    #[account(mut)]
-    pub battle: Account<'info, Battle>,
-    #[account(mut)]
-    pub offer: Account<'info, Offer>,
+pub struct InitLeaderboard<'info> {
+    #[account(has_one = admin)]
+    pub config: Account<'info, Config>,
+    #[account(init, payer = admin, space = 8 + Leaderboard::INIT_SPACE, seeds = [b"leaderboard"], bump)]
+    pub leaderboard: Account<'info, Leaderboard>,
     #[account(mut)]
-    pub treasury: UncheckedAccount<'info>,
-    // SPL relevant accounts
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(season_id: u32)]
+pub struct StartSeason<'info> {
+    #[account(mut, has_one = admin)]
+    pub config: Account<'info, Config>,
+    #[account(init, payer = admin, space = 8 + Season::INIT_SPACE, seeds = [b"season", &season_id.to_le_bytes()], bump)]
+    pub season: Account<'info, Season>,
+    // required once a season has run (Config.current_season > 0)
+    #[account(seeds = [b"season", &previous_season.id.to_le_bytes()], bump = previous_season.bump)]
+    pub previous_season: Option<Account<'info, Season>>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", season], verified in sol_escrow
     #[account(mut)]
-    pub battle_escrow: Option<Account<'info, TokenAccount>>,
+    pub season_sol_escrow: Option<UncheckedAccount<'info>>,
     #[account(mut)]
-    pub treasury_ata: Option<Account<'info, TokenAccount>>,
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateProgression<'info> {
+    pub character: Account<'info, Character>,
+    /// CHECK: a Progression on an older layout doesn't deserialize; the PDA, owner, discriminator and size are checked
+    /// here and in migrate_progression
+    #[account(mut, owner = crate::ID, seeds = [b"progress", character.nft_mint.as_ref()], bump)]
+    pub progression: UncheckedAccount<'info>,
     #[account(mut)]
-    pub player1_ata: Option<Account<'info, TokenAccount>>,
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RecordSeasonResult<'info> {
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+    #[account(mut, seeds = [b"season", &season.id.to_le_bytes()], bump = season.bump)]
+    pub season: Account<'info, Season>,
+    pub character: Account<'info, Character>,
+    #[account(mut, seeds = [b"progress", character.nft_mint.as_ref()], bump = progression.bump)]
+    pub progression: Account<'info, Progression>,
+    #[account(init, payer = payer, space = 8 + SeasonResult::INIT_SPACE, seeds = [b"season_result", season.key().as_ref(), character.key().as_ref()], bump)]
+    pub season_result: Account<'info, SeasonResult>,
+    // re-ranks the character after its soft reset
+    #[account(mut, seeds = [b"leaderboard"], bump = leaderboard.bump)]
+    pub leaderboard: Account<'info, Leaderboard>,
     #[account(mut)]
-    pub player2_ata: Option<Account<'info, TokenAccount>>,
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimSeasonReward<'info> {
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+    #[account(mut, seeds = [b"season", &season.id.to_le_bytes()], bump = season.bump)]
+    pub season: Account<'info, Season>,
+    pub character: Account<'info, Character>,
+    #[account(mut, seeds = [b"season_result", season.key().as_ref(), character.key().as_ref()], bump = season_result.bump)]
+    pub season_result: Account<'info, SeasonResult>,
+    // the reward goes to whoever holds the character NFT now
+    #[account(constraint = nft_ata.mint == character.nft_mint && nft_ata.owner == player.key() && nft_ata.amount == 1 @ GameError::NotNftOwner)]
+    pub nft_ata: Account<'info, TokenAccount>,
+    /// CHECK: zero-data SOL escrow PDA [b"sol_escrow", season], verified in sol_escrow
     #[account(mut)]
-    pub player1_owner: Signer<'info>,
+    pub season_sol_escrow: Option<UncheckedAccount<'info>>,
     #[account(mut)]
-    pub player2_owner: Signer<'info>,
-    pub token_program: Program<'info, Token>,
+    pub player: Signer<'info>,
     pub system_program: Program<'info, System>,
 }
 
@@ -998,9 +4996,29 @@ pub struct Config {
     pub inactivity_timeout: i64,
     pub spl_whitelist: Vec<Pubkey>,
     pub trait_authority: Pubkey,
+    pub max_actions_per_hour: u16, // offers + joins + approvals per wallet; 0 = unlimited
+    pub max_turns: u16, // per battle; 0 = unlimited
+    pub time_bank_secs: i64, // chess clock per player; 0 = off
+    // level-gap handicap on the higher-level attacker's damage; gap 0 = off
+    pub handicap_level_gap: u16,
+    pub handicap_bps_per_level: u16,
+    pub handicap_max_bps: u16,
+    // progression level needed for the Berserker / Counter stances
+    pub berserker_unlock_level: u16,
+    pub counter_unlock_level: u16,
+    // skip_idle_turn: per-turn deadline (0 = off), HP penalty per skip, consecutive skips before a forfeit
+    pub turn_deadline_secs: i64,
+    pub skip_penalty_bps: u16,
+    pub max_skips: u8,
+    pub forfeit_keeper_bps: u16, // forfeit_by_timeout caller's cut of the idle player's stake
+    pub elo_k_factor: u16,
+    pub current_season: u32, // latest Season id started; 0 = none yet
+    // current season's window; MMR only moves inside it, so ratings are frozen between seasons
+    pub season_start_ts: i64,
+    pub season_end_ts: i64,
     pub bump: u8,
 }
-impl Config { pub const INIT_SPACE: usize = 32 + 2 + 8 + 4 + (32 * 8) + 32 + 1; }
+impl Config { pub const INIT_SPACE: usize = 32 + 2 + 8 + 4 + (32 * 8) + 32 + 2 + 2 + 8 + 2 + 2 + 2 + 2 + 2 + 8 + 2 + 1 + 2 + 2 + 4 + 8 + 8 + 1; }
 
 #[account]
 pub struct EntropyPool {
@@ -1047,82 +5065,551 @@ pub struct Character {
     pub mod_crit_bps: i16,
     pub rarity: u8,
     pub created_at: i64,
+    pub element: Element,
+    pub armor_pen_bps: u16, // share of the defender's flat defense ignored
+    pub crit_resist_bps: u16, // subtracted from the attacker's crit chance
+    pub equipment: [EquipSlot; EQUIPMENT_SLOTS],
+    pub fusion_ready_at: i64, // fusion cooldown
+    // display profile (bounded by MAX_NAME_LEN / MAX_EMOJI_LEN / MAX_BANNER_LEN)
+    pub name: String,
+    pub emoji: String,
+    pub banner: String,
+    pub active_battle: Pubkey, // battle this character is locked to; default when free
+    pub bump: u8,
+}
+impl Character {
+    pub const INIT_SPACE: usize = 32 + 1 + 4 + 4 + 2 + 2 + 2 + 4 + 2 + 1 + 2 + 1 + 1 + 2 + 2 + 2 + 1 + 8 + 1 + 2 + 2 + EquipSlot::SIZE * EQUIPMENT_SLOTS + 8 + (4 + MAX_NAME_LEN) + (4 + MAX_EMOJI_LEN) + (4 + MAX_BANNER_LEN) + 32 + 1;
+}
+
+#[account]
+pub struct Progression {
+    pub nft_mint: Pubkey,
+    pub xp: u64,
+    pub level: u16,
+    pub mmr: u64,
+    pub last_played: i64,
+    // unspent stat points from level-ups, and points already spent per StatKind
+    pub stat_points: u16,
+    pub points_spent: [u16; 7],
+    pub season: u32, // last Season recorded (SeasonResult written, MMR soft-reset); 0 = none
+    // rated results, counted where MMR moves (achievement requirements)
+    pub battles: u32,
+    pub wins: u32,
+    pub win_streak: u16,
+    pub best_win_streak: u16,
+    pub flawless_wins: u32,
+    pub bump: u8,
+}
+impl Progression { pub const INIT_SPACE: usize = 32 + 8 + 2 + 8 + 8 + 2 + 2 * 7 + 4 + 4 + 4 + 2 + 2 + 4 + 1; }
+// earlier Progression layouts, grown in place by migrate_progression: V1 had no season, V2 no achievement counters
+pub const PROGRESSION_V1_SPACE: usize = 32 + 8 + 2 + 8 + 8 + 2 + 2 * 7 + 1;
+pub const PROGRESSION_V2_SPACE: usize = PROGRESSION_V1_SPACE + 4;
+
+#[account]
+pub struct PlayerMaterials {
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub characters_retired: u32,
+    pub bump: u8,
+}
+impl PlayerMaterials { pub const INIT_SPACE: usize = 32 + 8 + 4 + 1; }
+
+#[account]
+pub struct PlayerVault {
+    pub owner: Pubkey,
+    pub sol_balance: u64, // lamports above rent held for the owner
+    pub bump: u8,
+}
+impl PlayerVault { pub const INIT_SPACE: usize = 32 + 8 + 1; }
+
+// Owner of the single escrow ATA for one SPL mint; stakes per offer/request/battle live in those accounts' amounts
+#[account]
+pub struct EscrowVault {
+    pub mint: Pubkey,
+    pub total_escrowed: u64, // sum of all ledger amounts currently held in the escrow ATA
+    pub bump: u8,
+}
+impl EscrowVault { pub const INIT_SPACE: usize = 32 + 8 + 1; }
+
+// Per-wallet sliding-window counter (previous + current hour bucket)
+#[account]
+pub struct RateLimit {
+    pub wallet: Pubkey,
+    pub bucket: i64, // unix_timestamp / RATE_WINDOW_SECS of the current bucket
+    pub current_count: u16,
+    pub previous_count: u16,
+    pub bump: u8,
+}
+impl RateLimit { pub const INIT_SPACE: usize = 32 + 8 + 2 + 2 + 1; }
+
+#[account]
+pub struct Equipment {
+    pub item_mint: Pubkey,
+    pub mods: ItemModifiers,
+    pub equipped_on: Pubkey, // Character PDA, default when unequipped
+    pub bump: u8,
+}
+impl Equipment { pub const INIT_SPACE: usize = 32 + ItemModifiers::SIZE + 32 + 1; }
+
+#[account]
+pub struct ClassRegistry {
+    pub class: CharacterClass,
+    pub stats: ClassStats,
+    pub bump: u8,
+}
+impl ClassRegistry { pub const INIT_SPACE: usize = 1 + ClassStats::SIZE + 1; }
+
+#[account]
+pub struct Loadout {
+    pub nft_mint: Pubkey,
+    pub abilities: Vec<Ability>,
+    pub bump: u8,
+}
+impl Loadout { pub const INIT_SPACE: usize = 32 + (4 + MAX_LOADOUT_ABILITIES) + 1; }
+
+#[account]
+pub struct Offer {
+    pub creator: Pubkey,
+    pub offer_nonce: u64,
+    pub currency: Currency,
+    pub stake_amount: u64,
+    pub min_level: u16,
+    pub max_level: u16,
+    pub allowed_classes: Vec<CharacterClass>,
+    pub auto_approve: bool,
+    pub start_ts: i64,
+    pub inactivity_timeout: i64,
+    pub created_at: i64,
+    pub is_active: bool,
+    // recurring offers are relisted from the creator's vault after each battle settles
+    pub recurring: bool,
+    pub last_battle: Pubkey,
+    pub relist_count: u32,
+    pub rounds: u8, // best of N (odd)
+    pub commit_reveal: bool, // stances are committed as hashes and revealed before each turn resolves
+    pub async_mode: bool, // players submit whole strategies, resolve_async_battle plays the battle out
+    pub reconnect_grace_secs: i64, // added to the inactivity timeout for each player's first timeout
+    pub creator_character: Pubkey, // fixed at creation for auto_approve offers (default otherwise)
+    pub target_opponent: Option<Pubkey>, // private offer: the only wallet allowed to join
+    pub requests: Vec<Pubkey>, // pending requests, in join order (update_offer needs none)
+    pub request_count: u32, // joins over the offer's lifetime
+    pub min_mmr: u64, // challenger's Progression.mmr must fall in [min_mmr, max_mmr]
+    pub max_mmr: u64,
+    pub registry_page: Option<u32>, // OfferRegistry page listing this offer
+    pub bump: u8,
+}
+impl Offer { pub const INIT_SPACE: usize = 32 + 8 + Currency::SIZE + 8 + 2 + 2 + (4 + MAX_ALLOWED_CLASSES) + 1 + 8 + 8 + 8 + 1 + 1 + 32 + 4 + 1 + 1 + 1 + 1 + 8 + 32 + (1 + 32) + (4 + 32 * MAX_PENDING_REQUESTS) + 4 + 8 + 8 + (1 + 4) + 1; }
+
+// lobby summary of an open offer, kept on an OfferRegistry page
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct OfferListing {
+    pub offer: Pubkey,
+    pub creator: Pubkey,
+    pub currency: Currency,
+    pub stake: u64,
+    pub min_level: u16,
+    pub max_level: u16,
+    pub min_mmr: u64,
+    pub max_mmr: u64,
+    pub created_at: i64,
+}
+impl OfferListing {
+    pub const SIZE: usize = 32 + 32 + Currency::SIZE + 8 + 2 + 2 + 8 + 8 + 8;
+
+    pub fn of(offer: &Account<Offer>) -> Self {
+        OfferListing {
+            offer: offer.key(),
+            creator: offer.creator,
+            currency: offer.currency.clone(),
+            stake: offer.stake_amount,
+            min_level: offer.min_level,
+            max_level: offer.max_level,
+            min_mmr: offer.min_mmr,
+            max_mmr: offer.max_mmr,
+            created_at: offer.created_at,
+        }
+    }
+}
+
+#[account]
+pub struct OfferRegistry {
+    pub page: u32,
+    pub listings: Vec<OfferListing>,
+    pub bump: u8,
+}
+impl OfferRegistry { pub const INIT_SPACE: usize = 4 + (4 + OFFER_REGISTRY_PAGE_SIZE * OfferListing::SIZE) + 1; }
+
+#[account]
+pub struct Request {
+    pub offer: Pubkey,
+    pub challenger: Pubkey,
+    pub character: Pubkey,
+    pub offered_stake: u64,
+    pub created_at: i64,
+    pub status: JoinStatus,
+    pub abilities: Vec<Ability>, // challenger's loadout at join; empty = class signature ability
+    pub bump: u8,
+}
+impl Request { pub const INIT_SPACE: usize = 32 + 32 + 32 + 8 + 8 + 1 + (4 + MAX_LOADOUT_ABILITIES) + 1; }
+
+#[account]
+pub struct Battle {
+    pub battle_id: u64,
+    pub player1: Pubkey,
+    pub player2: Pubkey,
+    pub start_ts: i64,
+    pub current_turn: u8,
+    pub turn_number: u64,
+    pub player1_health: u64,
+    pub player2_health: u64,
+    pub state: BattleState,
+    pub player1_stance: StanceType,
+    pub player2_stance: StanceType,
+    pub created_at: i64,
+    pub inactivity_timeout: i64,
+    pub last_action_ts: i64,
+    pub winner: Option<Pubkey>,
+    pub player1_dot_damage: u64,
+    pub player2_dot_damage: u64,
+    pub player1_dot_turns: u8,
+    pub player2_dot_turns: u8,
+    pub player1_reflection: u16,
+    pub player2_reflection: u16,
+    pub player1_miss_count: u16,
+    pub player2_miss_count: u16,
+    pub last_entropy_index: u64,
+    // mutual pause: pending consent, freeze start, pause time used so far
+    pub pause_requested_by: Option<Pubkey>,
+    pub paused_at: i64,
+    pub total_paused_secs: i64,
+    pub player1_stake: u64,
+    pub player2_stake: u64,
+    pub settled: bool,
+    // ready check
+    pub player1_ready: bool,
+    pub player2_ready: bool,
+    pub ready_deadline: i64,
+    pub bonus_pot: u64,
+    pub player1_class: Option<CharacterClass>,
+    pub player2_class: Option<CharacterClass>,
+    // where the escrow went when the battle was settled (finalize / no-show / abandon)
+    pub settlement: Settlement,
+    pub player1_character: Pubkey,
+    pub player2_character: Pubkey,
+    // turn actions: guard from last turn's defend (bps of the next hit), own-turn cooldowns, item charges left
+    pub player1_guard_bps: u16,
+    pub player2_guard_bps: u16,
+    pub player1_defend_cooldown: u8,
+    pub player2_defend_cooldown: u8,
+    pub player1_heal_cooldown: u8,
+    pub player2_heal_cooldown: u8,
+    pub player1_items: u8,
+    pub player2_items: u8,
+    // active status effects per player (at most MAX_STATUS_EFFECTS each)
+    pub player1_effects: Vec<StatusEffect>,
+    pub player2_effects: Vec<StatusEffect>,
+    // best-of-N: knockouts needed to take the battle, knockouts so far, current round (1-based)
+    pub rounds_to_win: u8,
+    pub player1_round_wins: u8,
+    pub player2_round_wins: u8,
+    pub round_number: u8,
+    pub max_turns: u16, // copied from Config at approve; 0 = unlimited
+    // mutual draw: who offered (pending until the opponent accepts), and whether the battle ended that way
+    pub pending_draw: Option<Pubkey>,
+    pub mutual_draw: bool,
+    // chess clock: seconds left per player (only when time_control)
+    pub time_control: bool,
+    pub player1_time_bank: i64,
+    pub player2_time_bank: i64,
+    // commit-reveal: hash(stance, salt) per player for turn commit_turn, and whether it has been opened
+    pub commit_reveal: bool,
+    pub commit_turn: u64,
+    pub player1_commitment: Option<[u8; 32]>,
+    pub player2_commitment: Option<[u8; 32]>,
+    pub player1_revealed: bool,
+    pub player2_revealed: bool,
+    // ability loadouts frozen at approve, with own-turn cooldowns per loadout slot
+    pub player1_abilities: Vec<Ability>,
+    pub player2_abilities: Vec<Ability>,
+    pub player1_ability_cooldowns: [u8; MAX_LOADOUT_ABILITIES],
+    pub player2_ability_cooldowns: [u8; MAX_LOADOUT_ABILITIES],
+    pub arena: ArenaModifier,
+    pub player1_heals: u8, // heal actions left this battle
+    pub player2_heals: u8,
+    pub player1_stamina: u16,
+    pub player2_stamina: u16,
+    // character NFTs frozen at ready-up (battle PDA seeds need the offer nonce to sign the thaw)
+    pub offer_nonce: u64,
+    pub player1_nft_frozen: bool,
+    pub player2_nft_frozen: bool,
+    // async mode: strategies revealed against player{1,2}_commitment, cycled by resolve_async_battle
+    pub async_mode: bool,
+    pub player1_strategy: Vec<AsyncMove>,
+    pub player2_strategy: Vec<AsyncMove>,
+    // skip_idle_turn settings snapshotted at approve, and consecutive skips per player
+    pub turn_deadline: i64,
+    pub skip_penalty_bps: u16,
+    pub max_skips: u8,
+    pub player1_skips: u8,
+    pub player2_skips: u8,
+    // reconnect grace from the offer; a player's grace is used up once they come back from a timeout
+    pub reconnect_grace: i64,
+    pub player1_grace_used: bool,
+    pub player2_grace_used: bool,
+    pub has_turn_log: bool, // TurnLog opened: every turn must append to it
+    pub bounty_pot: u64, // crowd-funded via add_bounty; unlike bonus_pot it pays the protocol fee
+    pub settled_at: i64, // close_battle waits BATTLE_CLOSE_DELAY_SECS past this
+    pub forfeited: bool, // ended by a forfeit path: settle_forfeit pays out without player signatures
+    pub elo_k_factor: u16, // Config.elo_k_factor at creation
+    // Config season window at creation; a result outside it doesn't move MMR (both 0 = no season yet)
+    pub rated_from: i64,
+    pub rated_until: i64,
+    pub bump: u8,
+}
+impl Battle { pub const INIT_SPACE: usize = 8 + 32 + 32 + 8 + 1 + 8 + 8 + 8 + 1 + 1 + 1 + 8 + 8 + 8 + 32 + 8 + 8 + 1 + 1 + 2 + 2 + 2 + 8 + (1 + 32) + 8 + 8 + 8 + 8 + 1 + 1 + 1 + 8 + 8 + 2 + 2 + Settlement::SIZE + 32 + 32 + 2 + 2 + 1 + 1 + 1 + 1 + 1 + 1 + (4 + MAX_STATUS_EFFECTS * StatusEffect::INIT_SPACE) * 2 + 1 + 1 + 1 + 1 + 2 + (1 + 32) + 1 + 1 + 8 + 8 + 1 + 8 + (1 + 32) * 2 + 1 + 1 + (4 + MAX_LOADOUT_ABILITIES) * 2 + MAX_LOADOUT_ABILITIES * 2 + 1 + 1 + 1 + 2 + 2 + 8 + 1 + 1 + 1 + (4 + MAX_ASYNC_STRATEGY * AsyncMove::SIZE) * 2 + 1 + 8 + 2 + 1 + 1 + 1 + 8 + 1 + 1 + 1 + 8 + 8 + 1 + 2 + 8 + 8 + 1; }
+
+// quick-play queue entry, one per wallet; the stake sits on the entry's private offer
+#[account]
+pub struct MatchQueue {
+    pub player: Pubkey,
+    pub character: Pubkey,
+    pub offer: Pubkey,
+    pub currency: Currency,
+    pub stake: u64,
+    pub mmr: u64, // Progression.mmr when queued
+    pub mmr_band: u64, // widest MMR gap this player accepts
+    pub created_at: i64,
+    pub bump: u8,
+}
+impl MatchQueue { pub const INIT_SPACE: usize = 32 + 32 + 32 + Currency::SIZE + 8 + 8 + 8 + 8 + 1; }
+
+// pending rematch of old_battle; the proposer's stake sits on the private offer's escrow until accepted
+#[account]
+pub struct Rematch {
+    pub old_battle: Pubkey,
+    pub offer: Pubkey,
+    pub proposer: Pubkey,
+    pub opponent: Pubkey,
+    pub proposer_character: Pubkey,
+    pub opponent_character: Pubkey,
+    pub opponent_stake: u64,
+    pub created_at: i64,
+    pub accepted: bool,
+    pub bump: u8,
+}
+impl Rematch { pub const INIT_SPACE: usize = 32 * 6 + 8 + 8 + 1 + 1; }
+
+// 2v2 battle: seats 0-1 are team A, 2-3 team B; SOL stakes sit on [b"sol_escrow", team_battle]
+#[account]
+pub struct TeamBattle {
+    pub creator: Pubkey,
+    pub nonce: u64,
+    pub stake_per_player: u64,
+    pub members: [TeamMember; TEAM_SEATS],
+    pub state: BattleState,
+    pub turn_cursor: u8, // index into TEAM_TURN_ORDER
+    pub turn_number: u64,
+    pub last_entropy_index: u64,
+    pub inactivity_timeout: i64,
+    pub last_action_ts: i64,
+    pub created_at: i64,
+    pub winner_team: Option<u8>,
+    pub settled: bool,
+    pub bump: u8,
+}
+impl TeamBattle {
+    pub const INIT_SPACE: usize = 32 + 8 + 8 + TeamMember::SIZE * TEAM_SEATS + 1 + 1 + 8 + 8 + 8 + 8 + 8 + (1 + 1) + 1 + 1;
+
+    pub fn team_down(&self, team: usize) -> bool {
+        self.members[team * TEAM_SIZE..(team + 1) * TEAM_SIZE].iter().all(|m| m.health == 0)
+    }
+
+    // moves to the next seat in TEAM_TURN_ORDER that is still alive
+    pub fn advance_turn(&mut self) {
+        for _ in 0..TEAM_SEATS {
+            self.turn_cursor = (self.turn_cursor + 1) % TEAM_SEATS as u8;
+            if self.members[TEAM_TURN_ORDER[self.turn_cursor as usize]].health > 0 { break; }
+        }
+    }
+}
+
+// PvE raid boss; the reward pool sits on [b"sol_escrow", boss]
+#[account]
+pub struct Boss {
+    pub boss_id: u64,
+    pub max_hp: u64,
+    pub health: u64,
+    pub damage_min: u16,
+    pub damage_max: u16,
+    pub defense: u16,
+    pub script: Vec<u16>, // move damage bps (bounded by MAX_BOSS_SCRIPT), one picked from entropy per retaliation
+    pub reward_pool: u64,
+    pub last_entropy_index: u64,
+    pub defeated_at: i64, // 0 while alive
+    pub bump: u8,
+}
+impl Boss { pub const INIT_SPACE: usize = 8 + 8 + 8 + 2 + 2 + 2 + (4 + 2 * MAX_BOSS_SCRIPT) + 8 + 8 + 8 + 1; }
+
+// One character's raid progress against one boss
+#[account]
+pub struct RaidEntry {
+    pub boss: Pubkey,
+    pub character: Pubkey,
+    pub damage_dealt: u64,
+    pub health: u64, // raid-only HP, starts at the character's max_hp
+    pub attacks: u32,
+    pub next_attack_at: i64,
+    pub claimed: bool,
+    pub bump: u8,
+}
+impl RaidEntry { pub const INIT_SPACE: usize = 32 + 32 + 8 + 8 + 4 + 8 + 1 + 1; }
+
+#[account]
+pub struct Achievement {
+    pub nft_mint: Pubkey,
+    pub kind: AchievementKind,
+    pub unlocked_at: i64,
+    pub bump: u8,
+}
+impl Achievement { pub const INIT_SPACE: usize = 32 + 1 + 8 + 1; }
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct LeaderboardEntry {
+    pub nft_mint: Pubkey,
+    pub mmr: u64,
+}
+impl LeaderboardEntry { pub const SIZE: usize = 32 + 8; }
+
+// top characters by MMR, highest first
+#[account]
+pub struct Leaderboard {
+    pub entries: Vec<LeaderboardEntry>,
+    pub bump: u8,
+}
+impl Leaderboard { pub const INIT_SPACE: usize = (4 + LEADERBOARD_SIZE * LeaderboardEntry::SIZE) + 1; }
+
+#[account]
+pub struct Season {
+    pub id: u32,
+    pub start_ts: i64,
+    pub end_ts: i64,
+    pub tier_mmr: [u64; SEASON_REWARD_TIERS], // final MMR needed for tier 1, 2, 3
+    pub tier_rewards: [u64; SEASON_REWARD_TIERS], // lamports per character at each tier
+    pub reward_pool: u64,
+    pub claimed: u64,
+    pub results: u32, // SeasonResults recorded
+    pub tier_counts: [u32; SEASON_REWARD_TIERS], // recorded results per tier; sizes the pro-rata payout
+    pub bump: u8,
+}
+impl Season { pub const INIT_SPACE: usize = 4 + 8 + 8 + 8 * SEASON_REWARD_TIERS + 8 * SEASON_REWARD_TIERS + 8 + 8 + 4 + 4 * SEASON_REWARD_TIERS + 1; }
+
+// One character's final standing in one season; its existence gates the seasonal reward
+#[account]
+pub struct SeasonResult {
+    pub season: u32,
+    pub nft_mint: Pubkey,
+    pub final_mmr: u64,
+    pub tier: u8, // 0 = below every tier threshold
+    pub reward_claimed: bool,
+    pub bump: u8,
+}
+impl SeasonResult { pub const INIT_SPACE: usize = 4 + 32 + 8 + 1 + 1 + 1; }
+
+// Ring buffer of a battle's last TURN_LOG_CAPACITY turns; entry i sits at (turn index % capacity)
+#[account]
+pub struct TurnLog {
+    pub battle: Pubkey,
+    pub total_turns: u64, // entries ever written
+    pub entries: Vec<TurnLogEntry>,
+    pub bump: u8,
+}
+impl TurnLog { pub const INIT_SPACE: usize = 32 + 8 + (4 + TURN_LOG_CAPACITY * TurnLogEntry::SIZE) + 1; }
+
+#[account]
+pub struct TutorialProgress {
+    pub player: Pubkey,
+    pub step: u8,
+    pub turn: u32,
+    pub player_health: u64,
+    pub ai_health: u64,
+    pub last_base: u64,
+    pub combo_count: u8,
+    pub completed: bool,
+    pub reward_claimed: bool,
+    pub bump: u8,
+}
+impl TutorialProgress { pub const INIT_SPACE: usize = 32 + 1 + 4 + 8 + 8 + 8 + 1 + 1 + 1 + 1; }
+
+// Zero-stake practice duel against a program AI that mirrors the character's stats
+#[account]
+pub struct PracticeBattle {
+    pub player: Pubkey,
+    pub character: Pubkey,
+    pub player_health: u64,
+    pub ai_health: u64,
+    pub ai_stance: StanceType, // the AI defends with its last stance
+    pub turn: u32,
+    pub last_entropy_index: u64,
+    pub active: bool,
+    pub last_reward_at: i64, // kept across restarts, rate-limits practice XP
     pub bump: u8,
 }
-impl Character {
-    pub const INIT_SPACE: usize = 32 + 1 + 4 + 4 + 2 + 2 + 2 + 4 + 2 + 1 + 2 + 1 + 1 + 2 + 2 + 2 + 1 + 8 + 1;
+impl PracticeBattle { pub const INIT_SPACE: usize = 32 + 32 + 8 + 8 + 1 + 4 + 8 + 1 + 8 + 1; }
+
+// Aggregate results for one class (kind 0) or one stance (kind 1). Stance stats count the stance each
+// player was in when the battle ended.
+#[account]
+pub struct WinrateStats {
+    pub kind: u8,
+    pub key: u8,
+    pub battles: u64,
+    pub wins: u64,
+    pub losses: u64,
+    pub draws: u64,
+    pub bump: u8,
 }
+impl WinrateStats { pub const INIT_SPACE: usize = 1 + 1 + 8 + 8 + 8 + 8 + 1; }
 
+// Per-class damage multiplier (bps, 10_000 = neutral) maintained by the seasonal rebalance
 #[account]
-pub struct Progression {
-    pub nft_mint: Pubkey,
-    pub xp: u64,
-    pub level: u16,
-    pub mmr: u64,
-    pub last_played: i64,
+pub struct ClassBalance {
+    pub class: CharacterClass,
+    pub damage_bps: u16,
+    pub season: u32,
     pub bump: u8,
 }
-impl Progression { pub const INIT_SPACE: usize = 32 + 8 + 2 + 8 + 8 + 1; }
+impl ClassBalance { pub const INIT_SPACE: usize = 1 + 2 + 4 + 1; }
 
 #[account]
-pub struct Offer {
-    pub creator: Pubkey,
-    pub offer_nonce: u64,
-    pub currency: Currency,
-    pub stake_amount: u64,
-    pub min_level: u16,
-    pub max_level: u16,
-    pub allowed_classes: Vec<CharacterClass>,
-    pub auto_approve: bool,
-    pub start_ts: i64,
-    pub inactivity_timeout: i64,
-    pub created_at: i64,
-    pub is_active: bool,
+pub struct Guild {
+    pub authority: Pubkey,
+    pub guild_id: u64,
+    pub contribution_bps: u16,
+    pub points: u64,
+    pub member_count: u32,
+    pub perk_tier: u8,
     pub bump: u8,
 }
-impl Offer { pub const INIT_SPACE: usize = 32 + 8 + Currency::SIZE + 8 + 2 + 2 + 4 + 1 + 8 + 8 + 8 + 1 + 1; }
+impl Guild { pub const INIT_SPACE: usize = 32 + 8 + 2 + 8 + 4 + 1 + 1; }
 
 #[account]
-pub struct Request {
-    pub offer: Pubkey,
-    pub challenger: Pubkey,
-    pub character: Pubkey,
-    pub offered_stake: u64,
-    pub created_at: i64,
-    pub status: JoinStatus,
+pub struct GuildMember {
+    pub player: Pubkey,
+    pub guild: Pubkey,
+    pub points_contributed: u64,
+    pub joined_at: i64,
     pub bump: u8,
 }
-impl Request { pub const INIT_SPACE: usize = 32 + 32 + 32 + 8 + 8 + 1 + 1; }
+impl GuildMember { pub const INIT_SPACE: usize = 32 + 32 + 8 + 8 + 1; }
 
 #[account]
-pub struct Battle {
-    pub battle_id: u64,
-    pub player1: Pubkey,
-    pub player2: Pubkey,
-    pub start_ts: i64,
-    pub current_turn: u8,
-    pub turn_number: u64,
-    pub player1_health: u64,
-    pub player2_health: u64,
-    pub state: BattleState,
-    pub player1_stance: StanceType,
-    pub player2_stance: StanceType,
-    pub created_at: i64,
-    pub inactivity_timeout: i64,
-    pub last_action_ts: i64,
-    pub winner: Option<Pubkey>,
-    pub player1_dot_damage: u64,
-    pub player2_dot_damage: u64,
-    pub player1_dot_turns: u8,
-    pub player2_dot_turns: u8,
-    pub player1_reflection: u16,
-    pub player2_reflection: u16,
-    pub player1_miss_count: u16,
-    pub player2_miss_count: u16,
-    pub last_entropy_index: u64,
+pub struct GuildContribution {
+    pub battle: Pubkey,
+    pub player: Pubkey,
+    pub points: u64,
     pub bump: u8,
 }
-impl Battle { pub const INIT_SPACE: usize = 8 + 32 + 32 + 8 + 1 + 8 + 8 + 8 + 1 + 1 + 1 + 8 + 8 + 8 + 32 + 8 + 8 + 1 + 1 + 2 + 2 + 2 + 8 + 1; }
+impl GuildContribution { pub const INIT_SPACE: usize = 32 + 32 + 8 + 1; }
 
 // ------------------------
 // ENUMS & SMALL TYPES
@@ -1131,11 +5618,91 @@ impl Battle { pub const INIT_SPACE: usize = 8 + 32 + 32 + 8 + 1 + 8 + 8 + 8 + 1
 pub enum CharacterClass { Warrior=0, Assassin=1, Mage=2, Tank=3, Trickster=4 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
-pub enum BattleState { Waiting=0, Active=1, Finished=2 }
+pub enum BattleState { Waiting=0, Active=1, Finished=2, Paused=3 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
 pub enum StanceType { Balanced=0, Aggressive=1, Defensive=2, Berserker=3, Counter=4 }
 
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
+pub enum TurnAction { Attack=0, Defend=1, Heal=2, Item=3 }
+
+// WinStreak: ACHIEVEMENT_WIN_STREAK wins in a row; Veteran: ACHIEVEMENT_VETERAN_BATTLES rated battles
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
+pub enum AchievementKind { FirstWin=0, WinStreak=1, FlawlessVictory=2, Veteran=3 }
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
+pub enum ArenaModifier { Neutral=0, LowGravity=1, Bloodbath=2, Frenzy=3, Fortress=4 }
+impl ArenaModifier {
+    pub fn from_roll(roll: u64) -> ArenaModifier {
+        match roll { 1 => ArenaModifier::LowGravity, 2 => ArenaModifier::Bloodbath, 3 => ArenaModifier::Frenzy, 4 => ArenaModifier::Fortress, _ => ArenaModifier::Neutral }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
+pub enum Element { Fire=0, Water=1, Earth=2, Air=3 }
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
+pub enum StatKind { Hp=0, Damage=1, Crit=2, Dodge=3, Defense=4, ArmorPen=5, CritResist=6 }
+
+// Ability catalogue: the former class specials plus a generic quick strike
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
+pub enum Ability { Cleave=0, Backstab=1, Ignite=2, Bulwark=3, Hex=4, QuickStrike=5 }
+impl Ability {
+    // own turns until the ability can be used again
+    pub fn cooldown(self) -> u8 {
+        match self { Ability::Cleave => 3, Ability::Backstab => 4, Ability::Ignite => 3, Ability::Bulwark => 4, Ability::Hex => 2, Ability::QuickStrike => 2 }
+    }
+    // damage multiplier when played from an async strategy (no status side effects there)
+    pub fn burst_fp(self) -> u128 {
+        match self { Ability::Cleave | Ability::Backstab => FP_SCALE * 3, Ability::Hex => FP_SCALE * 2, Ability::QuickStrike => FP_SCALE * 3 / 2, Ability::Ignite | Ability::Bulwark => FP_SCALE }
+    }
+    // what a character without a Loadout brings
+    pub fn signature(class: CharacterClass) -> Ability {
+        match class {
+            CharacterClass::Warrior => Ability::Cleave,
+            CharacterClass::Assassin => Ability::Backstab,
+            CharacterClass::Mage => Ability::Ignite,
+            CharacterClass::Tank => Ability::Bulwark,
+            CharacterClass::Trickster => Ability::Hex,
+        }
+    }
+}
+
+// action: TurnAction as u8, or TURN_LOG_STUNNED; rolls: attack = [base, crit, dodge], other actions = [action roll, 0, 0]
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct TurnLogEntry {
+    pub turn_number: u64,
+    pub actor: u8, // 1 or 2
+    pub action: u8,
+    pub stance: u8,
+    pub ability: Option<u8>,
+    pub rolls: [u16; 3],
+    pub damage: u64, // damage dealt, or HP healed / guard bps for non-attack actions
+    pub is_crit: bool,
+    pub dodged: bool,
+    pub player1_health: u64,
+    pub player2_health: u64,
+}
+impl TurnLogEntry { pub const SIZE: usize = 8 + 1 + 1 + 1 + 2 + 2 * 3 + 8 + 1 + 1 + 8 + 8; }
+
+// One step of an async strategy: stance, plus an optional loadout slot to fire when it's off cooldown
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct AsyncMove {
+    pub stance: StanceType,
+    pub ability: Option<u8>,
+}
+impl AsyncMove { pub const SIZE: usize = 1 + 2; }
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
+pub enum StatusKind { Stun=0, Poison=1, Shield=2, Buff=3, Debuff=4 }
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    pub magnitude: u16,
+    pub turns_left: u8,
+}
+
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
 pub enum JoinStatus { Pending=0, Approved=1, Rejected=2, Withdrawn=3 }
 
@@ -1146,6 +5713,91 @@ pub enum Currency {
 }
 impl Currency { pub const SIZE: usize = 1 + 32; } // approximate
 
+// Settlement breakdown: fee is the protocol fee (or the crank fee on abandon), winner_payout includes bonus_paid,
+// draw_to_treasury is the post-fee pot of a no-winner finalize, refunds come from no-show / abandon
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default)]
+pub struct Settlement {
+    pub fee: u64,
+    pub winner_payout: u64,
+    pub bonus_paid: u64,
+    pub draw_to_treasury: u64,
+    pub refund_player1: u64,
+    pub refund_player2: u64,
+}
+impl Settlement { pub const SIZE: usize = 8 * 6; }
+
+// View return data
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct BattleSummary {
+    pub battle_id: u64,
+    pub state: BattleState,
+    pub current_turn: u8,
+    pub turn_number: u64,
+    pub player1_health: u64,
+    pub player2_health: u64,
+    pub winner: Option<Pubkey>,
+    pub secs_until_timeout: i64,
+    pub stake_total: u64,
+    pub bonus_pot: u64,
+    pub bounty_pot: u64,
+    pub settled: bool,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct CharacterCombatStats {
+    pub nft_mint: Pubkey,
+    pub base_class: CharacterClass,
+    pub level: u16,
+    pub max_hp: u32,
+    pub damage_min: u64,
+    pub damage_max: u64,
+    pub crit_bps: u16,
+    pub crit_multiplier_fp: u32,
+    pub dodge_bps: u16,
+    pub defense: u16,
+    pub special_cooldown: u8,
+    pub combo_count: u8,
+}
+
+// Equipment modifiers: flat damage / flat defense / crit and dodge bps, summed over equipped slots
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, Debug)]
+pub struct ItemModifiers {
+    pub attack: i16,
+    pub defense: i16,
+    pub crit_bps: i16,
+    pub dodge_bps: i16,
+}
+impl ItemModifiers { pub const SIZE: usize = 2 * 4; }
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, Debug)]
+pub struct EquipSlot {
+    pub item_mint: Pubkey, // default = empty slot
+    pub mods: ItemModifiers,
+}
+impl EquipSlot { pub const SIZE: usize = 32 + ItemModifiers::SIZE; }
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct TeamMember {
+    pub player: Pubkey, // default = empty seat
+    pub character: Pubkey,
+    pub health: u64,
+    pub stance: StanceType, // last stance used, defends with it until the next own turn
+}
+impl TeamMember { pub const SIZE: usize = 32 + 32 + 8 + 1; }
+
+// Per-class base stats held in a ClassRegistry
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct ClassStats {
+    pub max_hp: u32,
+    pub base_damage_min: u16,
+    pub base_damage_max: u16,
+    pub crit_bps: u16,
+    pub dodge_bps: u16,
+    pub defense: u16,
+    pub signature: Ability, // default ability for battles without a Loadout
+}
+impl ClassStats { pub const SIZE: usize = 4 + 2 + 2 + 2 + 2 + 2 + 1; }
+
 // Trait bundle
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
 pub struct TraitBundle {
@@ -1154,34 +5806,140 @@ pub struct TraitBundle {
     pub defense_bps: i16,
     pub crit_bps: i16,
     pub nonce: i64,
+    pub armor_pen_bps: i16,
+    pub crit_resist_bps: i16,
 }
 
 // ------------------------
 // EVENTS
 // ------------------------
 #[event] pub struct ConfigCreated { pub config: Pubkey, pub admin: Pubkey }
+#[event] pub struct VaultDeposited { pub vault: Pubkey, pub owner: Pubkey, pub amount: u64, pub spl: bool }
+#[event] pub struct VaultWithdrawn { pub vault: Pubkey, pub owner: Pubkey, pub amount: u64, pub spl: bool }
+#[event] pub struct EscrowVaultCreated { pub escrow: Pubkey, pub mint: Pubkey, pub escrow_ata: Pubkey }
+#[event] pub struct RateLimitUpdated { pub config: Pubkey, pub max_actions_per_hour: u16 }
+#[event] pub struct MaxTurnsUpdated { pub config: Pubkey, pub max_turns: u16 }
+#[event] pub struct TimeBankUpdated { pub config: Pubkey, pub time_bank_secs: i64 }
+#[event] pub struct LevelHandicapUpdated { pub config: Pubkey, pub level_gap: u16, pub bps_per_level: u16, pub max_bps: u16 }
+#[event] pub struct StanceUnlocksUpdated { pub config: Pubkey, pub berserker_level: u16, pub counter_level: u16 }
+#[event] pub struct EloKFactorUpdated { pub config: Pubkey, pub k_factor: u16 }
+#[event] pub struct ForfeitKeeperUpdated { pub config: Pubkey, pub keeper_bps: u16 }
+#[event] pub struct SkipTurnUpdated { pub config: Pubkey, pub turn_deadline_secs: i64, pub penalty_bps: u16, pub max_skips: u8 }
+#[event] pub struct HandicapApplied { pub battle: Pubkey, pub attacker: Pubkey, pub level_gap: u16, pub reduction_bps: u16 }
+#[event] pub struct TimeForfeited { pub battle: Pubkey, pub loser: Pubkey, pub winner: Pubkey }
 #[event] pub struct EntropyPoolCreated { pub pool: Pubkey, pub vrf_oracle: Pubkey }
 #[event] pub struct SeedBatchRefilled { pub pool: Pubkey, pub added: u64, pub total_available: u64 }
 #[event] pub struct ProgressionCreated { pub nft_mint: Pubkey }
+#[event] pub struct ProgressionMigrated { pub nft_mint: Pubkey, pub old_space: u32 }
 #[event] pub struct CharacterCreated { pub nft_mint: Pubkey, pub owner: Pubkey }
+#[event] pub struct CharacterRetired { pub nft_mint: Pubkey, pub owner: Pubkey, pub level: u16, pub rarity: u8, pub materials: u64 }
+#[event] pub struct CharacterUnlocked { pub nft_mint: Pubkey, pub battle: Pubkey }
+#[event] pub struct CharacterProfileSet { pub nft_mint: Pubkey, pub name: String }
+#[event] pub struct CharactersFused { pub parent_a: Pubkey, pub parent_b: Pubkey, pub child_mint: Pubkey, pub owner: Pubkey, pub base_class: CharacterClass }
+#[event] pub struct CharactersMerged { pub source_mint: Pubkey, pub target_mint: Pubkey, pub owner: Pubkey, pub xp_carried: u64, pub target_level: u16 }
+#[event] pub struct ClassStatsSet { pub class: CharacterClass, pub stats: ClassStats }
+#[event] pub struct EquipmentRegistered { pub item_mint: Pubkey, pub mods: ItemModifiers }
+#[event] pub struct ItemEquipped { pub nft_mint: Pubkey, pub item_mint: Pubkey, pub slot: u8 }
+#[event] pub struct ItemUnequipped { pub nft_mint: Pubkey, pub item_mint: Pubkey, pub slot: u8 }
 #[event] pub struct TraitApplied { pub nft_mint: Pubkey, pub by: Pubkey }
+#[event] pub struct StatPointsSpent { pub nft_mint: Pubkey, pub stat: u8, pub points: u16, pub remaining: u16 }
+#[event] pub struct LoadoutSet { pub nft_mint: Pubkey, pub abilities: Vec<u8> }
 #[event] pub struct OfferCreated { pub offer: Pubkey, pub creator: Pubkey, pub stake: u64 }
 #[event] pub struct JoinRequested { pub offer: Pubkey, pub request: Pubkey, pub challenger: Pubkey, pub stake: u64 }
 #[event] pub struct RequestWithdrawn { pub request: Pubkey, pub by: Pubkey }
+#[event] pub struct ChallengerRejected { pub offer: Pubkey, pub request: Pubkey, pub challenger: Pubkey, pub refunded: u64 }
 #[event] pub struct OfferCancelled { pub offer: Pubkey, pub by: Pubkey }
+#[event] pub struct OfferClosed { pub offer: Pubkey, pub creator: Pubkey }
+#[event] pub struct RequestClosed { pub request: Pubkey, pub challenger: Pubkey }
+#[event] pub struct OfferListed { pub offer: Pubkey, pub page: u32 }
+#[event] pub struct OfferUnlisted { pub offer: Pubkey, pub page: u32 }
+#[event] pub struct OfferUpdated { pub offer: Pubkey, pub min_level: u16, pub max_level: u16, pub start_ts: i64, pub old_stake: u64, pub stake: u64 }
+#[event] pub struct OfferRelisted { pub offer: Pubkey, pub creator: Pubkey, pub stake: u64, pub relist_count: u32 }
+#[event] pub struct QueueEntered { pub entry: Pubkey, pub player: Pubkey, pub character: Pubkey, pub stake: u64, pub mmr: u64, pub mmr_band: u64 }
+#[event] pub struct QueueLeft { pub entry: Pubkey, pub player: Pubkey }
+#[event] pub struct QueueMatched { pub battle: Pubkey, pub player1: Pubkey, pub player2: Pubkey, pub mmr_gap: u64 }
+#[event] pub struct RematchProposed { pub rematch: Pubkey, pub old_battle: Pubkey, pub offer: Pubkey, pub proposer: Pubkey, pub opponent: Pubkey, pub stake: u64, pub opponent_stake: u64 }
+#[event] pub struct RematchAccepted { pub rematch: Pubkey, pub battle: Pubkey, pub old_battle: Pubkey }
+#[event] pub struct RematchCancelled { pub rematch: Pubkey, pub old_battle: Pubkey }
+#[event] pub struct ArenaRolled { pub battle: Pubkey, pub arena: u8 }
 #[event] pub struct BattleCreated { pub battle: Pubkey, pub player1: Pubkey, pub player2: Pubkey, pub first_turn: u8, pub stake_total: u64 }
+#[event] pub struct BattleClosed { pub battle: Pubkey, pub rent_receiver: Pubkey }
 #[event] pub struct BattleForfeited { pub battle: Pubkey, pub winner: Pubkey }
+#[event] pub struct ForfeitKeeperPaid { pub battle: Pubkey, pub keeper: Pubkey, pub amount: u64 }
+#[event] pub struct TurnSkipped { pub battle: Pubkey, pub player: Pubkey, pub skips: u8, pub hp_penalty: u64 }
 #[event] pub struct BattleEnded { pub battle: Pubkey, pub winner: Option<Pubkey> }
+#[event] pub struct AchievementUnlocked { pub nft_mint: Pubkey, pub kind: AchievementKind, pub unlocked_at: i64 }
+#[event] pub struct LeaderboardUpdated { pub leaderboard: Pubkey, pub nft_mint: Pubkey, pub mmr: u64, pub rank: Option<u16> }
+#[event] pub struct MmrUpdated { pub battle: Pubkey, pub nft_mint: Pubkey, pub old_mmr: u64, pub new_mmr: u64 }
 #[event] pub struct DamageClamped { pub battle: Pubkey, pub attacker: Pubkey }
 #[event] pub struct ComboApplied { pub battle: Pubkey, pub attacker: Pubkey, pub combo: u8, pub added: u64 }
 #[event] pub struct SpecialUsed { pub battle: Pubkey, pub attacker: Pubkey, pub special: u8 }
+#[event] pub struct ActionResolved { pub battle: Pubkey, pub player: Pubkey, pub action: u8, pub amount: u64 }
+#[event] pub struct GuardApplied { pub battle: Pubkey, pub defender: Pubkey, pub blocked: u64 }
+#[event] pub struct StatusApplied { pub battle: Pubkey, pub target: Pubkey, pub kind: u8, pub magnitude: u16, pub turns: u8 }
+#[event] pub struct StatusTicked { pub battle: Pubkey, pub player: Pubkey, pub poison_damage: u64, pub stunned: bool }
+#[event] pub struct ShieldAbsorbed { pub battle: Pubkey, pub defender: Pubkey, pub absorbed: u64 }
+#[event] pub struct TurnLimitReached { pub battle: Pubkey, pub turn_number: u64, pub winner: Option<Pubkey> }
+#[event] pub struct RoundEnded { pub battle: Pubkey, pub round: u8, pub player1_round_wins: u8, pub player2_round_wins: u8 }
+#[event] pub struct DotTicked { pub battle: Pubkey, pub player: Pubkey, pub damage: u64, pub turns_left: u8, pub health: u64 }
 #[event] pub struct AttackMissed { pub battle: Pubkey, pub attacker: Pubkey, pub defender: Pubkey }
 #[event] pub struct ReflectionApplied { pub battle: Pubkey, pub defender: Pubkey, pub reflected: u64 }
 #[event] pub struct CounterApplied { pub battle: Pubkey, pub player: Pubkey, pub damage: u64 }
 #[event] pub struct SelfDamageApplied { pub battle: Pubkey, pub player: Pubkey, pub damage: u64 }
 #[event] pub struct LifeConsumed { pub character: Pubkey, pub remaining: u8 }
 #[event] pub struct TurnResolved { pub battle: Pubkey, pub turn_number: u64, pub attacker: Pubkey, pub defender: Pubkey, pub damage_dealt: u64, pub is_crit: bool }
-#[event] pub struct BattleSettled { pub battle: Pubkey, pub total_paid: u64 }
+#[event] pub struct BattleSettled { pub battle: Pubkey, pub winner: Option<Pubkey>, pub settlement: Settlement }
+#[event] pub struct TutorialStarted { pub player: Pubkey }
+#[event] pub struct TutorialTurnResolved { pub player: Pubkey, pub step: u8, pub damage: u64, pub ai_health: u64, pub combo: u8 }
+#[event] pub struct TutorialCompleted { pub player: Pubkey, pub turns: u32 }
+#[event] pub struct TutorialRewardClaimed { pub player: Pubkey, pub nft_mint: Pubkey, pub xp: u64 }
+#[event] pub struct PracticeStarted { pub player: Pubkey, pub nft_mint: Pubkey }
+#[event] pub struct PracticeTurnResolved { pub player: Pubkey, pub turn: u32, pub damage: u64, pub is_crit: bool, pub ai_stance: u8, pub ai_damage: u64, pub player_health: u64, pub ai_health: u64 }
+#[event] pub struct PracticeFinished { pub player: Pubkey, pub nft_mint: Pubkey, pub won: bool, pub turns: u32, pub xp: u64 }
+#[event] pub struct WinrateRecorded { pub stats: Pubkey, pub kind: u8, pub key: u8, pub battles: u64, pub wins: u64 }
+#[event] pub struct ClassBalanceAdjusted { pub class: CharacterClass, pub season: u32, pub battles: u64, pub winrate_bps: u64, pub old_damage_bps: u16, pub new_damage_bps: u16 }
+#[event] pub struct GuildCreated { pub guild: Pubkey, pub authority: Pubkey, pub contribution_bps: u16 }
+#[event] pub struct GuildJoined { pub guild: Pubkey, pub player: Pubkey }
+#[event] pub struct GuildLeft { pub guild: Pubkey, pub player: Pubkey }
+#[event] pub struct GuildPointsContributed { pub guild: Pubkey, pub player: Pubkey, pub battle: Pubkey, pub points: u64, pub guild_points: u64 }
+#[event] pub struct GuildPerkUnlocked { pub guild: Pubkey, pub tier: u8, pub fee_discount_bps: u16 }
+#[event] pub struct SpectatorReacted { pub battle: Pubkey, pub spectator: Pubkey, pub emote: u8, pub tip: u64, pub bonus_pot: u64 }
+#[event] pub struct BountyAdded { pub battle: Pubkey, pub contributor: Pubkey, pub amount: u64, pub bounty_pot: u64 }
+#[event] pub struct BonusFunded { pub battle: Pubkey, pub sponsor: Pubkey, pub amount: u64, pub bonus_pot: u64 }
+#[event] pub struct PauseRequested { pub battle: Pubkey, pub by: Pubkey }
+#[event] pub struct MoveCommitted { pub battle: Pubkey, pub player: Pubkey, pub turn_number: u64 }
+#[event] pub struct MoveRevealed { pub battle: Pubkey, pub player: Pubkey, pub turn_number: u64, pub stance: u8 }
+#[event] pub struct AsyncStrategyCommitted { pub battle: Pubkey, pub player: Pubkey }
+#[event] pub struct AsyncStrategyRevealed { pub battle: Pubkey, pub player: Pubkey, pub moves: u8 }
+#[event] pub struct AsyncBattleResolved { pub battle: Pubkey, pub turns: u16, pub winner: Option<Pubkey> }
+#[event] pub struct DrawOffered { pub battle: Pubkey, pub by: Pubkey }
+#[event] pub struct DrawAgreed { pub battle: Pubkey }
+#[event] pub struct BattlePaused { pub battle: Pubkey, pub paused_at: i64 }
+#[event] pub struct ResumeRequested { pub battle: Pubkey, pub by: Pubkey }
+#[event] pub struct LateShowForfeited { pub battle: Pubkey, pub no_show: Pubkey, pub winner: Pubkey }
+#[event] pub struct TurnLogOpened { pub battle: Pubkey, pub turn_log: Pubkey, pub from_turn: u64 }
+#[event] pub struct CharacterNftReleased { pub battle: Pubkey, pub nft_mint: Pubkey }
+#[event] pub struct PlayerReady { pub battle: Pubkey, pub player: Pubkey }
+#[event] pub struct BattleActivated { pub battle: Pubkey, pub first_turn: u8 }
+#[event] pub struct NoShowSettled { pub battle: Pubkey, pub player1_ready: bool, pub player2_ready: bool, pub paid_player1: u64, pub paid_player2: u64, pub penalty: u64 }
+#[event] pub struct BattleAbandoned { pub battle: Pubkey, pub cranker: Pubkey, pub refund_player1: u64, pub refund_player2: u64, pub crank_fee: u64 }
+#[event] pub struct BattleResumed { pub battle: Pubkey, pub paused_secs: i64, pub total_paused_secs: i64 }
+#[event] pub struct TeamBattleCreated { pub team_battle: Pubkey, pub creator: Pubkey, pub stake_per_player: u64 }
+#[event] pub struct TeamMemberJoined { pub team_battle: Pubkey, pub player: Pubkey, pub team: u8, pub seat: u8 }
+#[event] pub struct TeamMemberLeft { pub team_battle: Pubkey, pub player: Pubkey, pub seat: u8 }
+#[event] pub struct TeamBattleStarted { pub team_battle: Pubkey, pub first_team: u8 }
+#[event] pub struct TeamTurnResolved { pub team_battle: Pubkey, pub turn_number: u64, pub attacker_seat: u8, pub target_seat: u8, pub damage: u64, pub recoil: u64, pub is_crit: bool, pub dodged: bool }
+#[event] pub struct TeamBattleFinished { pub team_battle: Pubkey, pub winner_team: u8, pub by_timeout: bool }
+#[event] pub struct TeamBattleSettled { pub team_battle: Pubkey, pub winner_team: u8, pub fee: u64, pub payout_each: u64 }
+#[event] pub struct BossCreated { pub boss: Pubkey, pub boss_id: u64, pub max_hp: u64, pub reward_pool: u64 }
+#[event] pub struct BossRewardFunded { pub boss: Pubkey, pub funder: Pubkey, pub amount: u64, pub reward_pool: u64 }
+#[event] pub struct BossAttacked { pub boss: Pubkey, pub nft_mint: Pubkey, pub damage: u64, pub is_crit: bool, pub boss_health: u64 }
+#[event] pub struct BossRetaliated { pub boss: Pubkey, pub nft_mint: Pubkey, pub move_index: u8, pub damage: u64, pub health: u64 }
+#[event] pub struct BossDefeated { pub boss: Pubkey, pub finisher: Pubkey, pub reward_pool: u64 }
+#[event] pub struct RaidRewardClaimed { pub boss: Pubkey, pub nft_mint: Pubkey, pub player: Pubkey, pub damage_dealt: u64, pub reward: u64 }
+#[event] pub struct SeasonStarted { pub season: Pubkey, pub season_id: u32, pub start_ts: i64, pub end_ts: i64, pub reward_pool: u64 }
+#[event] pub struct SeasonResultRecorded { pub season: Pubkey, pub nft_mint: Pubkey, pub final_mmr: u64, pub tier: u8, pub new_mmr: u64 }
+#[event] pub struct SeasonRewardClaimed { pub season: Pubkey, pub nft_mint: Pubkey, pub player: Pubkey, pub tier: u8, pub reward: u64 }
 
 // ------------------------
 // HELPERS: FP math, entropy consumption, levelup
@@ -1259,23 +6017,598 @@ impl EntropyPool {
     }
 }
 
+// PlayerVault PDAs are program-owned and carry data, so lamports move by direct debit/credit (no system transfer)
+fn pay_from_pda(from: &AccountInfo, to: &AccountInfo, amount: u64) -> Result<()> {
+    if amount == 0 { return Ok(()); }
+    let rent_floor = Rent::get()?.minimum_balance(from.data_len());
+    let available = from.lamports().saturating_sub(rent_floor);
+    require!(amount <= available, GameError::MathOverflow);
+    **from.try_borrow_mut_lamports()? -= amount;
+    **to.try_borrow_mut_lamports()? = to.lamports().checked_add(amount).ok_or(GameError::MathOverflow)?;
+    Ok(())
+}
+
+// SOL stakes sit on zero-data, system-owned PDAs ([b"sol_escrow", holder]) rather than on the Offer / Request / Battle
+// data accounts, so stake lamports never mix with rent and the data accounts can be closed on their own.
+fn sol_escrow<'info>(escrow: &Option<UncheckedAccount<'info>>, holder: &Pubkey) -> Result<(AccountInfo<'info>, u8)> {
+    let escrow = escrow.as_ref().ok_or(GameError::MissingEscrowAccount)?;
+    let (addr, bump) = Pubkey::find_program_address(&[b"sol_escrow", holder.as_ref()], &crate::ID);
+    require_keys_eq!(escrow.key(), addr, GameError::MissingEscrowAccount);
+    Ok((escrow.to_account_info(), bump))
+}
+
+// wallets pay by system transfer; program-owned PDAs (player vaults) by direct debit
+fn sol_escrow_deposit<'info>(from: &AccountInfo<'info>, escrow: &AccountInfo<'info>, amount: u64) -> Result<()> {
+    if amount == 0 { return Ok(()); }
+    // the runtime rejects a funded zero-data account below the rent-exempt minimum
+    require!(escrow.lamports().saturating_add(amount) >= Rent::get()?.minimum_balance(0), GameError::StakeBelowRentMinimum);
+    if from.owner == &crate::ID {
+        return pay_from_pda(from, escrow, amount);
+    }
+    invoke_signed(&system_instruction::transfer(from.key, escrow.key, amount), &[from.clone(), escrow.clone()], &[])?;
+    Ok(())
+}
+
+fn sol_escrow_release<'info>(escrow: &AccountInfo<'info>, holder: &Pubkey, bump: u8, to: &AccountInfo<'info>, amount: u64) -> Result<()> {
+    if amount == 0 { return Ok(()); }
+    invoke_signed(&system_instruction::transfer(escrow.key, to.key, amount), &[escrow.clone(), to.clone()], &[&[b"sol_escrow", holder.as_ref(), &[bump]]])?;
+    Ok(())
+}
+
+// Moves whatever is left on a SOL escrow PDA to `to` (no-op when the escrow isn't passed or is empty)
+fn sweep_sol_escrow<'info>(escrow: &Option<UncheckedAccount<'info>>, holder: &Pubkey, to: &AccountInfo<'info>) -> Result<()> {
+    if escrow.is_none() { return Ok(()); }
+    let (escrow, bump) = sol_escrow(escrow, holder)?;
+    sol_escrow_release(&escrow, holder, bump, to, escrow.lamports())
+}
+
+// SPL stakes of every offer/request/battle share one escrow ATA per mint, owned by the EscrowVault PDA
+fn spl_escrow<'a, 'info>(vault: &'a mut Option<Account<'info, EscrowVault>>, ata: &'a Option<Account<'info, TokenAccount>>, mint: Pubkey) -> Result<(&'a mut Account<'info, EscrowVault>, &'a Account<'info, TokenAccount>)> {
+    let (vault, ata) = match (vault.as_mut(), ata.as_ref()) {
+        (Some(vault), Some(ata)) => (vault, ata),
+        _ => return err!(GameError::MissingEscrowAccount),
+    };
+    require!(vault.mint == mint && ata.mint == mint && ata.owner == vault.key(), GameError::MissingEscrowAccount);
+    Ok((vault, ata))
+}
+
+fn escrow_deposit<'info>(escrow: &mut Account<'info, EscrowVault>, escrow_ata: &Account<'info, TokenAccount>, from: AccountInfo<'info>, authority: AccountInfo<'info>, signer_seeds: &[&[&[u8]]], token_program: AccountInfo<'info>, amount: u64) -> Result<()> {
+    if amount == 0 { return Ok(()); }
+    let cpi_accounts = token::Transfer { from, to: escrow_ata.to_account_info(), authority };
+    token::transfer(CpiContext::new_with_signer(token_program, cpi_accounts, signer_seeds), amount)?;
+    escrow.total_escrowed = escrow.total_escrowed.checked_add(amount).ok_or(GameError::MathOverflow)?;
+    Ok(())
+}
+
+// Pays `amount` of a ledger entry out of the shared escrow ATA (escrow PDA signs)
+fn escrow_release<'info>(escrow: &mut Account<'info, EscrowVault>, escrow_ata: &Account<'info, TokenAccount>, to: AccountInfo<'info>, token_program: AccountInfo<'info>, amount: u64) -> Result<()> {
+    if amount == 0 { return Ok(()); }
+    escrow.total_escrowed = escrow.total_escrowed.checked_sub(amount).ok_or(GameError::MathOverflow)?;
+    let cpi_accounts = token::Transfer { from: escrow_ata.to_account_info(), to, authority: escrow.to_account_info() };
+    let bump = [escrow.bump];
+    let signer_seeds = &[&[b"escrow", escrow.mint.as_ref(), &bump][..]];
+    token::transfer(CpiContext::new_with_signer(token_program, cpi_accounts, signer_seeds), amount)
+}
+
+// Bumps every WinrateStats passed in `accounts` that matches either player's class or final stance.
+// A shared class/stance counts both players on the same account; passing an account twice is rejected.
+fn record_winrates<'info>(battle: &Battle, accounts: &[AccountInfo<'info>]) -> Result<()> {
+    let sides = [
+        (battle.player1, battle.player1_class, battle.player1_stance),
+        (battle.player2, battle.player2_class, battle.player2_stance),
+    ];
+    let mut seen: Vec<Pubkey> = Vec::with_capacity(accounts.len());
+    for info in accounts.iter() {
+        require!(!seen.contains(info.key), GameError::InvalidIndex);
+        seen.push(info.key());
+        let mut stats: Account<WinrateStats> = Account::try_from(info)?;
+        for (player, class, stance) in sides.iter() {
+            let matches = match stats.kind {
+                STATS_KIND_CLASS => class.map(|c| c as u8) == Some(stats.key),
+                STATS_KIND_STANCE => *stance as u8 == stats.key,
+                _ => false,
+            };
+            if !matches { continue; }
+            stats.battles = stats.battles.saturating_add(1);
+            match battle.winner {
+                Some(w) if w == *player => stats.wins = stats.wins.saturating_add(1),
+                Some(_) => stats.losses = stats.losses.saturating_add(1),
+                None => stats.draws = stats.draws.saturating_add(1),
+            }
+        }
+        stats.exit(&crate::ID)?;
+        emit!(WinrateRecorded { stats: info.key(), kind: stats.kind, key: stats.key, battles: stats.battles, wins: stats.wins });
+    }
+    Ok(())
+}
+
+// Sliding window: the previous bucket's count is weighted by how much of it still overlaps the last hour.
+fn consume_rate_limit(rl: &mut Account<RateLimit>, wallet: Pubkey, max_per_hour: u16, now: i64, bump: u8) -> Result<()> {
+    let bucket = now / RATE_WINDOW_SECS;
+    if rl.wallet == Pubkey::default() {
+        rl.wallet = wallet;
+        rl.bump = bump;
+    }
+    if bucket != rl.bucket {
+        rl.previous_count = if bucket == rl.bucket + 1 { rl.current_count } else { 0 };
+        rl.current_count = 0;
+        rl.bucket = bucket;
+    }
+    if max_per_hour > 0 {
+        let remaining_secs = RATE_WINDOW_SECS - now.rem_euclid(RATE_WINDOW_SECS);
+        let weighted_prev = (rl.previous_count as i64) * remaining_secs / RATE_WINDOW_SECS;
+        require!(weighted_prev + (rl.current_count as i64) < max_per_hour as i64, GameError::RateLimited);
+    }
+    rl.current_count = rl.current_count.saturating_add(1);
+    Ok(())
+}
+
+// fee discount for the winner's guild tier; zero unless the member account matches the winner and guild
+fn winner_guild_discount_bps(winner: Option<Pubkey>, member: &Option<Account<GuildMember>>, guild: &Option<Account<Guild>>) -> u16 {
+    match (winner, member, guild) {
+        (Some(w), Some(m), Some(g)) if m.player == w && m.guild == g.key() && g.perk_tier > 0 => GUILD_FEE_DISCOUNT_BPS[(g.perk_tier as usize).min(GUILD_FEE_DISCOUNT_BPS.len()) - 1],
+        _ => 0,
+    }
+}
+
+// Handles a knockout (either side at 0 HP). In best-of-N the knockout wins the round and, until someone has
+// rounds_to_win, a fresh round starts with full HP, effects cleared and the round's loser moving first.
+// Otherwise the battle ends: winner by remaining HP, HP persisted onto both characters, XP awarded.
+// Returns whether the turn is over (round or battle ended).
+fn end_battle_if_dead<'info>(battle: &mut Account<'info, Battle>, attacker_is_player1: bool, attacker_char: &mut Account<'info, Character>, defender_char: &mut Account<'info, Character>, attacker_prog: &mut Account<'info, Progression>, defender_prog: &mut Account<'info, Progression>, leaderboard: &mut Account<'info, Leaderboard>) -> Result<bool> {
+    if battle.player1_health > 0 && battle.player2_health > 0 {
+        return Ok(false);
+    }
+    if battle.player1_health > battle.player2_health {
+        battle.player1_round_wins = battle.player1_round_wins.saturating_add(1);
+    } else if battle.player2_health > battle.player1_health {
+        battle.player2_round_wins = battle.player2_round_wins.saturating_add(1);
+    }
+    let decided = battle.player1_round_wins >= battle.rounds_to_win || battle.player2_round_wins >= battle.rounds_to_win;
+    if battle.rounds_to_win > 1 && !decided {
+        emit!(RoundEnded { battle: battle.key(), round: battle.round_number, player1_round_wins: battle.player1_round_wins, player2_round_wins: battle.player2_round_wins });
+        let (p1_max, p2_max) = if attacker_is_player1 { (attacker_char.max_hp, defender_char.max_hp) } else { (defender_char.max_hp, attacker_char.max_hp) };
+        battle.current_turn = if battle.player1_health == 0 { 1 } else { 2 };
+        battle.turn_number = battle.turn_number.saturating_add(1);
+        battle.round_number = battle.round_number.saturating_add(1);
+        battle.player1_health = p1_max as u64;
+        battle.player2_health = p2_max as u64;
+        battle.player1_dot_damage = 0;
+        battle.player2_dot_damage = 0;
+        battle.player1_dot_turns = 0;
+        battle.player2_dot_turns = 0;
+        battle.player1_guard_bps = 0;
+        battle.player2_guard_bps = 0;
+        battle.player1_effects.clear();
+        battle.player2_effects.clear();
+        battle.player1_stamina = MAX_STAMINA;
+        battle.player2_stamina = MAX_STAMINA;
+        return Ok(true);
+    }
+    let winner_opt = if battle.player1_health > battle.player2_health { Some(battle.player1) } else if battle.player2_health > battle.player1_health { Some(battle.player2) } else { None };
+    finish_battle(battle, winner_opt, attacker_is_player1, attacker_char, defender_char, attacker_prog, defender_prog, leaderboard)?;
+    Ok(true)
+}
+
+// Battle over: HP persisted onto both characters, XP awarded (WIN_XP to the winner, DRAW_XP to both on a draw).
+// One 'info for every account: the two progressions are swapped into player order below
+fn finish_battle<'info>(battle: &mut Account<'info, Battle>, winner_opt: Option<Pubkey>, attacker_is_player1: bool, attacker_char: &mut Account<'info, Character>, defender_char: &mut Account<'info, Character>, attacker_prog: &mut Account<'info, Progression>, defender_prog: &mut Account<'info, Progression>, leaderboard: &mut Account<'info, Leaderboard>) -> Result<()> {
+    battle.state = BattleState::Finished;
+    battle.winner = winner_opt;
+    // remaining HP persists on the characters (a level-up below refills it)
+    let (attacker_hp, defender_hp) = if attacker_is_player1 { (battle.player1_health, battle.player2_health) } else { (battle.player2_health, battle.player1_health) };
+    attacker_char.current_hp = attacker_hp.min(attacker_char.max_hp as u64) as u32;
+    defender_char.current_hp = defender_hp.min(defender_char.max_hp as u64) as u32;
+    // award xp
+    let attacker_pk = if attacker_is_player1 { battle.player1 } else { battle.player2 };
+    // flawless: the winner ends on full HP (checked before a level-up raises max_hp)
+    let flawless = match winner_opt {
+        Some(wpk) if wpk == attacker_pk => attacker_hp >= attacker_char.max_hp as u64,
+        Some(_) => defender_hp >= defender_char.max_hp as u64,
+        None => false,
+    };
+    match winner_opt {
+        Some(wpk) if wpk == attacker_pk => {
+            attacker_prog.xp = attacker_prog.xp.saturating_add(WIN_XP);
+            level_up_if_needed(attacker_prog, attacker_char)?;
+        }
+        Some(_) => {
+            defender_prog.xp = defender_prog.xp.saturating_add(WIN_XP);
+            level_up_if_needed(defender_prog, defender_char)?;
+        }
+        None => {
+            // draw
+            attacker_prog.xp = attacker_prog.xp.saturating_add(DRAW_XP);
+            defender_prog.xp = defender_prog.xp.saturating_add(DRAW_XP);
+        }
+    }
+    let (p1_prog, p2_prog) = if attacker_is_player1 { (attacker_prog, defender_prog) } else { (defender_prog, attacker_prog) };
+    apply_elo(battle, p1_prog, p2_prog)?;
+    record_battle_stats(battle, p1_prog, p2_prog, flawless);
+    leaderboard_record(leaderboard, p1_prog);
+    leaderboard_record(leaderboard, p2_prog);
+    emit!(BattleEnded { battle: battle.key(), winner: battle.winner });
+    Ok(())
+}
+
+// Elo update for a finished battle: player1 scores 1 / 0.5 / 0 for a win / draw / loss, player2 the mirror
+fn apply_elo(battle: &Account<Battle>, p1_prog: &mut Progression, p2_prog: &mut Progression) -> Result<()> {
+    // ratings are frozen outside the season the battle was created in, so a SeasonResult snapshot stays final
+    let now = Clock::get()?.unix_timestamp;
+    if battle.rated_until > 0 && (now < battle.rated_from || now >= battle.rated_until) { return Ok(()); }
+    // last_played marks a rated result in the current season (SeasonResult eligibility)
+    p1_prog.last_played = now;
+    p2_prog.last_played = now;
+    let k = battle.elo_k_factor as i64;
+    if k == 0 { return Ok(()); }
+    let score1: i64 = match battle.winner { Some(w) if w == battle.player1 => 10_000, Some(_) => 0, None => 5_000 };
+    let delta = k * (score1 - elo_expected_bps(p1_prog.mmr, p2_prog.mmr) as i64) / 10_000;
+    for (prog, d) in [(p1_prog, delta), (p2_prog, -delta)] {
+        let old_mmr = prog.mmr;
+        prog.mmr = old_mmr.saturating_add_signed(d);
+        emit!(MmrUpdated { battle: battle.key(), nft_mint: prog.nft_mint, old_mmr, new_mmr: prog.mmr });
+    }
+    Ok(())
+}
+
+// a season's results can be recorded until the next season starts or SEASON_RECORD_WINDOW_SECS after it ends
+fn season_recording_open(cfg: &Config, season: &Season, now: i64) -> bool {
+    cfg.current_season == season.id && now < season.end_ts.saturating_add(SEASON_RECORD_WINDOW_SECS)
+}
+
+// full tier reward while the pool covers every recorded result, otherwise scaled down by pool / owed
+fn season_reward_for(season: &Season, tier: u8) -> u64 {
+    let full = season.tier_rewards[tier as usize - 1];
+    let owed: u128 = season.tier_counts.iter().zip(season.tier_rewards.iter()).map(|(&n, &r)| n as u128 * r as u128).sum();
+    if owed <= season.reward_pool as u128 { return full; }
+    (full as u128 * season.reward_pool as u128 / owed) as u64
+}
+
+fn elo_expected_bps(rating: u64, opponent: u64) -> u64 {
+    let lead = rating.abs_diff(opponent).min(800);
+    let i = (lead / 50) as usize;
+    let next = ELO_EXPECTED_BPS[(i + 1).min(ELO_EXPECTED_BPS.len() - 1)];
+    let expected = ELO_EXPECTED_BPS[i] + (next - ELO_EXPECTED_BPS[i]) * (lead % 50) / 50;
+    if rating >= opponent { expected } else { 10_000 - expected }
+}
+
+// win / streak / flawless counters behind achievements; a forfeit win is never flawless
+fn record_battle_stats(battle: &Account<Battle>, p1_prog: &mut Progression, p2_prog: &mut Progression, flawless: bool) {
+    for (prog, player) in [(p1_prog, battle.player1), (p2_prog, battle.player2)] {
+        prog.battles = prog.battles.saturating_add(1);
+        if battle.winner == Some(player) {
+            prog.wins = prog.wins.saturating_add(1);
+            prog.win_streak = prog.win_streak.saturating_add(1);
+            prog.best_win_streak = prog.best_win_streak.max(prog.win_streak);
+            if flawless { prog.flawless_wins = prog.flawless_wins.saturating_add(1); }
+        } else {
+            prog.win_streak = 0;
+        }
+    }
+}
+
+// forfeit paths: progressions are seed-bound to the battle's characters by ForfeitByTimeout
+fn forfeit_elo(battle: &Account<Battle>, p1: &mut Account<Progression>, p2: &mut Account<Progression>, leaderboard: &mut Account<Leaderboard>) -> Result<()> {
+    apply_elo(battle, p1, p2)?;
+    record_battle_stats(battle, p1, p2, false);
+    leaderboard_record(leaderboard, p1);
+    leaderboard_record(leaderboard, p2);
+    Ok(())
+}
+
+// Re-rank one character: its old entry is dropped, then it is re-inserted by MMR (behind equal ratings) if it makes
+// the top LEADERBOARD_SIZE. A character falling off leaves its slot to whoever next moves above the last entry.
+fn leaderboard_record(board: &mut Account<Leaderboard>, prog: &Progression) {
+    let old_rank = board.entries.iter().position(|e| e.nft_mint == prog.nft_mint);
+    if let Some(i) = old_rank { board.entries.remove(i); }
+    let at = board.entries.iter().position(|e| e.mmr < prog.mmr).unwrap_or(board.entries.len());
+    let rank = if at < LEADERBOARD_SIZE {
+        board.entries.insert(at, LeaderboardEntry { nft_mint: prog.nft_mint, mmr: prog.mmr });
+        board.entries.truncate(LEADERBOARD_SIZE);
+        Some(at as u16 + 1)
+    } else {
+        None
+    };
+    if old_rank.is_some() || rank.is_some() {
+        emit!(LeaderboardUpdated { leaderboard: board.key(), nft_mint: prog.nft_mint, mmr: prog.mmr, rank });
+    }
+}
+
+// Turn cap: more round wins takes it, then the higher share of max HP left; equal shares are a draw
+fn turn_limit_winner(battle: &Battle, p1_max_hp: u32, p2_max_hp: u32) -> Option<Pubkey> {
+    if battle.player1_round_wins != battle.player2_round_wins {
+        return Some(if battle.player1_round_wins > battle.player2_round_wins { battle.player1 } else { battle.player2 });
+    }
+    let p1_bps = battle.player1_health.saturating_mul(10_000) / (p1_max_hp.max(1) as u64);
+    let p2_bps = battle.player2_health.saturating_mul(10_000) / (p2_max_hp.max(1) as u64);
+    if p1_bps > p2_bps { Some(battle.player1) } else if p2_bps > p1_bps { Some(battle.player2) } else { None }
+}
+
+// Start of the owner's turn: sums poison, reports a stun, takes a turn off every effect and drops expired ones
+fn tick_status_effects(effects: &mut Vec<StatusEffect>) -> (u64, bool) {
+    let mut poison = 0u64;
+    let mut stunned = false;
+    for fx in effects.iter_mut() {
+        match fx.kind {
+            StatusKind::Poison => poison = poison.saturating_add(fx.magnitude as u64),
+            StatusKind::Stun => stunned = true,
+            _ => {}
+        }
+        fx.turns_left = fx.turns_left.saturating_sub(1);
+    }
+    effects.retain(|fx| fx.turns_left > 0);
+    (poison, stunned)
+}
+
+// Same kind already active -> keep the stronger magnitude / longer duration; otherwise add it if there's room
+fn apply_status(battle: Pubkey, target: Pubkey, effects: &mut Vec<StatusEffect>, kind: StatusKind, magnitude: u16, turns: u8) {
+    if let Some(fx) = effects.iter_mut().find(|fx| fx.kind == kind) {
+        fx.magnitude = fx.magnitude.max(magnitude);
+        fx.turns_left = fx.turns_left.max(turns);
+    } else if effects.len() < MAX_STATUS_EFFECTS {
+        effects.push(StatusEffect { kind, magnitude, turns_left: turns });
+    } else {
+        return;
+    }
+    emit!(StatusApplied { battle, target, kind: kind as u8, magnitude, turns });
+}
+
+// outgoing damage multiplier (bps) from buffs / debuffs
+fn status_damage_bps(effects: &[StatusEffect]) -> u64 {
+    let mut bps = 10_000i64;
+    for fx in effects {
+        match fx.kind {
+            StatusKind::Buff => bps += fx.magnitude as i64,
+            StatusKind::Debuff => bps -= fx.magnitude as i64,
+            _ => {}
+        }
+    }
+    bps.max(0) as u64
+}
+
+// shields soak incoming damage and are used up by what they absorb; returns the amount absorbed
+fn absorb_with_shield(effects: &mut Vec<StatusEffect>, damage: u64) -> u64 {
+    let mut absorbed = 0u64;
+    for fx in effects.iter_mut().filter(|fx| fx.kind == StatusKind::Shield) {
+        let take = (fx.magnitude as u64).min(damage - absorbed);
+        fx.magnitude -= take as u16;
+        absorbed += take;
+    }
+    effects.retain(|fx| fx.kind != StatusKind::Shield || fx.magnitude > 0);
+    absorbed
+}
+
+// battle HP back onto a Character; skipped when the account isn't passed or isn't the battle's character
+// Who the battle is waiting on: the player to move, unless commit-reveal is held up by one side's commit / reveal
+fn idle_is_player1(battle: &Battle) -> bool {
+    // async: whoever is behind on commit / reveal of their strategy
+    if battle.async_mode {
+        let p1_stage = battle.player1_commitment.is_some() as u8 + !battle.player1_strategy.is_empty() as u8;
+        let p2_stage = battle.player2_commitment.is_some() as u8 + !battle.player2_strategy.is_empty() as u8;
+        return p1_stage < p2_stage || (p1_stage == p2_stage && battle.current_turn == 1);
+    }
+    if battle.commit_reveal && battle.commit_turn == battle.turn_number {
+        let p1_stage = battle.player1_commitment.is_some() as u8 + battle.player1_revealed as u8;
+        let p2_stage = battle.player2_commitment.is_some() as u8 + battle.player2_revealed as u8;
+        if p1_stage != p2_stage { return p1_stage < p2_stage; }
+    }
+    battle.current_turn == 1
+}
+
+// Appends to the battle's TurnLog ring buffer when one is open
+fn record_turn(log: &mut Option<Account<TurnLog>>, entry: TurnLogEntry) {
+    if let Some(log) = log.as_mut() {
+        let slot = (log.total_turns % TURN_LOG_CAPACITY as u64) as usize;
+        if slot < log.entries.len() { log.entries[slot] = entry; } else { log.entries.push(entry); }
+        log.total_turns = log.total_turns.saturating_add(1);
+    }
+}
+
+// Berserker / Counter need the Config unlock level; the other stances are always available
+fn stance_unlocked(cfg: &Config, stance: StanceType, level: u16) -> bool {
+    match stance {
+        StanceType::Berserker => level >= cfg.berserker_unlock_level,
+        StanceType::Counter => level >= cfg.counter_unlock_level,
+        _ => true,
+    }
+}
+
+// Damage cut (bps) for an attacker out-levelling the defender by at least Config.handicap_level_gap
+fn level_handicap_bps(cfg: &Config, attacker_level: u16, defender_level: u16) -> u16 {
+    let gap = attacker_level.saturating_sub(defender_level);
+    if cfg.handicap_level_gap == 0 || gap < cfg.handicap_level_gap { return 0; }
+    let levels = gap - cfg.handicap_level_gap + 1;
+    levels.saturating_mul(cfg.handicap_bps_per_level).min(cfg.handicap_max_bps)
+}
+
+// Sum of the modifiers in a character's equipment slots
+fn equipment_totals(ch: &Character) -> ItemModifiers {
+    ch.equipment.iter().filter(|s| s.item_mint != Pubkey::default()).fold(ItemModifiers::default(), |acc, s| ItemModifiers {
+        attack: acc.attack.saturating_add(s.mods.attack),
+        defense: acc.defense.saturating_add(s.mods.defense),
+        crit_bps: acc.crit_bps.saturating_add(s.mods.crit_bps),
+        dodge_bps: acc.dodge_bps.saturating_add(s.mods.dodge_bps),
+    })
+}
+
+// Metaplex FreezeDelegatedAccount (26) / ThawDelegatedAccount (27) on a character NFT, the battle PDA signing as delegate
+fn set_nft_frozen<'info>(battle: &Account<'info, Battle>, nft_ata: AccountInfo<'info>, edition: AccountInfo<'info>, mint: AccountInfo<'info>, token_program: AccountInfo<'info>, metadata_program: AccountInfo<'info>, freeze: bool) -> Result<()> {
+    let ix = Instruction {
+        program_id: TOKEN_METADATA_PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new_readonly(battle.key(), true),
+            AccountMeta::new(nft_ata.key(), false),
+            AccountMeta::new_readonly(edition.key(), false),
+            AccountMeta::new_readonly(mint.key(), false),
+            AccountMeta::new_readonly(token_program.key(), false),
+        ],
+        data: vec![if freeze { 26 } else { 27 }],
+    };
+    let nonce = battle.offer_nonce.to_le_bytes();
+    let bump = [battle.bump];
+    let seeds: &[&[u8]] = &[b"battle", &nonce, battle.player1.as_ref(), battle.player2.as_ref(), &bump];
+    invoke_signed(&ix, &[battle.to_account_info(), nft_ata, edition, mint, token_program, metadata_program], &[seeds])?;
+    Ok(())
+}
+
+// Clears a character's battle lock if it belongs to this battle
+fn release_character(character: &mut Option<Account<Character>>, expected: Pubkey, battle: Pubkey) {
+    if let Some(ch) = character.as_mut().filter(|c| c.key() == expected && c.active_battle == battle) {
+        ch.active_battle = Pubkey::default();
+    }
+}
+
+fn unlist_offer(registry: &mut Account<OfferRegistry>, offer: Pubkey) {
+    let before = registry.listings.len();
+    registry.listings.retain(|l| l.offer != offer);
+    if registry.listings.len() != before {
+        emit!(OfferUnlisted { offer, page: registry.page });
+    }
+}
+
+// Offer that never goes on the open market (rematches, queue entries): holds one side's stake until the battle is created
+#[allow(clippy::too_many_arguments)]
+fn init_private_offer(offer: &mut Offer, creator: Pubkey, offer_nonce: u64, currency: Currency, stake: u64, character: Pubkey, target_opponent: Option<Pubkey>, now: i64, bump: u8) {
+    offer.creator = creator;
+    offer.offer_nonce = offer_nonce;
+    offer.currency = currency;
+    offer.stake_amount = stake;
+    offer.min_level = 0;
+    offer.max_level = u16::MAX;
+    offer.allowed_classes = vec![];
+    offer.auto_approve = false;
+    offer.start_ts = now;
+    offer.inactivity_timeout = 0;
+    offer.created_at = now;
+    offer.is_active = false;
+    offer.recurring = false;
+    offer.last_battle = Pubkey::default();
+    offer.relist_count = 0;
+    offer.rounds = 1;
+    offer.commit_reveal = false;
+    offer.async_mode = false;
+    offer.reconnect_grace_secs = 0;
+    offer.creator_character = character;
+    offer.target_opponent = target_opponent;
+    offer.requests = Vec::new();
+    offer.request_count = 0;
+    offer.min_mmr = 0;
+    offer.max_mmr = u64::MAX;
+    offer.registry_page = None;
+    offer.bump = bump;
+}
+
+// Fills a freshly created Battle from its offer (approve_challenger, auto-approve joins, rematches); player1 is the offer creator
+#[allow(clippy::too_many_arguments)]
+fn init_battle(battle: &mut Battle, offer: &Offer, cfg: &Config, player2: Pubkey, characters: [&Account<Character>; 2], abilities: [Vec<Ability>; 2], player2_stake: u64, start_ts: i64, now: i64, bump: u8) {
+    let [player1_abilities, player2_abilities] = abilities;
+    battle.battle_id = offer.offer_nonce.wrapping_add(now as u64);
+    battle.player1 = offer.creator;
+    battle.offer_nonce = offer.offer_nonce;
+    battle.player2 = player2;
+    battle.start_ts = start_ts;
+    battle.current_turn = 0;
+    battle.turn_number = 0;
+    battle.player1_health = characters[0].max_hp as u64;
+    battle.player2_health = characters[1].max_hp as u64;
+    battle.player1_character = characters[0].key();
+    battle.player2_character = characters[1].key();
+    battle.player1_items = ITEMS_PER_BATTLE;
+    battle.player2_items = ITEMS_PER_BATTLE;
+    battle.player1_heals = HEALS_PER_BATTLE;
+    battle.player2_heals = HEALS_PER_BATTLE;
+    battle.player1_stamina = MAX_STAMINA;
+    battle.player2_stamina = MAX_STAMINA;
+    battle.rounds_to_win = offer.rounds.max(1) / 2 + 1;
+    battle.round_number = 1;
+    battle.max_turns = cfg.max_turns;
+    battle.time_control = cfg.time_bank_secs > 0;
+    battle.turn_deadline = cfg.turn_deadline_secs;
+    battle.skip_penalty_bps = cfg.skip_penalty_bps;
+    battle.max_skips = cfg.max_skips;
+    battle.elo_k_factor = cfg.elo_k_factor;
+    battle.rated_from = cfg.season_start_ts;
+    battle.rated_until = cfg.season_end_ts;
+    battle.player1_time_bank = cfg.time_bank_secs;
+    battle.player2_time_bank = cfg.time_bank_secs;
+    battle.commit_reveal = offer.commit_reveal;
+    battle.async_mode = offer.async_mode;
+    battle.reconnect_grace = offer.reconnect_grace_secs;
+    battle.player1_abilities = player1_abilities;
+    battle.player2_abilities = player2_abilities;
+    // ready check: battle stays Waiting until both players confirm
+    battle.state = BattleState::Waiting;
+    battle.ready_deadline = start_ts.max(now).saturating_add(READY_WINDOW_SECS);
+    battle.player1_stance = StanceType::Balanced;
+    battle.player2_stance = StanceType::Balanced;
+    battle.created_at = now;
+    // set inactivity timeout from offer or config
+    battle.inactivity_timeout = if offer.inactivity_timeout > 0 { offer.inactivity_timeout } else { cfg.inactivity_timeout };
+    battle.last_action_ts = now;
+    battle.bump = bump;
+    battle.last_entropy_index = 0;
+    battle.player1_stake = offer.stake_amount;
+    battle.player2_stake = player2_stake;
+}
+
+// Picks the first mover and then the arena, consuming 2 entropy entries with per-battle monotonicity
+fn roll_battle_opening(pool: &mut Account<EntropyPool>, signer: &Pubkey, battle: &mut Account<Battle>) -> Result<()> {
+    require!(pool.total_available >= 2, GameError::NoEntropyAvailable);
+    let (choice, used_index) = pool.consume_mixed_u64_return_index(signer, b"first_mover", battle.turn_number as u32, 0, 1)?;
+    require!(used_index > battle.last_entropy_index, GameError::SeedReplay);
+    battle.last_entropy_index = used_index;
+    battle.current_turn = if choice == 0 { 1 } else { 2 };
+    let (arena_roll, arena_index) = pool.consume_mixed_u64_return_index(signer, b"arena", battle.turn_number as u32, 0, ARENA_MODIFIER_COUNT - 1)?;
+    require!(arena_index > battle.last_entropy_index, GameError::SeedReplay);
+    battle.last_entropy_index = arena_index;
+    battle.arena = ArenaModifier::from_roll(arena_roll);
+    emit!(ArenaRolled { battle: battle.key(), arena: battle.arena as u8 });
+    Ok(())
+}
+
+// Practice AI policy: guards below 30% HP, presses a player below 30%, otherwise mixes stances from the roll
+fn practice_ai_stance(ai_health: u64, player_health: u64, max_hp: u64, roll: u64) -> StanceType {
+    if ai_health.saturating_mul(10) < max_hp.saturating_mul(3) {
+        StanceType::Defensive
+    } else if player_health.saturating_mul(10) < max_hp.saturating_mul(3) {
+        StanceType::Aggressive
+    } else {
+        [StanceType::Balanced, StanceType::Aggressive, StanceType::Defensive, StanceType::Counter][roll as usize % 4]
+    }
+}
+
+// Seats a player in a team battle and locks their character to it
+fn seat_team_member(tb: &mut Account<TeamBattle>, seat: usize, player: Pubkey, character: &mut Account<Character>) -> Result<()> {
+    require!(!tb.members.iter().any(|m| m.player == player), GameError::AlreadyOnTeam);
+    require!(character.active_battle == Pubkey::default(), GameError::CharacterInBattle);
+    character.active_battle = tb.key();
+    tb.members[seat] = TeamMember { player, character: character.key(), health: character.max_hp as u64, stance: StanceType::Balanced };
+    Ok(())
+}
+
+fn write_back_hp(character: &mut Account<Character>, health: u64) {
+    character.current_hp = health.min(character.max_hp as u64) as u32;
+}
+
 // level up logic: simple quadratic XP curve
 fn next_level_xp(level: u16) -> u64 {
     // 100 * level^2
     let l = level as u64;
     100u64.saturating_mul(l.saturating_mul(l))
 }
+// total XP ever earned: every completed level's requirement plus the current remainder
+fn lifetime_xp(prog: &Progression) -> u64 {
+    (1..prog.level).fold(prog.xp, |acc, l| acc.saturating_add(next_level_xp(l)))
+}
 fn level_up_if_needed(prog: &mut Account<Progression>, ch: &mut Account<Character>) -> Result<()> {
     loop {
         let need = next_level_xp(prog.level);
         if prog.xp >= need {
             prog.xp = prog.xp.saturating_sub(need);
             prog.level = prog.level.saturating_add(1);
-            // evolve stats modestly
-            ch.max_hp = ch.max_hp.saturating_add((ch.max_hp / 20).max(1)); // +5%
+            // stats are no longer bumped automatically: the owner spends the points (spend_stat_points)
+            prog.stat_points = prog.stat_points.saturating_add(STAT_POINTS_PER_LEVEL);
             ch.current_hp = ch.max_hp;
-            ch.base_damage_min = ch.base_damage_min.saturating_add((ch.base_damage_min / 10).max(1));
-            ch.base_damage_max = ch.base_damage_max.saturating_add((ch.base_damage_max / 10).max(1));
             emit!(ProgressionLevelUp { nft_mint: prog.nft_mint, new_level: prog.level });
         } else { break; }
     }
@@ -1309,6 +6642,72 @@ pub enum GameError {
     #[msg("Auto-approve disabled")] AutoApproveDisabled,
     #[msg("SPL not whitelisted")] SPLNotWhitelisted,
     #[msg("Timeout not reached")] TimeoutNotReached,
+    #[msg("Pause budget exhausted")] PauseBudgetExhausted,
+    #[msg("Waiting for the other player's consent")] ConsentPending,
+    #[msg("Battle already settled")] AlreadySettled,
+    #[msg("Ready window closed")] ReadyWindowClosed,
+    #[msg("Ready window still open")] ReadyWindowOpen,
+    #[msg("Battle has not started")] BattleNotStarted,
+    #[msg("Tutorial already completed")] TutorialAlreadyCompleted,
+    #[msg("Tutorial not completed")] TutorialNotCompleted,
+    #[msg("Tutorial step requirement not met")] TutorialStepNotMet,
+    #[msg("Tutorial reward already claimed")] TutorialRewardClaimed,
+    #[msg("Too many actions this hour")] RateLimited,
+    #[msg("Insufficient vault balance")] InsufficientVaultBalance,
+    #[msg("Offer is not recurring")] OfferNotRecurring,
+    #[msg("Missing or mismatched escrow account")] MissingEscrowAccount,
+    #[msg("SOL escrow balance would sit below the rent-exempt minimum")] StakeBelowRentMinimum,
+    #[msg("Specials can only be used with the attack action")] SpecialRequiresAttack,
+    #[msg("Action on cooldown")] ActionOnCooldown,
+    #[msg("No items left this battle")] NoItemsLeft,
+    #[msg("No draw offer from the opponent")] NoDrawOffered,
+    #[msg("Time bank exhausted")] TimeBankExpired,
+    #[msg("Move already committed for this turn")] AlreadyCommitted,
+    #[msg("Move already revealed for this turn")] AlreadyRevealed,
+    #[msg("Both players must commit before revealing")] MovesNotCommitted,
+    #[msg("Both moves must be revealed before the turn resolves")] MovesNotRevealed,
+    #[msg("Stance does not match the commitment")] CommitmentMismatch,
+    #[msg("Invalid ability loadout or slot")] InvalidLoadout,
+    #[msg("Not enough unspent stat points")] NotEnoughStatPoints,
+    #[msg("Stat point cap for this level reached")] StatCapReached,
+    #[msg("Action not allowed in this arena")] ArenaForbidsAction,
+    #[msg("No heals left this battle")] NoHealsLeft,
+    #[msg("Not enough stamina")] InsufficientStamina,
+    #[msg("Item is already equipped")] ItemAlreadyEquipped,
+    #[msg("Item is not equipped on this character")] ItemNotEquipped,
+    #[msg("Equipment slot occupied")] SlotOccupied,
+    #[msg("Fusion on cooldown")] FusionOnCooldown,
+    #[msg("Profile field too long")] ProfileTooLong,
+    #[msg("Character is already in a battle")] CharacterInBattle,
+    #[msg("Player already holds a seat in this team battle")] AlreadyOnTeam,
+    #[msg("Team is full")] TeamFull,
+    #[msg("Invalid target")] InvalidTarget,
+    #[msg("Boss already defeated")] BossDefeated,
+    #[msg("Boss is still alive")] BossAlive,
+    #[msg("Character is down for this raid")] RaidCharacterDown,
+    #[msg("Raid reward already claimed")] RaidRewardClaimed,
+    #[msg("Stance not unlocked at this level")] StanceLocked,
+    #[msg("Idle turns in this battle are skipped, not forfeited")] SkipTurnRequired,
+    #[msg("Battle has a turn log that must be passed")] TurnLogRequired,
+    #[msg("Rematch already accepted")] RematchAlreadyAccepted,
+    #[msg("Auto-approve needs the creator's character and the battle accounts")] AutoApproveAccountsMissing,
+    #[msg("Offer is reserved for another opponent")] NotTargetOpponent,
+    #[msg("Offer has pending requests")] OfferHasPendingRequests,
+    #[msg("Offer has too many pending requests")] OfferRequestsFull,
+    #[msg("Character MMR outside the offer's range")] MmrOutOfRange,
+    #[msg("Queue entries are not compatible")] QueueMismatch,
+    #[msg("Offer registry page is full")] RegistryPageFull,
+    #[msg("Offer still holds a stake, requests or a recurring listing")] OfferInUse,
+    #[msg("Season has not ended")] SeasonNotEnded,
+    #[msg("Season result already recorded for this character")] SeasonAlreadyRecorded,
+    #[msg("Character played no rated battle this season")] SeasonNotPlayed,
+    #[msg("Season reward already claimed")] SeasonRewardClaimed,
+    #[msg("No season reward for this result")] NoSeasonReward,
+    #[msg("Achievement requirements not met")] AchievementNotEarned,
+    #[msg("Token account owner or mint does not match the battle")] TokenAccountMismatch,
+    #[msg("Season results can no longer be recorded")] SeasonRecordingClosed,
+    #[msg("Season results are still being recorded")] SeasonRecordingOpen,
+    #[msg("Progression is already on the current layout")] ProgressionAlreadyMigrated,
 }
 
 // Additional events used in level up
@@ -0,0 +1,292 @@
+//! Off-chain operator for the prediction program:
+//! - settles game pools whose battle has finished (permissionless crank, earns crank_fee)
+//! - books settled single-bet results (record_single_results) so the pool's claims can open
+//! - resolves parlay tickets once every leg's game pool is settled (needs the parlay pool authority key)
+//! - flags battles that can't be settled normally (finished without a winner) and voids markets whose
+//!   battle account was closed before settlement
+//! - logs parlay pool health every pass
+//!
+//! Usage: keeper <rpc_url> <keypair_path> [poll_secs]
+use anchor_client::solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use anchor_client::solana_sdk::commitment_config::CommitmentConfig;
+use anchor_client::solana_sdk::instruction::AccountMeta;
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use anchor_client::solana_sdk::signature::{read_keypair_file, Keypair, Signer};
+use anchor_client::{Client, Cluster, Program};
+use anchor_lang::{AccountDeserialize, Discriminator};
+use anchor_spl::associated_token::get_associated_token_address;
+use prediction::{
+    claims_open, parse_battle_snapshot, BattleStateDiscriminant, GamePool, ParlayPool, ParlayTicket, Referral, SingleBet,
+    BATTLECHAIN_PROGRAM_ID, MAX_CLAIM_BATCH, OUTCOME_NO_WINNER,
+};
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const DEFAULT_POLL_SECS: u64 = 15;
+
+fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 3 {
+        anyhow::bail!("usage: keeper <rpc_url> <keypair_path> [poll_secs]");
+    }
+    let payer = Rc::new(read_keypair_file(&args[2]).map_err(|e| anyhow::anyhow!("keypair: {e}"))?);
+    let poll = args.get(3).and_then(|s| s.parse().ok()).unwrap_or(DEFAULT_POLL_SECS);
+    let cluster = Cluster::Custom(args[1].clone(), args[1].replace("http", "ws"));
+    let client = Client::new_with_options(cluster, payer.clone(), CommitmentConfig::confirmed());
+    let program = client.program(prediction::ID)?;
+
+    loop {
+        if let Err(e) = run_pass(&program, &payer) {
+            eprintln!("[keeper] pass failed: {e:#}");
+        }
+        std::thread::sleep(Duration::from_secs(poll));
+    }
+}
+
+fn run_pass(program: &Program<Rc<Keypair>>, payer: &Keypair) -> anyhow::Result<()> {
+    let pools: Vec<(Pubkey, ParlayPool)> = fetch_all(program)?;
+    let game_pools: Vec<(Pubkey, GamePool)> = fetch_all(program)?;
+    let by_pool: HashMap<Pubkey, &ParlayPool> = pools.iter().map(|(k, p)| (*k, p)).collect();
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+
+    // 1. settle finished battles
+    for (key, game) in game_pools.iter().filter(|(_, g)| g.initialized && !g.is_settled) {
+        match battle_state(program, &game.pool_id)? {
+            Some((state, has_winner)) if state == BattleStateDiscriminant::Finished as u8 => {
+                if !has_winner {
+                    println!("[keeper] battle {} finished without a winner; pool {} settles as OUTCOME_NO_WINNER ({})", game.pool_id, key, OUTCOME_NO_WINNER);
+                }
+                match settle_game_pool(program, payer, key, game) {
+                    Ok(sig) => println!("[keeper] settled game pool {key}: {sig}"),
+                    Err(e) => eprintln!("[keeper] settle {key} failed: {e:#}"),
+                }
+            }
+            Some(_) => {}
+            None => {
+                // battle account closed before settlement: void the market so every bet can reclaim its stake
+                match void_game_pool(program, payer, key, game) {
+                    Ok(sig) => println!("[keeper] battle {} missing, voided game pool {key}: {sig}", game.pool_id),
+                    Err(e) => eprintln!("[keeper] void {key} failed: {e:#}"),
+                }
+            }
+        }
+    }
+
+    // 1b. book single-bet results; claims on a pool stay shut until every bet is recorded
+    for (key, game) in game_pools.iter().filter(|(_, g)| g.is_settled && g.unrecorded_bets > 0) {
+        let Some(pool) = by_pool.get(&game.parlay_pool) else { continue };
+        if !claims_open(game, pool.dispute_window_secs, now) {
+            continue;
+        }
+        let bets: Vec<(Pubkey, SingleBet)> = fetch_bets(program, key)?;
+        let pending: Vec<&(Pubkey, SingleBet)> = bets.iter().filter(|(_, b)| !b.recorded).collect();
+        for batch in pending.chunks(MAX_CLAIM_BATCH) {
+            match record_results(program, payer, &game.parlay_pool, pool, key, batch) {
+                Ok(sig) => println!("[keeper] recorded {} result(s) in game pool {key}: {sig}", batch.len()),
+                Err(e) => eprintln!("[keeper] record results in {key} failed: {e:#}"),
+            }
+        }
+    }
+
+    // 2. resolve parlay tickets whose legs are all settled
+    let by_battle: HashMap<Pubkey, &GamePool> = game_pools.iter().map(|(_, g)| (g.pool_id, g)).collect();
+    let tickets: Vec<(Pubkey, ParlayTicket)> = fetch_all(program)?;
+    for (key, ticket) in tickets.iter().filter(|(_, t)| !t.resolved) {
+        let mut won = true;
+        let mut ready = true;
+        let mut voided = false;
+        for (battle, outcome) in ticket.games.iter().zip(ticket.chosen_outcomes.iter()) {
+            match by_battle.get(battle) {
+                Some(g) if g.voided => voided = true,
+                Some(g) if g.is_settled => won &= g.winning_outcome == Some(*outcome),
+                _ => ready = false,
+            }
+        }
+        if voided {
+            // the multiplier was priced on every leg, so a voided leg is left to the authority to resolve
+            println!("[keeper] ticket {key} has a voided leg, skipping (resolve manually)");
+            continue;
+        }
+        if !ready {
+            continue;
+        }
+        // every leg settles against the ticket's parlay pool
+        let Some(pool_key) = ticket.games.first().and_then(|b| by_battle.get(b)).map(|g| g.parlay_pool) else { continue };
+        let Some(pool) = by_pool.get(&pool_key) else { continue };
+        match resolve_ticket(program, payer, &pool_key, pool, key, ticket, won) {
+            Ok(sig) => println!("[keeper] resolved ticket {key} won={won}: {sig}"),
+            Err(e) => eprintln!("[keeper] resolve {key} failed: {e:#}"),
+        }
+    }
+
+    // 3. pool health
+    for (key, pool) in &pools {
+        let committed = pool.pending_payouts.saturating_add(pool.haircut_debt);
+        let utilization_bps = if pool.liquidity_balance == 0 { 0 } else { committed.saturating_mul(10_000) / pool.liquidity_balance };
+        println!(
+            "[health] pool {key} liquidity={} floor={} reserve={} pending={} haircut_debt={} utilization_bps={} paused(bets={}, place={}, settle={}, withdraw={})",
+            pool.liquidity_balance, pool.liquidity_floor, pool.protocol_reserve, pool.pending_payouts, pool.haircut_debt, utilization_bps,
+            pool.bets_paused, pool.placement_paused, pool.settlement_paused, pool.withdrawals_paused,
+        );
+    }
+    Ok(())
+}
+
+/// All accounts of type T owned by the prediction program (matched on the Anchor discriminator).
+fn fetch_all<T: AccountDeserialize + Discriminator>(program: &Program<Rc<Keypair>>) -> anyhow::Result<Vec<(Pubkey, T)>> {
+    let filter = RpcFilterType::Memcmp(Memcmp::new_base58_encoded(0, &T::discriminator()));
+    let raw = program.rpc().get_program_accounts_with_config(
+        &prediction::ID,
+        anchor_client::solana_client::rpc_config::RpcProgramAccountsConfig {
+            filters: Some(vec![filter]),
+            ..Default::default()
+        },
+    )?;
+    Ok(raw
+        .into_iter()
+        .filter_map(|(k, acc)| T::try_deserialize(&mut acc.data.as_slice()).ok().map(|t| (k, t)))
+        .collect())
+}
+
+/// SingleBet accounts of one game pool (`pool` follows the 8-byte discriminator and `bettor`).
+fn fetch_bets(program: &Program<Rc<Keypair>>, game_pool: &Pubkey) -> anyhow::Result<Vec<(Pubkey, SingleBet)>> {
+    let filters = vec![
+        RpcFilterType::Memcmp(Memcmp::new_base58_encoded(0, &SingleBet::discriminator())),
+        RpcFilterType::Memcmp(Memcmp::new_base58_encoded(8 + 32, game_pool.as_ref())),
+    ];
+    let raw = program.rpc().get_program_accounts_with_config(
+        &prediction::ID,
+        anchor_client::solana_client::rpc_config::RpcProgramAccountsConfig {
+            filters: Some(filters),
+            ..Default::default()
+        },
+    )?;
+    Ok(raw
+        .into_iter()
+        .filter_map(|(k, acc)| SingleBet::try_deserialize(&mut acc.data.as_slice()).ok().map(|b| (k, b)))
+        .collect())
+}
+
+/// (state, has_winner) of a Battle account, parsed by the program's own BattleSnapshot reader. None only
+/// when the account is gone or no longer a battle; RPC failures are errors, not "battle closed".
+fn battle_state(program: &Program<Rc<Keypair>>, battle: &Pubkey) -> anyhow::Result<Option<(u8, bool)>> {
+    let Some(acc) = program.rpc().get_account_with_commitment(battle, CommitmentConfig::confirmed())?.value else {
+        return Ok(None);
+    };
+    if acc.owner != BATTLECHAIN_PROGRAM_ID {
+        return Ok(None);
+    }
+    Ok(parse_battle_snapshot(&acc.data).ok().map(|snap| (snap.state, snap.winner_present == 1)))
+}
+
+/// The season leaderboard results are currently booked into.
+fn leaderboard_for(pool_key: &Pubkey, pool: &ParlayPool) -> Pubkey {
+    Pubkey::find_program_address(&[b"leaderboard", pool_key.as_ref(), &pool.current_season.to_le_bytes()], &prediction::ID).0
+}
+
+fn record_results(
+    program: &Program<Rc<Keypair>>,
+    payer: &Keypair,
+    pool_key: &Pubkey,
+    pool: &ParlayPool,
+    game_pool: &Pubkey,
+    bets: &[&(Pubkey, SingleBet)],
+) -> anyhow::Result<String> {
+    let mut remaining = Vec::with_capacity(bets.len() * 2);
+    for (key, bet) in bets {
+        let stats = Pubkey::find_program_address(&[b"predictor", bet.bettor.as_ref()], &prediction::ID).0;
+        remaining.push(AccountMeta::new(*key, false));
+        remaining.push(AccountMeta::new(stats, false));
+    }
+    let sig = program
+        .request()
+        .accounts(prediction::accounts::RecordSingleResults {
+            parlay_pool: *pool_key,
+            game_pool: *game_pool,
+            leaderboard: leaderboard_for(pool_key, pool),
+            cranker: payer.pubkey(),
+        })
+        .accounts(remaining)
+        .args(prediction::instruction::RecordSingleResults {})
+        .send()?;
+    Ok(sig.to_string())
+}
+
+fn void_game_pool(program: &Program<Rc<Keypair>>, payer: &Keypair, key: &Pubkey, game: &GamePool) -> anyhow::Result<String> {
+    let sig = program
+        .request()
+        .accounts(prediction::accounts::VoidGamePool {
+            parlay_pool: game.parlay_pool,
+            game_pool: *key,
+            battle: game.pool_id,
+            cranker: payer.pubkey(),
+        })
+        .args(prediction::instruction::VoidGamePool {})
+        .send()?;
+    Ok(sig.to_string())
+}
+
+fn settle_game_pool(program: &Program<Rc<Keypair>>, payer: &Keypair, key: &Pubkey, game: &GamePool) -> anyhow::Result<String> {
+    let sig = program
+        .request()
+        .accounts(prediction::accounts::SettleSinglePool {
+            parlay_pool: game.parlay_pool,
+            game_pool: *key,
+            battle: game.pool_id,
+            cranker: payer.pubkey(),
+            // SPL markets pay the crank fee from the pool escrow ATA into the keeper's ATA
+            game_pool_escrow: game.token_mint.map(|mint| get_associated_token_address(key, &mint)),
+            cranker_ata: game.token_mint.map(|mint| get_associated_token_address(&payer.pubkey(), &mint)),
+            token_program: anchor_spl::token::ID,
+            system_program: anchor_client::solana_sdk::system_program::ID,
+        })
+        .args(prediction::instruction::SettleSinglePool {})
+        .send()?;
+    Ok(sig.to_string())
+}
+
+/// `payer` must be the parlay pool's authority (resolve_parlay_ticket rejects anyone else).
+fn resolve_ticket(
+    program: &Program<Rc<Keypair>>,
+    payer: &Keypair,
+    pool_key: &Pubkey,
+    pool: &ParlayPool,
+    ticket_key: &Pubkey,
+    ticket: &ParlayTicket,
+    won: bool,
+) -> anyhow::Result<String> {
+    let pda = |seeds: &[&[u8]]| Pubkey::find_program_address(seeds, &prediction::ID).0;
+    let exists = |key: Pubkey| -> anyhow::Result<Option<Pubkey>> {
+        Ok(program.rpc().get_account_with_commitment(&key, CommitmentConfig::confirmed())?.value.map(|_| key))
+    };
+    let owner = ticket.owner;
+    // required by the program once the fund exists
+    let insurance_fund = pool.insurance_enabled.then(|| pda(&[b"insurance", pool_key.as_ref()]));
+    let bettor_stats = exists(pda(&[b"bettor_stats", owner.as_ref(), pool_key.as_ref()]))?;
+    // a bound referrer is credited on the loss fee; the per-(referrer, pool) balance is opened on its first credit
+    let (referral, referrer_balance) = match exists(pda(&[b"referral", owner.as_ref()]))? {
+        Some(referral_key) => {
+            let r: Referral = program.account(referral_key)?;
+            (Some(referral_key), Some(pda(&[b"referrer", r.referrer.as_ref(), pool_key.as_ref()])))
+        }
+        None => (None, None),
+    };
+    let sig = program
+        .request()
+        .accounts(prediction::accounts::ResolveParlayTicket {
+            parlay_pool: *pool_key,
+            parlay_ticket: *ticket_key,
+            insurance_fund,
+            bettor_stats,
+            predictor_stats: pda(&[b"predictor", owner.as_ref()]),
+            referral,
+            referrer_balance,
+            leaderboard: leaderboard_for(pool_key, pool),
+            signer: payer.pubkey(),
+            system_program: anchor_client::solana_sdk::system_program::ID,
+        })
+        .args(prediction::instruction::ResolveParlayTicket { won })
+        .send()?;
+    Ok(sig.to_string())
+}
@@ -14,10 +14,19 @@ pub mod prediction {
     use super::*;
 
     // -------------------------
-    // Parlay pool initialization (singleton)
+    // Pool registry + per-mint parlay pools
     // -------------------------
-    /// Initialize the global parlay pool. `token_mint = None` => SOL pool (lamports)
-    /// `token_mint = Some(mint)` => SPL pool for that mint
+    /// Create the registry that lists every parlay pool (one per currency).
+    pub fn initialize_pool_registry(ctx: Context<InitializePoolRegistry>) -> Result<()> {
+        let registry = &mut ctx.accounts.registry;
+        registry.authority = ctx.accounts.authority.key();
+        registry.pools = Vec::new();
+        registry.bump = *ctx.bumps.get("registry").unwrap_or(&0);
+        Ok(())
+    }
+
+    /// Initialize the parlay pool for a currency. `token_mint = None` => SOL pool (lamports, seeds ["parlay_pool", "native"])
+    /// `token_mint = Some(mint)` => SPL pool for that mint (seeds ["parlay_pool", mint])
     pub fn initialize_parlay_pool(
         ctx: Context<InitializeParlayPool>,
         token_mint: Option<Pubkey>,
@@ -32,14 +41,275 @@ pub mod prediction {
         pool.liquidity_balance = 0;
         pool.liquidity_floor = liquidity_floor;
         pool.protocol_reserve = 0;
+        pool.total_shares = 0;
+        pool.total_fee_weight = 0;
+        pool.lp_mint = None;
+        pool.lp_fee_share_bps = 0;
+        pool.acc_fee_per_share = 0;
+        pool.lp_fee_reserve = 0;
+        pool.pending_payouts = 0;
+        pool.referral_fee_bps = 0;
+        pool.referral_owed = 0;
+        pool.drawdown_limit_bps = 0;
+        pool.drawdown_window_secs = 0;
+        pool.window_start_ts = 0;
+        pool.window_start_liquidity = 0;
+        pool.bets_paused = false;
+        pool.guardian = ctx.accounts.authority.key();
+        pool.placement_paused = false;
+        pool.settlement_paused = false;
+        pool.withdrawals_paused = false;
+        pool.crank_fee = 0;
+        pool.arbiter = ctx.accounts.authority.key();
+        pool.dispute_window_secs = 0;
+        pool.dispute_bond = 0;
+        pool.promo_budget = 0;
+        pool.max_pool_liability = 0;
+        pool.haircut_debt = 0;
+        pool.haircut_reserve = 0;
+        pool.compliance_flags = 0;
+        pool.insurance_enabled = false;
+        pool.escrowed_fees = 0;
         pool.protocol_fee_bps = protocol_fee_bps;
         pool.min_stake = min_stake;
         pool.max_multiplier_x100 = max_multiplier_x100;
+        pool.current_season = 0;
         pool.bump = *ctx.bumps.get("parlay_pool").unwrap_or(&0);
+
+        // season 0 is an inactive placeholder so result recording always has a board to book into
+        let board = &mut ctx.accounts.leaderboard;
+        board.pool = ctx.accounts.parlay_pool.key();
+        board.season = 0;
+        board.start_ts = 0;
+        board.end_ts = 0;
+        board.entries = Vec::new();
+        board.bump = *ctx.bumps.get("leaderboard").unwrap_or(&0);
+
+        let registry = &mut ctx.accounts.registry;
+        require!(registry.authority == ctx.accounts.authority.key(), PredictionError::Unauthorized);
+        require!(registry.pools.len() < MAX_PARLAY_POOLS, PredictionError::RegistryFull);
+        registry.pools.push(PoolEntry { token_mint, pool: ctx.accounts.parlay_pool.key() });
         emit!(ParlayPoolCreated { pool: ctx.accounts.parlay_pool.key(), token_mint });
         Ok(())
     }
 
+    /// Set the slice (bps) of parlay-side protocol fees routed to liquidity providers.
+    pub fn set_lp_fee_share(ctx: Context<UpdateParlayConfig>, lp_fee_share_bps: u16) -> Result<()> {
+        require!(lp_fee_share_bps <= 10_000, PredictionError::InvalidArgs);
+        ctx.accounts.parlay_pool.lp_fee_share_bps = lp_fee_share_bps;
+        emit!(LpFeeShareUpdated { pool: ctx.accounts.parlay_pool.key(), lp_fee_share_bps });
+        Ok(())
+    }
+
+    /// Configure the drawdown circuit breaker: if liquidity falls more than `drawdown_limit_bps`
+    /// below its level at the start of the current `window_secs` window, new bets are paused.
+    /// `drawdown_limit_bps = 0` disables the breaker.
+    pub fn set_circuit_breaker(ctx: Context<UpdateParlayConfig>, drawdown_limit_bps: u16, window_secs: i64) -> Result<()> {
+        require!(drawdown_limit_bps <= 10_000 && window_secs >= 0, PredictionError::InvalidArgs);
+        let pool = &mut ctx.accounts.parlay_pool;
+        pool.drawdown_limit_bps = drawdown_limit_bps;
+        pool.drawdown_window_secs = window_secs;
+        pool.window_start_ts = Clock::get()?.unix_timestamp;
+        pool.window_start_liquidity = pool.liquidity_balance;
+        emit!(CircuitBreakerConfigured { pool: pool.key(), drawdown_limit_bps, window_secs });
+        Ok(())
+    }
+
+    /// Resume bet placement after the circuit breaker tripped. Starts a fresh drawdown window.
+    pub fn resume_betting(ctx: Context<UpdateParlayConfig>) -> Result<()> {
+        let pool = &mut ctx.accounts.parlay_pool;
+        pool.bets_paused = false;
+        pool.window_start_ts = Clock::get()?.unix_timestamp;
+        pool.window_start_liquidity = pool.liquidity_balance;
+        emit!(BettingResumed { pool: pool.key(), liquidity: pool.liquidity_balance });
+        Ok(())
+    }
+
+    // -------------------------
+    // Guardian pause
+    // -------------------------
+    /// Authority sets the guardian key allowed to pause (but not unpause) the pool.
+    pub fn set_guardian(ctx: Context<UpdateParlayConfig>, guardian: Pubkey) -> Result<()> {
+        ctx.accounts.parlay_pool.guardian = guardian;
+        emit!(GuardianUpdated { pool: ctx.accounts.parlay_pool.key(), guardian });
+        Ok(())
+    }
+
+    /// Guardian (or authority) pauses any combination of placement / settlement / withdrawals immediately.
+    pub fn guardian_pause(ctx: Context<GuardianPause>, placement: bool, settlement: bool, withdrawals: bool) -> Result<()> {
+        let pool = &mut ctx.accounts.parlay_pool;
+        let signer = ctx.accounts.signer.key();
+        require!(signer == pool.guardian || signer == pool.authority, PredictionError::Unauthorized);
+        pool.placement_paused |= placement;
+        pool.settlement_paused |= settlement;
+        pool.withdrawals_paused |= withdrawals;
+        emit!(PauseStateChanged { pool: pool.key(), placement: pool.placement_paused, settlement: pool.settlement_paused, withdrawals: pool.withdrawals_paused });
+        Ok(())
+    }
+
+    /// Only the authority lifts pauses; each flag passed as true is cleared.
+    pub fn unpause(ctx: Context<UpdateParlayConfig>, placement: bool, settlement: bool, withdrawals: bool) -> Result<()> {
+        let pool = &mut ctx.accounts.parlay_pool;
+        pool.placement_paused &= !placement;
+        pool.settlement_paused &= !settlement;
+        pool.withdrawals_paused &= !withdrawals;
+        emit!(PauseStateChanged { pool: pool.key(), placement: pool.placement_paused, settlement: pool.settlement_paused, withdrawals: pool.withdrawals_paused });
+        Ok(())
+    }
+
+    /// Move protocol fees out of the parlay pool to a treasury. Before transferring, protocol_reserve is
+    /// reconciled against the real vault balance minus everything owed to others (LP liquidity, LP fees,
+    /// resolved-but-unclaimed payouts, insurance), since some fee paths only bump the counter.
+    pub fn withdraw_reserve(ctx: Context<WithdrawReserve>, amount: u64) -> Result<()> {
+        let actual = match ctx.accounts.parlay_pool.token_mint {
+            None => {
+                let rent_min = Rent::get()?.minimum_balance(8 + ParlayPool::INIT_SPACE);
+                ctx.accounts.parlay_pool.to_account_info().lamports().saturating_sub(rent_min)
+            }
+            Some(_) => ctx.accounts.parlay_vault_ata.as_ref().ok_or(PredictionError::InvalidArgs)?.amount,
+        };
+        // the fund's balance backs insurance draws, so it can't be left out of the solvency check
+        require!(!ctx.accounts.parlay_pool.insurance_enabled || ctx.accounts.insurance_fund.is_some(), PredictionError::MissingInsuranceFund);
+        let insurance = ctx.accounts.insurance_fund.as_ref().map(|f| f.balance).unwrap_or(0);
+        let pool = &mut ctx.accounts.parlay_pool;
+        let owed = pool.liquidity_balance
+            .saturating_add(pool.lp_fee_reserve)
+            .saturating_add(pool.pending_payouts)
+            .saturating_add(pool.referral_owed)
+            .saturating_add(pool.promo_budget)
+            .saturating_add(pool.haircut_reserve)
+            .saturating_add(insurance);
+        // fees still in game pool escrows are counted in the ledgers above but not in `actual`
+        let backed = actual.saturating_add(pool.escrowed_fees).saturating_sub(owed);
+        if pool.protocol_reserve > backed {
+            emit!(ReserveReconciled { pool: pool.key(), counter: pool.protocol_reserve, backed });
+            pool.protocol_reserve = backed;
+        }
+        require!(amount > 0 && amount <= vault_backed_reserve(pool), PredictionError::InsufficientReserve);
+        pool.protocol_reserve = pool.protocol_reserve.saturating_sub(amount);
+
+        match ctx.accounts.parlay_pool.token_mint {
+            None => {
+                invoke_signed(
+                    &system_instruction::transfer(&ctx.accounts.parlay_pool.key(), &ctx.accounts.treasury.key(), amount),
+                    &[ctx.accounts.parlay_pool.to_account_info(), ctx.accounts.treasury.to_account_info()],
+                    &[&[b"parlay_pool", ctx.accounts.parlay_pool.mint_seed(), &[ctx.accounts.parlay_pool.bump]]],
+                )?;
+            }
+            Some(_) => {
+                let cpi_accounts = token::Transfer {
+                    from: ctx.accounts.parlay_vault_ata.to_account_info(),
+                    to: ctx.accounts.treasury_ata.to_account_info(),
+                    authority: ctx.accounts.parlay_pool.to_account_info(),
+                };
+                let bump = [ctx.accounts.parlay_pool.bump];
+                let signer_seeds = &[&[b"parlay_pool", ctx.accounts.parlay_pool.mint_seed(), &bump][..]];
+                token::transfer(CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds), amount)?;
+            }
+        }
+
+        emit!(ReserveWithdrawn { pool: ctx.accounts.parlay_pool.key(), treasury: ctx.accounts.treasury.key(), amount });
+        Ok(())
+    }
+
+    /// Audit the pool's counters against real balances. Anyone can run it to emit reconciliation events;
+    /// with `correct = true` (authority only) the counters are rewritten to match. The parlay vault must hold
+    /// liquidity + every sub-ledger (less claim fees still in game pool escrows); a shortfall is absorbed by protocol_reserve first, then liquidity, and a
+    /// surplus is credited to protocol_reserve. Unsettled game pools are passed in remaining_accounts
+    /// ([game_pool] for SOL, [game_pool, game_pool_escrow] for SPL) and their total_staked is checked too.
+    pub fn reconcile_pool<'info>(ctx: Context<'_, '_, '_, 'info, ReconcilePool<'info>>, correct: bool) -> Result<()> {
+        let is_authority = ctx.accounts.auditor.key() == ctx.accounts.parlay_pool.authority;
+        require!(!correct || is_authority, PredictionError::Unauthorized);
+        let actual = match ctx.accounts.parlay_pool.token_mint {
+            None => {
+                let rent_min = Rent::get()?.minimum_balance(8 + ParlayPool::INIT_SPACE);
+                ctx.accounts.parlay_pool.to_account_info().lamports().saturating_sub(rent_min)
+            }
+            Some(_) => ctx.accounts.parlay_vault_ata.as_ref().ok_or(PredictionError::InvalidArgs)?.amount,
+        };
+        require!(!ctx.accounts.parlay_pool.insurance_enabled || ctx.accounts.insurance_fund.is_some(), PredictionError::MissingInsuranceFund);
+        let insurance = ctx.accounts.insurance_fund.as_ref().map(|f| f.balance).unwrap_or(0);
+        let pool = &mut ctx.accounts.parlay_pool;
+        let expected = pool.liquidity_balance
+            .saturating_add(pool.protocol_reserve)
+            .saturating_add(pool.lp_fee_reserve)
+            .saturating_add(pool.pending_payouts)
+            .saturating_add(pool.referral_owed)
+            .saturating_add(pool.promo_budget)
+            .saturating_add(pool.haircut_reserve)
+            .saturating_add(insurance)
+            .saturating_sub(pool.escrowed_fees);
+        emit!(PoolReconciled { pool: pool.key(), expected, actual, corrected: correct && expected != actual });
+        if correct {
+            if actual < expected {
+                let mut shortfall = expected - actual;
+                let from_reserve = shortfall.min(pool.protocol_reserve);
+                pool.protocol_reserve -= from_reserve;
+                shortfall -= from_reserve;
+                pool.liquidity_balance = pool.liquidity_balance.saturating_sub(shortfall);
+            } else {
+                pool.protocol_reserve = pool.protocol_reserve.saturating_add(actual - expected);
+            }
+        }
+
+        let parlay_key = ctx.accounts.parlay_pool.key();
+        let stride = if ctx.accounts.parlay_pool.token_mint.is_some() { 2 } else { 1 };
+        require!(ctx.remaining_accounts.len() % stride == 0, PredictionError::InvalidArgs);
+        for chunk in ctx.remaining_accounts.chunks(stride) {
+            let mut game: Account<'info, GamePool> = Account::try_from(&chunk[0])?;
+            require!(game.parlay_pool == parlay_key, PredictionError::InvalidPool);
+            // after settlement total_staked is a payout snapshot, not a balance
+            if game.is_settled {
+                continue;
+            }
+            let held = match game.token_mint {
+                None => {
+                    let rent_min = Rent::get()?.minimum_balance(8 + GamePool::INIT_SPACE);
+                    chunk[0].lamports().saturating_sub(rent_min)
+                }
+                Some(_) => {
+                    let escrow: Account<'info, TokenAccount> = Account::try_from(&chunk[1])?;
+                    require!(escrow.owner == game.key() && Some(escrow.mint) == game.token_mint, PredictionError::InvalidTokenAccount);
+                    escrow.amount
+                }
+            };
+            emit!(GamePoolReconciled { pool: game.pool_id, expected: game.total_staked, actual: held, corrected: correct && held != game.total_staked });
+            if correct && held != game.total_staked {
+                game.total_staked = held;
+                game.exit(&crate::ID)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Create the insurance fund for the parlay pool. `fee_share_bps` of every losing-ticket fee is
+    /// routed into it; winning payouts that would breach the liquidity floor draw from it first.
+    pub fn initialize_insurance_fund(ctx: Context<InitializeInsuranceFund>, fee_share_bps: u16) -> Result<()> {
+        require!(fee_share_bps <= 10_000, PredictionError::InvalidArgs);
+        let fund_key = ctx.accounts.insurance_fund.key();
+        let pool_key = ctx.accounts.parlay_pool.key();
+        let fund = &mut ctx.accounts.insurance_fund;
+        fund.pool = pool_key;
+        fund.balance = 0;
+        fund.total_drawn = 0;
+        fund.fee_share_bps = fee_share_bps;
+        fund.bump = *ctx.bumps.get("insurance_fund").unwrap_or(&0);
+        ctx.accounts.parlay_pool.insurance_enabled = true;
+        emit!(InsuranceFundCreated { fund: fund_key, pool: pool_key, fee_share_bps });
+        Ok(())
+    }
+
+    /// Create the LP mint for the parlay pool. Mint authority is the parlay pool PDA, so LP tokens
+    /// can only be minted/burned alongside internal share accounting.
+    pub fn initialize_lp_mint(ctx: Context<InitializeLpMint>, _decimals: u8) -> Result<()> {
+        let pool = &mut ctx.accounts.parlay_pool;
+        require!(pool.lp_mint.is_none(), PredictionError::LpMintAlreadySet);
+        // existing shares (minted before the LP mint existed) stay internal-only
+        pool.lp_mint = Some(ctx.accounts.lp_mint.key());
+        emit!(LpMintCreated { pool: pool.key(), lp_mint: ctx.accounts.lp_mint.key() });
+        Ok(())
+    }
+
     // -------------------------
     // Place a single-game bet (per-battle)
     // -------------------------
@@ -53,20 +323,38 @@ pub mod prediction {
     ) -> Result<()> {
         let pool = &mut ctx.accounts.game_pool;
         let cfg = &ctx.accounts.parlay_pool; // reuse parlay_pool as global config (holds fee/min stake)
+        require!(!cfg.bets_paused, PredictionError::BettingPaused);
+        require!(!cfg.placement_paused, PredictionError::OperationPaused);
+        require!(cfg.compliance_flags == 0 && pool.compliance_flags == 0, PredictionError::MarketDelisted);
         require!(stake_amount >= cfg.min_stake, PredictionError::StakeTooSmall);
+        require!(chosen_outcome <= OUTCOME_NO_WINNER, PredictionError::InvalidArgs);
+        enforce_wallet_limits(&mut ctx.accounts.bettor_stats, &ctx.accounts.exclusion, stake_amount, Clock::get()?.unix_timestamp)?;
 
-        // Validate battle is in a state that allows betting (not Finished)
+        // Validate battle is in a state that allows betting (Waiting or Active; a Paused battle is frozen
+        // by its admin, so no new exposure is taken until it resumes)
         // We attempt to deserialize a minimal snapshot of your Battle account
         let battle_snapshot = deserialize_battle_snapshot(&ctx.accounts.battle)?;
         require!(battle_snapshot.state != BattleStateDiscriminant::Finished as u8, PredictionError::BattleClosed);
+        require!(battle_snapshot.state != BattleStateDiscriminant::Paused as u8, PredictionError::BattleClosed);
 
         // Initialize game pool if empty
         if pool.initialized == false {
             pool.pool_id = ctx.accounts.battle.key();
+            pool.parlay_pool = ctx.accounts.parlay_pool.key();
             pool.token_mint = ctx.accounts.parlay_pool.token_mint;
             pool.total_staked = 0;
             pool.is_settled = false;
             pool.winning_outcome = None;
+            pool.open_bets = 0;
+            pool.settled_at = 0;
+            pool.disputed = false;
+            pool.promo = false;
+            pool.promo_bonus_bps = 0;
+            pool.net_liability = 0;
+            pool.outcome_totals = [0; 3];
+            pool.unrecorded_bets = 0;
+            pool.voided = false;
+            pool.fees_booked = 0;
             pool.bump = *ctx.bumps.get("game_pool").unwrap_or(&0);
             pool.initialized = true;
         } else {
@@ -74,6 +362,21 @@ pub mod prediction {
             require!(!pool.is_settled, PredictionError::PoolAlreadySettled);
         }
 
+        // net exposure: a bettor on both sides only adds their worst-case net payout to the pool's liability
+        let position = &mut ctx.accounts.position;
+        if position.bettor == Pubkey::default() {
+            position.game_pool = pool.key();
+            position.bettor = ctx.accounts.bettor.key();
+            position.bump = *ctx.bumps.get("position").unwrap_or(&0);
+        }
+        let before = net_exposure(&position.stakes);
+        position.stakes[chosen_outcome as usize] = position.stakes[chosen_outcome as usize].saturating_add(stake_amount);
+        let after = net_exposure(&position.stakes);
+        pool.net_liability = pool.net_liability.saturating_sub(before).saturating_add(after);
+        if ctx.accounts.parlay_pool.max_pool_liability > 0 {
+            require!(pool.net_liability <= ctx.accounts.parlay_pool.max_pool_liability, PredictionError::ExposureCapExceeded);
+        }
+
         // Create Bet PDA (already created in accounts)
         let bet = &mut ctx.accounts.single_bet;
         bet.bettor = ctx.accounts.bettor.key();
@@ -81,8 +384,16 @@ pub mod prediction {
         bet.chosen_outcome = chosen_outcome;
         bet.stake = stake_amount;
         bet.claimed = false;
+        bet.recorded = false;
+        bet.streak = 0;
         bet.bump = *ctx.bumps.get("single_bet").unwrap_or(&0);
 
+        let stats = &mut ctx.accounts.predictor_stats;
+        if stats.wallet == Pubkey::default() {
+            stats.wallet = bet.bettor;
+            stats.bump = *ctx.bumps.get("predictor_stats").unwrap_or(&0);
+        }
+
         // Transfer stake into escrow (game_pool_escrow)
         match pool.token_mint {
             None => {
@@ -123,7 +434,21 @@ pub mod prediction {
             }
         }
 
-        emit!(SingleBetPlaced { pool: pool.pool_id, bettor: bet.bettor, stake: bet.stake, choice: bet.chosen_outcome });
+        pool.open_bets = pool.open_bets.saturating_add(1);
+        pool.unrecorded_bets = pool.unrecorded_bets.saturating_add(1);
+        let now = Clock::get()?.unix_timestamp;
+        record_volume(&mut ctx.accounts.bettor_stats, ctx.accounts.bettor.key(), ctx.accounts.parlay_pool.key(), stake_amount, now, *ctx.bumps.get("bettor_stats").unwrap_or(&0));
+        pool.outcome_totals[chosen_outcome as usize] = pool.outcome_totals[chosen_outcome as usize].saturating_add(stake_amount);
+        emit!(SingleBetPlaced {
+            version: EVENT_VERSION,
+            pool: pool.pool_id,
+            bettor: bet.bettor,
+            stake: bet.stake,
+            choice: bet.chosen_outcome,
+            outcome_totals: pool.outcome_totals,
+            implied_odds_x100: implied_odds_x100(pool),
+            pool_liquidity: pool.total_staked,
+        });
         Ok(())
     }
 
@@ -131,24 +456,179 @@ pub mod prediction {
     // Resolve single game pool (called after battle finished)
     // -------------------------
     /// Mark the winning outcome and lock pool snapshot for payouts.
-    /// This should be called by an oracle / admin or the Battle program (if integrated)
-    pub fn settle_single_pool(
-        ctx: Context<SettleSinglePool>,
-        winning_outcome: u8,
-    ) -> Result<()> {
+    /// Permissionless crank: the outcome is derived from the finished Battle account (see OUTCOME_*), and
+    /// the caller earns parlay_pool.crank_fee out of the game pool escrow (capped at the pool's stake).
+    pub fn settle_single_pool(ctx: Context<SettleSinglePool>) -> Result<()> {
+        require!(!ctx.accounts.parlay_pool.settlement_paused, PredictionError::OperationPaused);
         let pool = &mut ctx.accounts.game_pool;
         require!(pool.initialized && !pool.is_settled, PredictionError::PoolAlreadySettled);
+        require!(pool.pool_id == ctx.accounts.battle.key(), PredictionError::InvalidPool);
 
-        // Validate the passed battle is finished and matches chosen outcome (deserialization)
+        // Validate the passed battle is finished and read the winner from it
         let battle_snapshot = deserialize_battle_snapshot(&ctx.accounts.battle)?;
         require!(battle_snapshot.state == BattleStateDiscriminant::Finished as u8, PredictionError::BattleNotFinished);
+        let winning_outcome = battle_snapshot.outcome();
+
+        // crank reward comes out of the escrow before payouts are snapshotted
+        let crank_fee = ctx.accounts.parlay_pool.crank_fee.min(pool.total_staked);
+        if crank_fee > 0 {
+            let to = match pool.token_mint {
+                None => ctx.accounts.cranker.to_account_info(),
+                Some(_) => ctx.accounts.cranker_ata.as_ref().ok_or(PredictionError::InvalidArgs)?.to_account_info(),
+            };
+            let escrow = ctx.accounts.game_pool_escrow.as_ref().map(|e| e.to_account_info());
+            game_pool_transfer(pool, escrow, to, &ctx.accounts.token_program, crank_fee)?;
+            pool.total_staked = pool.total_staked.saturating_sub(crank_fee);
+        }
 
         // store winning side and snapshot liquidity
         pool.winning_outcome = Some(winning_outcome);
         pool.is_settled = true;
         pool.snapshot_liquidity = pool.total_staked;
+        pool.settled_at = Clock::get()?.unix_timestamp;
+        pool.disputed = false;
 
         emit!(SinglePoolSettled { pool: pool.pool_id, winning_outcome });
+        emit!(SettlementCranked { pool: pool.pool_id, cranker: ctx.accounts.cranker.key(), fee: crank_fee });
+        Ok(())
+    }
+
+    /// Void a market whose battle account was closed before the pool settled. Permissionless: the pool is
+    /// marked settled with no outcome, nothing is booked to PredictorStats, and every SingleBet claims its
+    /// full stake back (no fee) through claim_single / claim_many.
+    pub fn void_game_pool(ctx: Context<VoidGamePool>) -> Result<()> {
+        let pool = &mut ctx.accounts.game_pool;
+        require!(pool.initialized && !pool.is_settled, PredictionError::PoolAlreadySettled);
+        let battle = &ctx.accounts.battle;
+        require!(battle.data_is_empty() || battle.owner != &BATTLECHAIN_PROGRAM_ID, PredictionError::BattleStillOpen);
+
+        pool.voided = true;
+        pool.is_settled = true;
+        pool.winning_outcome = None;
+        pool.snapshot_liquidity = pool.total_staked;
+        pool.settled_at = Clock::get()?.unix_timestamp;
+        pool.disputed = false;
+        pool.net_liability = 0;
+        pool.unrecorded_bets = 0;
+
+        emit!(GamePoolVoided { pool: pool.pool_id, open_bets: pool.open_bets, refundable: pool.total_staked });
+        Ok(())
+    }
+
+    // -------------------------
+    // Settlement disputes
+    // -------------------------
+    /// Authority configures the arbiter, the post-settlement challenge window (0 disables disputes and
+    /// the claim delay) and the SOL bond a challenger must post.
+    pub fn set_dispute_config(ctx: Context<UpdateParlayConfig>, arbiter: Pubkey, window_secs: i64, bond_lamports: u64) -> Result<()> {
+        require!(window_secs >= 0, PredictionError::InvalidArgs);
+        let pool = &mut ctx.accounts.parlay_pool;
+        pool.arbiter = arbiter;
+        pool.dispute_window_secs = window_secs;
+        pool.dispute_bond = bond_lamports;
+        emit!(DisputeConfigUpdated { pool: pool.key(), arbiter, window_secs, bond_lamports });
+        Ok(())
+    }
+
+    /// Flag a settled game pool's outcome as wrong within the challenge window. The bond (lamports) is
+    /// escrowed in the Dispute PDA and claims stay frozen until the arbiter rules.
+    pub fn raise_dispute(ctx: Context<RaiseDispute>, proposed_outcome: u8) -> Result<()> {
+        let pool = &mut ctx.accounts.game_pool;
+        let window = ctx.accounts.parlay_pool.dispute_window_secs;
+        let now = Clock::get()?.unix_timestamp;
+        require!(pool.is_settled && !pool.disputed && !pool.voided, PredictionError::InvalidArgs);
+        require!(window > 0 && now < pool.settled_at.saturating_add(window), PredictionError::DisputeWindowClosed);
+        require!(pool.winning_outcome != Some(proposed_outcome), PredictionError::InvalidArgs);
+
+        let bond = ctx.accounts.parlay_pool.dispute_bond;
+        if bond > 0 {
+            anchor_lang::system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    anchor_lang::system_program::Transfer {
+                        from: ctx.accounts.challenger.to_account_info(),
+                        to: ctx.accounts.dispute.to_account_info(),
+                    },
+                ),
+                bond,
+            )?;
+        }
+
+        let dispute = &mut ctx.accounts.dispute;
+        dispute.game_pool = pool.key();
+        dispute.challenger = ctx.accounts.challenger.key();
+        dispute.proposed_outcome = proposed_outcome;
+        dispute.bond = bond;
+        dispute.opened_at = now;
+        dispute.bump = *ctx.bumps.get("dispute").unwrap_or(&0);
+        pool.disputed = true;
+
+        emit!(DisputeRaised { pool: pool.pool_id, challenger: dispute.challenger, proposed_outcome, bond });
+        Ok(())
+    }
+
+    /// Arbiter rules on a dispute. Upheld: the outcome is replaced and the challenger gets the bond back.
+    /// Rejected: the bond goes to the pool authority. Either way the Dispute account is closed and the
+    /// claim delay restarts from now.
+    pub fn resolve_dispute(ctx: Context<ResolveDispute>, uphold: bool) -> Result<()> {
+        let pool = &mut ctx.accounts.game_pool;
+        require!(pool.disputed, PredictionError::InvalidArgs);
+        let dispute = &ctx.accounts.dispute;
+        if uphold {
+            pool.winning_outcome = Some(dispute.proposed_outcome);
+        }
+        pool.disputed = false;
+        pool.settled_at = Clock::get()?.unix_timestamp;
+
+        let recipient = if uphold { ctx.accounts.challenger.to_account_info() } else { ctx.accounts.authority.to_account_info() };
+        emit!(DisputeResolved { pool: pool.pool_id, challenger: dispute.challenger, upheld: uphold, winning_outcome: pool.winning_outcome.unwrap_or(OUTCOME_NO_WINNER) });
+        ctx.accounts.dispute.close(recipient)?;
+        Ok(())
+    }
+
+    // -------------------------
+    // Record single-bet results
+    // -------------------------
+    /// Permissionless crank that books every bet of a settled game pool into its owner's PredictorStats
+    /// (record, single-pick streak, leaderboard) once the dispute window has passed. Claims on the pool stay
+    /// locked until all of its bets are recorded, so a losing pick can't be kept off the record by never
+    /// claiming it. Wins are booked at the 2x market payout, before fees and bonuses.
+    /// remaining_accounts per bet: [single_bet, predictor_stats], up to MAX_CLAIM_BATCH pairs.
+    pub fn record_single_results<'info>(ctx: Context<'_, '_, '_, 'info, RecordSingleResults<'info>>) -> Result<()> {
+        let remaining = ctx.remaining_accounts;
+        require!(!remaining.is_empty() && remaining.len() % 2 == 0, PredictionError::InvalidArgs);
+        require!(remaining.len() / 2 <= MAX_CLAIM_BATCH, PredictionError::InvalidArgs);
+        let pool = &mut ctx.accounts.game_pool;
+        require!(pool.is_settled && !pool.voided, PredictionError::PoolNotSettled);
+        require!(claims_open(pool, ctx.accounts.parlay_pool.dispute_window_secs, Clock::get()?.unix_timestamp), PredictionError::ClaimsLocked);
+        let season = Some(ctx.accounts.leaderboard.season);
+
+        for pair in remaining.chunks(2) {
+            let mut bet: Account<'info, SingleBet> = Account::try_from(&pair[0])?;
+            let mut stats: Account<'info, PredictorStats> = Account::try_from(&pair[1])?;
+            require!(bet.pool == pool.key(), PredictionError::InvalidPool);
+            require!(!bet.recorded, PredictionError::AlreadyRecorded);
+            let stats_bump = stats.bump;
+            let expected = Pubkey::create_program_address(&[b"predictor", bet.bettor.as_ref(), &[stats_bump]], &crate::ID).map_err(|_| error!(PredictionError::InvalidArgs))?;
+            require!(stats.key() == expected, PredictionError::InvalidArgs);
+
+            let won = pool.winning_outcome == Some(bet.chosen_outcome);
+            let returned = if won { bet.stake.saturating_mul(2) } else { 0 };
+            record_prediction(&mut stats, bet.bettor, bet.stake, returned, stats_bump, season)?;
+            if won {
+                // the bonus on this claim is priced off the streak it extends
+                bet.streak = stats.single_streak;
+                stats.single_streak = stats.single_streak.saturating_add(1);
+            } else {
+                stats.single_streak = 0;
+            }
+            bet.recorded = true;
+            pool.unrecorded_bets = pool.unrecorded_bets.saturating_sub(1);
+            update_leaderboard(&mut ctx.accounts.leaderboard, &stats);
+            emit!(SingleResultRecorded { bettor: bet.bettor, pool: pool.pool_id, won, single_streak: stats.single_streak });
+            bet.exit(&crate::ID)?;
+            stats.exit(&crate::ID)?;
+        }
         Ok(())
     }
 
@@ -158,107 +638,265 @@ pub mod prediction {
     /// Bettor can claim a single bet. If they are a winner they may:
     /// - withdraw immediately (receive snapshot payout)
     /// - OR restake into global parlay pool by creating a restake position.
+    /// Either way the fee is booked (referral cut, protocol_reserve, rebates) the same as in claim_many.
     pub fn claim_single(
         ctx: Context<ClaimSingle>,
         restake_into_parlay: bool,
     ) -> Result<()> {
+        require!(!ctx.accounts.parlay_pool.withdrawals_paused, PredictionError::OperationPaused);
         let pool = &mut ctx.accounts.game_pool;
         let bet = &mut ctx.accounts.single_bet;
         require!(pool.is_settled, PredictionError::PoolNotSettled);
+        require!(claims_open(pool, ctx.accounts.parlay_pool.dispute_window_secs, Clock::get()?.unix_timestamp), PredictionError::ClaimsLocked);
+        require!(pool.unrecorded_bets == 0, PredictionError::ResultsNotRecorded);
         require!(!bet.claimed, PredictionError::AlreadyClaimed);
-
-        // determine winners/lossers
-        let is_winner = match pool.winning_outcome {
-            Some(w) => w == bet.chosen_outcome,
-            None => false,
-        };
-
-        if !is_winner {
-            // losers get nothing (their stake already in pool). Mark claimed to avoid double spend.
-            bet.claimed = true;
-            emit!(SingleClaimed { bettor: bet.bettor, pool: pool.pool_id, payout: 0 });
+        // every path below consumes the bet; the SingleBet account is closed to the bettor on return
+        pool.open_bets = pool.open_bets.saturating_sub(1);
+        bet.claimed = true;
+
+        // losers get nothing (their stake already in pool), a voided market refunds the stake; see price_single_claim
+        let claim = price_single_claim(
+            pool,
+            bet,
+            ctx.accounts.position.as_ref(),
+            ctx.accounts.parlay_pool.protocol_fee_bps,
+            vault_backed_reserve(&ctx.accounts.parlay_pool),
+            ctx.accounts.parlay_pool.promo_budget,
+        )?;
+        // streak + promo bonuses sit in the parlay vault (protocol_reserve / promo_budget)
+        let parlay_pool = &mut ctx.accounts.parlay_pool;
+        parlay_pool.protocol_reserve = parlay_pool.protocol_reserve.saturating_sub(claim.streak_bonus);
+        parlay_pool.promo_budget = parlay_pool.promo_budget.saturating_sub(claim.promo_bonus);
+        emit_claim_bonuses(bet, pool.pool_id, &claim);
+        let vault_bonus = claim.streak_bonus.saturating_add(claim.promo_bonus);
+        let bettor_key = ctx.accounts.bettor.key();
+
+        if restake_into_parlay && claim.payout > 0 {
+            // move the payout from the game pool escrow into the parlay vault, then own it as liquidity
+            let to = match pool.token_mint {
+                None => ctx.accounts.parlay_pool.to_account_info(),
+                Some(_) => ctx.accounts.parlay_vault_ata.as_ref().ok_or(PredictionError::InvalidArgs)?.to_account_info(),
+            };
+            let escrow = ctx.accounts.game_pool_escrow.as_ref().map(|e| e.to_account_info());
+            game_pool_transfer(pool, escrow, to, &ctx.accounts.token_program, claim.payout)?;
+            let restake_bump = *ctx.bumps.get("restake_pos").unwrap_or(&0);
+            let minted = restake_claim(
+                &mut ctx.accounts.parlay_pool,
+                &mut ctx.accounts.restake_pos,
+                bettor_key,
+                claim.payout,
+                vault_bonus,
+                restake_bump,
+                &ctx.accounts.lp_mint,
+                &ctx.accounts.bettor_lp_ata,
+                &ctx.accounts.token_program,
+            )?;
+            book_single_fee(
+                &mut ctx.accounts.parlay_pool,
+                ctx.accounts.referral.as_ref(),
+                ctx.accounts.referrer_balance.as_deref(),
+                &ctx.accounts.bettor.to_account_info(),
+                &ctx.accounts.system_program.to_account_info(),
+                ctx.accounts.bettor_stats.as_mut(),
+                bettor_key,
+                claim.fee,
+            )?;
+            emit!(SingleClaimedRestaked { version: EVENT_VERSION, bettor: bettor_key, pool: pool.pool_id, restake_amt: claim.payout.saturating_add(vault_bonus), shares_minted: minted, fee: claim.fee, pool_liquidity: ctx.accounts.parlay_pool.liquidity_balance });
             return Ok(());
         }
 
-        // compute payout: winners share losing stakes.
-        // For simplicity: payout = bet.stake + (losers_total * bet.stake / winners_total)
-        // We must iterate bets to compute totals -- here we assume an off-chain indexer or we store aggregated totals.
-        // For MVP, we assume pool stores totals per outcome (not implemented in minimal code; this is conceptual).
-        // We'll compute a naive payout: payout = stake * 2 (50/50). In production replace with aggregated accounting.
-        let naive_payout = bet.stake.saturating_mul(2);
+        // Pay out to bettor: the payout from the game pool escrow, bonuses from the parlay vault
+        let bettor_to = match pool.token_mint {
+            None => ctx.accounts.bettor.to_account_info(),
+            Some(_) => ctx.accounts.bettor_ata.as_ref().ok_or(PredictionError::InvalidArgs)?.to_account_info(),
+        };
+        let escrow = ctx.accounts.game_pool_escrow.as_ref().map(|e| e.to_account_info());
+        let vault = ctx.accounts.parlay_vault_ata.as_ref().map(|v| v.to_account_info());
+        game_pool_transfer(pool, escrow, bettor_to.clone(), &ctx.accounts.token_program, claim.payout)?;
+        parlay_pool_transfer(&ctx.accounts.parlay_pool, vault, bettor_to, &ctx.accounts.token_program, vault_bonus)?;
+        book_single_fee(
+            &mut ctx.accounts.parlay_pool,
+            ctx.accounts.referral.as_ref(),
+            ctx.accounts.referrer_balance.as_deref(),
+            &ctx.accounts.bettor.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            ctx.accounts.bettor_stats.as_mut(),
+            bettor_key,
+            claim.fee,
+        )?;
+        emit!(SingleClaimed { version: EVENT_VERSION, bettor: bettor_key, pool: pool.pool_id, payout: claim.payout.saturating_add(vault_bonus), fee: claim.fee, pool_liquidity: ctx.accounts.parlay_pool.liquidity_balance });
+        Ok(())
+    }
 
-        // apply protocol fee (if any) from parlay_pool config
-        let fee_bps = ctx.accounts.parlay_pool.protocol_fee_bps as u128;
-        let fee = ((naive_payout as u128) * fee_bps / 10_000u128) as u64;
-        let payout_after_fee = naive_payout.saturating_sub(fee);
+    // -------------------------
+    // Batch claim of single bets (withdraw or restake)
+    // -------------------------
+    /// Claim up to MAX_CLAIM_BATCH settled SingleBet positions of one bettor in a single transaction, priced exactly
+    /// like claim_single (hedge netting included). remaining_accounts per bet: [game_pool, single_bet, position] for
+    /// SOL pools, [game_pool, single_bet, position, game_pool_escrow] for SPL pools. Every bet (won or lost) is closed
+    /// to the bettor; with `restake_into_parlay` the combined payout and bonuses become one restake deposit. Fees,
+    /// referral credit and rebates are aggregated and booked once at the end.
+    pub fn claim_many<'info>(ctx: Context<'_, '_, '_, 'info, ClaimMany<'info>>, restake_into_parlay: bool) -> Result<()> {
+        require!(!ctx.accounts.parlay_pool.withdrawals_paused, PredictionError::OperationPaused);
+        let spl = ctx.accounts.parlay_pool.token_mint.is_some();
+        let stride = if spl { 4 } else { 3 };
+        let remaining = ctx.remaining_accounts;
+        require!(!remaining.is_empty() && remaining.len() % stride == 0, PredictionError::InvalidArgs);
+        require!(remaining.len() / stride <= MAX_CLAIM_BATCH, PredictionError::InvalidArgs);
+
+        let bettor_key = ctx.accounts.bettor.key();
+        let parlay_key = ctx.accounts.parlay_pool.key();
+        let protocol_fee_bps = ctx.accounts.parlay_pool.protocol_fee_bps;
+        // the payout lands in the parlay vault when restaking, with the bettor otherwise
+        let vault = ctx.accounts.parlay_vault_ata.as_ref().map(|v| v.to_account_info());
+        let payout_to = match (restake_into_parlay, spl) {
+            (false, false) => ctx.accounts.bettor.to_account_info(),
+            (false, true) => ctx.accounts.bettor_ata.as_ref().ok_or(PredictionError::InvalidArgs)?.to_account_info(),
+            (true, false) => ctx.accounts.parlay_pool.to_account_info(),
+            (true, true) => vault.clone().ok_or(PredictionError::InvalidArgs)?,
+        };
+        let mut total_fee = 0u64;
+        let mut total_bonus = 0u64;
+        let mut total_promo = 0u64;
+        let mut total_paid = 0u64;
+        let mut count = 0u32;
+        let now = Clock::get()?.unix_timestamp;
+
+        for chunk in remaining.chunks(stride) {
+            let mut pool: Account<'info, GamePool> = Account::try_from(&chunk[0])?;
+            let bet: Account<'info, SingleBet> = Account::try_from(&chunk[1])?;
+            let position: Account<'info, BettorPosition> = Account::try_from(&chunk[2])?;
+            require!(pool.parlay_pool == parlay_key, PredictionError::InvalidPool);
+            require!(bet.pool == pool.key() && bet.bettor == bettor_key, PredictionError::Unauthorized);
+            require!(pool.is_settled, PredictionError::PoolNotSettled);
+            require!(claims_open(&pool, ctx.accounts.parlay_pool.dispute_window_secs, now), PredictionError::ClaimsLocked);
+            require!(pool.unrecorded_bets == 0, PredictionError::ResultsNotRecorded);
+            require!(!bet.claimed, PredictionError::AlreadyClaimed);
+            pool.open_bets = pool.open_bets.saturating_sub(1);
+
+            let claim = price_single_claim(
+                &mut pool,
+                &bet,
+                Some(&position),
+                protocol_fee_bps,
+                vault_backed_reserve(&ctx.accounts.parlay_pool).saturating_sub(total_bonus),
+                ctx.accounts.parlay_pool.promo_budget.saturating_sub(total_promo),
+            )?;
+            game_pool_transfer(&pool, chunk.get(3).cloned(), payout_to.clone(), &ctx.accounts.token_program, claim.payout)?;
+            emit_claim_bonuses(&bet, pool.pool_id, &claim);
+            total_fee = total_fee.saturating_add(claim.fee);
+            total_bonus = total_bonus.saturating_add(claim.streak_bonus);
+            total_promo = total_promo.saturating_add(claim.promo_bonus);
+            total_paid = total_paid.saturating_add(claim.payout);
+
+            pool.exit(&crate::ID)?;
+            bet.close(ctx.accounts.bettor.to_account_info())?;
+            count += 1;
+        }
 
-        // if restake into parlay
+        // streak + promo bonuses sit in the parlay vault: paid out in one transfer, or restaked with the payouts
+        let vault_bonus = total_bonus.saturating_add(total_promo);
+        ctx.accounts.parlay_pool.protocol_reserve = ctx.accounts.parlay_pool.protocol_reserve.saturating_sub(total_bonus);
+        ctx.accounts.parlay_pool.promo_budget = ctx.accounts.parlay_pool.promo_budget.saturating_sub(total_promo);
+        let mut shares_minted = 0u64;
         if restake_into_parlay {
-            // move payout_after_fee into global parlay pool as liquidity
-            let parlay_pool = &mut ctx.accounts.parlay_pool;
-            match parlay_pool.token_mint {
-                None => {
-                    // SOL: transfer from game_pool account to parlay_pool PDA
-                    // In reality, the game_pool escrow held the lamports — program must sign to transfer
-                    // For MVP we expect the bettor to deposit into parlay pool directly client-side
-                    // We'll mark the restake position locally for illustration.
-                    // TODO: real lamport movement needs PDAs signing; skip here.
-                    return Err(error!(PredictionError.Unimplemented).into());
-                }
-                Some(_) => {
-                    // SPL: transfer from game_pool_escrow -> parlay_pool_vault
-                    let cpi_accounts = token::Transfer {
-                        from: ctx.accounts.game_pool_escrow.to_account_info(),
-                        to: ctx.accounts.parlay_vault_ata.to_account_info(),
-                        authority: ctx.accounts.game_pool.to_account_info(),
-                    };
-                    let signer_seeds = &[&[b"game_pool", pool.pool_id.as_ref(), &[pool.bump]][..]];
-                    token::transfer(CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds), payout_after_fee)?;
-                    parlay_pool.liquidity_balance = parlay_pool.liquidity_balance.saturating_add(payout_after_fee);
-                }
-            }
+            let restake_bump = *ctx.bumps.get("restake_pos").unwrap_or(&0);
+            shares_minted = restake_claim(
+                &mut ctx.accounts.parlay_pool,
+                &mut ctx.accounts.restake_pos,
+                bettor_key,
+                total_paid,
+                vault_bonus,
+                restake_bump,
+                &ctx.accounts.lp_mint,
+                &ctx.accounts.bettor_lp_ata,
+                &ctx.accounts.token_program,
+            )?;
+        } else {
+            parlay_pool_transfer(&ctx.accounts.parlay_pool, vault, payout_to, &ctx.accounts.token_program, vault_bonus)?;
+        }
 
-            // Create restake position record (ticket) pointing to parlay pool
-            let restake = &mut ctx.accounts.restake_pos;
-            restake.owner = ctx.accounts.bettor.key();
-            restake.pool = ctx.accounts.parlay_pool.key();
-            restake.share = payout_after_fee; // in snapshot model, we record share as amount; dynamic share logic would store normalized shares
-            restake.created_at = Clock::get()?.unix_timestamp;
-            restake.bump = *ctx.bumps.get("restake_pos").unwrap_or(&0);
+        book_single_fee(
+            &mut ctx.accounts.parlay_pool,
+            ctx.accounts.referral.as_ref(),
+            ctx.accounts.referrer_balance.as_deref(),
+            &ctx.accounts.bettor.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            ctx.accounts.bettor_stats.as_mut(),
+            bettor_key,
+            total_fee,
+        )?;
+
+        emit!(ManySinglesClaimed { bettor: bettor_key, pool: parlay_key, count, payout: total_paid.saturating_add(vault_bonus), shares_minted });
+        Ok(())
+    }
 
-            bet.claimed = true;
-            emit!(SingleClaimedRestaked { bettor: bet.bettor, pool: pool.pool_id, restake_amt: payout_after_fee });
-            return Ok(());
-        } else {
-            // Pay out to bettor
-            match pool.token_mint {
-                None => {
-                    // SOL: transfer lamports from pool escrow -> bettor
-                    // For MVP assume pool lamports available and program signs — this requires correct PDA seeds
+    // -------------------------
+    // Close a fully settled game pool
+    // -------------------------
+    /// Permissionless cleanup once every bet is claimed, or after CLAIM_GRACE_PERIOD since settlement.
+    /// Unclaimed funds left after the grace period are swept into the parlay pool's protocol_reserve;
+    /// the escrow ATA and GamePool rent go to the cranker. Bets still open at that point are forfeited
+    /// (counted in GamePoolClosed.forfeited_bets); their owners reclaim the rent with close_orphaned_bet.
+    pub fn close_game_pool(ctx: Context<CloseGamePool>) -> Result<()> {
+        let pool = &ctx.accounts.game_pool;
+        require!(pool.is_settled, PredictionError::PoolNotSettled);
+        let now = Clock::get()?.unix_timestamp;
+        require!(!pool.disputed, PredictionError::ClaimsLocked);
+        require!(pool.unrecorded_bets == 0, PredictionError::ResultsNotRecorded);
+        require!(pool.open_bets == 0 || now >= pool.settled_at.saturating_add(CLAIM_GRACE_PERIOD), PredictionError::PoolHasOpenBets);
+        let bump = [pool.bump];
+        let signer_seeds = &[&[b"game_pool", pool.pool_id.as_ref(), pool.parlay_pool.as_ref(), &bump][..]];
+
+        let mut swept = 0u64;
+        match pool.token_mint {
+            None => {
+                // leave only the rent-exempt minimum; `close = cranker` returns that
+                let rent_min = Rent::get()?.minimum_balance(8 + GamePool::INIT_SPACE);
+                swept = ctx.accounts.game_pool.to_account_info().lamports().saturating_sub(rent_min);
+                if swept > 0 {
                     invoke_signed(
-                        &system_instruction::transfer(&ctx.accounts.game_pool.key(), &ctx.accounts.bettor.key(), payout_after_fee),
-                        &[ctx.accounts.game_pool.to_account_info(), ctx.accounts.bettor.to_account_info()],
-                        &[&[b"game_pool", pool.pool_id.as_ref(), &[pool.bump]]],
+                        &system_instruction::transfer(&ctx.accounts.game_pool.key(), &ctx.accounts.parlay_pool.key(), swept),
+                        &[ctx.accounts.game_pool.to_account_info(), ctx.accounts.parlay_pool.to_account_info(), ctx.accounts.system_program.to_account_info()],
+                        signer_seeds,
                     )?;
                 }
-                Some(_) => {
-                    // SPL transfer from game_pool_escrow -> bettor_ata
+            }
+            Some(_) => {
+                let escrow = ctx.accounts.game_pool_escrow.as_ref().ok_or(PredictionError::InvalidArgs)?;
+                swept = escrow.amount;
+                if swept > 0 {
                     let cpi_accounts = token::Transfer {
-                        from: ctx.accounts.game_pool_escrow.to_account_info(),
-                        to: ctx.accounts.bettor_ata.to_account_info(),
+                        from: escrow.to_account_info(),
+                        to: ctx.accounts.parlay_vault_ata.to_account_info(),
                         authority: ctx.accounts.game_pool.to_account_info(),
                     };
-                    let signer_seeds = &[&[b"game_pool", pool.pool_id.as_ref(), &[pool.bump]][..]];
-                    token::transfer(CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds), payout_after_fee)?;
+                    token::transfer(CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds), swept)?;
                 }
+                let cpi_accounts = CloseAccount {
+                    account: escrow.to_account_info(),
+                    destination: ctx.accounts.cranker.to_account_info(),
+                    authority: ctx.accounts.game_pool.to_account_info(),
+                };
+                token::close_account(CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds))?;
             }
-            // update protocol reserve with fee (if applicable)
-            ctx.accounts.parlay_pool.protocol_reserve = ctx.accounts.parlay_pool.protocol_reserve.saturating_add(fee);
-
-            bet.claimed = true;
-            emit!(SingleClaimed { bettor: bet.bettor, pool: pool.pool_id, payout: payout_after_fee });
-            return Ok(());
         }
+        // claim fees in the sweep were booked into protocol_reserve / referral_owed when the bets were claimed
+        let unclaimed = swept.saturating_sub(ctx.accounts.game_pool.fees_booked);
+        ctx.accounts.parlay_pool.escrowed_fees = ctx.accounts.parlay_pool.escrowed_fees.saturating_sub(ctx.accounts.game_pool.fees_booked);
+        ctx.accounts.parlay_pool.protocol_reserve = ctx.accounts.parlay_pool.protocol_reserve.saturating_add(unclaimed);
+
+        emit!(GamePoolClosed { pool: ctx.accounts.game_pool.key(), closed_by: ctx.accounts.cranker.key(), unclaimed_swept: unclaimed, forfeited_bets: ctx.accounts.game_pool.open_bets });
+        Ok(())
+    }
+
+    /// Close a SingleBet left behind by close_game_pool, returning its rent to the bettor. Any winnings
+    /// were already forfeited to protocol_reserve by the sweep, so nothing else is paid.
+    pub fn close_orphaned_bet(ctx: Context<CloseOrphanedBet>) -> Result<()> {
+        require!(ctx.accounts.game_pool.data_is_empty(), PredictionError::GamePoolStillOpen);
+        let bet = &ctx.accounts.single_bet;
+        emit!(OrphanedBetClosed { bettor: bet.bettor, pool: bet.pool, chosen_outcome: bet.chosen_outcome, stake: bet.stake });
+        Ok(())
     }
 
     // -------------------------
@@ -266,15 +904,31 @@ pub mod prediction {
     // -------------------------
     /// The client must provide the list of game IDs they reference (we don't verify all games on-chain here for gas).
     /// For security you may require validation via indexer or off-chain oracle at placement time.
-    pub fn place_parlay_bet(
-        ctx: Context<PlaceParlayBet>,
+    ///
+    /// remaining_accounts: the GamePool PDA of every leg, in `games` order (pass the address even if no
+    /// single-bet market exists yet), so delisted markets can't be backed through a parlay.
+    pub fn place_parlay_bet<'info>(
+        ctx: Context<'_, '_, '_, 'info, PlaceParlayBet<'info>>,
         games: Vec<Pubkey>,        // battle pubkeys
         chosen_outcomes: Vec<u8>,  // matching vector
         stake: u64,
     ) -> Result<()> {
         let parlay = &mut ctx.accounts.parlay_pool;
+        require!(!parlay.bets_paused, PredictionError::BettingPaused);
+        require!(!parlay.placement_paused, PredictionError::OperationPaused);
+        require!(parlay.compliance_flags == 0, PredictionError::MarketDelisted);
         require!(games.len() == chosen_outcomes.len(), PredictionError::InvalidArgs);
+        require!(ctx.remaining_accounts.len() == games.len(), PredictionError::InvalidArgs);
+        for (battle, info) in games.iter().zip(ctx.remaining_accounts.iter()) {
+            let (expected, _) = Pubkey::find_program_address(&[b"game_pool", battle.as_ref(), parlay.key().as_ref()], &crate::ID);
+            require!(info.key() == expected, PredictionError::InvalidPool);
+            if !info.data_is_empty() {
+                let leg: Account<GamePool> = Account::try_from(info)?;
+                require!(leg.compliance_flags == 0, PredictionError::MarketDelisted);
+            }
+        }
         require!(stake >= parlay.min_stake, PredictionError::StakeTooSmall);
+        enforce_wallet_limits(&mut ctx.accounts.bettor_stats, &ctx.accounts.exclusion, stake, Clock::get()?.unix_timestamp)?;
 
         // compute theoretical multiplier (simple formula: 1.5x per leg for demo)
         let legs = games.len();
@@ -333,11 +987,23 @@ pub mod prediction {
         ticket.resolved = false;
         ticket.won = None;
         ticket.claimed = false;
+        ticket.entitled = 0;
+        ticket.haircut = 0;
+        ticket.haircut_repaid = 0;
+        ticket.haircut_index = 0;
         ticket.created_at = Clock::get()?.unix_timestamp;
         ticket.bump = *ctx.bumps.get("parlay_ticket").unwrap_or(&0);
 
+        let stats = &mut ctx.accounts.predictor_stats;
+        if stats.wallet == Pubkey::default() {
+            stats.wallet = ticket.owner;
+            stats.bump = *ctx.bumps.get("predictor_stats").unwrap_or(&0);
+        }
+
+        record_volume(&mut ctx.accounts.bettor_stats, ctx.accounts.bettor.key(), ctx.accounts.parlay_pool.key(), stake, ticket.created_at, *ctx.bumps.get("bettor_stats").unwrap_or(&0));
+
         // emit
-        emit!(ParlayBetPlaced { ticket: ctx.accounts.parlay_ticket.key(), bettor: ticket.owner, stake: ticket.stake, multiplier_x100: ticket.multiplier_x100 });
+        emit!(ParlayBetPlaced { version: EVENT_VERSION, ticket: ctx.accounts.parlay_ticket.key(), bettor: ticket.owner, stake: ticket.stake, multiplier_x100: ticket.multiplier_x100, pool_liquidity: ctx.accounts.parlay_pool.liquidity_balance });
         Ok(())
     }
 
@@ -349,6 +1015,9 @@ pub mod prediction {
         ctx: Context<ResolveParlayTicket>,
         won: bool,
     ) -> Result<()> {
+        require!(!ctx.accounts.parlay_pool.settlement_paused, PredictionError::OperationPaused);
+        require_keys_eq!(ctx.accounts.signer.key(), ctx.accounts.parlay_pool.authority, PredictionError::Unauthorized);
+        require!(!ctx.accounts.parlay_pool.insurance_enabled || ctx.accounts.insurance_fund.is_some(), PredictionError::MissingInsuranceFund);
         let ticket = &mut ctx.accounts.parlay_ticket;
         require!(!ticket.resolved, PredictionError::AlreadyResolved);
         ticket.resolved = true;
@@ -356,33 +1025,137 @@ pub mod prediction {
 
         if !won {
             // if lost, stake remains in pool; protocol takes fee portion immediately
-            let fee = ((ticket.stake as u128) * (ctx.accounts.parlay_pool.protocol_fee_bps as u128) / 10_000u128) as u64;
-            ctx.accounts.parlay_pool.protocol_reserve = ctx.accounts.parlay_pool.protocol_reserve.saturating_add(fee);
+            let mut fee = ((ticket.stake as u128) * (ctx.accounts.parlay_pool.protocol_fee_bps as u128) / 10_000u128) as u64;
+            let fee_charged = fee;
+            if let Some(stats) = ctx.accounts.bettor_stats.as_mut() {
+                require!(stats.bettor == ticket.owner, PredictionError::InvalidArgs);
+                record_fee(stats, fee, Clock::get()?.unix_timestamp);
+            }
+            let owner = ticket.owner;
+            let referral_cut = credit_referrer(&mut ctx.accounts.parlay_pool, ctx.accounts.referral.as_ref(), ctx.accounts.referrer_balance.as_deref(), &ctx.accounts.signer.to_account_info(), &ctx.accounts.system_program.to_account_info(), owner, fee)?;
+            fee = fee.saturating_sub(referral_cut);
+            // insurance slice is carved out first, the rest is split between LPs and protocol_reserve.
+            // The fund is a sub-ledger of the vault, so its slice leaves liquidity_balance
+            if let Some(fund) = ctx.accounts.insurance_fund.as_mut() {
+                let cut = ((fee as u128) * (fund.fee_share_bps as u128) / 10_000u128) as u64;
+                fund.balance = fund.balance.saturating_add(cut);
+                ctx.accounts.parlay_pool.liquidity_balance = ctx.accounts.parlay_pool.liquidity_balance.saturating_sub(cut);
+                fee = fee.saturating_sub(cut);
+                emit!(InsuranceFunded { fund: fund.key(), amount: cut, balance: fund.balance });
+            }
+            // the referral cut and the LP / protocol slices are vault sub-ledgers as well: the stake landed in
+            // liquidity_balance at placement, so they leave it here or LPs would be paid twice
+            ctx.accounts.parlay_pool.liquidity_balance = ctx.accounts.parlay_pool.liquidity_balance.saturating_sub(fee.saturating_add(referral_cut));
+            accrue_protocol_fee(&mut ctx.accounts.parlay_pool, fee)?;
             // pool retains (stake - fee) so liquidity increases
             // For SPL the stake already sits in parlay_vault_ata; no transfer needed
-            emit!(ParlayResolved { ticket: ctx.accounts.parlay_ticket.key(), won: false });
+            // outstanding haircuts are repaid from this revenue before it counts as LP profit
+            let retained = ctx.accounts.parlay_pool.liquidity_balance.min(ticket.stake.saturating_sub(fee_charged));
+            let repaid = set_aside_haircut_repayment(&mut ctx.accounts.parlay_pool, retained);
+            if repaid > 0 {
+                emit!(HaircutRepaymentReserved { pool: ctx.accounts.parlay_pool.key(), amount: repaid, outstanding: ctx.accounts.parlay_pool.haircut_debt });
+            }
+            let bump = ctx.accounts.predictor_stats.bump;
+            record_prediction(&mut ctx.accounts.predictor_stats, ticket.owner, ticket.stake, 0, bump, Some(ctx.accounts.leaderboard.season))?;
+            update_leaderboard(&mut ctx.accounts.leaderboard, &ctx.accounts.predictor_stats);
+            emit!(ParlayResolved { version: EVENT_VERSION, ticket: ctx.accounts.parlay_ticket.key(), won: false, payout: 0, fee: fee_charged, pool_liquidity: ctx.accounts.parlay_pool.liquidity_balance });
             return Ok(());
         } else {
             // mark snapshot payout based on current pool liquidity and multiplier
             // payout = stake * multiplier_x100/100 * pool_factor
             // simple pool_factor = liquidity_balance / initial_reference (we'll use 1.0 baseline)
             // For MVP use: payout = stake * multiplier_x100 / 100 (clamped by pool and max cap)
-            let mut payout = (ticket.stake as u128) * (ticket.multiplier_x100 as u128) / 100u128;
-            // clamp payout to available liquidity minus floor
+            let entitled = ((ticket.stake as u128) * (ticket.multiplier_x100 as u128) / 100u128) as u64;
+            // pay from available liquidity above the floor first
             let pool_liq = ctx.accounts.parlay_pool.liquidity_balance;
             let available = pool_liq.saturating_sub(ctx.accounts.parlay_pool.liquidity_floor);
-            if (payout as u128) > (available as u128) {
-                payout = available as u128;
+            let from_pool = entitled.min(available);
+            // any shortfall is drawn from the insurance fund before haircutting the winner
+            let mut from_insurance = 0u64;
+            if let Some(fund) = ctx.accounts.insurance_fund.as_mut() {
+                from_insurance = entitled.saturating_sub(from_pool).min(fund.balance);
+                if from_insurance > 0 {
+                    fund.balance = fund.balance.saturating_sub(from_insurance);
+                    fund.total_drawn = fund.total_drawn.saturating_add(from_insurance);
+                    emit!(InsuranceDrawn { fund: fund.key(), ticket: ticket.key(), amount: from_insurance, balance: fund.balance });
+                }
+            }
+            let payout = from_pool.saturating_add(from_insurance);
+            let haircut = entitled.saturating_sub(payout);
+            ticket.entitled = entitled;
+            ticket.haircut = haircut;
+            ticket.haircut_repaid = 0;
+            ticket.haircut_index = ctx.accounts.parlay_pool.haircut_index;
+            if haircut > 0 {
+                // recorded as pool debt, repaid pro-rata out of future revenue (see claim_haircut_repayment)
+                ctx.accounts.parlay_pool.haircut_debt = ctx.accounts.parlay_pool.haircut_debt.saturating_add(haircut);
+                emit!(PayoutHaircut { ticket: ticket.key(), entitled, paid: payout, haircut });
             }
 
-            ticket.payout_snapshot = payout as u64;
-            // deduct payout from liquidity (it will be paid at claim)
-            ctx.accounts.parlay_pool.liquidity_balance = ctx.accounts.parlay_pool.liquidity_balance.saturating_sub(ticket.payout_snapshot);
-            emit!(ParlayResolved { ticket: ctx.accounts.parlay_ticket.key(), won: true });
+            ticket.payout_snapshot = payout;
+            ctx.accounts.parlay_pool.pending_payouts = ctx.accounts.parlay_pool.pending_payouts.saturating_add(payout);
+            // deduct the pool-funded part from liquidity (it will be paid at claim); the insurance part
+            // sits in the vault outside liquidity_balance (loss-path cuts are taken out of it when funded)
+            ctx.accounts.parlay_pool.liquidity_balance = ctx.accounts.parlay_pool.liquidity_balance.saturating_sub(from_pool);
+            check_drawdown(&mut ctx.accounts.parlay_pool, Clock::get()?.unix_timestamp);
+            let bump = ctx.accounts.predictor_stats.bump;
+            record_prediction(&mut ctx.accounts.predictor_stats, ticket.owner, ticket.stake, payout, bump, Some(ctx.accounts.leaderboard.season))?;
+            update_leaderboard(&mut ctx.accounts.leaderboard, &ctx.accounts.predictor_stats);
+            emit!(ParlayResolved { version: EVENT_VERSION, ticket: ctx.accounts.parlay_ticket.key(), won: true, payout, fee: 0, pool_liquidity: ctx.accounts.parlay_pool.liquidity_balance });
             return Ok(());
         }
     }
 
+    /// Collect the haircut repayment this ticket has accrued. Every set-aside bumps `haircut_index` by the
+    /// amount repaid per unit of outstanding debt; a ticket accrues `owed * (index - checkpoint)`, so what it
+    /// recovers does not depend on when it (or anyone else) claims.
+    pub fn claim_haircut_repayment(ctx: Context<ClaimHaircutRepayment>) -> Result<()> {
+        require!(!ctx.accounts.parlay_pool.withdrawals_paused, PredictionError::OperationPaused);
+        let ticket = &mut ctx.accounts.parlay_ticket;
+        let owed = ticket.haircut.saturating_sub(ticket.haircut_repaid);
+        let pool = &mut ctx.accounts.parlay_pool;
+        require!(owed > 0 && pool.haircut_debt > 0, PredictionError::NothingToWithdraw);
+        let index_delta = pool.haircut_index.saturating_sub(ticket.haircut_index);
+        let accrued = (owed as u128).checked_mul(index_delta).ok_or(PredictionError::MathOverflow)? / ACC_FEE_SCALE;
+        let amount = (accrued.min(owed as u128) as u64).min(pool.haircut_reserve);
+        require!(amount > 0, PredictionError::NothingToWithdraw);
+        ticket.haircut_repaid = ticket.haircut_repaid.saturating_add(amount);
+        ticket.haircut_index = pool.haircut_index;
+        pool.haircut_reserve = pool.haircut_reserve.saturating_sub(amount);
+        pool.haircut_debt = pool.haircut_debt.saturating_sub(amount);
+        // accrual beyond what this ticket was still owed goes back to the rest of the debt
+        let surplus = accrued.saturating_sub(owed as u128) as u64;
+        if pool.haircut_debt == 0 {
+            pool.liquidity_balance = pool.liquidity_balance.saturating_add(pool.haircut_reserve);
+            pool.haircut_reserve = 0;
+        } else if surplus > 0 {
+            pool.haircut_index = pool.haircut_index.saturating_add((surplus as u128) * ACC_FEE_SCALE / (pool.haircut_debt as u128));
+        }
+
+        match ctx.accounts.parlay_pool.token_mint {
+            None => {
+                invoke_signed(
+                    &system_instruction::transfer(&ctx.accounts.parlay_pool.key(), &ctx.accounts.owner.key(), amount),
+                    &[ctx.accounts.parlay_pool.to_account_info(), ctx.accounts.owner.to_account_info()],
+                    &[&[b"parlay_pool", ctx.accounts.parlay_pool.mint_seed(), &[ctx.accounts.parlay_pool.bump]]],
+                )?;
+            }
+            Some(_) => {
+                let cpi_accounts = token::Transfer {
+                    from: ctx.accounts.parlay_vault_ata.to_account_info(),
+                    to: ctx.accounts.owner_ata.to_account_info(),
+                    authority: ctx.accounts.parlay_pool.to_account_info(),
+                };
+                let bump = [ctx.accounts.parlay_pool.bump];
+                let signer_seeds = &[&[b"parlay_pool", ctx.accounts.parlay_pool.mint_seed(), &bump][..]];
+                token::transfer(CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds), amount)?;
+            }
+        }
+
+        emit!(HaircutRepaid { ticket: ctx.accounts.parlay_ticket.key(), owner: ctx.accounts.owner.key(), amount, remaining: owed.saturating_sub(amount) });
+        Ok(())
+    }
+
     // -------------------------
     // Claim parlay payout or restake claim
     // -------------------------
@@ -393,29 +1166,43 @@ pub mod prediction {
         ctx: Context<ClaimParlay>,
         restake: bool,
     ) -> Result<()> {
+        require!(!ctx.accounts.parlay_pool.withdrawals_paused, PredictionError::OperationPaused);
         let ticket = &mut ctx.accounts.parlay_ticket;
         require!(ticket.resolved, PredictionError::NotResolved);
         require!(ticket.won == Some(true), PredictionError::NotWinner);
         require!(!ticket.claimed, PredictionError::AlreadyClaimed);
 
         let payout = ticket.payout_snapshot;
+        ctx.accounts.parlay_pool.pending_payouts = ctx.accounts.parlay_pool.pending_payouts.saturating_sub(payout);
         // protocol fee on payout (optional)
         let fee = ((payout as u128) * (ctx.accounts.parlay_pool.protocol_fee_bps as u128) / 10_000u128) as u64;
         let payout_after_fee = payout.saturating_sub(fee);
-        ctx.accounts.parlay_pool.protocol_reserve = ctx.accounts.parlay_pool.protocol_reserve.saturating_add(fee);
+        let bettor_key = ctx.accounts.bettor.key();
+        let referral_cut = credit_referrer(&mut ctx.accounts.parlay_pool, ctx.accounts.referral.as_ref(), ctx.accounts.referrer_balance.as_deref(), &ctx.accounts.bettor.to_account_info(), &ctx.accounts.system_program.to_account_info(), bettor_key, fee)?;
+        accrue_protocol_fee(&mut ctx.accounts.parlay_pool, fee.saturating_sub(referral_cut))?;
+        if let Some(stats) = ctx.accounts.bettor_stats.as_mut() {
+            record_fee(stats, fee, Clock::get()?.unix_timestamp);
+        }
 
         if restake {
-            // simply increase pool liquidity by payout_after_fee (user converts payout into pool shares)
-            ctx.accounts.parlay_pool.liquidity_balance = ctx.accounts.parlay_pool.liquidity_balance.saturating_add(payout_after_fee);
-            // create restake position (store snapshot share = payout_after_fee / new_pool_liq)
+            // user converts payout into pool shares priced against pre-deposit liquidity
+            let parlay_pool = &mut ctx.accounts.parlay_pool;
+            let minted = shares_for_deposit(parlay_pool, payout_after_fee)?;
+            parlay_pool.liquidity_balance = parlay_pool.liquidity_balance.saturating_add(payout_after_fee);
+            parlay_pool.total_shares = parlay_pool.total_shares.checked_add(minted).ok_or(PredictionError::MathOverflow)?;
+            let lp_minted = mint_lp_tokens(&ctx.accounts.parlay_pool, &ctx.accounts.lp_mint, &ctx.accounts.bettor_lp_ata, &ctx.accounts.token_program, minted)?;
             let restake = &mut ctx.accounts.restake_pos;
+            settle_lp_fees(&ctx.accounts.parlay_pool, restake)?;
             restake.owner = ctx.accounts.bettor.key();
             restake.pool = ctx.accounts.parlay_pool.key();
-            restake.share = payout_after_fee; // in simple model share is amount; normalized shares can be implemented
+            restake.share = restake.share.checked_add(minted).ok_or(PredictionError::MathOverflow)?;
+            restake.lp_minted_shares = restake.lp_minted_shares.saturating_add(lp_minted);
+            sync_fee_weight(&mut ctx.accounts.parlay_pool, restake)?;
+            restake.closed = false;
             restake.created_at = Clock::get()?.unix_timestamp;
             restake.bump = *ctx.bumps.get("restake_pos").unwrap_or(&0);
             ticket.claimed = true;
-            emit!(ParlayClaimedRestaked { ticket: ctx.accounts.parlay_ticket.key(), owner: restake.owner, amt: payout_after_fee });
+            emit!(ParlayClaimedRestaked { version: EVENT_VERSION, ticket: ctx.accounts.parlay_ticket.key(), owner: restake.owner, amt: payout_after_fee, shares_minted: minted, fee, pool_liquidity: ctx.accounts.parlay_pool.liquidity_balance });
             return Ok(());
         } else {
             // Payout to user
@@ -424,7 +1211,7 @@ pub mod prediction {
                     invoke_signed(
                         &system_instruction::transfer(&ctx.accounts.parlay_pool.key(), &ctx.accounts.bettor.key(), payout_after_fee),
                         &[ctx.accounts.parlay_pool.to_account_info(), ctx.accounts.bettor.to_account_info()],
-                        &[&[b"parlay_pool", &[ctx.accounts.parlay_pool.bump]]],
+                        &[&[b"parlay_pool", ctx.accounts.parlay_pool.mint_seed(), &[ctx.accounts.parlay_pool.bump]]],
                     )?;
                 }
                 Some(_) => {
@@ -433,12 +1220,13 @@ pub mod prediction {
                         to: ctx.accounts.bettor_ata.to_account_info(),
                         authority: ctx.accounts.parlay_pool.to_account_info(),
                     };
-                    let signer_seeds = &[&[b"parlay_pool", &[ctx.accounts.parlay_pool.bump]][..]];
+                    let bump = [ctx.accounts.parlay_pool.bump];
+                    let signer_seeds = &[&[b"parlay_pool", ctx.accounts.parlay_pool.mint_seed(), &bump][..]];
                     token::transfer(CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds), payout_after_fee)?;
                 }
             }
             ticket.claimed = true;
-            emit!(ParlayClaimed { ticket: ctx.accounts.parlay_ticket.key(), owner: ctx.accounts.bettor.key(), amt: payout_after_fee });
+            emit!(ParlayClaimed { version: EVENT_VERSION, ticket: ctx.accounts.parlay_ticket.key(), owner: ctx.accounts.bettor.key(), amt: payout_after_fee, fee, pool_liquidity: ctx.accounts.parlay_pool.liquidity_balance });
             return Ok(());
         }
     }
@@ -446,19 +1234,41 @@ pub mod prediction {
     // -------------------------
     // Withdraw restake (perp-like)
     // -------------------------
-    /// Unstake a restake_pos: compute its share relative to current pool liquidity
+    /// Unstake a restake_pos: burn its shares and pay out share * current_liquidity / total_shares
     pub fn withdraw_restake(ctx: Context<WithdrawRestake>) -> Result<()> {
+        require!(!ctx.accounts.parlay_pool.withdrawals_paused, PredictionError::OperationPaused);
         let restake = &mut ctx.accounts.restake_pos;
         require!(restake.owner == ctx.accounts.owner.key(), PredictionError::Unauthorized);
-
-        // simple model: share is raw amount; actual dynamic share accounting requires normalized shares
-        let payout = restake.share; // In a proper model: share * current_liquidity / total_shares
+        require!(!restake.closed && restake.share > 0, PredictionError::NothingToWithdraw);
+
+        // normalized shares: the position participates in pool P&L since it was minted
+        settle_lp_fees(&ctx.accounts.parlay_pool, restake)?;
+        let burned = restake.share;
+        let gross = amount_for_shares(burned, ctx.accounts.parlay_pool.liquidity_balance, ctx.accounts.parlay_pool.total_shares)?;
+        // early exit from a lock: the penalty never leaves the pool, so it accrues to remaining LPs
+        let penalty = early_exit_penalty(restake, gross, Clock::get()?.unix_timestamp);
+        let payout = gross.saturating_sub(penalty);
+        // same floor as withdraw_liquidity: what stays behind must still cover open parlay tickets
+        let remaining = ctx.accounts.parlay_pool.liquidity_balance.saturating_sub(payout);
+        require!(remaining >= ctx.accounts.parlay_pool.liquidity_floor, PredictionError::BelowLiquidityFloor);
 
         // apply exit fee (optional)
         let fee = ((payout as u128) * (ctx.accounts.parlay_pool.protocol_fee_bps as u128) / 10_000u128) as u64;
         let payout_after_fee = payout.saturating_sub(fee);
-        ctx.accounts.parlay_pool.protocol_reserve = ctx.accounts.parlay_pool.protocol_reserve.saturating_add(fee);
-        ctx.accounts.parlay_pool.liquidity_balance = ctx.accounts.parlay_pool.liquidity_balance.saturating_sub(payout_after_fee);
+        // the redeemed amount leaves LP liquidity; the fee slice moves to protocol_reserve
+        ctx.accounts.parlay_pool.liquidity_balance = remaining;
+        ctx.accounts.parlay_pool.total_shares = ctx.accounts.parlay_pool.total_shares.saturating_sub(burned);
+        // only shares that were minted as LP tokens have tokens to burn
+        let lp_burned = restake.lp_minted_shares;
+        restake.share = 0;
+        restake.lp_minted_shares = 0;
+        sync_fee_weight(&mut ctx.accounts.parlay_pool, restake)?;
+        if penalty > 0 {
+            emit!(EarlyExitPenalized { pool: ctx.accounts.parlay_pool.key(), owner: restake.owner, penalty });
+        }
+        // exit fee accrues after the shares are burned so the exiting LP doesn't earn on their own fee
+        accrue_protocol_fee(&mut ctx.accounts.parlay_pool, fee)?;
+        burn_lp_tokens(&ctx.accounts.parlay_pool, &ctx.accounts.lp_mint, &ctx.accounts.owner_lp_ata, &ctx.accounts.owner, &ctx.accounts.token_program, lp_burned)?;
 
         // transfer out
         match ctx.accounts.parlay_pool.token_mint {
@@ -466,7 +1276,7 @@ pub mod prediction {
                 invoke_signed(
                     &system_instruction::transfer(&ctx.accounts.parlay_pool.key(), &ctx.accounts.owner.key(), payout_after_fee),
                     &[ctx.accounts.parlay_pool.to_account_info(), ctx.accounts.owner.to_account_info()],
-                    &[&[b"parlay_pool", &[ctx.accounts.parlay_pool.bump]]],
+                    &[&[b"parlay_pool", ctx.accounts.parlay_pool.mint_seed(), &[ctx.accounts.parlay_pool.bump]]],
                 )?;
             }
             Some(_) => {
@@ -475,67 +1285,709 @@ pub mod prediction {
                     to: ctx.accounts.owner_ata.to_account_info(),
                     authority: ctx.accounts.parlay_pool.to_account_info(),
                 };
-                let signer_seeds = &[&[b"parlay_pool", &[ctx.accounts.parlay_pool.bump]][..]];
+                let bump = [ctx.accounts.parlay_pool.bump];
+                let signer_seeds = &[&[b"parlay_pool", ctx.accounts.parlay_pool.mint_seed(), &bump][..]];
                 token::transfer(CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds), payout_after_fee)?;
             }
         }
 
         // close restake position/account
         restake.closed = true;
-        emit!(RestakeWithdrawn { owner: ctx.accounts.owner.key(), amt: payout_after_fee });
+        emit!(RestakeWithdrawn { owner: ctx.accounts.owner.key(), amt: payout_after_fee, shares_burned: burned });
         Ok(())
     }
-}
 
-// -------------------------
-// Accounts / State
-// -------------------------
-#[account]
-pub struct ParlayPool {
-    pub authority: Pubkey,
-    pub token_mint: Option<Pubkey>, // None => SOL pool, Some => SPL mint
-    pub liquidity_balance: u64,
-    pub liquidity_floor: u64,
-    pub protocol_reserve: u64,
-    pub protocol_fee_bps: u16,
-    pub min_stake: u64,
-    pub max_multiplier_x100: u64,
-    pub bump: u8,
-    // reserved space
-    pub _padding: [u8; 32],
-}
+    // -------------------------
+    // Direct liquidity provisioning
+    // -------------------------
+    /// Deposit SOL/SPL straight into the parlay pool and receive normalized shares (and LP tokens
+    /// when the LP mint is configured). Shares live in the same RestakePosition PDA used by restakes.
+    pub fn deposit_liquidity(ctx: Context<DepositLiquidity>, amount: u64) -> Result<()> {
+        require!(amount > 0, PredictionError::InvalidArgs);
+        let minted = shares_for_deposit(&mut ctx.accounts.parlay_pool, amount)?;
+        require!(minted > 0, PredictionError::DepositTooSmall);
 
-impl ParlayPool {
-    pub const INIT_SPACE: usize = 32 + 1 + 32 + 8 + 8 + 8 + 2 + 8 + 8 + 1 + 32;
-}
+        match ctx.accounts.parlay_pool.token_mint {
+            None => {
+                invoke_signed(
+                    &system_instruction::transfer(&ctx.accounts.provider.key(), &ctx.accounts.parlay_pool.key(), amount),
+                    &[ctx.accounts.provider.to_account_info(), ctx.accounts.parlay_pool.to_account_info()],
+                    &[],
+                )?;
+            }
+            Some(_) => {
+                let cpi_accounts = token::Transfer {
+                    from: ctx.accounts.provider_ata.to_account_info(),
+                    to: ctx.accounts.parlay_vault_ata.to_account_info(),
+                    authority: ctx.accounts.provider.to_account_info(),
+                };
+                token::transfer(CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts), amount)?;
+            }
+        }
+        let parlay = &mut ctx.accounts.parlay_pool;
+        parlay.liquidity_balance = parlay.liquidity_balance.saturating_add(amount);
+        parlay.total_shares = parlay.total_shares.checked_add(minted).ok_or(PredictionError::MathOverflow)?;
+        let lp_minted = mint_lp_tokens(&ctx.accounts.parlay_pool, &ctx.accounts.lp_mint, &ctx.accounts.provider_lp_ata, &ctx.accounts.token_program, minted)?;
+
+        let position = &mut ctx.accounts.restake_pos;
+        settle_lp_fees(&ctx.accounts.parlay_pool, position)?;
+        position.owner = ctx.accounts.provider.key();
+        position.pool = ctx.accounts.parlay_pool.key();
+        position.share = position.share.checked_add(minted).ok_or(PredictionError::MathOverflow)?;
+        position.lp_minted_shares = position.lp_minted_shares.saturating_add(lp_minted);
+        sync_fee_weight(&mut ctx.accounts.parlay_pool, position)?;
+        position.closed = false;
+        position.created_at = Clock::get()?.unix_timestamp;
+        position.bump = *ctx.bumps.get("restake_pos").unwrap_or(&0);
+
+        emit!(LiquidityDeposited { pool: ctx.accounts.parlay_pool.key(), provider: position.owner, amount, shares_minted: minted });
+        Ok(())
+    }
 
-#[account]
-pub struct GamePool {
-    pub pool_id: Pubkey, // battle pubkey
-    pub token_mint: Option<Pubkey>,
-    pub total_staked: u64,
-    pub snapshot_liquidity: u64,
-    pub initialized: bool,
-    pub is_settled: bool,
-    pub winning_outcome: Option<u8>,
-    pub bump: u8,
-    pub _padding: [u8; 32],
-}
-impl GamePool {
-    pub const INIT_SPACE: usize = 32 + 1 + 32 + 8 + 8 + 1 + 1 + 1 + 32;
-}
+    /// Redeem `shares` from a liquidity position (partial withdrawals allowed).
+    /// The pool must stay at or above liquidity_floor after the withdrawal.
+    pub fn withdraw_liquidity(ctx: Context<WithdrawLiquidity>, shares: u64) -> Result<()> {
+        require!(!ctx.accounts.parlay_pool.withdrawals_paused, PredictionError::OperationPaused);
+        let position = &mut ctx.accounts.restake_pos;
+        require!(position.owner == ctx.accounts.provider.key(), PredictionError::Unauthorized);
+        require!(shares > 0 && shares <= position.share, PredictionError::InvalidArgs);
+        settle_lp_fees(&ctx.accounts.parlay_pool, position)?;
 
-#[account]
-pub struct SingleBet {
+        let parlay = &mut ctx.accounts.parlay_pool;
+        let gross = amount_for_shares(shares, parlay.liquidity_balance, parlay.total_shares)?;
+        // early exit from a lock: the penalty stays in the pool for remaining LPs
+        let penalty = early_exit_penalty(position, gross, Clock::get()?.unix_timestamp);
+        let amount = gross.saturating_sub(penalty);
+        let remaining = parlay.liquidity_balance.saturating_sub(amount);
+        require!(remaining >= parlay.liquidity_floor, PredictionError::BelowLiquidityFloor);
+
+        let fee = ((amount as u128) * (parlay.protocol_fee_bps as u128) / 10_000u128) as u64;
+        let amount_after_fee = amount.saturating_sub(fee);
+        // internal-only shares are redeemed first; the rest must be backed by LP tokens the provider burns
+        let lp_burned = lp_backed_shares(position, shares);
+        parlay.liquidity_balance = remaining;
+        parlay.total_shares = parlay.total_shares.saturating_sub(shares);
+        position.share = position.share.saturating_sub(shares);
+        position.lp_minted_shares = position.lp_minted_shares.saturating_sub(lp_burned);
+        sync_fee_weight(parlay, position)?;
+        position.closed = position.share == 0;
+        accrue_protocol_fee(parlay, fee)?;
+        if penalty > 0 {
+            emit!(EarlyExitPenalized { pool: parlay.key(), owner: position.owner, penalty });
+        }
+        burn_lp_tokens(&ctx.accounts.parlay_pool, &ctx.accounts.lp_mint, &ctx.accounts.provider_lp_ata, &ctx.accounts.provider, &ctx.accounts.token_program, lp_burned)?;
+
+        match ctx.accounts.parlay_pool.token_mint {
+            None => {
+                invoke_signed(
+                    &system_instruction::transfer(&ctx.accounts.parlay_pool.key(), &ctx.accounts.provider.key(), amount_after_fee),
+                    &[ctx.accounts.parlay_pool.to_account_info(), ctx.accounts.provider.to_account_info()],
+                    &[&[b"parlay_pool", ctx.accounts.parlay_pool.mint_seed(), &[ctx.accounts.parlay_pool.bump]]],
+                )?;
+            }
+            Some(_) => {
+                let cpi_accounts = token::Transfer {
+                    from: ctx.accounts.parlay_vault_ata.to_account_info(),
+                    to: ctx.accounts.provider_ata.to_account_info(),
+                    authority: ctx.accounts.parlay_pool.to_account_info(),
+                };
+                let bump = [ctx.accounts.parlay_pool.bump];
+                let signer_seeds = &[&[b"parlay_pool", ctx.accounts.parlay_pool.mint_seed(), &bump][..]];
+                token::transfer(CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds), amount_after_fee)?;
+            }
+        }
+
+        emit!(LiquidityWithdrawn { pool: ctx.accounts.parlay_pool.key(), provider: ctx.accounts.provider.key(), amount: amount_after_fee, shares_burned: shares });
+        Ok(())
+    }
+
+    /// Burn `shares` LP tokens held by the signer and pay out what they redeem. The shares come out of the
+    /// signer's own restake position and are capped by the LP tokens minted against it, so an active lock's
+    /// early-exit penalty applies and nobody else's share or fee weight is touched. Tokens that changed hands
+    /// have to go back to the wallet that minted them before they can be redeemed.
+    pub fn redeem_lp_tokens(ctx: Context<RedeemLpTokens>, shares: u64) -> Result<()> {
+        require!(!ctx.accounts.parlay_pool.withdrawals_paused, PredictionError::OperationPaused);
+        let position = &mut ctx.accounts.restake_pos;
+        require!(shares > 0 && shares <= position.lp_minted_shares, PredictionError::InvalidArgs);
+        settle_lp_fees(&ctx.accounts.parlay_pool, position)?;
+
+        let parlay = &mut ctx.accounts.parlay_pool;
+        let gross = amount_for_shares(shares, parlay.liquidity_balance, parlay.total_shares)?;
+        let penalty = early_exit_penalty(position, gross, Clock::get()?.unix_timestamp);
+        let amount = gross.saturating_sub(penalty);
+        let remaining = parlay.liquidity_balance.saturating_sub(amount);
+        require!(remaining >= parlay.liquidity_floor, PredictionError::BelowLiquidityFloor);
+
+        let fee = ((amount as u128) * (parlay.protocol_fee_bps as u128) / 10_000u128) as u64;
+        let amount_after_fee = amount.saturating_sub(fee);
+        parlay.liquidity_balance = remaining;
+        parlay.total_shares = parlay.total_shares.saturating_sub(shares);
+        position.share = position.share.saturating_sub(shares);
+        position.lp_minted_shares = position.lp_minted_shares.saturating_sub(shares);
+        sync_fee_weight(parlay, position)?;
+        position.closed = position.share == 0;
+        accrue_protocol_fee(parlay, fee)?;
+        if penalty > 0 {
+            emit!(EarlyExitPenalized { pool: parlay.key(), owner: position.owner, penalty });
+        }
+        burn_lp_tokens(&ctx.accounts.parlay_pool, &ctx.accounts.lp_mint, &ctx.accounts.holder_lp_ata, &ctx.accounts.holder, &ctx.accounts.token_program, shares)?;
+
+        match ctx.accounts.parlay_pool.token_mint {
+            None => {
+                invoke_signed(
+                    &system_instruction::transfer(&ctx.accounts.parlay_pool.key(), &ctx.accounts.holder.key(), amount_after_fee),
+                    &[ctx.accounts.parlay_pool.to_account_info(), ctx.accounts.holder.to_account_info()],
+                    &[&[b"parlay_pool", ctx.accounts.parlay_pool.mint_seed(), &[ctx.accounts.parlay_pool.bump]]],
+                )?;
+            }
+            Some(_) => {
+                let cpi_accounts = token::Transfer {
+                    from: ctx.accounts.parlay_vault_ata.to_account_info(),
+                    to: ctx.accounts.holder_ata.to_account_info(),
+                    authority: ctx.accounts.parlay_pool.to_account_info(),
+                };
+                let bump = [ctx.accounts.parlay_pool.bump];
+                let signer_seeds = &[&[b"parlay_pool", ctx.accounts.parlay_pool.mint_seed(), &bump][..]];
+                token::transfer(CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds), amount_after_fee)?;
+            }
+        }
+
+        emit!(LpTokensRedeemed { pool: ctx.accounts.parlay_pool.key(), holder: ctx.accounts.holder.key(), position: ctx.accounts.restake_pos.key(), amount: amount_after_fee, shares_burned: shares });
+        Ok(())
+    }
+
+    // -------------------------
+    // Prediction seasons & leaderboard
+    // -------------------------
+    /// Open a prediction season leaderboard (top LEADERBOARD_SIZE by season net profit) for this pool.
+    /// It becomes the pool's current season: every settled result is booked into it from now on.
+    pub fn start_prediction_season(ctx: Context<StartPredictionSeason>, season: u32, end_ts: i64) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        require!(end_ts > now, PredictionError::InvalidArgs);
+        require!(season > ctx.accounts.parlay_pool.current_season, PredictionError::InvalidArgs);
+        ctx.accounts.parlay_pool.current_season = season;
+        let board = &mut ctx.accounts.leaderboard;
+        board.pool = ctx.accounts.parlay_pool.key();
+        board.season = season;
+        board.start_ts = now;
+        board.end_ts = end_ts;
+        board.entries = Vec::new();
+        board.bump = *ctx.bumps.get("leaderboard").unwrap_or(&0);
+        emit!(PredictionSeasonStarted { leaderboard: ctx.accounts.leaderboard.key(), season, end_ts });
+        Ok(())
+    }
+
+    /// Top up a season's prize fund. Prizes are held by the Leaderboard PDA itself (lamports for the SOL pool,
+    /// its associated token account for SPL pools), never by the parlay vault. Anyone may fund.
+    pub fn fund_season_prizes(ctx: Context<FundSeasonPrizes>, amount: u64) -> Result<()> {
+        require!(amount > 0, PredictionError::InvalidArgs);
+        match ctx.accounts.parlay_pool.token_mint {
+            None => {
+                invoke_signed(
+                    &system_instruction::transfer(&ctx.accounts.funder.key(), &ctx.accounts.leaderboard.key(), amount),
+                    &[ctx.accounts.funder.to_account_info(), ctx.accounts.leaderboard.to_account_info()],
+                    &[],
+                )?;
+            }
+            Some(_) => {
+                let cpi_accounts = token::Transfer {
+                    from: ctx.accounts.funder_ata.as_ref().ok_or(PredictionError::InvalidArgs)?.to_account_info(),
+                    to: ctx.accounts.prize_vault_ata.as_ref().ok_or(PredictionError::InvalidArgs)?.to_account_info(),
+                    authority: ctx.accounts.funder.to_account_info(),
+                };
+                token::transfer(CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts), amount)?;
+            }
+        }
+        emit!(SeasonPrizesFunded { leaderboard: ctx.accounts.leaderboard.key(), funder: ctx.accounts.funder.key(), amount });
+        Ok(())
+    }
+
+    /// Pay a season prize to the leaderboard entry at `rank` (0-based) from the season's prize fund
+    /// (see fund_season_prizes). Authority-gated; callable once the season ended, once per rank.
+    pub fn award_season_prize(ctx: Context<AwardSeasonPrize>, rank: u8, amount: u64) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let board = &mut ctx.accounts.leaderboard;
+        require!(now >= board.end_ts, PredictionError::SeasonNotEnded);
+        let entry = board.entries.get_mut(rank as usize).ok_or(PredictionError::InvalidArgs)?;
+        require!(entry.wallet == ctx.accounts.winner.key(), PredictionError::InvalidArgs);
+        require!(!entry.prize_paid, PredictionError::AlreadyClaimed);
+        entry.prize_paid = true;
+
+        match ctx.accounts.parlay_pool.token_mint {
+            None => move_pda_lamports(&ctx.accounts.leaderboard.to_account_info(), &ctx.accounts.winner.to_account_info(), amount)?,
+            Some(_) => {
+                let prize_vault = ctx.accounts.prize_vault_ata.as_ref().ok_or(PredictionError::InvalidArgs)?;
+                require!(prize_vault.amount >= amount, PredictionError::InsufficientReserve);
+                let cpi_accounts = token::Transfer {
+                    from: prize_vault.to_account_info(),
+                    to: ctx.accounts.winner_ata.as_ref().ok_or(PredictionError::InvalidArgs)?.to_account_info(),
+                    authority: ctx.accounts.leaderboard.to_account_info(),
+                };
+                let pool_key = ctx.accounts.parlay_pool.key();
+                let season = ctx.accounts.leaderboard.season.to_le_bytes();
+                let bump = [ctx.accounts.leaderboard.bump];
+                let signer_seeds = &[&[b"leaderboard", pool_key.as_ref(), &season, &bump][..]];
+                token::transfer(CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds), amount)?;
+            }
+        }
+
+        emit!(SeasonPrizeAwarded { leaderboard: ctx.accounts.leaderboard.key(), winner: ctx.accounts.winner.key(), rank, amount });
+        Ok(())
+    }
+
+    // -------------------------
+    // Referrals
+    // -------------------------
+    /// Move `amount` of protocol_reserve into the promo budget that funds promo-market bonuses.
+    pub fn fund_promo_budget(ctx: Context<UpdateParlayConfig>, amount: u64) -> Result<()> {
+        let pool = &mut ctx.accounts.parlay_pool;
+        require!(amount > 0 && amount <= vault_backed_reserve(pool), PredictionError::InsufficientReserve);
+        pool.protocol_reserve = pool.protocol_reserve.saturating_sub(amount);
+        pool.promo_budget = pool.promo_budget.saturating_add(amount);
+        emit!(PromoBudgetFunded { pool: pool.key(), amount, budget: pool.promo_budget });
+        Ok(())
+    }
+
+    /// Flag a game pool as a promo market: protocol fees are waived on its claims and winners get
+    /// `bonus_bps` of their payout on top, paid from the promo budget while it lasts.
+    pub fn set_game_pool_promo(ctx: Context<SetGamePoolPromo>, promo: bool, bonus_bps: u16) -> Result<()> {
+        require!(bonus_bps <= 10_000, PredictionError::InvalidArgs);
+        let pool = &mut ctx.accounts.game_pool;
+        pool.promo = promo;
+        pool.promo_bonus_bps = if promo { bonus_bps } else { 0 };
+        emit!(GamePoolPromoSet { pool: pool.pool_id, promo, bonus_bps: pool.promo_bonus_bps });
+        Ok(())
+    }
+
+    /// Cap each game pool's net (hedge-adjusted) liability. 0 removes the cap.
+    pub fn set_max_pool_liability(ctx: Context<UpdateParlayConfig>, max_pool_liability: u64) -> Result<()> {
+        ctx.accounts.parlay_pool.max_pool_liability = max_pool_liability;
+        emit!(ExposureCapUpdated { pool: ctx.accounts.parlay_pool.key(), max_pool_liability });
+        Ok(())
+    }
+
+    /// Set COMPLIANCE_* flags on the whole parlay book (stops every new single and parlay bet in this currency).
+    pub fn set_pool_compliance(ctx: Context<UpdateParlayConfig>, flags: u8) -> Result<()> {
+        require!(flags & !COMPLIANCE_ALL == 0, PredictionError::InvalidArgs);
+        ctx.accounts.parlay_pool.compliance_flags = flags;
+        emit!(ComplianceFlagsSet { pool: ctx.accounts.parlay_pool.key(), game_pool: None, flags });
+        Ok(())
+    }
+
+    /// Set COMPLIANCE_* flags on one market. Works before the first bet: the GamePool is created here if
+    /// needed and finished by the first place_single_bet.
+    pub fn set_game_pool_compliance(ctx: Context<SetGamePoolCompliance>, flags: u8) -> Result<()> {
+        require!(flags & !COMPLIANCE_ALL == 0, PredictionError::InvalidArgs);
+        let pool = &mut ctx.accounts.game_pool;
+        if !pool.initialized {
+            pool.pool_id = ctx.accounts.battle.key();
+            pool.parlay_pool = ctx.accounts.parlay_pool.key();
+            pool.token_mint = ctx.accounts.parlay_pool.token_mint;
+            pool.bump = *ctx.bumps.get("game_pool").unwrap_or(&0);
+        }
+        pool.compliance_flags = flags;
+        emit!(ComplianceFlagsSet { pool: ctx.accounts.parlay_pool.key(), game_pool: Some(pool.pool_id), flags });
+        Ok(())
+    }
+
+    /// Set the fixed reward (in this pool's currency) paid to whoever cranks settle_single_pool.
+    pub fn set_crank_fee(ctx: Context<UpdateParlayConfig>, crank_fee: u64) -> Result<()> {
+        ctx.accounts.parlay_pool.crank_fee = crank_fee;
+        emit!(CrankFeeUpdated { pool: ctx.accounts.parlay_pool.key(), crank_fee });
+        Ok(())
+    }
+
+    /// Set the slice (bps) of a referred bettor's fees credited to their referrer.
+    pub fn set_referral_fee(ctx: Context<UpdateParlayConfig>, referral_fee_bps: u16) -> Result<()> {
+        require!(referral_fee_bps <= 10_000, PredictionError::InvalidArgs);
+        ctx.accounts.parlay_pool.referral_fee_bps = referral_fee_bps;
+        emit!(ReferralFeeUpdated { pool: ctx.accounts.parlay_pool.key(), referral_fee_bps });
+        Ok(())
+    }
+
+    /// Bind the signing bettor to a referrer (one-time). Rejects self-referral and direct
+    /// two-wallet loops (referrer already referred by this bettor).
+    pub fn bind_referrer(ctx: Context<BindReferrer>, referrer: Pubkey) -> Result<()> {
+        let bettor = ctx.accounts.bettor.key();
+        require!(referrer != bettor, PredictionError::SelfReferral);
+        if let Some(upstream) = ctx.accounts.referrer_referral.as_ref() {
+            require!(upstream.bettor == referrer, PredictionError::InvalidArgs);
+            require!(upstream.referrer != bettor, PredictionError::SelfReferral);
+        }
+        let referral = &mut ctx.accounts.referral;
+        referral.bettor = bettor;
+        referral.referrer = referrer;
+        referral.bound_at = Clock::get()?.unix_timestamp;
+        referral.bump = *ctx.bumps.get("referral").unwrap_or(&0);
+        // the referrer's per-pool ReferrerBalance is opened by the first fee credited in that pool
+        emit!(ReferrerBound { bettor, referrer });
+        Ok(())
+    }
+
+    /// Referrer withdraws their accumulated fee share for one pool.
+    pub fn claim_referral_rewards(ctx: Context<ClaimReferralRewards>) -> Result<()> {
+        require!(!ctx.accounts.parlay_pool.withdrawals_paused, PredictionError::OperationPaused);
+        let balance = &mut ctx.accounts.referrer_balance;
+        let amount = balance.claimable;
+        require!(amount > 0, PredictionError::NothingToWithdraw);
+        balance.claimable = 0;
+        balance.total_claimed = balance.total_claimed.saturating_add(amount);
+        ctx.accounts.parlay_pool.referral_owed = ctx.accounts.parlay_pool.referral_owed.saturating_sub(amount);
+
+        match ctx.accounts.parlay_pool.token_mint {
+            None => {
+                invoke_signed(
+                    &system_instruction::transfer(&ctx.accounts.parlay_pool.key(), &ctx.accounts.referrer.key(), amount),
+                    &[ctx.accounts.parlay_pool.to_account_info(), ctx.accounts.referrer.to_account_info()],
+                    &[&[b"parlay_pool", ctx.accounts.parlay_pool.mint_seed(), &[ctx.accounts.parlay_pool.bump]]],
+                )?;
+            }
+            Some(_) => {
+                let cpi_accounts = token::Transfer {
+                    from: ctx.accounts.parlay_vault_ata.to_account_info(),
+                    to: ctx.accounts.referrer_ata.to_account_info(),
+                    authority: ctx.accounts.parlay_pool.to_account_info(),
+                };
+                let bump = [ctx.accounts.parlay_pool.bump];
+                let signer_seeds = &[&[b"parlay_pool", ctx.accounts.parlay_pool.mint_seed(), &bump][..]];
+                token::transfer(CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds), amount)?;
+            }
+        }
+
+        emit!(ReferralRewardsClaimed { referrer: ctx.accounts.referrer.key(), pool: ctx.accounts.parlay_pool.key(), amount });
+        Ok(())
+    }
+
+    /// Pay out fee rebates earned from monthly volume tiers. Rebates are funded from protocol_reserve.
+    pub fn claim_fee_rebate(ctx: Context<ClaimFeeRebate>) -> Result<()> {
+        require!(!ctx.accounts.parlay_pool.withdrawals_paused, PredictionError::OperationPaused);
+        let stats = &mut ctx.accounts.bettor_stats;
+        let amount = stats.rebate_accrued;
+        require!(amount > 0, PredictionError::NothingToWithdraw);
+        // paid out of the parlay vault, so only the reserve that is already in it can fund the rebate
+        require!(vault_backed_reserve(&ctx.accounts.parlay_pool) >= amount, PredictionError::InsufficientReserve);
+        stats.rebate_accrued = 0;
+        stats.rebate_claimed = stats.rebate_claimed.saturating_add(amount);
+        ctx.accounts.parlay_pool.protocol_reserve = ctx.accounts.parlay_pool.protocol_reserve.saturating_sub(amount);
+
+        let to = match ctx.accounts.parlay_pool.token_mint {
+            None => ctx.accounts.bettor.to_account_info(),
+            Some(_) => ctx.accounts.bettor_ata.as_ref().ok_or(PredictionError::InvalidArgs)?.to_account_info(),
+        };
+        let vault = ctx.accounts.parlay_vault_ata.as_ref().map(|v| v.to_account_info());
+        parlay_pool_transfer(&ctx.accounts.parlay_pool, vault, to, &ctx.accounts.token_program, amount)?;
+
+        emit!(FeeRebateClaimed { bettor: ctx.accounts.bettor.key(), pool: ctx.accounts.parlay_pool.key(), amount });
+        Ok(())
+    }
+
+    // -------------------------
+    // Responsible gaming: daily caps + self-exclusion
+    // -------------------------
+    /// Set (or clear with 0) a wallet's daily stake cap in this pool's currency.
+    pub fn set_daily_stake_cap(ctx: Context<SetDailyStakeCap>, bettor: Pubkey, daily_cap: u64) -> Result<()> {
+        let stats = &mut ctx.accounts.bettor_stats;
+        if stats.bettor == Pubkey::default() {
+            stats.bettor = bettor;
+            stats.pool = ctx.accounts.parlay_pool.key();
+            stats.bump = *ctx.bumps.get("bettor_stats").unwrap_or(&0);
+        }
+        stats.daily_cap = daily_cap;
+        emit!(DailyStakeCapSet { bettor, pool: ctx.accounts.parlay_pool.key(), daily_cap });
+        Ok(())
+    }
+
+    /// Wallet excludes itself from betting (all pools) for at least `duration_secs`.
+    /// Calling again can only extend the exclusion and cancels any pending re-enable.
+    pub fn self_exclude(ctx: Context<SelfExclude>, duration_secs: i64) -> Result<()> {
+        require!(duration_secs >= MIN_SELF_EXCLUSION_SECS, PredictionError::InvalidArgs);
+        let now = Clock::get()?.unix_timestamp;
+        let exclusion = &mut ctx.accounts.exclusion;
+        if exclusion.wallet == Pubkey::default() {
+            exclusion.wallet = ctx.accounts.bettor.key();
+            exclusion.bump = *ctx.bumps.get("exclusion").unwrap_or(&0);
+        }
+        exclusion.excluded = true;
+        exclusion.excluded_until = exclusion.excluded_until.max(now.saturating_add(duration_secs));
+        exclusion.reenable_at = 0;
+        emit!(SelfExcluded { wallet: exclusion.wallet, excluded_until: exclusion.excluded_until });
+        Ok(())
+    }
+
+    /// Once the exclusion period has passed, start the SELF_EXCLUSION_COOLDOWN after which betting resumes.
+    pub fn request_reenable(ctx: Context<SelfExclude>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let exclusion = &mut ctx.accounts.exclusion;
+        require!(exclusion.excluded, PredictionError::InvalidArgs);
+        require!(now >= exclusion.excluded_until, PredictionError::SelfExcluded);
+        require!(exclusion.reenable_at == 0, PredictionError::InvalidArgs);
+        exclusion.reenable_at = now.saturating_add(SELF_EXCLUSION_COOLDOWN);
+        emit!(ReenableRequested { wallet: exclusion.wallet, reenable_at: exclusion.reenable_at });
+        Ok(())
+    }
+
+    /// Lock a liquidity/restake position for one of the LOCK_TIERS. Locking boosts the position's
+    /// fee-share weight until `lock_until`; withdrawing earlier pays the tier's early-exit penalty.
+    /// A lock can only be extended or upgraded, never shortened.
+    pub fn lock_position(ctx: Context<LockPosition>, tier: u8) -> Result<()> {
+        let (duration, boost_bps, penalty_bps) = *LOCK_TIERS.get(tier as usize).ok_or(PredictionError::InvalidLockTier)?;
+        let position = &mut ctx.accounts.restake_pos;
+        require!(position.owner == ctx.accounts.provider.key(), PredictionError::Unauthorized);
+        require!(!position.closed && position.share > 0, PredictionError::NothingToWithdraw);
+        let now = Clock::get()?.unix_timestamp;
+        let lock_until = now.saturating_add(duration);
+        require!(lock_until >= position.lock_until && boost_bps >= position.boost_bps, PredictionError::InvalidLockTier);
+
+        settle_lp_fees(&ctx.accounts.parlay_pool, position)?;
+        position.lock_until = lock_until;
+        position.boost_bps = boost_bps;
+        position.early_exit_penalty_bps = penalty_bps;
+        sync_fee_weight(&mut ctx.accounts.parlay_pool, position)?;
+
+        emit!(PositionLocked { pool: ctx.accounts.parlay_pool.key(), owner: position.owner, lock_until, boost_bps });
+        Ok(())
+    }
+
+    /// Pay out LP fee revenue accrued on a position (pro-rata via ParlayPool.acc_fee_per_share).
+    pub fn claim_lp_fees(ctx: Context<ClaimLpFees>) -> Result<()> {
+        require!(!ctx.accounts.parlay_pool.withdrawals_paused, PredictionError::OperationPaused);
+        let position = &mut ctx.accounts.restake_pos;
+        require!(position.owner == ctx.accounts.provider.key(), PredictionError::Unauthorized);
+        settle_lp_fees(&ctx.accounts.parlay_pool, position)?;
+        // also drops an expired lock boost back to 1x
+        sync_fee_weight(&mut ctx.accounts.parlay_pool, position)?;
+        let amount = position.fees_accrued;
+        require!(amount > 0, PredictionError::NothingToWithdraw);
+        position.fees_accrued = 0;
+        ctx.accounts.parlay_pool.lp_fee_reserve = ctx.accounts.parlay_pool.lp_fee_reserve.saturating_sub(amount);
+
+        match ctx.accounts.parlay_pool.token_mint {
+            None => {
+                invoke_signed(
+                    &system_instruction::transfer(&ctx.accounts.parlay_pool.key(), &ctx.accounts.provider.key(), amount),
+                    &[ctx.accounts.parlay_pool.to_account_info(), ctx.accounts.provider.to_account_info()],
+                    &[&[b"parlay_pool", ctx.accounts.parlay_pool.mint_seed(), &[ctx.accounts.parlay_pool.bump]]],
+                )?;
+            }
+            Some(_) => {
+                let cpi_accounts = token::Transfer {
+                    from: ctx.accounts.parlay_vault_ata.to_account_info(),
+                    to: ctx.accounts.provider_ata.to_account_info(),
+                    authority: ctx.accounts.parlay_pool.to_account_info(),
+                };
+                let bump = [ctx.accounts.parlay_pool.bump];
+                let signer_seeds = &[&[b"parlay_pool", ctx.accounts.parlay_pool.mint_seed(), &bump][..]];
+                token::transfer(CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds), amount)?;
+            }
+        }
+
+        emit!(LpFeesClaimed { pool: ctx.accounts.parlay_pool.key(), provider: ctx.accounts.provider.key(), amount });
+        Ok(())
+    }
+
+    // -------------------------
+    // Views (read-only, result in return data)
+    // -------------------------
+    /// Canonical odds for a game pool, Borsh-encoded `PoolOdds` via `sol_set_return_data`. Meant for CPI
+    /// callers and simulateTransaction clients; it writes nothing.
+    pub fn get_pool_odds(ctx: Context<GetPoolOdds>) -> Result<()> {
+        let pool = &ctx.accounts.game_pool;
+        let odds = PoolOdds {
+            battle: pool.pool_id,
+            total_staked: pool.total_staked,
+            outcome_totals: pool.outcome_totals,
+            implied_odds_x100: implied_odds_x100(pool),
+            is_settled: pool.is_settled,
+            winning_outcome: pool.winning_outcome,
+            net_liability: pool.net_liability,
+        };
+        anchor_lang::solana_program::program::set_return_data(&odds.try_to_vec()?);
+        Ok(())
+    }
+}
+
+// -------------------------
+// Accounts / State
+// -------------------------
+#[account]
+pub struct ParlayPool {
+    pub authority: Pubkey,
+    pub token_mint: Option<Pubkey>, // None => SOL pool, Some => SPL mint
+    pub liquidity_balance: u64,
+    pub liquidity_floor: u64,
+    pub protocol_reserve: u64,
+    pub total_shares: u64, // normalized LP shares outstanding across all restake positions
+    pub total_fee_weight: u64, // sum of lock-boosted position weights; LP fees are split by weight
+    pub lp_mint: Option<Pubkey>, // SPL mirror of shares, mint authority = this PDA
+    pub lp_fee_share_bps: u16, // slice of parlay-side fees routed to LPs
+    pub acc_fee_per_share: u128, // cumulative LP fees per unit of fee weight, scaled by ACC_FEE_SCALE
+    pub lp_fee_reserve: u64, // fees earmarked for LPs but not yet claimed; vault sub-ledger, debited from liquidity_balance when accrued out of it
+    pub pending_payouts: u64, // resolved winning tickets not yet claimed (already removed from liquidity_balance)
+    pub referral_fee_bps: u16, // slice of referred bettors' fees credited to referrers
+    pub referral_owed: u64, // credited to ReferrerBalance accounts, not yet claimed
+    // drawdown circuit breaker
+    pub drawdown_limit_bps: u16, // 0 => disabled
+    pub drawdown_window_secs: i64,
+    pub window_start_ts: i64,
+    pub window_start_liquidity: u64,
+    pub bets_paused: bool, // tripped by the drawdown circuit breaker
+    pub guardian: Pubkey, // may pause, only authority unpauses
+    pub placement_paused: bool,
+    pub settlement_paused: bool,
+    pub withdrawals_paused: bool,
+    pub crank_fee: u64, // paid to the permissionless settle_single_pool caller
+    pub arbiter: Pubkey, // rules on settlement disputes
+    pub dispute_window_secs: i64, // claims wait this long after settlement; 0 = no disputes
+    pub dispute_bond: u64, // lamports a challenger posts
+    pub promo_budget: u64, // earmarked for promo-market bonuses (held in the parlay vault)
+    pub max_pool_liability: u64, // cap on a game pool's net liability; 0 = uncapped
+    pub haircut_debt: u64, // unrepaid parlay haircuts
+    pub haircut_reserve: u64, // revenue set aside (out of liquidity) to repay haircut_debt
+    pub haircut_index: u128, // cumulative repayment per unit of outstanding haircut debt, scaled by ACC_FEE_SCALE
+    pub compliance_flags: u8, // COMPLIANCE_* bits for the whole book
+    pub insurance_enabled: bool, // InsuranceFund exists; every instruction touching it must then pass it
+    pub escrowed_fees: u64, // single-bet claim fees booked into protocol_reserve / referral_owed, still in game pool escrows
+    pub protocol_fee_bps: u16,
+    pub min_stake: u64,
+    pub max_multiplier_x100: u64,
+    pub current_season: u32, // prediction season whose Leaderboard results are booked into
+    pub bump: u8,
+    // reserved space
+    pub _padding: [u8; 3],
+}
+
+impl ParlayPool {
+    /// Second PDA seed: the SPL mint, or NATIVE_SEED for the SOL pool.
+    pub fn mint_seed(&self) -> &[u8] {
+        match &self.token_mint {
+            Some(mint) => mint.as_ref(),
+            None => NATIVE_SEED,
+        }
+    }
+
+    pub const INIT_SPACE: usize = 32 + 1 + 32 + 8 + 8 + 8 + 8 + 8 + (1 + 32) + 2 + 16 + 8 + 8 + 2 + 8 + 2 + 8 + 8 + 8 + 1 + 32 + 1 + 1 + 1 + 8 + 32 + 8 + 8 + 8 + 8 + 8 + 8 + 16 + 1 + 1 + 8 + 2 + 8 + 8 + 4 + 1 + 3;
+}
+
+/// Insurance sub-ledger of the parlay vault (like protocol_reserve, funds sit in the pool's
+/// lamports / vault ATA but are excluded from liquidity_balance).
+#[account]
+pub struct InsuranceFund {
+    pub pool: Pubkey,
+    pub balance: u64,
+    pub total_drawn: u64,
+    pub fee_share_bps: u16,
+    pub bump: u8,
+}
+impl InsuranceFund {
+    pub const INIT_SPACE: usize = 32 + 8 + 8 + 2 + 1 + 16;
+}
+
+pub const NATIVE_SEED: &[u8] = b"native";
+/// After this long, a settled game pool can be closed even if some bets were never claimed.
+pub const CLAIM_GRACE_PERIOD: i64 = 30 * 86_400;
+pub const MAX_PARLAY_POOLS: usize = 16;
+/// Max SingleBet positions settled by one claim_many call (compute budget bound).
+pub const MAX_CLAIM_BATCH: usize = 8;
+
+/// Registry of parlay pools so clients can enumerate every currency book.
+#[account]
+pub struct PoolRegistry {
+    pub authority: Pubkey,
+    pub pools: Vec<PoolEntry>,
+    pub bump: u8,
+}
+impl PoolRegistry {
+    pub const INIT_SPACE: usize = 32 + 4 + (PoolEntry::SIZE * MAX_PARLAY_POOLS) + 1;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct PoolEntry {
+    pub token_mint: Option<Pubkey>,
+    pub pool: Pubkey,
+}
+impl PoolEntry {
+    pub const SIZE: usize = (1 + 32) + 32;
+}
+
+/// Return data of `get_pool_odds`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct PoolOdds {
+    pub battle: Pubkey,
+    pub total_staked: u64,
+    pub outcome_totals: [u64; 3],
+    pub implied_odds_x100: [u64; 3],
+    pub is_settled: bool,
+    pub winning_outcome: Option<u8>,
+    pub net_liability: u64,
+}
+
+#[account]
+pub struct GamePool {
+    pub pool_id: Pubkey, // battle pubkey
+    pub parlay_pool: Pubkey, // currency book this market settles against
+    pub token_mint: Option<Pubkey>,
+    pub total_staked: u64,
+    pub snapshot_liquidity: u64,
+    pub initialized: bool,
+    pub is_settled: bool,
+    pub winning_outcome: Option<u8>,
+    pub open_bets: u32, // SingleBet accounts not yet claimed (and closed)
+    pub settled_at: i64,
+    pub disputed: bool, // claims frozen until the arbiter resolves the Dispute
+    pub promo: bool, // protocol fee waived on claims
+    pub promo_bonus_bps: u16, // winner bonus funded from ParlayPool.promo_budget
+    pub net_liability: u64, // sum of bettors' net (hedge-adjusted) worst-case payouts still open
+    pub outcome_totals: [u64; 3], // stake per OUTCOME_*
+    pub compliance_flags: u8, // COMPLIANCE_* bits for this market only
+    pub unrecorded_bets: u32, // bets not yet booked by record_single_results; claims wait for 0
+    pub voided: bool, // battle closed before settlement; every bet claims its stake back
+    pub fees_booked: u64, // claim fees already credited to the parlay pool's ledgers but still held in this escrow
+    pub bump: u8,
+    pub _padding: [u8; 19],
+}
+impl GamePool {
+    pub const INIT_SPACE: usize = 32 + 32 + 1 + 32 + 8 + 8 + 1 + 1 + 2 + 4 + 8 + 1 + 1 + 2 + 8 + 8 * 3 + 1 + 4 + 1 + 8 + 1 + 19;
+}
+
+/// A bettor's stakes per outcome in one game pool, used to net hedged positions.
+#[account]
+pub struct BettorPosition {
+    pub game_pool: Pubkey,
+    pub bettor: Pubkey,
+    pub stakes: [u64; 3], // indexed by OUTCOME_*
+    pub bump: u8,
+}
+impl BettorPosition {
+    pub const INIT_SPACE: usize = 32 + 32 + 8 * 3 + 1;
+}
+
+/// Open challenge against a game pool's settled outcome. Holds the challenger's bond in lamports.
+#[account]
+pub struct Dispute {
+    pub game_pool: Pubkey,
+    pub challenger: Pubkey,
+    pub proposed_outcome: u8,
+    pub bond: u64,
+    pub opened_at: i64,
+    pub bump: u8,
+}
+impl Dispute {
+    pub const INIT_SPACE: usize = 32 + 32 + 1 + 8 + 8 + 1;
+}
+
+#[account]
+pub struct SingleBet {
     pub bettor: Pubkey,
     pub pool: Pubkey,
     pub chosen_outcome: u8,
     pub stake: u64,
     pub claimed: bool,
+    pub recorded: bool, // booked into PredictorStats by record_single_results
+    pub streak: u16, // single_streak this win extended (prices the streak bonus)
     pub bump: u8,
 }
 impl SingleBet {
-    pub const INIT_SPACE: usize = 32 + 32 + 1 + 8 + 1 + 1 + 8;
+    pub const INIT_SPACE: usize = 32 + 32 + 1 + 8 + 1 + 1 + 2 + 1 + 8;
 }
 
 #[account]
@@ -548,78 +2000,702 @@ pub struct ParlayTicket {
     pub resolved: bool,
     pub won: Option<bool>,
     pub payout_snapshot: u64,
+    pub entitled: u64, // stake * multiplier at resolution, before any haircut
+    pub haircut: u64, // entitled - payout_snapshot when liquidity was short
+    pub haircut_repaid: u64,
+    pub haircut_index: u128, // parlay_pool.haircut_index when this ticket last accrued repayment
     pub claimed: bool,
     pub created_at: i64,
     pub bump: u8,
 }
 impl ParlayTicket {
     // rough estimate
-    pub const INIT_SPACE: usize = 32 + 4 + (32*8) + 4 + (8*8) + 8 + 1 + 1 + 8 + 8 + 8 + 1;
+    pub const INIT_SPACE: usize = 32 + 4 + (32*8) + 4 + (8*8) + 8 + 1 + 1 + 8 + 8 + 8 + 8 + 16 + 8 + 8 + 1;
 }
 
+/// Per-wallet prediction record (singles are booked by record_single_results, parlays at resolution).
 #[account]
-pub struct RestakePosition {
-    pub owner: Pubkey,
-    pub pool: Pubkey,
-    pub share: u64,
-    pub created_at: i64,
-    pub closed: bool,
+pub struct PredictorStats {
+    pub wallet: Pubkey,
+    pub bets: u32,
+    pub wins: u32,
+    pub total_staked: u64,
+    pub total_returned: u64,
+    pub roi_bps: i64, // (returned - staked) * 10_000 / staked
+    pub current_streak: u16,
+    pub best_streak: u16,
+    pub single_streak: u16, // consecutive correct single-bet picks (drives the streak bonus)
+    pub season: u32,
+    pub season_net: i64, // net profit in the current leaderboard season
     pub bump: u8,
 }
-impl RestakePosition {
-    pub const INIT_SPACE: usize = 32 + 32 + 8 + 8 + 1 + 1 + 8;
+impl PredictorStats {
+    pub const INIT_SPACE: usize = 32 + 4 + 4 + 8 + 8 + 8 + 2 + 2 + 2 + 4 + 8 + 1 + 8;
 }
 
-// -------------------------
-// Events
-// -------------------------
-#[event] pub struct ParlayPoolCreated { pub pool: Pubkey, pub token_mint: Option<Pubkey> }
-#[event] pub struct SingleBetPlaced { pub pool: Pubkey, pub bettor: Pubkey, pub stake: u64, pub choice: u8 }
-#[event] pub struct SinglePoolSettled { pub pool: Pubkey, pub winning_outcome: u8 }
-#[event] pub struct SingleClaimed { pub bettor: Pubkey, pub pool: Pubkey, pub payout: u64 }
-#[event] pub struct SingleClaimedRestaked { pub bettor: Pubkey, pub pool: Pubkey, pub restake_amt: u64 }
-#[event] pub struct ParlayBetPlaced { pub ticket: Pubkey, pub bettor: Pubkey, pub stake: u64, pub multiplier_x100: u64 }
-#[event] pub struct ParlayResolved { pub ticket: Pubkey, pub won: bool }
-#[event] pub struct ParlayClaimed { pub ticket: Pubkey, pub owner: Pubkey, pub amt: u64 }
-#[event] pub struct ParlayClaimedRestaked { pub ticket: Pubkey, pub owner: Pubkey, pub amt: u64 }
-#[event] pub struct RestakeWithdrawn { pub owner: Pubkey, pub amt: u64 }
-
-// -------------------------
-// Contexts (accounts for each instruction)
-// -------------------------
+pub const LEADERBOARD_SIZE: usize = 10;
 
-#[derive(Accounts)]
-pub struct InitializeParlayPool<'info> {
-    #[account(init, payer = authority, space = 8 + ParlayPool::INIT_SPACE, seeds = [b"parlay_pool"], bump)]
+/// Top-N predictors for a season, sorted by season_net descending.
+#[account]
+pub struct Leaderboard {
+    pub pool: Pubkey, // parlay pool whose bets are ranked
+    pub season: u32,
+    pub start_ts: i64,
+    pub end_ts: i64,
+    pub entries: Vec<LeaderboardEntry>,
+    pub bump: u8,
+}
+impl Leaderboard {
+    pub const INIT_SPACE: usize = 32 + 4 + 8 + 8 + 4 + (LeaderboardEntry::SIZE * LEADERBOARD_SIZE) + 1;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct LeaderboardEntry {
+    pub wallet: Pubkey,
+    pub season_net: i64,
+    pub wins: u32,
+    pub prize_paid: bool,
+}
+impl LeaderboardEntry {
+    pub const SIZE: usize = 32 + 8 + 4 + 1;
+}
+
+/// One-time binding of a bettor to the wallet that referred them.
+#[account]
+pub struct Referral {
+    pub bettor: Pubkey,
+    pub referrer: Pubkey,
+    pub bound_at: i64,
+    pub bump: u8,
+}
+impl Referral {
+    pub const INIT_SPACE: usize = 32 + 32 + 8 + 1;
+}
+
+/// Referrer's claimable fee share in one parlay pool's currency.
+#[account]
+pub struct ReferrerBalance {
+    pub referrer: Pubkey,
+    pub pool: Pubkey,
+    pub claimable: u64,
+    pub total_earned: u64,
+    pub total_claimed: u64,
+    pub bump: u8,
+}
+impl ReferrerBalance {
+    pub const INIT_SPACE: usize = 32 + 32 + 8 + 8 + 8 + 1;
+}
+
+/// Per-bettor, per-pool volume tracking for fee rebates.
+#[account]
+pub struct BettorStats {
+    pub bettor: Pubkey,
+    pub pool: Pubkey,
+    pub month_index: i64, // unix_ts / MONTH_SECS of the current volume bucket
+    pub month_volume: u64,
+    pub lifetime_volume: u64,
+    pub rebate_accrued: u64,
+    pub rebate_claimed: u64,
+    pub daily_cap: u64, // authority-set max stake per day; 0 = no cap
+    pub day_index: i64, // unix_ts / DAY_SECS of the current daily bucket
+    pub day_staked: u64,
+    pub bump: u8,
+}
+impl BettorStats {
+    pub const INIT_SPACE: usize = 32 + 32 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 1 + 8;
+}
+
+/// Wallet-wide self-exclusion. Betting stays blocked until `excluded_until` has passed AND the
+/// re-enable cool-down requested afterwards has elapsed.
+#[account]
+pub struct SelfExclusion {
+    pub wallet: Pubkey,
+    pub excluded: bool,
+    pub excluded_until: i64,
+    pub reenable_at: i64, // 0 = not requested
+    pub bump: u8,
+}
+impl SelfExclusion {
+    pub const INIT_SPACE: usize = 32 + 1 + 8 + 8 + 1;
+
+    pub fn is_excluded(&self, now: i64) -> bool {
+        self.excluded && (self.reenable_at == 0 || now < self.reenable_at)
+    }
+}
+
+#[account]
+pub struct RestakePosition {
+    pub owner: Pubkey,
+    pub pool: Pubkey,
+    pub share: u64, // normalized pool shares (see ParlayPool.total_shares)
+    pub fee_weight: u64, // share boosted by an active lock; counted in ParlayPool.total_fee_weight
+    pub fee_debt: u128, // fee_weight * acc_fee_per_share at last checkpoint
+    pub fees_accrued: u64, // settled, unclaimed LP fees
+    pub lock_until: i64, // 0 => unlocked
+    pub boost_bps: u16, // fee-weight boost while locked (10_000 = 1x)
+    pub early_exit_penalty_bps: u16,
+    pub created_at: i64,
+    pub closed: bool,
+    pub lp_minted_shares: u64, // part of `share` mirrored as LP tokens; the rest (minted before the LP mint) is internal-only
+    pub bump: u8,
+}
+impl RestakePosition {
+    pub const INIT_SPACE: usize = 32 + 32 + 8 + 8 + 16 + 8 + 8 + 2 + 2 + 8 + 1 + 8 + 1 + 8;
+}
+
+// -------------------------
+// Events
+// -------------------------
+#[event] pub struct ParlayPoolCreated { pub pool: Pubkey, pub token_mint: Option<Pubkey> }
+#[event] pub struct LpMintCreated { pub pool: Pubkey, pub lp_mint: Pubkey }
+/// Layout version of the bet/claim/resolution events below; bump whenever their fields change.
+pub const EVENT_VERSION: u8 = 2;
+
+// pool_liquidity: game pool total_staked for SingleBetPlaced, parlay pool liquidity_balance after the action otherwise
+#[event] pub struct SingleBetPlaced { pub version: u8, pub pool: Pubkey, pub bettor: Pubkey, pub stake: u64, pub choice: u8, pub outcome_totals: [u64; 3], pub implied_odds_x100: [u64; 3], pub pool_liquidity: u64 }
+#[event] pub struct SinglePoolSettled { pub pool: Pubkey, pub winning_outcome: u8 }
+#[event] pub struct SingleResultRecorded { pub bettor: Pubkey, pub pool: Pubkey, pub won: bool, pub single_streak: u16 }
+#[event] pub struct SingleClaimed { pub version: u8, pub bettor: Pubkey, pub pool: Pubkey, pub payout: u64, pub fee: u64, pub pool_liquidity: u64 }
+#[event] pub struct SingleClaimedRestaked { pub version: u8, pub bettor: Pubkey, pub pool: Pubkey, pub restake_amt: u64, pub shares_minted: u64, pub fee: u64, pub pool_liquidity: u64 }
+#[event] pub struct ManySinglesClaimed { pub bettor: Pubkey, pub pool: Pubkey, pub count: u32, pub payout: u64, pub shares_minted: u64 }
+#[event] pub struct GamePoolVoided { pub pool: Pubkey, pub open_bets: u32, pub refundable: u64 }
+#[event] pub struct SettlementCranked { pub pool: Pubkey, pub cranker: Pubkey, pub fee: u64 }
+#[event] pub struct CrankFeeUpdated { pub pool: Pubkey, pub crank_fee: u64 }
+#[event] pub struct DisputeConfigUpdated { pub pool: Pubkey, pub arbiter: Pubkey, pub window_secs: i64, pub bond_lamports: u64 }
+#[event] pub struct DisputeRaised { pub pool: Pubkey, pub challenger: Pubkey, pub proposed_outcome: u8, pub bond: u64 }
+#[event] pub struct DisputeResolved { pub pool: Pubkey, pub challenger: Pubkey, pub upheld: bool, pub winning_outcome: u8 }
+#[event] pub struct PromoBudgetFunded { pub pool: Pubkey, pub amount: u64, pub budget: u64 }
+#[event] pub struct GamePoolPromoSet { pub pool: Pubkey, pub promo: bool, pub bonus_bps: u16 }
+#[event] pub struct PromoBonusPaid { pub bettor: Pubkey, pub pool: Pubkey, pub bonus: u64 }
+#[event] pub struct ComplianceFlagsSet { pub pool: Pubkey, pub game_pool: Option<Pubkey>, pub flags: u8 }
+#[event] pub struct ExposureCapUpdated { pub pool: Pubkey, pub max_pool_liability: u64 }
+#[event] pub struct PoolReconciled { pub pool: Pubkey, pub expected: u64, pub actual: u64, pub corrected: bool }
+#[event] pub struct GamePoolReconciled { pub pool: Pubkey, pub expected: u64, pub actual: u64, pub corrected: bool }
+#[event] pub struct GamePoolClosed { pub pool: Pubkey, pub closed_by: Pubkey, pub unclaimed_swept: u64, pub forfeited_bets: u32 }
+#[event] pub struct OrphanedBetClosed { pub bettor: Pubkey, pub pool: Pubkey, pub chosen_outcome: u8, pub stake: u64 }
+#[event] pub struct ParlayBetPlaced { pub version: u8, pub ticket: Pubkey, pub bettor: Pubkey, pub stake: u64, pub multiplier_x100: u64, pub pool_liquidity: u64 }
+#[event] pub struct ParlayResolved { pub version: u8, pub ticket: Pubkey, pub won: bool, pub payout: u64, pub fee: u64, pub pool_liquidity: u64 }
+#[event] pub struct ParlayClaimed { pub version: u8, pub ticket: Pubkey, pub owner: Pubkey, pub amt: u64, pub fee: u64, pub pool_liquidity: u64 }
+#[event] pub struct ParlayClaimedRestaked { pub version: u8, pub ticket: Pubkey, pub owner: Pubkey, pub amt: u64, pub shares_minted: u64, pub fee: u64, pub pool_liquidity: u64 }
+#[event] pub struct LiquidityDeposited { pub pool: Pubkey, pub provider: Pubkey, pub amount: u64, pub shares_minted: u64 }
+#[event] pub struct SeedSharesLocked { pub pool: Pubkey, pub shares: u64 }
+#[event] pub struct LiquidityWithdrawn { pub pool: Pubkey, pub provider: Pubkey, pub amount: u64, pub shares_burned: u64 }
+#[event] pub struct LpTokensRedeemed { pub pool: Pubkey, pub holder: Pubkey, pub position: Pubkey, pub amount: u64, pub shares_burned: u64 }
+#[event] pub struct LpFeeShareUpdated { pub pool: Pubkey, pub lp_fee_share_bps: u16 }
+#[event] pub struct LpFeesDistributed { pub pool: Pubkey, pub lp_amount: u64, pub protocol_amount: u64 }
+#[event] pub struct LpFeesClaimed { pub pool: Pubkey, pub provider: Pubkey, pub amount: u64 }
+#[event] pub struct PositionLocked { pub pool: Pubkey, pub owner: Pubkey, pub lock_until: i64, pub boost_bps: u16 }
+#[event] pub struct EarlyExitPenalized { pub pool: Pubkey, pub owner: Pubkey, pub penalty: u64 }
+#[event] pub struct InsuranceFundCreated { pub fund: Pubkey, pub pool: Pubkey, pub fee_share_bps: u16 }
+#[event] pub struct InsuranceFunded { pub fund: Pubkey, pub amount: u64, pub balance: u64 }
+#[event] pub struct InsuranceDrawn { pub fund: Pubkey, pub ticket: Pubkey, pub amount: u64, pub balance: u64 }
+#[event] pub struct PayoutHaircut { pub ticket: Pubkey, pub entitled: u64, pub paid: u64, pub haircut: u64 }
+#[event] pub struct HaircutRepaymentReserved { pub pool: Pubkey, pub amount: u64, pub outstanding: u64 }
+#[event] pub struct HaircutRepaid { pub ticket: Pubkey, pub owner: Pubkey, pub amount: u64, pub remaining: u64 }
+#[event] pub struct CircuitBreakerConfigured { pub pool: Pubkey, pub drawdown_limit_bps: u16, pub window_secs: i64 }
+#[event] pub struct CircuitBreakerTripped { pub pool: Pubkey, pub window_start_liquidity: u64, pub liquidity: u64 }
+#[event] pub struct BettingResumed { pub pool: Pubkey, pub liquidity: u64 }
+#[event] pub struct ReserveReconciled { pub pool: Pubkey, pub counter: u64, pub backed: u64 }
+#[event] pub struct ReserveWithdrawn { pub pool: Pubkey, pub treasury: Pubkey, pub amount: u64 }
+#[event] pub struct FeeRebateAccrued { pub bettor: Pubkey, pub pool: Pubkey, pub amount: u64, pub tier_bps: u16 }
+#[event] pub struct FeeRebateClaimed { pub bettor: Pubkey, pub pool: Pubkey, pub amount: u64 }
+#[event] pub struct PredictionSeasonStarted { pub leaderboard: Pubkey, pub season: u32, pub end_ts: i64 }
+#[event] pub struct SeasonPrizesFunded { pub leaderboard: Pubkey, pub funder: Pubkey, pub amount: u64 }
+#[event] pub struct SeasonPrizeAwarded { pub leaderboard: Pubkey, pub winner: Pubkey, pub rank: u8, pub amount: u64 }
+#[event] pub struct StreakBonusPaid { pub bettor: Pubkey, pub pool: Pubkey, pub streak: u16, pub bonus: u64 }
+#[event] pub struct DailyStakeCapSet { pub bettor: Pubkey, pub pool: Pubkey, pub daily_cap: u64 }
+#[event] pub struct SelfExcluded { pub wallet: Pubkey, pub excluded_until: i64 }
+#[event] pub struct ReenableRequested { pub wallet: Pubkey, pub reenable_at: i64 }
+#[event] pub struct GuardianUpdated { pub pool: Pubkey, pub guardian: Pubkey }
+#[event] pub struct PauseStateChanged { pub pool: Pubkey, pub placement: bool, pub settlement: bool, pub withdrawals: bool }
+#[event] pub struct ReferralFeeUpdated { pub pool: Pubkey, pub referral_fee_bps: u16 }
+#[event] pub struct ReferrerBound { pub bettor: Pubkey, pub referrer: Pubkey }
+#[event] pub struct ReferralFeeCredited { pub referrer: Pubkey, pub bettor: Pubkey, pub amount: u64 }
+#[event] pub struct ReferralRewardsClaimed { pub referrer: Pubkey, pub pool: Pubkey, pub amount: u64 }
+#[event] pub struct RestakeWithdrawn { pub owner: Pubkey, pub amt: u64, pub shares_burned: u64 }
+
+// -------------------------
+// Contexts (accounts for each instruction)
+// -------------------------
+
+#[derive(Accounts)]
+pub struct InitializePoolRegistry<'info> {
+    #[account(init, payer = authority, space = 8 + PoolRegistry::INIT_SPACE, seeds = [b"pool_registry"], bump)]
+    pub registry: Account<'info, PoolRegistry>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(token_mint: Option<Pubkey>)]
+pub struct InitializeParlayPool<'info> {
+    #[account(init, payer = authority, space = 8 + ParlayPool::INIT_SPACE, seeds = [b"parlay_pool", token_mint.as_ref().map(|m| m.as_ref()).unwrap_or(NATIVE_SEED)], bump)]
     pub parlay_pool: Account<'info, ParlayPool>,
+    #[account(mut, seeds = [b"pool_registry"], bump = registry.bump)]
+    pub registry: Account<'info, PoolRegistry>,
+    #[account(init, payer = authority, space = 8 + Leaderboard::INIT_SPACE, seeds = [b"leaderboard", parlay_pool.key().as_ref(), &0u32.to_le_bytes()], bump)]
+    pub leaderboard: Account<'info, Leaderboard>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    // optional: token program & associated token program passed when SPL flows are used
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateParlayConfig<'info> {
+    #[account(mut, has_one = authority)]
+    pub parlay_pool: Account<'info, ParlayPool>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct GuardianPause<'info> {
+    #[account(mut)]
+    pub parlay_pool: Account<'info, ParlayPool>,
+    pub signer: Signer<'info>, // guardian or authority
+}
+
+#[derive(Accounts)]
+pub struct WithdrawReserve<'info> {
+    #[account(mut, has_one = authority)]
+    pub parlay_pool: Account<'info, ParlayPool>,
+    pub authority: Signer<'info>,
+    #[account(seeds = [b"insurance", parlay_pool.key().as_ref()], bump = insurance_fund.bump)]
+    pub insurance_fund: Option<Account<'info, InsuranceFund>>,
+    /// CHECK: SOL treasury destination chosen by the authority
+    #[account(mut)]
+    pub treasury: UncheckedAccount<'info>,
+    // SPL fields
+    #[account(mut)]
+    pub parlay_vault_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub treasury_ata: Option<Account<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ReconcilePool<'info> {
+    #[account(mut)]
+    pub parlay_pool: Account<'info, ParlayPool>,
+    pub auditor: Signer<'info>, // anyone; must be the authority to apply corrections
+    #[account(seeds = [b"insurance", parlay_pool.key().as_ref()], bump = insurance_fund.bump)]
+    pub insurance_fund: Option<Account<'info, InsuranceFund>>,
+    pub parlay_vault_ata: Option<Account<'info, TokenAccount>>,
+    // remaining_accounts: see reconcile_pool
+}
+
+#[derive(Accounts)]
+pub struct GetPoolOdds<'info> {
+    pub game_pool: Account<'info, GamePool>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeInsuranceFund<'info> {
+    #[account(mut, has_one = authority)]
+    pub parlay_pool: Account<'info, ParlayPool>,
+    #[account(init, payer = authority, space = 8 + InsuranceFund::INIT_SPACE, seeds = [b"insurance", parlay_pool.key().as_ref()], bump)]
+    pub insurance_fund: Account<'info, InsuranceFund>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(decimals: u8)]
+pub struct InitializeLpMint<'info> {
+    #[account(mut, has_one = authority)]
+    pub parlay_pool: Account<'info, ParlayPool>,
+    #[account(init, payer = authority, seeds = [b"lp_mint", parlay_pool.key().as_ref()], bump, mint::decimals = decimals, mint::authority = parlay_pool)]
+    pub lp_mint: Account<'info, Mint>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+#[instruction(chosen_outcome: u8)]
+pub struct PlaceSingleBet<'info> {
+    #[account(mut)]
+    pub parlay_pool: Account<'info, ParlayPool>, // used for config like min_stake & token_mint
+    #[account(init_if_needed, payer = bettor, space = 8 + GamePool::INIT_SPACE, seeds = [b"game_pool", battle.key().as_ref(), parlay_pool.key().as_ref()], bump)]
+    pub game_pool: Account<'info, GamePool>,
+    /// CHECK: the Battle account from the game program (deserialized for validation)
+    pub battle: UncheckedAccount<'info>,
+    // one bet per outcome, so a bettor can hold both sides of a market
+    #[account(init, payer = bettor, space = 8 + SingleBet::INIT_SPACE, seeds = [b"single_bet", game_pool.key().as_ref(), bettor.key.as_ref(), &[chosen_outcome]], bump)]
+    pub single_bet: Account<'info, SingleBet>,
+    #[account(init_if_needed, payer = bettor, space = 8 + BettorPosition::INIT_SPACE, seeds = [b"position", game_pool.key().as_ref(), bettor.key.as_ref()], bump)]
+    pub position: Account<'info, BettorPosition>,
+    #[account(init_if_needed, payer = bettor, space = 8 + BettorStats::INIT_SPACE, seeds = [b"bettor_stats", bettor.key.as_ref(), parlay_pool.key().as_ref()], bump)]
+    pub bettor_stats: Account<'info, BettorStats>,
+    #[account(init_if_needed, payer = bettor, space = 8 + SelfExclusion::INIT_SPACE, seeds = [b"exclusion", bettor.key.as_ref()], bump)]
+    pub exclusion: Account<'info, SelfExclusion>,
+    // created here so record_single_results can book the result without the bettor
+    #[account(init_if_needed, payer = bettor, space = 8 + PredictorStats::INIT_SPACE, seeds = [b"predictor", bettor.key.as_ref()], bump)]
+    pub predictor_stats: Account<'info, PredictorStats>,
+    #[account(mut)]
+    pub bettor: Signer<'info>,
+
+    // SOL flow: none needed other than game_pool PDA lamports held
+    // SPL flow: token accounts & escrow ATA for game_pool
+    #[account(mut)]
+    pub bettor_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub game_pool_escrow: Option<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct SettleSinglePool<'info> {
+    #[account(mut)]
+    pub parlay_pool: Account<'info, ParlayPool>,
+    #[account(mut, has_one = parlay_pool)]
+    pub game_pool: Account<'info, GamePool>,
+    /// CHECK: Battle account (owner + discriminator verified in deserialize_battle_snapshot)
+    pub battle: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub cranker: Signer<'info>, // anyone; receives crank_fee
+    #[account(mut)]
+    pub game_pool_escrow: Option<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub cranker_ata: Option<Account<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct VoidGamePool<'info> {
+    pub parlay_pool: Account<'info, ParlayPool>,
+    #[account(mut, has_one = parlay_pool)]
+    pub game_pool: Account<'info, GamePool>,
+    /// CHECK: the market's battle; void_game_pool only proceeds once it is closed
+    #[account(address = game_pool.pool_id @ PredictionError::InvalidPool)]
+    pub battle: UncheckedAccount<'info>,
+    pub cranker: Signer<'info>, // anyone
+}
+
+#[derive(Accounts)]
+pub struct SetGamePoolCompliance<'info> {
+    #[account(has_one = authority)]
+    pub parlay_pool: Account<'info, ParlayPool>,
+    #[account(init_if_needed, payer = authority, space = 8 + GamePool::INIT_SPACE, seeds = [b"game_pool", battle.key().as_ref(), parlay_pool.key().as_ref()], bump)]
+    pub game_pool: Account<'info, GamePool>,
+    /// CHECK: battle the market is for; only used as a seed
+    pub battle: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetGamePoolPromo<'info> {
+    #[account(has_one = authority)]
+    pub parlay_pool: Account<'info, ParlayPool>,
+    #[account(mut, has_one = parlay_pool)]
+    pub game_pool: Account<'info, GamePool>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RaiseDispute<'info> {
+    pub parlay_pool: Account<'info, ParlayPool>,
+    #[account(mut, has_one = parlay_pool)]
+    pub game_pool: Account<'info, GamePool>,
+    #[account(init, payer = challenger, space = 8 + Dispute::INIT_SPACE, seeds = [b"dispute", game_pool.key().as_ref()], bump)]
+    pub dispute: Account<'info, Dispute>,
+    #[account(mut)]
+    pub challenger: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ResolveDispute<'info> {
+    #[account(has_one = arbiter, has_one = authority)]
+    pub parlay_pool: Account<'info, ParlayPool>,
+    #[account(mut, has_one = parlay_pool)]
+    pub game_pool: Account<'info, GamePool>,
+    #[account(mut, has_one = game_pool, has_one = challenger, seeds = [b"dispute", game_pool.key().as_ref()], bump = dispute.bump)]
+    pub dispute: Account<'info, Dispute>,
+    pub arbiter: Signer<'info>,
+    /// CHECK: receives the bond if the dispute is upheld (checked against dispute.challenger)
+    #[account(mut)]
+    pub challenger: UncheckedAccount<'info>,
+    /// CHECK: receives a forfeited bond (checked against parlay_pool.authority)
+    #[account(mut)]
+    pub authority: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RecordSingleResults<'info> {
+    pub parlay_pool: Account<'info, ParlayPool>,
+    #[account(mut, has_one = parlay_pool)]
+    pub game_pool: Account<'info, GamePool>,
+    #[account(mut, seeds = [b"leaderboard", parlay_pool.key().as_ref(), &parlay_pool.current_season.to_le_bytes()], bump = leaderboard.bump)]
+    pub leaderboard: Account<'info, Leaderboard>,
+    pub cranker: Signer<'info>, // anyone
+    // remaining_accounts: see record_single_results
+}
+
+#[derive(Accounts)]
+pub struct ClaimSingle<'info> {
+    #[account(mut)]
+    pub parlay_pool: Account<'info, ParlayPool>,
+    #[account(mut, has_one = parlay_pool)]
+    pub game_pool: Account<'info, GamePool>,
+    #[account(mut, has_one = pool, has_one = bettor, close = bettor)]
+    pub single_bet: Account<'info, SingleBet>,
+    // enables hedge netting on the fee when the bettor also backed other outcomes
+    #[account(seeds = [b"position", game_pool.key().as_ref(), bettor.key.as_ref()], bump = position.bump)]
+    pub position: Option<Account<'info, BettorPosition>>,
+    #[account(mut)]
+    pub bettor: Signer<'info>,
+    #[account(mut, seeds = [b"bettor_stats", bettor.key.as_ref(), parlay_pool.key().as_ref()], bump = bettor_stats.bump)]
+    pub bettor_stats: Option<Account<'info, BettorStats>>,
+    #[account(seeds = [b"referral", bettor.key.as_ref()], bump = referral.bump)]
+    pub referral: Option<Account<'info, Referral>>,
+    /// CHECK: ReferrerBalance PDA of referral.referrer for this pool; credit_referrer checks it and creates it on
+    /// the first credit (paid by the caller)
+    #[account(mut)]
+    pub referrer_balance: Option<UncheckedAccount<'info>>,
+    // SPL flows
+    #[account(mut)]
+    pub bettor_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub game_pool_escrow: Option<Account<'info, TokenAccount>>,
+
+    // restake / parlay vault
+    #[account(mut, constraint = parlay_vault_ata.owner == parlay_pool.key() && Some(parlay_vault_ata.mint) == parlay_pool.token_mint @ PredictionError::InvalidTokenAccount)]
+    pub parlay_vault_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub parlay_pool: Account<'info, ParlayPool>,
+
+    // restake pos to create if restake chosen
+    #[account(init_if_needed, payer = bettor, space = 8 + RestakePosition::INIT_SPACE, seeds = [b"restake", bettor.key.as_ref(), parlay_pool.key().as_ref()], bump)]
+    pub restake_pos: Account<'info, RestakePosition>,
+    // LP token mirror (required once parlay_pool.lp_mint is set)
+    #[account(mut)]
+    pub lp_mint: Option<Account<'info, Mint>>,
+    #[account(mut)]
+    pub bettor_lp_ata: Option<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimMany<'info> {
+    #[account(mut)]
+    pub parlay_pool: Account<'info, ParlayPool>,
+    #[account(mut)]
+    pub bettor: Signer<'info>,
+    #[account(mut, seeds = [b"bettor_stats", bettor.key.as_ref(), parlay_pool.key().as_ref()], bump = bettor_stats.bump)]
+    pub bettor_stats: Option<Account<'info, BettorStats>>,
+    #[account(seeds = [b"referral", bettor.key.as_ref()], bump = referral.bump)]
+    pub referral: Option<Account<'info, Referral>>,
+    /// CHECK: ReferrerBalance PDA of referral.referrer for this pool; credit_referrer checks it and creates it on
+    /// the first credit (paid by the caller)
+    #[account(mut)]
+    pub referrer_balance: Option<UncheckedAccount<'info>>,
+    #[account(mut)]
+    pub bettor_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut, constraint = parlay_vault_ata.owner == parlay_pool.key() && Some(parlay_vault_ata.mint) == parlay_pool.token_mint @ PredictionError::InvalidTokenAccount)]
+    pub parlay_vault_ata: Option<Account<'info, TokenAccount>>,
+    // restake pos to create if restake chosen
+    #[account(init_if_needed, payer = bettor, space = 8 + RestakePosition::INIT_SPACE, seeds = [b"restake", bettor.key.as_ref(), parlay_pool.key().as_ref()], bump)]
+    pub restake_pos: Account<'info, RestakePosition>,
+    // LP token mirror (required once parlay_pool.lp_mint is set)
+    #[account(mut)]
+    pub lp_mint: Option<Account<'info, Mint>>,
+    #[account(mut)]
+    pub bettor_lp_ata: Option<Account<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    // remaining_accounts: see claim_many
+}
+
+#[derive(Accounts)]
+pub struct CloseGamePool<'info> {
+    #[account(mut)]
+    pub parlay_pool: Account<'info, ParlayPool>,
+    #[account(mut, has_one = parlay_pool, close = cranker)]
+    pub game_pool: Account<'info, GamePool>,
+    #[account(mut, constraint = game_pool_escrow.owner == game_pool.key() && Some(game_pool_escrow.mint) == game_pool.token_mint @ PredictionError::InvalidTokenAccount)]
+    pub game_pool_escrow: Option<Account<'info, TokenAccount>>,
+    #[account(mut, constraint = parlay_vault_ata.owner == parlay_pool.key() && Some(parlay_vault_ata.mint) == game_pool.token_mint @ PredictionError::InvalidTokenAccount)]
+    pub parlay_vault_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseOrphanedBet<'info> {
+    #[account(mut, has_one = bettor, close = bettor)]
+    pub single_bet: Account<'info, SingleBet>,
+    /// CHECK: the bet's game pool; must already be closed (no data)
+    #[account(address = single_bet.pool)]
+    pub game_pool: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub bettor: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct PlaceParlayBet<'info> {
+    #[account(mut)]
+    pub parlay_pool: Account<'info, ParlayPool>,
+    #[account(init, payer = bettor, space = 8 + ParlayTicket::INIT_SPACE, seeds = [b"parlay_ticket", bettor.key.as_ref(), parlay_pool.key().as_ref()], bump)]
+    pub parlay_ticket: Account<'info, ParlayTicket>,
+    #[account(init_if_needed, payer = bettor, space = 8 + BettorStats::INIT_SPACE, seeds = [b"bettor_stats", bettor.key.as_ref(), parlay_pool.key().as_ref()], bump)]
+    pub bettor_stats: Account<'info, BettorStats>,
+    #[account(init_if_needed, payer = bettor, space = 8 + SelfExclusion::INIT_SPACE, seeds = [b"exclusion", bettor.key.as_ref()], bump)]
+    pub exclusion: Account<'info, SelfExclusion>,
+    // created here so resolve_parlay_ticket can always book the result
+    #[account(init_if_needed, payer = bettor, space = 8 + PredictorStats::INIT_SPACE, seeds = [b"predictor", bettor.key.as_ref()], bump)]
+    pub predictor_stats: Account<'info, PredictorStats>,
+    #[account(mut)]
+    pub bettor: Signer<'info>,
+
+    // SPL fields
+    #[account(mut)]
+    pub bettor_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub parlay_vault_ata: Option<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct ResolveParlayTicket<'info> {
+    #[account(mut)]
+    pub parlay_pool: Account<'info, ParlayPool>,
+    #[account(mut, seeds = [b"parlay_ticket", parlay_ticket.owner.as_ref(), parlay_pool.key().as_ref()], bump = parlay_ticket.bump)]
+    pub parlay_ticket: Account<'info, ParlayTicket>,
+    #[account(mut, seeds = [b"insurance", parlay_pool.key().as_ref()], bump = insurance_fund.bump)]
+    pub insurance_fund: Option<Account<'info, InsuranceFund>>,
+    #[account(mut, seeds = [b"bettor_stats", parlay_ticket.owner.as_ref(), parlay_pool.key().as_ref()], bump = bettor_stats.bump)]
+    pub bettor_stats: Option<Account<'info, BettorStats>>,
+    #[account(mut, seeds = [b"predictor", parlay_ticket.owner.as_ref()], bump = predictor_stats.bump)]
+    pub predictor_stats: Account<'info, PredictorStats>,
+    #[account(seeds = [b"referral", parlay_ticket.owner.as_ref()], bump = referral.bump)]
+    pub referral: Option<Account<'info, Referral>>,
+    /// CHECK: ReferrerBalance PDA of referral.referrer for this pool; credit_referrer checks it and creates it on
+    /// the first credit (paid by the caller)
+    #[account(mut)]
+    pub referrer_balance: Option<UncheckedAccount<'info>>,
+    #[account(mut, seeds = [b"leaderboard", parlay_pool.key().as_ref(), &parlay_pool.current_season.to_le_bytes()], bump = leaderboard.bump)]
+    pub leaderboard: Account<'info, Leaderboard>,
+    #[account(mut)]
+    pub signer: Signer<'info>, // must be parlay_pool.authority; pays for a first-time ReferrerBalance
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimHaircutRepayment<'info> {
+    #[account(mut)]
+    pub parlay_pool: Account<'info, ParlayPool>,
+    #[account(mut, has_one = owner)]
+    pub parlay_ticket: Account<'info, ParlayTicket>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(mut)]
+    pub owner_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub parlay_vault_ata: Option<Account<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimParlay<'info> {
+    #[account(mut)]
+    pub parlay_pool: Account<'info, ParlayPool>,
+    #[account(mut, has_one = owner)]
+    pub parlay_ticket: Account<'info, ParlayTicket>,
+    #[account(mut)]
+    pub bettor: Signer<'info>,
+    #[account(mut, seeds = [b"bettor_stats", bettor.key.as_ref(), parlay_pool.key().as_ref()], bump = bettor_stats.bump)]
+    pub bettor_stats: Option<Account<'info, BettorStats>>,
+    #[account(seeds = [b"referral", bettor.key.as_ref()], bump = referral.bump)]
+    pub referral: Option<Account<'info, Referral>>,
+    /// CHECK: ReferrerBalance PDA of referral.referrer for this pool; credit_referrer checks it and creates it on
+    /// the first credit (paid by the caller)
+    #[account(mut)]
+    pub referrer_balance: Option<UncheckedAccount<'info>>,
+    // SPL fields
+    #[account(mut)]
+    pub parlay_vault_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub bettor_ata: Option<Account<'info, TokenAccount>>,
+    #[account(init_if_needed, payer = bettor, space = 8 + RestakePosition::INIT_SPACE, seeds = [b"restake", bettor.key.as_ref(), parlay_pool.key().as_ref()], bump)]
+    pub restake_pos: Account<'info, RestakePosition>,
+    #[account(mut)]
+    pub lp_mint: Option<Account<'info, Mint>>,
+    #[account(mut)]
+    pub bettor_lp_ata: Option<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawRestake<'info> {
+    #[account(mut)]
+    pub parlay_pool: Account<'info, ParlayPool>,
+    #[account(mut, seeds = [b"restake", owner.key.as_ref(), parlay_pool.key().as_ref()], bump = restake_pos.bump)]
+    pub restake_pos: Account<'info, RestakePosition>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
     #[account(mut)]
-    pub authority: Signer<'info>,
-    pub system_program: Program<'info, System>,
-    // optional: token program & associated token program passed when SPL flows are used
+    pub owner_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut, constraint = parlay_vault_ata.owner == parlay_pool.key() && Some(parlay_vault_ata.mint) == parlay_pool.token_mint @ PredictionError::InvalidTokenAccount)]
+    pub parlay_vault_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub lp_mint: Option<Account<'info, Mint>>,
+    #[account(mut)]
+    pub owner_lp_ata: Option<Account<'info, TokenAccount>>,
+
     pub token_program: Program<'info, Token>,
     pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
     pub rent: Sysvar<'info, Rent>,
 }
 
 #[derive(Accounts)]
-pub struct PlaceSingleBet<'info> {
+pub struct DepositLiquidity<'info> {
     #[account(mut)]
-    pub parlay_pool: Account<'info, ParlayPool>, // used for config like min_stake & token_mint
-    #[account(init_if_needed, payer = bettor, space = 8 + GamePool::INIT_SPACE, seeds = [b"game_pool", battle.key().as_ref()], bump)]
-    pub game_pool: Account<'info, GamePool>,
-    /// CHECK: the Battle account from the game program (deserialized for validation)
-    pub battle: UncheckedAccount<'info>,
-    #[account(init, payer = bettor, space = 8 + SingleBet::INIT_SPACE, seeds = [b"single_bet", game_pool.key().as_ref(), bettor.key.as_ref()], bump)]
-    pub single_bet: Account<'info, SingleBet>,
+    pub parlay_pool: Account<'info, ParlayPool>,
+    #[account(init_if_needed, payer = provider, space = 8 + RestakePosition::INIT_SPACE, seeds = [b"restake", provider.key.as_ref(), parlay_pool.key().as_ref()], bump)]
+    pub restake_pos: Account<'info, RestakePosition>,
     #[account(mut)]
-    pub bettor: Signer<'info>,
-
-    // SOL flow: none needed other than game_pool PDA lamports held
-    // SPL flow: token accounts & escrow ATA for game_pool
+    pub provider: Signer<'info>,
+    // SPL fields
+    #[account(mut, constraint = provider_ata.owner == provider.key() @ PredictionError::InvalidTokenAccount)]
+    pub provider_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut, constraint = parlay_vault_ata.owner == parlay_pool.key() && Some(parlay_vault_ata.mint) == parlay_pool.token_mint @ PredictionError::InvalidTokenAccount)]
+    pub parlay_vault_ata: Option<Account<'info, TokenAccount>>,
     #[account(mut)]
-    pub bettor_ata: Option<Account<'info, TokenAccount>>,
+    pub lp_mint: Option<Account<'info, Mint>>,
     #[account(mut)]
-    pub game_pool_escrow: Option<Account<'info, TokenAccount>>,
+    pub provider_lp_ata: Option<Account<'info, TokenAccount>>,
 
     pub token_program: Program<'info, Token>,
     pub associated_token_program: Program<'info, AssociatedToken>,
@@ -628,130 +2704,777 @@ pub struct PlaceSingleBet<'info> {
 }
 
 #[derive(Accounts)]
-pub struct SettleSinglePool<'info> {
+pub struct WithdrawLiquidity<'info> {
     #[account(mut)]
     pub parlay_pool: Account<'info, ParlayPool>,
+    #[account(mut, seeds = [b"restake", provider.key.as_ref(), parlay_pool.key().as_ref()], bump = restake_pos.bump)]
+    pub restake_pos: Account<'info, RestakePosition>,
     #[account(mut)]
-    pub game_pool: Account<'info, GamePool>,
-    /// CHECK: Battle account
-    pub battle: UncheckedAccount<'info>,
-    pub signer: Signer<'info>, // oracle/admin
+    pub provider: Signer<'info>,
+    #[account(mut)]
+    pub provider_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut, constraint = parlay_vault_ata.owner == parlay_pool.key() && Some(parlay_vault_ata.mint) == parlay_pool.token_mint @ PredictionError::InvalidTokenAccount)]
+    pub parlay_vault_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub lp_mint: Option<Account<'info, Mint>>,
+    #[account(mut)]
+    pub provider_lp_ata: Option<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct ClaimSingle<'info> {
+pub struct RedeemLpTokens<'info> {
     #[account(mut)]
     pub parlay_pool: Account<'info, ParlayPool>,
+    // the holder's own position, which the tokens were minted against
+    #[account(mut, seeds = [b"restake", holder.key.as_ref(), parlay_pool.key().as_ref()], bump = restake_pos.bump)]
+    pub restake_pos: Account<'info, RestakePosition>,
     #[account(mut)]
-    pub game_pool: Account<'info, GamePool>,
-    #[account(mut, has_one = pool)]
-    pub single_bet: Account<'info, SingleBet>,
-    #[account(mut)]
-    pub bettor: Signer<'info>,
-    // SPL flows
-    #[account(mut)]
-    pub bettor_ata: Option<Account<'info, TokenAccount>>,
+    pub holder: Signer<'info>,
     #[account(mut)]
-    pub game_pool_escrow: Option<Account<'info, TokenAccount>>,
-
-    // restake / parlay vault
+    pub holder_ata: Option<Account<'info, TokenAccount>>,
     #[account(mut)]
     pub parlay_vault_ata: Option<Account<'info, TokenAccount>>,
     #[account(mut)]
-    pub parlay_pool: Account<'info, ParlayPool>,
-
-    // restake pos to create if restake chosen
-    #[account(init_if_needed, payer = bettor, space = 8 + RestakePosition::INIT_SPACE, seeds = [b"restake", bettor.key.as_ref(), parlay_pool.key().as_ref()], bump)]
-    pub restake_pos: Account<'info, RestakePosition>,
+    pub lp_mint: Option<Account<'info, Mint>>,
+    #[account(mut)]
+    pub holder_lp_ata: Option<Account<'info, TokenAccount>>,
 
     pub token_program: Program<'info, Token>,
-    pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
 }
 
 #[derive(Accounts)]
-pub struct PlaceParlayBet<'info> {
-    #[account(mut)]
+#[instruction(season: u32)]
+pub struct StartPredictionSeason<'info> {
+    #[account(mut, has_one = authority)]
     pub parlay_pool: Account<'info, ParlayPool>,
-    #[account(init, payer = bettor, space = 8 + ParlayTicket::INIT_SPACE, seeds = [b"parlay_ticket", bettor.key.as_ref(), parlay_pool.key().as_ref()], bump)]
-    pub parlay_ticket: Account<'info, ParlayTicket>,
+    #[account(init, payer = authority, space = 8 + Leaderboard::INIT_SPACE, seeds = [b"leaderboard", parlay_pool.key().as_ref(), &season.to_le_bytes()], bump)]
+    pub leaderboard: Account<'info, Leaderboard>,
     #[account(mut)]
-    pub bettor: Signer<'info>,
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
 
-    // SPL fields
+#[derive(Accounts)]
+pub struct FundSeasonPrizes<'info> {
+    pub parlay_pool: Account<'info, ParlayPool>,
+    #[account(mut, seeds = [b"leaderboard", parlay_pool.key().as_ref(), &leaderboard.season.to_le_bytes()], bump = leaderboard.bump)]
+    pub leaderboard: Account<'info, Leaderboard>,
     #[account(mut)]
-    pub bettor_ata: Option<Account<'info, TokenAccount>>,
+    pub funder: Signer<'info>,
     #[account(mut)]
-    pub parlay_vault_ata: Option<Account<'info, TokenAccount>>,
+    pub funder_ata: Option<Account<'info, TokenAccount>>,
+    // the leaderboard's associated token account (created by the funder)
+    #[account(mut, constraint = prize_vault_ata.owner == leaderboard.key() && Some(prize_vault_ata.mint) == parlay_pool.token_mint @ PredictionError::InvalidTokenAccount)]
+    pub prize_vault_ata: Option<Account<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
 
+#[derive(Accounts)]
+pub struct AwardSeasonPrize<'info> {
+    #[account(has_one = authority)]
+    pub parlay_pool: Account<'info, ParlayPool>,
+    #[account(mut, seeds = [b"leaderboard", parlay_pool.key().as_ref(), &leaderboard.season.to_le_bytes()], bump = leaderboard.bump)]
+    pub leaderboard: Account<'info, Leaderboard>,
+    pub authority: Signer<'info>,
+    /// CHECK: must match the leaderboard entry at `rank`
+    #[account(mut)]
+    pub winner: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub winner_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut, constraint = prize_vault_ata.owner == leaderboard.key() && Some(prize_vault_ata.mint) == parlay_pool.token_mint @ PredictionError::InvalidTokenAccount)]
+    pub prize_vault_ata: Option<Account<'info, TokenAccount>>,
     pub token_program: Program<'info, Token>,
-    pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
 }
 
 #[derive(Accounts)]
-pub struct ResolveParlayTicket<'info> {
+#[instruction(referrer: Pubkey)]
+pub struct BindReferrer<'info> {
+    #[account(init, payer = bettor, space = 8 + Referral::INIT_SPACE, seeds = [b"referral", bettor.key.as_ref()], bump)]
+    pub referral: Account<'info, Referral>,
+    // the referrer's own binding, if any (used to reject A<->B loops)
+    #[account(seeds = [b"referral", referrer.as_ref()], bump = referrer_referral.bump)]
+    pub referrer_referral: Option<Account<'info, Referral>>,
+    #[account(mut)]
+    pub bettor: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimReferralRewards<'info> {
     #[account(mut)]
     pub parlay_pool: Account<'info, ParlayPool>,
+    #[account(mut, has_one = referrer, seeds = [b"referrer", referrer.key.as_ref(), parlay_pool.key().as_ref()], bump = referrer_balance.bump)]
+    pub referrer_balance: Account<'info, ReferrerBalance>,
     #[account(mut)]
-    pub parlay_ticket: Account<'info, ParlayTicket>,
-    pub signer: Signer<'info>, // oracle/admin
+    pub referrer: Signer<'info>,
+    #[account(mut)]
+    pub referrer_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub parlay_vault_ata: Option<Account<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct ClaimParlay<'info> {
+pub struct ClaimFeeRebate<'info> {
     #[account(mut)]
     pub parlay_pool: Account<'info, ParlayPool>,
-    #[account(mut, has_one = owner)]
-    pub parlay_ticket: Account<'info, ParlayTicket>,
+    #[account(mut, has_one = bettor, seeds = [b"bettor_stats", bettor.key.as_ref(), parlay_pool.key().as_ref()], bump = bettor_stats.bump)]
+    pub bettor_stats: Account<'info, BettorStats>,
     #[account(mut)]
     pub bettor: Signer<'info>,
-    // SPL fields
     #[account(mut)]
+    pub bettor_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut, constraint = parlay_vault_ata.owner == parlay_pool.key() && Some(parlay_vault_ata.mint) == parlay_pool.token_mint @ PredictionError::InvalidTokenAccount)]
     pub parlay_vault_ata: Option<Account<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(bettor: Pubkey)]
+pub struct SetDailyStakeCap<'info> {
+    #[account(has_one = authority)]
+    pub parlay_pool: Account<'info, ParlayPool>,
+    #[account(init_if_needed, payer = authority, space = 8 + BettorStats::INIT_SPACE, seeds = [b"bettor_stats", bettor.as_ref(), parlay_pool.key().as_ref()], bump)]
+    pub bettor_stats: Account<'info, BettorStats>,
     #[account(mut)]
-    pub bettor_ata: Option<Account<'info, TokenAccount>>,
-    #[account(init_if_needed, payer = bettor, space = 8 + RestakePosition::INIT_SPACE, seeds = [b"restake", bettor.key.as_ref(), parlay_pool.key().as_ref()], bump)]
-    pub restake_pos: Account<'info, RestakePosition>,
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
 
-    pub token_program: Program<'info, Token>,
-    pub associated_token_program: Program<'info, AssociatedToken>,
+#[derive(Accounts)]
+pub struct SelfExclude<'info> {
+    #[account(init_if_needed, payer = bettor, space = 8 + SelfExclusion::INIT_SPACE, seeds = [b"exclusion", bettor.key.as_ref()], bump)]
+    pub exclusion: Account<'info, SelfExclusion>,
+    #[account(mut)]
+    pub bettor: Signer<'info>,
     pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
 }
 
 #[derive(Accounts)]
-pub struct WithdrawRestake<'info> {
+pub struct LockPosition<'info> {
     #[account(mut)]
     pub parlay_pool: Account<'info, ParlayPool>,
-    #[account(mut)]
+    #[account(mut, seeds = [b"restake", provider.key.as_ref(), parlay_pool.key().as_ref()], bump = restake_pos.bump)]
     pub restake_pos: Account<'info, RestakePosition>,
+    pub provider: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimLpFees<'info> {
     #[account(mut)]
-    pub owner: Signer<'info>,
+    pub parlay_pool: Account<'info, ParlayPool>,
+    #[account(mut, seeds = [b"restake", provider.key.as_ref(), parlay_pool.key().as_ref()], bump = restake_pos.bump)]
+    pub restake_pos: Account<'info, RestakePosition>,
     #[account(mut)]
-    pub owner_ata: Option<Account<'info, TokenAccount>>,
+    pub provider: Signer<'info>,
     #[account(mut)]
+    pub provider_ata: Option<Account<'info, TokenAccount>>,
+    #[account(mut, constraint = parlay_vault_ata.owner == parlay_pool.key() && Some(parlay_vault_ata.mint) == parlay_pool.token_mint @ PredictionError::InvalidTokenAccount)]
     pub parlay_vault_ata: Option<Account<'info, TokenAccount>>,
 
     pub token_program: Program<'info, Token>,
-    pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
 }
 
 // -------------------------
 // Helper functions & Battle deserialization (caveat)
 // -------------------------
 
+/// Fixed-point scale for ParlayPool.acc_fee_per_share
+pub const ACC_FEE_SCALE: u128 = 1_000_000_000_000u128; // 1e12
+
+/// Restake lock tiers: (duration secs, fee-weight boost bps, early-exit penalty bps).
+/// Tier 0 is "unlocked".
+pub const LOCK_TIERS: [(i64, u16, u16); 4] = [
+    (0, 10_000, 0),
+    (7 * 86_400, 11_000, 200),
+    (30 * 86_400, 12_500, 500),
+    (90 * 86_400, 15_000, 1_000),
+];
+
+/// Split a parlay-side protocol fee between LPs (via the accumulator) and protocol_reserve.
+/// With no fee weight outstanding the whole fee goes to protocol_reserve.
+fn accrue_protocol_fee(pool: &mut Account<ParlayPool>, fee: u64) -> Result<()> {
+    if fee == 0 {
+        return Ok(());
+    }
+    let lp_cut = if pool.total_fee_weight > 0 { ((fee as u128) * (pool.lp_fee_share_bps as u128) / 10_000u128) as u64 } else { 0 };
+    if lp_cut > 0 {
+        let delta = (lp_cut as u128).checked_mul(ACC_FEE_SCALE).ok_or(PredictionError::MathOverflow)? / (pool.total_fee_weight as u128);
+        pool.acc_fee_per_share = pool.acc_fee_per_share.checked_add(delta).ok_or(PredictionError::MathOverflow)?;
+        pool.lp_fee_reserve = pool.lp_fee_reserve.saturating_add(lp_cut);
+    }
+    let protocol_cut = fee.saturating_sub(lp_cut);
+    pool.protocol_reserve = pool.protocol_reserve.saturating_add(protocol_cut);
+    emit!(LpFeesDistributed { pool: pool.key(), lp_amount: lp_cut, protocol_amount: protocol_cut });
+    Ok(())
+}
+
+fn fee_debt_for(pool: &ParlayPool, weight: u64) -> Result<u128> {
+    (weight as u128).checked_mul(pool.acc_fee_per_share).ok_or(error!(PredictionError::MathOverflow))
+}
+
+/// Move fees earned since the last checkpoint into `fees_accrued`. Call before changing `share`
+/// or lock parameters, then call sync_fee_weight() once the new values are written.
+fn settle_lp_fees(pool: &ParlayPool, position: &mut RestakePosition) -> Result<()> {
+    let earned = fee_debt_for(pool, position.fee_weight)?.saturating_sub(position.fee_debt) / ACC_FEE_SCALE;
+    position.fees_accrued = position.fees_accrued.saturating_add(earned as u64);
+    position.fee_debt = fee_debt_for(pool, position.fee_weight)?;
+    Ok(())
+}
+
+/// Recompute the position's fee weight (share, boosted while its lock is active), update the pool
+/// total, and reset fee_debt. An expired lock falls back to 1x at the next sync.
+fn sync_fee_weight(pool: &mut ParlayPool, position: &mut RestakePosition) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let boost_bps = if now < position.lock_until { position.boost_bps.max(10_000) } else { 10_000 };
+    let weight = ((position.share as u128) * (boost_bps as u128) / 10_000u128) as u64;
+    pool.total_fee_weight = pool.total_fee_weight.saturating_sub(position.fee_weight).checked_add(weight).ok_or(PredictionError::MathOverflow)?;
+    position.fee_weight = weight;
+    position.fee_debt = fee_debt_for(pool, weight)?;
+    Ok(())
+}
+
+/// Record a settled prediction (returned = 0 for a loss) and refresh ROI / streaks.
+/// `season` is the leaderboard season being played, if any; a new season restarts season_net.
+fn record_prediction(stats: &mut Account<PredictorStats>, wallet: Pubkey, staked: u64, returned: u64, bump: u8, season: Option<u32>) -> Result<()> {
+    if stats.wallet == Pubkey::default() {
+        stats.wallet = wallet;
+        stats.bump = bump;
+    }
+    if let Some(season) = season {
+        if stats.season != season {
+            stats.season = season;
+            stats.season_net = 0;
+        }
+    }
+    let won = returned > 0;
+    stats.bets = stats.bets.saturating_add(1);
+    stats.total_staked = stats.total_staked.saturating_add(staked);
+    stats.total_returned = stats.total_returned.saturating_add(returned);
+    if won {
+        stats.wins = stats.wins.saturating_add(1);
+        stats.current_streak = stats.current_streak.saturating_add(1);
+        stats.best_streak = stats.best_streak.max(stats.current_streak);
+    } else {
+        stats.current_streak = 0;
+    }
+    let net = (returned as i128) - (staked as i128);
+    stats.season_net = (stats.season_net as i128).saturating_add(net).clamp(i64::MIN as i128, i64::MAX as i128) as i64;
+    if stats.total_staked > 0 {
+        let roi = ((stats.total_returned as i128) - (stats.total_staked as i128)) * 10_000 / (stats.total_staked as i128);
+        stats.roi_bps = roi.clamp(i64::MIN as i128, i64::MAX as i128) as i64;
+    }
+    Ok(())
+}
+
+/// Insert/refresh the wallet on the season leaderboard, keeping the top LEADERBOARD_SIZE by season_net.
+fn update_leaderboard(board: &mut Account<Leaderboard>, stats: &PredictorStats) {
+    let now = Clock::get().map(|c| c.unix_timestamp).unwrap_or(0);
+    if now < board.start_ts || now >= board.end_ts || stats.season != board.season {
+        return;
+    }
+    board.entries.retain(|e| e.wallet != stats.wallet);
+    let entry = LeaderboardEntry { wallet: stats.wallet, season_net: stats.season_net, wins: stats.wins, prize_paid: false };
+    let pos = board.entries.iter().position(|e| e.season_net < entry.season_net).unwrap_or(board.entries.len());
+    if pos < LEADERBOARD_SIZE {
+        board.entries.insert(pos, entry);
+        board.entries.truncate(LEADERBOARD_SIZE);
+    }
+}
+
+/// A priced SingleBet claim: `payout` comes out of the game pool escrow (after fee), the bonuses out of the
+/// parlay vault. Shared by claim_single and claim_many so both price a bet the same way.
+struct SingleClaim {
+    payout: u64,
+    fee: u64,
+    streak_bonus: u64,
+    promo_bonus: u64,
+}
+
+/// Price one SingleBet claim. A voided market refunds the stake (no fee, no bonuses); a losing bet gets nothing.
+/// Winners get the naive 2x payout minus the protocol fee, which promo markets waive and which hedged bettors
+/// (position on other outcomes too) pay on the net payout only; their net exposure leaves the pool's liability,
+/// split pro rata across the position's winning bets.
+/// Bonuses are capped by what's left of protocol_reserve / promo_budget.
+fn price_single_claim(
+    pool: &mut Account<GamePool>,
+    bet: &SingleBet,
+    position: Option<&Account<BettorPosition>>,
+    protocol_fee_bps: u16,
+    reserve_available: u64,
+    promo_available: u64,
+) -> Result<SingleClaim> {
+    let mut claim = SingleClaim { payout: 0, fee: 0, streak_bonus: 0, promo_bonus: 0 };
+    if pool.voided {
+        claim.payout = bet.stake;
+        return Ok(claim);
+    }
+    if pool.winning_outcome != Some(bet.chosen_outcome) {
+        return Ok(claim);
+    }
+    // For MVP payout = stake * 2 (50/50). In production replace with aggregated accounting.
+    let naive_payout = bet.stake.saturating_mul(2);
+    let fee_bps = if pool.promo { 0 } else { protocol_fee_bps as u128 };
+    let mut fee_base = naive_payout;
+    if let Some(position) = position {
+        require!(position.game_pool == pool.key() && position.bettor == bet.bettor, PredictionError::InvalidArgs);
+        // a position may hold several bets on the winning outcome: each nets its stake-weighted slice of the
+        // hedge and of the position's exposure, so across all of them the position is netted exactly once
+        let backed = position.stakes[bet.chosen_outcome as usize];
+        if backed > 0 {
+            let slice = |x: u64| ((x as u128) * (bet.stake.min(backed) as u128) / (backed as u128)) as u64;
+            let hedged = position.stakes.iter().sum::<u64>().saturating_sub(backed);
+            fee_base = naive_payout.saturating_sub(slice(hedged));
+            pool.net_liability = pool.net_liability.saturating_sub(slice(net_exposure(&position.stakes)));
+        }
+    }
+    claim.fee = ((fee_base as u128) * fee_bps / 10_000u128) as u64;
+    claim.payout = naive_payout.saturating_sub(claim.fee);
+    // the fee is booked right away (book_single_fee) but stays in this escrow until close_game_pool sweeps it
+    pool.fees_booked = pool.fees_booked.saturating_add(claim.fee);
+    // streak bonus: escalates with consecutive correct single picks (as of record_single_results)
+    claim.streak_bonus = streak_bonus_for(bet.streak, claim.payout).min(reserve_available);
+    claim.promo_bonus = promo_bonus_for(pool, claim.payout, promo_available);
+    Ok(claim)
+}
+
+fn emit_claim_bonuses(bet: &SingleBet, pool_id: Pubkey, claim: &SingleClaim) {
+    if claim.streak_bonus > 0 {
+        emit!(StreakBonusPaid { bettor: bet.bettor, pool: pool_id, streak: bet.streak, bonus: claim.streak_bonus });
+    }
+    if claim.promo_bonus > 0 {
+        emit!(PromoBonusPaid { bettor: bet.bettor, pool: pool_id, bonus: claim.promo_bonus });
+    }
+}
+
+/// Move `amount` out of a game pool's escrow (lamports on the GamePool PDA, or its escrow ATA for SPL pools),
+/// signed by the GamePool PDA.
+fn game_pool_transfer<'info>(
+    pool: &Account<'info, GamePool>,
+    escrow: Option<AccountInfo<'info>>,
+    to: AccountInfo<'info>,
+    token_program: &Program<'info, Token>,
+    amount: u64,
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    let bump = [pool.bump];
+    let signer_seeds = &[&[b"game_pool", pool.pool_id.as_ref(), pool.parlay_pool.as_ref(), &bump][..]];
+    match pool.token_mint {
+        None => move_pda_lamports(&pool.to_account_info(), &to, amount)?,
+        Some(_) => {
+            let cpi_accounts = token::Transfer { from: escrow.ok_or(PredictionError::InvalidArgs)?, to, authority: pool.to_account_info() };
+            token::transfer(CpiContext::new_with_signer(token_program.to_account_info(), cpi_accounts, signer_seeds), amount)?;
+        }
+    }
+    Ok(())
+}
+
+/// Move `amount` out of the parlay vault (lamports on the ParlayPool PDA, or its vault ATA for SPL pools).
+fn parlay_pool_transfer<'info>(
+    pool: &Account<'info, ParlayPool>,
+    vault: Option<AccountInfo<'info>>,
+    to: AccountInfo<'info>,
+    token_program: &Program<'info, Token>,
+    amount: u64,
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    let bump = [pool.bump];
+    let signer_seeds = &[&[b"parlay_pool", pool.mint_seed(), &bump][..]];
+    match pool.token_mint {
+        None => move_pda_lamports(&pool.to_account_info(), &to, amount)?,
+        Some(_) => {
+            let cpi_accounts = token::Transfer { from: vault.ok_or(PredictionError::InvalidArgs)?, to, authority: pool.to_account_info() };
+            token::transfer(CpiContext::new_with_signer(token_program.to_account_info(), cpi_accounts, signer_seeds), amount)?;
+        }
+    }
+    Ok(())
+}
+
+/// Move lamports out of one of this program's data-carrying PDAs. The System program refuses to debit an
+/// account that holds data, so the balances are adjusted directly; `from` has to stay rent-exempt.
+fn move_pda_lamports(from: &AccountInfo, to: &AccountInfo, amount: u64) -> Result<()> {
+    let rent_min = Rent::get()?.minimum_balance(from.data_len());
+    require!(from.lamports() >= amount.saturating_add(rent_min), PredictionError::InsufficientLamports);
+    let credited = to.lamports().checked_add(amount).ok_or(PredictionError::MathOverflow)?;
+    **from.try_borrow_mut_lamports()? -= amount;
+    **to.try_borrow_mut_lamports()? = credited;
+    Ok(())
+}
+
+/// Turn a claim already moved into the parlay vault (`amount`) plus bonuses that sit there (`vault_bonus`,
+/// already taken out of protocol_reserve / promo_budget) into liquidity owned by `owner`'s restake position.
+/// Shares are priced against liquidity before the deposit lands. Returns the shares minted.
+#[allow(clippy::too_many_arguments)]
+fn restake_claim<'info>(
+    parlay_pool: &mut Account<'info, ParlayPool>,
+    restake: &mut Account<'info, RestakePosition>,
+    owner: Pubkey,
+    amount: u64,
+    vault_bonus: u64,
+    bump: u8,
+    lp_mint: &Option<Account<'info, Mint>>,
+    owner_lp_ata: &Option<Account<'info, TokenAccount>>,
+    token_program: &Program<'info, Token>,
+) -> Result<u64> {
+    let restake_amt = amount.saturating_add(vault_bonus);
+    let minted = shares_for_deposit(parlay_pool, restake_amt)?;
+    parlay_pool.liquidity_balance = parlay_pool.liquidity_balance.saturating_add(restake_amt);
+    parlay_pool.total_shares = parlay_pool.total_shares.checked_add(minted).ok_or(PredictionError::MathOverflow)?;
+    let lp_minted = mint_lp_tokens(parlay_pool, lp_mint, owner_lp_ata, token_program, minted)?;
+
+    // Create (or top up) restake position record pointing to parlay pool
+    settle_lp_fees(parlay_pool, restake)?;
+    restake.owner = owner;
+    restake.pool = parlay_pool.key();
+    restake.share = restake.share.checked_add(minted).ok_or(PredictionError::MathOverflow)?;
+    restake.lp_minted_shares = restake.lp_minted_shares.saturating_add(lp_minted);
+    sync_fee_weight(parlay_pool, restake)?;
+    restake.closed = false;
+    restake.created_at = Clock::get()?.unix_timestamp;
+    restake.bump = bump;
+    Ok(minted)
+}
+
+/// Book the protocol fee of a single-bet claim: the referrer's slice first, the rest to protocol_reserve,
+/// and the fee counted towards the bettor's rebate tier. The fee itself is still in the game pool escrow
+/// (see GamePool.fees_booked), so it's tracked in escrowed_fees until close_game_pool sweeps it.
+#[allow(clippy::too_many_arguments)]
+fn book_single_fee<'info>(
+    parlay_pool: &mut Account<'info, ParlayPool>,
+    referral: Option<&Account<'info, Referral>>,
+    referrer_balance: Option<&AccountInfo<'info>>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    bettor_stats: Option<&mut Account<'info, BettorStats>>,
+    bettor: Pubkey,
+    fee: u64,
+) -> Result<()> {
+    let referral_cut = credit_referrer(parlay_pool, referral, referrer_balance, payer, system_program, bettor, fee)?;
+    parlay_pool.protocol_reserve = parlay_pool.protocol_reserve.saturating_add(fee.saturating_sub(referral_cut));
+    parlay_pool.escrowed_fees = parlay_pool.escrowed_fees.saturating_add(fee);
+    if let Some(stats) = bettor_stats {
+        record_fee(stats, fee, Clock::get()?.unix_timestamp);
+    }
+    Ok(())
+}
+
+/// Part of protocol_reserve that actually sits in the parlay vault and can be paid out of it.
+fn vault_backed_reserve(pool: &ParlayPool) -> u64 {
+    pool.protocol_reserve.saturating_sub(pool.escrowed_fees)
+}
+
+/// Subsidized bonus for a winning claim on a promo market, capped by what's left in the promo budget.
+fn promo_bonus_for(pool: &GamePool, payout: u64, budget: u64) -> u64 {
+    if !pool.promo {
+        return 0;
+    }
+    (((payout as u128) * (pool.promo_bonus_bps as u128) / 10_000u128) as u64).min(budget)
+}
+
+/// Parimutuel odds per outcome (x100): total staked / staked on that outcome. 0 when nobody backed it.
+fn implied_odds_x100(pool: &GamePool) -> [u64; 3] {
+    let mut odds = [0u64; 3];
+    for (i, staked) in pool.outcome_totals.iter().enumerate() {
+        if *staked > 0 {
+            odds[i] = ((pool.total_staked as u128) * 100u128 / (*staked as u128)) as u64;
+        }
+    }
+    odds
+}
+
+/// Worst-case net payout of a position under the naive 2x single-bet payout: the best winning side's
+/// payout minus everything the bettor staked across outcomes.
+fn net_exposure(stakes: &[u64; 3]) -> u64 {
+    let total = stakes.iter().fold(0u64, |acc, s| acc.saturating_add(*s));
+    let worst = stakes.iter().map(|s| s.saturating_mul(2)).max().unwrap_or(0);
+    worst.saturating_sub(total)
+}
+
+/// Claims on a settled game pool open once the dispute window has passed with no open dispute.
+pub fn claims_open(pool: &GamePool, dispute_window_secs: i64, now: i64) -> bool {
+    !pool.disputed && now >= pool.settled_at.saturating_add(dispute_window_secs)
+}
+
+/// Credit the bettor's referrer with referral_fee_bps of `fee`. Returns the referral cut, which the
+/// caller removes from the fee before it reaches LPs / protocol_reserve. No-op without a binding.
+fn credit_referrer<'info>(
+    pool: &mut Account<ParlayPool>,
+    referral: Option<&Account<Referral>>,
+    balance: Option<&AccountInfo<'info>>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    bettor: Pubkey,
+    fee: u64,
+) -> Result<u64> {
+    let (Some(referral), Some(balance_info)) = (referral, balance) else { return Ok(0) };
+    require!(referral.bettor == bettor, PredictionError::InvalidArgs);
+    let cut = ((fee as u128) * (pool.referral_fee_bps as u128) / 10_000u128) as u64;
+    if cut == 0 {
+        return Ok(0);
+    }
+    let mut balance = open_referrer_balance(balance_info, payer, system_program, referral.referrer, pool.key())?;
+    balance.claimable = balance.claimable.saturating_add(cut);
+    balance.total_earned = balance.total_earned.saturating_add(cut);
+    balance.exit(&crate::ID)?;
+    pool.referral_owed = pool.referral_owed.saturating_add(cut);
+    emit!(ReferralFeeCredited { referrer: referral.referrer, bettor, amount: cut });
+    Ok(cut)
+}
+
+/// Load the (referrer, pool) ReferrerBalance, creating it when this is the referrer's first credit in the pool.
+fn open_referrer_balance<'info>(
+    info: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    referrer: Pubkey,
+    pool: Pubkey,
+) -> Result<Account<'info, ReferrerBalance>> {
+    let (expected, bump) = Pubkey::find_program_address(&[b"referrer", referrer.as_ref(), pool.as_ref()], &crate::ID);
+    require!(info.key() == expected, PredictionError::InvalidArgs);
+    if info.data_is_empty() {
+        let space = 8 + ReferrerBalance::INIT_SPACE;
+        anchor_lang::system_program::create_account(
+            CpiContext::new_with_signer(
+                system_program.clone(),
+                anchor_lang::system_program::CreateAccount { from: payer.clone(), to: info.clone() },
+                &[&[b"referrer", referrer.as_ref(), pool.as_ref(), &[bump]]],
+            ),
+            Rent::get()?.minimum_balance(space),
+            space as u64,
+            &crate::ID,
+        )?;
+        let fresh = ReferrerBalance { referrer, pool, claimable: 0, total_earned: 0, total_claimed: 0, bump };
+        fresh.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+    }
+    let balance: Account<'info, ReferrerBalance> = Account::try_from(info)?;
+    require!(balance.referrer == referrer && balance.pool == pool, PredictionError::InvalidArgs);
+    Ok(balance)
+}
+
+/// Streak bonus per prior consecutive correct single pick, and its cap.
+pub const STREAK_BONUS_STEP_BPS: u64 = 200; // +2% of payout per streak step
+pub const MAX_STREAK_BONUS_BPS: u64 = 1_000; // capped at +10%
+
+fn streak_bonus_for(streak: u16, payout: u64) -> u64 {
+    let bps = (streak as u64).saturating_mul(STREAK_BONUS_STEP_BPS).min(MAX_STREAK_BONUS_BPS);
+    ((payout as u128) * (bps as u128) / 10_000u128) as u64
+}
+
+pub const MONTH_SECS: i64 = 30 * 86_400;
+/// Monthly volume tiers: (min volume in pool base units, share of fees rebated in bps).
+/// Checked from the highest tier down.
+pub const VOLUME_REBATE_TIERS: [(u64, u16); 3] = [
+    (1_000_000_000_000, 2_500),
+    (100_000_000_000, 1_000),
+    (10_000_000_000, 500),
+];
+
+fn rebate_tier_bps(month_volume: u64) -> u16 {
+    VOLUME_REBATE_TIERS.iter().find(|(min, _)| month_volume >= *min).map(|(_, bps)| *bps).unwrap_or(0)
+}
+
+fn roll_month(stats: &mut BettorStats, now: i64) {
+    let month = now / MONTH_SECS;
+    if month != stats.month_index {
+        stats.month_index = month;
+        stats.month_volume = 0;
+    }
+}
+
+pub const DAY_SECS: i64 = 86_400;
+pub const MIN_SELF_EXCLUSION_SECS: i64 = DAY_SECS;
+pub const SELF_EXCLUSION_COOLDOWN: i64 = 7 * DAY_SECS;
+
+fn roll_day(stats: &mut BettorStats, now: i64) {
+    let day = now / DAY_SECS;
+    if day != stats.day_index {
+        stats.day_index = day;
+        stats.day_staked = 0;
+    }
+}
+
+/// Reject the bet if the wallet is self-excluded or it would exceed its daily stake cap.
+fn enforce_wallet_limits(stats: &mut Account<BettorStats>, exclusion: &Account<SelfExclusion>, stake: u64, now: i64) -> Result<()> {
+    require!(!exclusion.is_excluded(now), PredictionError::SelfExcluded);
+    roll_day(stats, now);
+    if stats.daily_cap > 0 {
+        require!(stats.day_staked.saturating_add(stake) <= stats.daily_cap, PredictionError::DailyLimitExceeded);
+    }
+    Ok(())
+}
+
+/// Add stake volume to the bettor's monthly bucket (initializing the account on first use).
+fn record_volume(stats: &mut Account<BettorStats>, bettor: Pubkey, pool: Pubkey, stake: u64, now: i64, bump: u8) {
+    if stats.bettor == Pubkey::default() {
+        stats.bettor = bettor;
+        stats.pool = pool;
+        stats.bump = bump;
+    }
+    roll_month(stats, now);
+    roll_day(stats, now);
+    stats.month_volume = stats.month_volume.saturating_add(stake);
+    stats.day_staked = stats.day_staked.saturating_add(stake);
+    stats.lifetime_volume = stats.lifetime_volume.saturating_add(stake);
+}
+
+/// Accrue a rebate on a fee the bettor just paid, at the tier their current monthly volume reached.
+fn record_fee(stats: &mut Account<BettorStats>, fee: u64, now: i64) {
+    roll_month(stats, now);
+    let tier_bps = rebate_tier_bps(stats.month_volume);
+    let rebate = ((fee as u128) * (tier_bps as u128) / 10_000u128) as u64;
+    if rebate > 0 {
+        stats.rebate_accrued = stats.rebate_accrued.saturating_add(rebate);
+        emit!(FeeRebateAccrued { bettor: stats.bettor, pool: stats.pool, amount: rebate, tier_bps });
+    }
+}
+
+/// Move up to `revenue` out of liquidity into the haircut repayment reserve, until the reserve covers
+/// the outstanding haircut debt, and bump `haircut_index` so each ticket accrues its share. Returns the
+/// amount set aside.
+fn set_aside_haircut_repayment(pool: &mut ParlayPool, revenue: u64) -> u64 {
+    let shortfall = pool.haircut_debt.saturating_sub(pool.haircut_reserve);
+    let amount = revenue.min(shortfall);
+    pool.liquidity_balance = pool.liquidity_balance.saturating_sub(amount);
+    pool.haircut_reserve = pool.haircut_reserve.saturating_add(amount);
+    if amount > 0 {
+        pool.haircut_index = pool.haircut_index.saturating_add((amount as u128) * ACC_FEE_SCALE / (pool.haircut_debt as u128));
+    }
+    amount
+}
+
+/// Trip the circuit breaker if payouts drained more than drawdown_limit_bps of liquidity within the
+/// current window. Windows roll over (tumbling) once drawdown_window_secs have elapsed.
+/// Only called from payout paths, so LP deposits/withdrawals don't count as drawdown.
+fn check_drawdown(pool: &mut Account<ParlayPool>, now: i64) {
+    if pool.drawdown_limit_bps == 0 || pool.bets_paused {
+        return;
+    }
+    if now.saturating_sub(pool.window_start_ts) >= pool.drawdown_window_secs {
+        pool.window_start_ts = now;
+        pool.window_start_liquidity = pool.liquidity_balance;
+        return;
+    }
+    let max_drop = ((pool.window_start_liquidity as u128) * (pool.drawdown_limit_bps as u128) / 10_000u128) as u64;
+    if pool.window_start_liquidity.saturating_sub(pool.liquidity_balance) > max_drop {
+        pool.bets_paused = true;
+        emit!(CircuitBreakerTripped { pool: pool.key(), window_start_liquidity: pool.window_start_liquidity, liquidity: pool.liquidity_balance });
+    }
+}
+
+/// Penalty withheld when redeeming `amount` from a position that is still locked.
+fn early_exit_penalty(position: &RestakePosition, amount: u64, now: i64) -> u64 {
+    if now >= position.lock_until {
+        return 0;
+    }
+    ((amount as u128) * (position.early_exit_penalty_bps as u128) / 10_000u128) as u64
+}
+
+/// Shares to mint for depositing `amount` into `pool`. Must be called with the liquidity *before* the deposit is
+/// added. Only an empty pool mints 1:1. Parlay stakes grow liquidity_balance without minting shares, so liquidity
+/// sitting in a pool with no shares is first backed 1:1 by locked seed shares that no position owns; otherwise
+/// the first LP would own it all. A pool with shares outstanding but no liquidity takes no deposits.
+fn shares_for_deposit(pool: &mut Account<ParlayPool>, amount: u64) -> Result<u64> {
+    if pool.total_shares == 0 {
+        if pool.liquidity_balance > 0 {
+            pool.total_shares = pool.liquidity_balance;
+            emit!(SeedSharesLocked { pool: pool.key(), shares: pool.total_shares });
+        } else {
+            return Ok(amount);
+        }
+    }
+    require!(pool.liquidity_balance > 0, PredictionError::PoolDrained);
+    let minted = (amount as u128).checked_mul(pool.total_shares as u128).ok_or(PredictionError::MathOverflow)? / (pool.liquidity_balance as u128);
+    Ok(minted as u64)
+}
+
+/// Mint LP tokens 1:1 with newly minted shares and return how many were minted (track it in
+/// RestakePosition.lp_minted_shares). No-op while the pool has no LP mint configured.
+fn mint_lp_tokens<'info>(
+    pool: &Account<'info, ParlayPool>,
+    lp_mint: &Option<Account<'info, Mint>>,
+    to: &Option<Account<'info, TokenAccount>>,
+    token_program: &Program<'info, Token>,
+    shares: u64,
+) -> Result<u64> {
+    let Some(expected) = pool.lp_mint else { return Ok(0) };
+    if shares == 0 {
+        return Ok(0);
+    }
+    let mint = lp_mint.as_ref().ok_or(PredictionError::MissingLpAccounts)?;
+    let to = to.as_ref().ok_or(PredictionError::MissingLpAccounts)?;
+    require!(mint.key() == expected && to.mint == expected, PredictionError::InvalidLpMint);
+    let cpi_accounts = token::MintTo {
+        mint: mint.to_account_info(),
+        to: to.to_account_info(),
+        authority: pool.to_account_info(),
+    };
+    let bump = [pool.bump];
+    let signer_seeds = &[&[b"parlay_pool", pool.mint_seed(), &bump][..]];
+    token::mint_to(CpiContext::new_with_signer(token_program.to_account_info(), cpi_accounts, signer_seeds), shares)?;
+    Ok(shares)
+}
+
+/// LP tokens to burn when redeeming `shares` from a position: internal-only shares go first.
+fn lp_backed_shares(position: &RestakePosition, shares: u64) -> u64 {
+    shares.saturating_sub(position.share.saturating_sub(position.lp_minted_shares))
+}
+
+/// Burn LP tokens matching shares being redeemed. The token holder signs for their own LP ATA.
+fn burn_lp_tokens<'info>(
+    pool: &Account<'info, ParlayPool>,
+    lp_mint: &Option<Account<'info, Mint>>,
+    from: &Option<Account<'info, TokenAccount>>,
+    owner: &Signer<'info>,
+    token_program: &Program<'info, Token>,
+    shares: u64,
+) -> Result<()> {
+    let Some(expected) = pool.lp_mint else { return Ok(()) };
+    if shares == 0 {
+        return Ok(());
+    }
+    let mint = lp_mint.as_ref().ok_or(PredictionError::MissingLpAccounts)?;
+    let from = from.as_ref().ok_or(PredictionError::MissingLpAccounts)?;
+    require!(mint.key() == expected && from.mint == expected, PredictionError::InvalidLpMint);
+    let cpi_accounts = token::Burn {
+        mint: mint.to_account_info(),
+        from: from.to_account_info(),
+        authority: owner.to_account_info(),
+    };
+    token::burn(CpiContext::new(token_program.to_account_info(), cpi_accounts), shares)
+}
+
+/// Pool amount redeemable for `shares`: shares * liquidity / total_shares (rounds down in favor of the pool).
+fn amount_for_shares(shares: u64, liquidity: u64, total_shares: u64) -> Result<u64> {
+    if total_shares == 0 {
+        return Ok(0);
+    }
+    let amount = (shares as u128).checked_mul(liquidity as u128).ok_or(PredictionError::MathOverflow)? / (total_shares as u128);
+    Ok(amount as u64)
+}
+
 /// Minimal "Battle snapshot" layout that MUST match the battle program's Account layout for these fields.
 /// If the real Battle struct changes, this deserialization will break.
 /// It's highly recommended to have a shared crate for both programs that defines the exact Battle layout.
+/// TODO: no shared state crate exists yet (both programs are still single-file). Once the game program
+/// exports `Battle`, replace this snapshot and deserialize_battle_snapshot with
+/// `Account<'info, battlechain::Battle>` / `Battle::try_deserialize` and drop the BATTLE_*_OFFSET constants.
 #[repr(C)]
 #[derive(AnchorDeserialize, AnchorSerialize, Clone, Debug)]
 pub struct BattleSnapshot {
-    // Anchor account discriminator (8 bytes) omitted when reading via try_from_slice
+    // read at fixed offsets after verifying owner + discriminator (see deserialize_battle_snapshot)
     pub battle_id: u64,
     // We'll read the state byte as u8 (matching BattleState enum in game program)
     pub state: u8,
@@ -759,6 +3482,37 @@ pub struct BattleSnapshot {
     pub winner_present: u8,
     pub winner: [u8; 32],
     pub start_ts: i64,
+    pub player1: Pubkey,
+    pub player2: Pubkey,
+}
+
+/// Single-bet outcome encoding: which side of the battle won.
+pub const OUTCOME_PLAYER1: u8 = 0;
+pub const OUTCOME_PLAYER2: u8 = 1;
+pub const OUTCOME_NO_WINNER: u8 = 2;
+
+/// Compliance flags (ParlayPool / GamePool `compliance_flags`). Either bit stops new bets; claims, refunds and
+/// LP flows are unaffected. RESTRICTED marks a market delisted for jurisdiction reasons (frontends hide it),
+/// CLOSED_TO_NEW_BETS just stops intake.
+pub const COMPLIANCE_RESTRICTED: u8 = 1 << 0;
+pub const COMPLIANCE_CLOSED_TO_NEW_BETS: u8 = 1 << 1;
+pub const COMPLIANCE_ALL: u8 = COMPLIANCE_RESTRICTED | COMPLIANCE_CLOSED_TO_NEW_BETS;
+
+impl BattleSnapshot {
+    /// Winning outcome of a finished battle, derived from the on-chain winner.
+    pub fn outcome(&self) -> u8 {
+        if self.winner_present != 1 {
+            return OUTCOME_NO_WINNER;
+        }
+        let winner = Pubkey::new_from_array(self.winner);
+        if winner == self.player1 {
+            OUTCOME_PLAYER1
+        } else if winner == self.player2 {
+            OUTCOME_PLAYER2
+        } else {
+            OUTCOME_NO_WINNER
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -766,38 +3520,61 @@ pub enum BattleStateDiscriminant {
     Waiting = 0,
     Active = 1,
     Finished = 2,
+    Paused = 3,
 }
 
+/// Anchor account discriminator of the game program's `Battle` (sha256("account:Battle")[..8]).
+pub const BATTLE_DISCRIMINATOR: [u8; 8] = [81, 148, 121, 71, 63, 166, 116, 24];
+
+// Byte offsets into the Battle account data (after the 8-byte discriminator), per the game program's
+// field order: battle_id, player1, player2, start_ts, current_turn, turn_number, player1_health,
+// player2_health, state, player1_stance, player2_stance, created_at, inactivity_timeout,
+// last_action_ts, winner (Option<Pubkey>).
+const BATTLE_ID_OFFSET: usize = 8;
+const BATTLE_PLAYER1_OFFSET: usize = 8 + 8;
+const BATTLE_PLAYER2_OFFSET: usize = BATTLE_PLAYER1_OFFSET + 32;
+const BATTLE_START_TS_OFFSET: usize = 8 + 8 + 32 + 32;
+const BATTLE_STATE_OFFSET: usize = BATTLE_START_TS_OFFSET + 8 + 1 + 8 + 8 + 8;
+const BATTLE_WINNER_OFFSET: usize = BATTLE_STATE_OFFSET + 1 + 1 + 1 + 8 + 8 + 8;
+
 fn deserialize_battle_snapshot(account: &AccountInfo) -> Result<BattleSnapshot> {
-    // naive deserialization: try to skip anchor discriminator (8 bytes) and then deserialize fields
-    // This is brittle and requires exact matching layout
-    let data = &account.try_borrow_data()?;
-    if data.len() < 8 + 8 + 1 + 1 + 32 + 8 {
-        return Err(error!(PredictionError.InvalidBattleAccount));
-    }
-    // skip discriminator
-    let slice = &data[8..];
-    let mut cursor = std::io::Cursor::new(slice);
-    let battle_id = u64::try_from_slice_from_reader(&mut cursor).map_err(|_| error!(PredictionError.InvalidBattleAccount))?;
-    // read state u8
-    let mut state_buf = [0u8;1];
-    cursor.read_exact(&mut state_buf).map_err(|_| error!(PredictionError.InvalidBattleAccount))?;
-    let state = state_buf[0];
-    // read winner presence
-    let mut present = [0u8;1];
-    cursor.read_exact(&mut present).map_err(|_| error!(PredictionError.InvalidBattleAccount))?;
-    let mut winner = [0u8;32];
-    cursor.read_exact(&mut winner).map_err(|_| error!(PredictionError.InvalidBattleAccount))?;
-    let mut ts_buf = [0u8;8];
-    cursor.read_exact(&mut ts_buf).map_err(|_| error!(PredictionError.InvalidBattleAccount))?;
-    let start_ts = i64::from_le_bytes(ts_buf);
+    // only accounts owned by the game program with the Battle discriminator are accepted
+    require!(account.owner == &BATTLECHAIN_PROGRAM_ID, PredictionError::InvalidBattleAccount);
+    let data = account.try_borrow_data()?;
+    parse_battle_snapshot(&data)
+}
+
+/// Read a BattleSnapshot out of raw Battle account data (discriminator included). Callers check the
+/// account owner; the keeper uses this so it reads the same offsets as the program.
+pub fn parse_battle_snapshot(data: &[u8]) -> Result<BattleSnapshot> {
+    require!(data.len() >= BATTLE_WINNER_OFFSET + 1 + 32, PredictionError::InvalidBattleAccount);
+    require!(data[..8] == BATTLE_DISCRIMINATOR, PredictionError::InvalidBattleAccount);
+
+    let read_u64 = |at: usize| {
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(&data[at..at + 8]);
+        buf
+    };
+    let battle_id = u64::from_le_bytes(read_u64(BATTLE_ID_OFFSET));
+    let start_ts = i64::from_le_bytes(read_u64(BATTLE_START_TS_OFFSET));
+    let state = data[BATTLE_STATE_OFFSET];
+    require!(state <= BattleStateDiscriminant::Paused as u8, PredictionError::InvalidBattleAccount);
+    let player1 = Pubkey::try_from(&data[BATTLE_PLAYER1_OFFSET..BATTLE_PLAYER1_OFFSET + 32]).map_err(|_| error!(PredictionError::InvalidBattleAccount))?;
+    let player2 = Pubkey::try_from(&data[BATTLE_PLAYER2_OFFSET..BATTLE_PLAYER2_OFFSET + 32]).map_err(|_| error!(PredictionError::InvalidBattleAccount))?;
+    let winner_present = data[BATTLE_WINNER_OFFSET];
+    let mut winner = [0u8; 32];
+    if winner_present == 1 {
+        winner.copy_from_slice(&data[BATTLE_WINNER_OFFSET + 1..BATTLE_WINNER_OFFSET + 33]);
+    }
 
     Ok(BattleSnapshot {
         battle_id,
         state,
-        winner_present: present[0],
+        winner_present,
         winner,
         start_ts,
+        player1,
+        player2,
     })
 }
 
@@ -859,4 +3636,62 @@ pub enum PredictionError {
     Unauthorized,
     #[msg("Unimplemented flow")]
     Unimplemented,
+    #[msg("Math overflow")]
+    MathOverflow,
+    #[msg("Nothing to withdraw")]
+    NothingToWithdraw,
+    #[msg("LP mint already initialized")]
+    LpMintAlreadySet,
+    #[msg("LP mint / LP token account missing")]
+    MissingLpAccounts,
+    #[msg("Invalid LP mint")]
+    InvalidLpMint,
+    #[msg("Deposit too small to mint shares")]
+    DepositTooSmall,
+    #[msg("Withdrawal would breach liquidity floor")]
+    BelowLiquidityFloor,
+    #[msg("Invalid lock tier")]
+    InvalidLockTier,
+    #[msg("Betting paused by circuit breaker")]
+    BettingPaused,
+    #[msg("Pool registry full")]
+    RegistryFull,
+    #[msg("Pool still has unclaimed bets")]
+    PoolHasOpenBets,
+    #[msg("Insufficient protocol reserve")]
+    InsufficientReserve,
+    #[msg("Season not ended")]
+    SeasonNotEnded,
+    #[msg("Self-referral not allowed")]
+    SelfReferral,
+    #[msg("Wallet is self-excluded from betting")]
+    SelfExcluded,
+    #[msg("Daily stake limit exceeded")]
+    DailyLimitExceeded,
+    #[msg("Operation paused by guardian")]
+    OperationPaused,
+    #[msg("Claims locked: dispute window open or dispute pending")]
+    ClaimsLocked,
+    #[msg("Dispute window closed")]
+    DisputeWindowClosed,
+    #[msg("Game pool exposure cap exceeded")]
+    ExposureCapExceeded,
+    #[msg("Market is delisted or closed to new bets")]
+    MarketDelisted,
+    #[msg("Token account owner or mint mismatch")]
+    InvalidTokenAccount,
+    #[msg("Game pool still open")]
+    GamePoolStillOpen,
+    #[msg("Bet results not recorded yet")]
+    ResultsNotRecorded,
+    #[msg("Bet result already recorded")]
+    AlreadyRecorded,
+    #[msg("Battle account still exists")]
+    BattleStillOpen,
+    #[msg("Pool has shares outstanding but no liquidity")]
+    PoolDrained,
+    #[msg("Insurance fund account required")]
+    MissingInsuranceFund,
+    #[msg("Transfer would leave the account below rent exemption")]
+    InsufficientLamports,
 }